@@ -0,0 +1,213 @@
+//! Attachments - Images and PDFs sent to the model as content parts.
+//!
+//! Chat input can attach files with `@screenshot.png` or `@spec.pdf` and
+//! images from the clipboard with `/paste`. Images become base64 `image_url`
+//! parts and PDFs become `file` parts, the OpenAI forms; each adapter converts
+//! them to its provider's image and document blocks. Models the registry does
+//! not mark as vision-capable get a text note instead.
+
+use base64::Engine;
+use serde_json::{json, Value};
+use std::fs;
+use std::path::Path;
+
+/// Largest image accepted inline (Anthropic's limit; others allow more)
+pub const MAX_IMAGE_BYTES: usize = 5 * 1024 * 1024;
+
+/// Largest PDF accepted inline
+pub const MAX_DOCUMENT_BYTES: usize = 32 * 1024 * 1024;
+
+/// Media type of an attachable file, by extension
+pub fn media_type(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_str()?.to_lowercase();
+    match extension.as_str() {
+        "png" => Some("image/png"),
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "gif" => Some("image/gif"),
+        "webp" => Some("image/webp"),
+        "pdf" => Some("application/pdf"),
+        _ => None,
+    }
+}
+
+fn data_url(media_type: &str, bytes: &[u8]) -> String {
+    format!(
+        "data:{};base64,{}",
+        media_type,
+        base64::engine::general_purpose::STANDARD.encode(bytes)
+    )
+}
+
+/// An inline image content part
+pub fn image_part(media_type: &str, bytes: &[u8]) -> Value {
+    json!({
+        "type": "image_url",
+        "image_url": {"url": data_url(media_type, bytes)}
+    })
+}
+
+/// An inline PDF content part
+pub fn document_part(filename: &str, bytes: &[u8]) -> Value {
+    json!({
+        "type": "file",
+        "file": {
+            "filename": filename,
+            "file_data": data_url("application/pdf", bytes)
+        }
+    })
+}
+
+/// Read an image or PDF into a content part
+pub fn load(path: &Path) -> Result<Value, String> {
+    let media_type =
+        media_type(path).ok_or_else(|| format!("{} is not an image or PDF", path.display()))?;
+    let bytes = fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let image = media_type.starts_with("image/");
+    let limit = if image {
+        MAX_IMAGE_BYTES
+    } else {
+        MAX_DOCUMENT_BYTES
+    };
+    if bytes.len() > limit {
+        return Err(format!(
+            "{} is {} bytes, over the {} byte limit for attachments",
+            path.display(),
+            bytes.len(),
+            limit
+        ));
+    }
+
+    if image {
+        Ok(image_part(media_type, &bytes))
+    } else {
+        let filename = path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "document.pdf".to_string());
+        Ok(document_part(&filename, &bytes))
+    }
+}
+
+/// Text sent in place of an attachment for a model that cannot view it
+pub fn placeholder(path: &str) -> String {
+    format!(
+        "[{} was not attached: this model does not accept images or documents]",
+        path
+    )
+}
+
+/// PNG-encode RGBA pixels as an image part
+pub fn png_part(width: usize, height: usize, rgba: &[u8]) -> Result<Value, String> {
+    let mut bytes = Vec::new();
+    let mut encoder = png::Encoder::new(&mut bytes, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(rgba))
+        .map_err(|e| format!("Failed to encode image: {}", e))?;
+    if bytes.len() > MAX_IMAGE_BYTES {
+        return Err(format!(
+            "Image is {} bytes as PNG, over the {} byte limit for attachments",
+            bytes.len(),
+            MAX_IMAGE_BYTES
+        ));
+    }
+    Ok(image_part("image/png", &bytes))
+}
+
+/// The image on the clipboard as an image part
+pub fn clipboard_image() -> Result<Value, String> {
+    let mut clipboard =
+        arboard::Clipboard::new().map_err(|e| format!("Clipboard not available: {}", e))?;
+    let image = clipboard
+        .get_image()
+        .map_err(|e| format!("No image on the clipboard: {}", e))?;
+    png_part(image.width, image.height, &image.bytes)
+}
+
+/// Message content for text and attachments: the text alone when nothing is
+/// attached, otherwise a text part followed by the attachments
+pub fn message_content(text: &str, attachments: Vec<Value>) -> Value {
+    if attachments.is_empty() {
+        return json!(text);
+    }
+    let mut parts = vec![json!({"type": "text", "text": text})];
+    parts.extend(attachments);
+    Value::Array(parts)
+}
+
+/// Plain text of message content that may be a string or content parts
+pub fn content_text(content: &Value) -> String {
+    match content {
+        Value::String(text) => text.clone(),
+        Value::Array(parts) => parts
+            .iter()
+            .filter_map(|p| p["text"].as_str())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proxy::ContentPart;
+
+    #[test]
+    fn test_media_type() {
+        assert_eq!(media_type(Path::new("a/shot.PNG")), Some("image/png"));
+        assert_eq!(media_type(Path::new("photo.jpeg")), Some("image/jpeg"));
+        assert_eq!(media_type(Path::new("spec.pdf")), Some("application/pdf"));
+        assert_eq!(media_type(Path::new("main.rs")), None);
+        assert_eq!(media_type(Path::new("Makefile")), None);
+    }
+
+    #[test]
+    fn test_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let image = dir.path().join("dot.gif");
+        fs::write(&image, b"GIF89a").unwrap();
+        let part: ContentPart = serde_json::from_value(load(&image).unwrap()).unwrap();
+        assert_eq!(part.content_type, "image_url");
+        assert_eq!(part.inline_data(), Some(("image/gif", "R0lGODlh")));
+
+        let pdf = dir.path().join("spec.pdf");
+        fs::write(&pdf, b"%PDF-1.7").unwrap();
+        let part: ContentPart = serde_json::from_value(load(&pdf).unwrap()).unwrap();
+        assert_eq!(part.content_type, "file");
+        assert_eq!(part.filename(), Some("spec.pdf"));
+        assert_eq!(
+            part.inline_data(),
+            Some(("application/pdf", "JVBERi0xLjc="))
+        );
+
+        assert!(load(&dir.path().join("notes.txt")).is_err());
+        assert!(load(&dir.path().join("missing.png")).is_err());
+    }
+
+    #[test]
+    fn test_png_part() {
+        let part = png_part(2, 1, &[255, 0, 0, 255, 0, 0, 255, 255]).unwrap();
+        let url = part["image_url"]["url"].as_str().unwrap();
+        let data = url.strip_prefix("data:image/png;base64,").unwrap();
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(data)
+            .unwrap();
+        assert!(bytes.starts_with(b"\x89PNG\r\n\x1a\n"));
+
+        // Pixel data must match the dimensions
+        assert!(png_part(2, 2, &[0; 8]).is_err());
+    }
+
+    #[test]
+    fn test_message_content() {
+        assert_eq!(message_content("hi", vec![]), json!("hi"));
+        let content = message_content("look", vec![image_part("image/png", b"x")]);
+        assert_eq!(content[0], json!({"type": "text", "text": "look"}));
+        assert_eq!(content[1]["type"], "image_url");
+        assert_eq!(content_text(&content), "look");
+        assert_eq!(content_text(&json!("plain")), "plain");
+    }
+}
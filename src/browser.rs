@@ -0,0 +1,835 @@
+//! Stateful headless browser sessions for the `browser` tool.
+//!
+//! Unlike `web_browser`, which loads a page once and scrapes it, the `browser`
+//! tool keeps a Chrome instance alive between calls so an agent can drive a
+//! web app: navigate, click, type, wait for elements, run JavaScript, read the
+//! console and inspect the accessibility tree.
+//!
+//! Sessions are keyed by name (default `"default"`) and live until closed or
+//! the process exits. Every request the tab makes (documents, frames, scripts,
+//! XHR and fetch, redirects) is paused through CDP `Fetch` interception and
+//! failed unless its URL passes the network policy; actions that read the
+//! page re-check its URL first. Requires the `browser` feature.
+
+use crate::network::NetworkPolicy;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Session name used when the tool call doesn't specify one
+pub const DEFAULT_SESSION: &str = "default";
+
+/// Default timeout for `wait_for`, in milliseconds
+#[cfg(feature = "browser")]
+const DEFAULT_WAIT_MS: u64 = 5000;
+
+/// Maximum console entries kept per session
+#[cfg(feature = "browser")]
+const MAX_CONSOLE_ENTRIES: usize = 500;
+
+/// Maximum characters returned for page text and JS results
+#[cfg(feature = "browser")]
+const MAX_OUTPUT_CHARS: usize = 50000;
+
+#[cfg(feature = "browser")]
+fn truncate_output(mut output: String) -> String {
+    if output.len() > MAX_OUTPUT_CHARS {
+        let mut end = MAX_OUTPUT_CHARS;
+        while !output.is_char_boundary(end) {
+            end -= 1;
+        }
+        let total = output.len();
+        output.truncate(end);
+        output.push_str(&format!("...\n\n(output truncated, {} total chars)", total));
+    }
+    output
+}
+
+#[cfg(feature = "browser")]
+mod session {
+    use super::*;
+    use headless_chrome::browser::tab::RequestPausedDecision;
+    use headless_chrome::browser::transport::{SessionId, Transport};
+    use headless_chrome::protocol::cdp::types::Event;
+    use headless_chrome::protocol::cdp::Fetch::events::RequestPausedEvent;
+    use headless_chrome::protocol::cdp::Fetch::{FailRequest, RequestPattern};
+    use headless_chrome::protocol::cdp::Network::ErrorReason;
+    use headless_chrome::protocol::cdp::{Accessibility, Runtime};
+    use headless_chrome::{Browser, LaunchOptions, Tab};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    /// A live browser with one tab and its captured console output
+    pub(super) struct BrowserSession {
+        // Kept alive for the lifetime of the session; dropping it kills Chrome
+        _browser: Browser,
+        pub tab: Arc<Tab>,
+        pub console: Arc<Mutex<Vec<String>>>,
+        /// Policy the request interceptor enforces, updated on every tool call
+        policy: Arc<Mutex<NetworkPolicy>>,
+    }
+
+    /// URLs that never reach the network
+    fn is_local_scheme(url: &str) -> bool {
+        ["about:", "data:", "blob:"]
+            .iter()
+            .any(|scheme| url.starts_with(scheme))
+    }
+
+    /// Active sessions by name. Callers clone a session out and release the
+    /// lock before driving it, so one slow action doesn't stall other sessions.
+    pub(super) static BROWSER_SESSIONS: std::sync::LazyLock<
+        Mutex<HashMap<String, Arc<BrowserSession>>>,
+    > = std::sync::LazyLock::new(|| Mutex::new(HashMap::new()));
+
+    /// Requests to pause: all of them, at the request stage
+    pub(super) fn request_patterns() -> Vec<RequestPattern> {
+        vec![RequestPattern {
+            url_pattern: Some("*".to_string()),
+            resource_Type: None,
+            request_stage: None,
+        }]
+    }
+
+    /// Let a paused request continue if the policy allows its URL, fail it
+    /// otherwise
+    pub(super) fn request_decision(
+        policy: &NetworkPolicy,
+        url: &str,
+        request_id: String,
+    ) -> RequestPausedDecision {
+        if is_local_scheme(url) || policy.check_url(url).is_ok() {
+            RequestPausedDecision::Continue(None)
+        } else {
+            RequestPausedDecision::Fail(FailRequest {
+                request_id,
+                error_reason: ErrorReason::BlockedByClient,
+            })
+        }
+    }
+
+    /// Render a console argument the way DevTools would
+    fn remote_object_to_string(obj: &Runtime::RemoteObject) -> String {
+        match &obj.value {
+            Some(Value::String(s)) => s.clone(),
+            Some(v) => v.to_string(),
+            None => obj
+                .description
+                .clone()
+                .unwrap_or_else(|| format!("{:?}", obj.Type).to_lowercase()),
+        }
+    }
+
+    impl BrowserSession {
+        pub fn launch(policy: &NetworkPolicy) -> Result<Self, String> {
+            let browser = Browser::new(
+                LaunchOptions::default_builder()
+                    .headless(true)
+                    .idle_browser_timeout(Duration::from_secs(3600))
+                    .build()
+                    .map_err(|e| format!("Failed to configure browser: {}", e))?,
+            )
+            .map_err(|e| format!("Failed to launch browser: {}", e))?;
+
+            let tab = browser
+                .new_tab()
+                .map_err(|e| format!("Failed to create browser tab: {}", e))?;
+
+            let console = Arc::new(Mutex::new(Vec::new()));
+            let sink = Arc::clone(&console);
+            tab.add_event_listener(Arc::new(move |event: &Event| {
+                let line = match event {
+                    Event::RuntimeConsoleAPICalled(e) => {
+                        let level = serde_json::to_value(&e.params.Type)
+                            .ok()
+                            .and_then(|v| v.as_str().map(String::from))
+                            .unwrap_or_else(|| "log".to_string());
+                        let text: Vec<String> =
+                            e.params.args.iter().map(remote_object_to_string).collect();
+                        format!("[{}] {}", level, text.join(" "))
+                    }
+                    Event::RuntimeExceptionThrown(e) => {
+                        let details = &e.params.exception_details;
+                        let description = details
+                            .exception
+                            .as_ref()
+                            .and_then(|ex| ex.description.clone())
+                            .unwrap_or_default();
+                        format!("[exception] {} {}", details.text, description)
+                            .trim_end()
+                            .to_string()
+                    }
+                    _ => return,
+                };
+                if let Ok(mut console) = sink.lock() {
+                    console.push(line);
+                    let excess = console.len().saturating_sub(MAX_CONSOLE_ENTRIES);
+                    console.drain(..excess);
+                }
+            }))
+            .map_err(|e| format!("Failed to capture console: {}", e))?;
+            tab.enable_runtime()
+                .map_err(|e| format!("Failed to enable runtime: {}", e))?;
+
+            // Pause every request and fail the ones the policy blocks
+            let policy = Arc::new(Mutex::new(policy.clone()));
+            let guard = Arc::clone(&policy);
+            tab.enable_request_interception(Arc::new(
+                move |_: Arc<Transport>, _: SessionId, event: RequestPausedEvent| {
+                    let params = event.params;
+                    match guard.lock() {
+                        Ok(policy) => {
+                            request_decision(&policy, &params.request.url, params.request_id)
+                        }
+                        Err(_) => RequestPausedDecision::Fail(FailRequest {
+                            request_id: params.request_id,
+                            error_reason: ErrorReason::BlockedByClient,
+                        }),
+                    }
+                },
+            ))
+            .map_err(|e| format!("Failed to intercept requests: {}", e))?;
+            tab.enable_fetch(Some(&request_patterns()), None)
+                .map_err(|e| format!("Failed to enable request interception: {}", e))?;
+
+            Ok(Self {
+                _browser: browser,
+                tab,
+                console,
+                policy,
+            })
+        }
+
+        /// Apply the policy of the current tool call to later requests
+        pub fn set_policy(&self, policy: &NetworkPolicy) {
+            if let Ok(mut current) = self.policy.lock() {
+                *current = policy.clone();
+            }
+        }
+
+        /// Make sure the tab is on a URL the policy allows, leaving the page if not
+        fn enforce_policy(&self, policy: &NetworkPolicy) -> Result<(), String> {
+            let url = self.tab.get_url();
+            if url.is_empty() || url.starts_with("about:") {
+                return Ok(());
+            }
+            if let Err(e) = policy.check_url(&url) {
+                let _ = self.tab.navigate_to("about:blank");
+                return Err(e);
+            }
+            Ok(())
+        }
+
+        fn page_summary(&self) -> String {
+            let title = self.tab.get_title().unwrap_or_default();
+            format!("URL: {}\nTitle: {}", self.tab.get_url(), title)
+        }
+
+        pub fn navigate(&self, url: &str, policy: &NetworkPolicy) -> Result<String, String> {
+            policy.check_url(url)?;
+            self.tab
+                .navigate_to(url)
+                .map_err(|e| format!("Failed to navigate to URL: {}", e))?;
+            self.tab
+                .wait_until_navigated()
+                .map_err(|e| format!("Navigation timeout: {}", e))?;
+            self.enforce_policy(policy)?;
+            Ok(format!("Navigated.\n{}", self.page_summary()))
+        }
+
+        pub fn click(&self, selector: &str, policy: &NetworkPolicy) -> Result<String, String> {
+            self.tab
+                .wait_for_element(selector)
+                .map_err(|e| format!("Element '{}' not found: {}", selector, e))?
+                .click()
+                .map_err(|e| format!("Failed to click '{}': {}", selector, e))?;
+            // Clicks on links may navigate; give the page a moment and re-check
+            std::thread::sleep(Duration::from_millis(200));
+            self.enforce_policy(policy)?;
+            Ok(format!("Clicked '{}'.\n{}", selector, self.page_summary()))
+        }
+
+        pub fn type_text(
+            &self,
+            selector: &str,
+            text: &str,
+            submit: bool,
+            policy: &NetworkPolicy,
+        ) -> Result<String, String> {
+            let element = self
+                .tab
+                .wait_for_element(selector)
+                .map_err(|e| format!("Element '{}' not found: {}", selector, e))?;
+            element
+                .click()
+                .map_err(|e| format!("Failed to focus '{}': {}", selector, e))?;
+            element
+                .type_into(text)
+                .map_err(|e| format!("Failed to type into '{}': {}", selector, e))?;
+            if submit {
+                self.tab
+                    .press_key("Enter")
+                    .map_err(|e| format!("Failed to press Enter: {}", e))?;
+                std::thread::sleep(Duration::from_millis(200));
+                self.enforce_policy(policy)?;
+            }
+            Ok(format!(
+                "Typed {} chars into '{}'{}.",
+                text.chars().count(),
+                selector,
+                if submit { " and pressed Enter" } else { "" }
+            ))
+        }
+
+        pub fn wait_for(
+            &self,
+            selector: &str,
+            timeout_ms: u64,
+            policy: &NetworkPolicy,
+        ) -> Result<String, String> {
+            self.enforce_policy(policy)?;
+            let element = self
+                .tab
+                .wait_for_element_with_custom_timeout(selector, Duration::from_millis(timeout_ms))
+                .map_err(|e| {
+                    format!(
+                        "Timed out after {}ms waiting for '{}': {}",
+                        timeout_ms, selector, e
+                    )
+                })?;
+            // The wait may have spanned a navigation
+            self.enforce_policy(policy)?;
+            let text = element.get_inner_text().unwrap_or_default();
+            Ok(format!(
+                "Found '{}'. Text: {}",
+                selector,
+                truncate_output(text.trim().to_string())
+            ))
+        }
+
+        pub fn evaluate(&self, script: &str, policy: &NetworkPolicy) -> Result<String, String> {
+            let result = self
+                .tab
+                .evaluate(script, true)
+                .map_err(|e| format!("JavaScript error: {}", e))?;
+            self.enforce_policy(policy)?;
+            Ok(truncate_output(match result.value {
+                Some(Value::String(s)) => s,
+                Some(v) => serde_json::to_string_pretty(&v).unwrap_or_else(|_| v.to_string()),
+                None => result
+                    .description
+                    .unwrap_or_else(|| format!("{:?}", result.Type).to_lowercase()),
+            }))
+        }
+
+        pub fn content(&self, policy: &NetworkPolicy) -> Result<String, String> {
+            self.enforce_policy(policy)?;
+            let text = self
+                .tab
+                .evaluate("document.body ? document.body.innerText : ''", false)
+                .map_err(|e| format!("Failed to read page text: {}", e))?
+                .value
+                .and_then(|v| v.as_str().map(String::from))
+                .unwrap_or_default();
+            Ok(truncate_output(format!(
+                "{}\n\n{}",
+                self.page_summary(),
+                text
+            )))
+        }
+
+        pub fn console(&self, clear: bool, policy: &NetworkPolicy) -> Result<String, String> {
+            self.enforce_policy(policy)?;
+            let mut console = match self.console.lock() {
+                Ok(c) => c,
+                Err(_) => return Ok("Console log unavailable".to_string()),
+            };
+            let output = if console.is_empty() {
+                "No console output.".to_string()
+            } else {
+                console.join("\n")
+            };
+            if clear {
+                console.clear();
+            }
+            Ok(truncate_output(output))
+        }
+
+        pub fn accessibility_tree(&self, policy: &NetworkPolicy) -> Result<String, String> {
+            self.enforce_policy(policy)?;
+            let tree = self
+                .tab
+                .call_method(Accessibility::GetFullAXTree {
+                    depth: None,
+                    frame_id: None,
+                })
+                .map_err(|e| format!("Failed to get accessibility tree: {}", e))?;
+
+            let nodes: Vec<AxEntry> = tree
+                .nodes
+                .into_iter()
+                .map(|n| AxEntry {
+                    id: n.node_id,
+                    parent: n.parent_id,
+                    children: n.child_ids.unwrap_or_default(),
+                    ignored: n.ignored,
+                    role: ax_value_string(n.role.as_ref()),
+                    name: ax_value_string(n.name.as_ref()),
+                    value: ax_value_string(n.value.as_ref()),
+                })
+                .collect();
+
+            Ok(truncate_output(format_ax_tree(&nodes)))
+        }
+    }
+
+    fn ax_value_string(value: Option<&Accessibility::AXValue>) -> String {
+        match value.and_then(|v| v.value.as_ref()) {
+            Some(Value::String(s)) => s.clone(),
+            Some(Value::Null) | None => String::new(),
+            Some(v) => v.to_string(),
+        }
+    }
+}
+
+/// Flattened accessibility node used for rendering the tree
+#[cfg_attr(not(feature = "browser"), allow(dead_code))]
+#[derive(Debug, Clone)]
+pub(crate) struct AxEntry {
+    pub id: String,
+    pub parent: Option<String>,
+    pub children: Vec<String>,
+    pub ignored: bool,
+    pub role: String,
+    pub name: String,
+    pub value: String,
+}
+
+/// Render accessibility nodes as an indented outline
+///
+/// Ignored and unnamed generic nodes are skipped, but their children are
+/// still shown at the parent's depth.
+#[cfg_attr(not(feature = "browser"), allow(dead_code))]
+pub(crate) fn format_ax_tree(nodes: &[AxEntry]) -> String {
+    let by_id: HashMap<&str, &AxEntry> = nodes.iter().map(|n| (n.id.as_str(), n)).collect();
+    let roots: Vec<&AxEntry> = nodes
+        .iter()
+        .filter(|n| n.parent.as_deref().is_none_or(|p| !by_id.contains_key(p)))
+        .collect();
+
+    fn walk(node: &AxEntry, depth: usize, by_id: &HashMap<&str, &AxEntry>, out: &mut Vec<String>) {
+        let hidden = node.ignored
+            || node.role == "none"
+            || (node.name.is_empty()
+                && node.value.is_empty()
+                && matches!(node.role.as_str(), "generic" | "GenericContainer" | ""));
+
+        let child_depth = if hidden {
+            depth
+        } else {
+            let mut line = format!("{}- {}", "  ".repeat(depth), node.role);
+            if !node.name.is_empty() {
+                line.push_str(&format!(" \"{}\"", node.name));
+            }
+            if !node.value.is_empty() {
+                line.push_str(&format!(" value=\"{}\"", node.value));
+            }
+            out.push(line);
+            depth + 1
+        };
+
+        for child in &node.children {
+            if let Some(child) = by_id.get(child.as_str()) {
+                walk(child, child_depth, by_id, out);
+            }
+        }
+    }
+
+    let mut out = Vec::new();
+    for root in roots {
+        walk(root, 0, &by_id, &mut out);
+    }
+
+    if out.is_empty() {
+        "Accessibility tree is empty.".to_string()
+    } else {
+        out.join("\n")
+    }
+}
+
+/// Execute the `browser` tool
+#[cfg(feature = "browser")]
+pub fn execute_browser_tool(
+    args: &HashMap<String, Value>,
+    policy: &NetworkPolicy,
+) -> (String, bool) {
+    use session::{BrowserSession, BROWSER_SESSIONS};
+    use std::sync::Arc;
+
+    let action = match args.get("action").and_then(|v| v.as_str()) {
+        Some(a) => a,
+        None => return ("Missing 'action' argument".to_string(), true),
+    };
+    let name = args
+        .get("session")
+        .and_then(|v| v.as_str())
+        .unwrap_or(DEFAULT_SESSION)
+        .to_string();
+    let str_arg = |key: &str| -> Result<&str, String> {
+        args.get(key)
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| format!("Missing '{}' argument for action '{}'", key, action))
+    };
+
+    let sessions = || {
+        BROWSER_SESSIONS
+            .lock()
+            .map_err(|e| format!("Browser session lock poisoned: {}", e))
+    };
+
+    if action == "close" {
+        // Chrome exits once an action still running on the session finishes
+        return match sessions().map(|mut sessions| sessions.remove(&name)) {
+            Ok(Some(_)) => (format!("Closed browser session '{}'.", name), false),
+            Ok(None) => (format!("No browser session '{}' to close.", name), true),
+            Err(e) => (e, true),
+        };
+    }
+
+    let known = [
+        "navigate",
+        "click",
+        "type",
+        "wait_for",
+        "evaluate",
+        "content",
+        "console",
+        "accessibility_tree",
+    ];
+    if !known.contains(&action) {
+        return (
+            format!(
+                "Unknown browser action '{}'. Available: {}, close",
+                action,
+                known.join(", ")
+            ),
+            true,
+        );
+    }
+
+    if action == "navigate" {
+        let url = match str_arg("url") {
+            Ok(u) => u,
+            Err(e) => return (e, true),
+        };
+        // Check before launching Chrome so blocked URLs cost nothing
+        if let Err(e) = policy.check_url(url) {
+            return (e, true);
+        }
+        let running = match sessions() {
+            Ok(sessions) => sessions.contains_key(&name),
+            Err(e) => return (e, true),
+        };
+        if !running {
+            // Launch without holding the lock; a session another call
+            // started in the meantime wins
+            let launched = match BrowserSession::launch(policy) {
+                Ok(s) => Arc::new(s),
+                Err(e) => return (e, true),
+            };
+            match sessions() {
+                Ok(mut sessions) => {
+                    sessions.entry(name.clone()).or_insert(launched);
+                }
+                Err(e) => return (e, true),
+            }
+        }
+    }
+
+    let session = match sessions().map(|sessions| sessions.get(&name).cloned()) {
+        Ok(Some(s)) => s,
+        Err(e) => return (e, true),
+        Ok(None) => {
+            return (
+                format!(
+                    "No browser session '{}'. Start one with action 'navigate'.",
+                    name
+                ),
+                true,
+            )
+        }
+    };
+    session.set_policy(policy);
+
+    let result = match action {
+        "navigate" => str_arg("url").and_then(|url| session.navigate(url, policy)),
+        "click" => str_arg("selector").and_then(|sel| session.click(sel, policy)),
+        "type" => str_arg("selector").and_then(|sel| {
+            let text = str_arg("text")?;
+            let submit = args
+                .get("submit")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false);
+            session.type_text(sel, text, submit, policy)
+        }),
+        "wait_for" => str_arg("selector").and_then(|sel| {
+            let timeout = args
+                .get("timeout_ms")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(DEFAULT_WAIT_MS);
+            session.wait_for(sel, timeout, policy)
+        }),
+        "evaluate" => str_arg("script").and_then(|script| session.evaluate(script, policy)),
+        "content" => session.content(policy),
+        "console" => session.console(
+            args.get("clear").and_then(|v| v.as_bool()).unwrap_or(false),
+            policy,
+        ),
+        "accessibility_tree" => session.accessibility_tree(policy),
+        _ => unreachable!("action validated above"),
+    };
+
+    match result {
+        Ok(output) => (output, false),
+        Err(e) => (e, true),
+    }
+}
+
+#[cfg(not(feature = "browser"))]
+pub fn execute_browser_tool(
+    _args: &HashMap<String, Value>,
+    _policy: &NetworkPolicy,
+) -> (String, bool) {
+    (
+        "Browser feature not enabled. Rebuild with `cargo build --features browser`".to_string(),
+        true,
+    )
+}
+
+/// Close all browser sessions (kills their Chrome processes)
+pub fn close_all_sessions() {
+    #[cfg(feature = "browser")]
+    if let Ok(mut sessions) = session::BROWSER_SESSIONS.lock() {
+        sessions.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn args(value: Value) -> HashMap<String, Value> {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn entry(id: &str, parent: Option<&str>, children: &[&str], role: &str, name: &str) -> AxEntry {
+        AxEntry {
+            id: id.to_string(),
+            parent: parent.map(String::from),
+            children: children.iter().map(|c| c.to_string()).collect(),
+            ignored: false,
+            role: role.to_string(),
+            name: name.to_string(),
+            value: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_format_ax_tree() {
+        let nodes = vec![
+            entry("1", None, &["2"], "RootWebArea", "Test Page"),
+            entry("2", Some("1"), &["3", "4"], "generic", ""),
+            entry("3", Some("2"), &[], "heading", "Hello"),
+            entry("4", Some("2"), &[], "button", "Submit"),
+        ];
+        let tree = format_ax_tree(&nodes);
+        assert_eq!(
+            tree,
+            "- RootWebArea \"Test Page\"\n  - heading \"Hello\"\n  - button \"Submit\""
+        );
+        assert_eq!(format_ax_tree(&[]), "Accessibility tree is empty.");
+    }
+
+    #[cfg(not(feature = "browser"))]
+    #[test]
+    fn test_browser_tool_requires_feature() {
+        let (output, is_error) = execute_browser_tool(
+            &args(json!({"action": "navigate", "url": "https://example.com"})),
+            &NetworkPolicy::default(),
+        );
+        assert!(is_error);
+        assert!(output.contains("feature"));
+    }
+
+    #[cfg(feature = "browser")]
+    #[test]
+    fn test_browser_tool_argument_errors() {
+        let policy = NetworkPolicy::default();
+
+        let (output, is_error) = execute_browser_tool(&args(json!({})), &policy);
+        assert!(is_error);
+        assert!(output.contains("Missing 'action'"));
+
+        let (output, is_error) = execute_browser_tool(&args(json!({"action": "fly"})), &policy);
+        assert!(is_error);
+        assert!(output.contains("Unknown browser action"));
+
+        let (output, is_error) = execute_browser_tool(
+            &args(json!({"action": "navigate", "session": "no-url"})),
+            &policy,
+        );
+        assert!(is_error);
+        assert!(output.contains("Missing 'url'"));
+
+        let (output, is_error) = execute_browser_tool(
+            &args(json!({"action": "click", "selector": "#go", "session": "missing"})),
+            &policy,
+        );
+        assert!(is_error);
+        assert!(output.contains("No browser session 'missing'"));
+
+        // Blocked before Chrome is ever launched
+        let (output, is_error) = execute_browser_tool(
+            &args(
+                json!({"action": "navigate", "url": "http://169.254.169.254/", "session": "ssrf"}),
+            ),
+            &policy,
+        );
+        assert!(is_error);
+        assert!(output.contains("Blocked by network policy"));
+    }
+
+    #[cfg(feature = "browser")]
+    #[test]
+    fn test_request_interception() {
+        use headless_chrome::browser::tab::RequestPausedDecision;
+        use headless_chrome::protocol::cdp::Network::ErrorReason;
+        use session::{request_decision, request_patterns};
+
+        let patterns = request_patterns();
+        assert_eq!(patterns.len(), 1);
+        assert_eq!(patterns[0].url_pattern.as_deref(), Some("*"));
+        assert!(patterns[0].resource_Type.is_none());
+        assert!(patterns[0].request_stage.is_none());
+
+        let policy = NetworkPolicy::default();
+        for url in [
+            "https://8.8.8.8/app.js",
+            "about:blank",
+            "data:text/html,<p>hi</p>",
+        ] {
+            assert!(
+                matches!(
+                    request_decision(&policy, url, "1".to_string()),
+                    RequestPausedDecision::Continue(None)
+                ),
+                "{}",
+                url
+            );
+        }
+        for url in [
+            "http://169.254.169.254/latest/meta-data/",
+            "http://127.0.0.1:8080/",
+            "file:///etc/passwd",
+        ] {
+            match request_decision(&policy, url, "7".to_string()) {
+                RequestPausedDecision::Fail(fail) => {
+                    assert_eq!(fail.request_id, "7");
+                    assert!(matches!(fail.error_reason, ErrorReason::BlockedByClient));
+                }
+                other => panic!("{} not failed: {:?}", url, other),
+            }
+        }
+
+        let blocked = NetworkPolicy {
+            blocked_domains: vec!["example.com".to_string()],
+            ..Default::default()
+        };
+        assert!(matches!(
+            request_decision(&blocked, "https://example.com/", "2".to_string()),
+            RequestPausedDecision::Fail(_)
+        ));
+    }
+
+    /// Serve a single static HTML page on a random local port
+    #[cfg(feature = "browser")]
+    fn serve_static(html: &'static str) -> String {
+        use std::io::{Read, Write};
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                let mut buf = [0u8; 4096];
+                let _ = stream.read(&mut buf);
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    html.len(),
+                    html
+                );
+                let _ = stream.write_all(response.as_bytes());
+            }
+        });
+        format!("http://{}/", addr)
+    }
+
+    /// End-to-end test against a local static server. Skipped when no Chrome is installed.
+    #[cfg(feature = "browser")]
+    #[test]
+    fn test_browser_session_against_local_server() {
+        if headless_chrome::browser::default_executable().is_err() {
+            eprintln!("Skipping browser test: Chrome/Chromium not found");
+            return;
+        }
+
+        let url = serve_static(
+            r#"<!doctype html><html><head><title>Fixture</title></head><body>
+<h1>Fixture</h1>
+<input id="name" aria-label="Name">
+<button id="go" onclick="document.getElementById('out').textContent = 'Hello ' + document.getElementById('name').value; console.log('clicked', 42);">Greet</button>
+<p id="out"></p>
+</body></html>"#,
+        );
+        // The fixture lives on loopback, which the default policy blocks
+        let policy = NetworkPolicy {
+            allow_private_networks: true,
+            ..Default::default()
+        };
+        let run = |value: Value| {
+            let mut a = args(value);
+            a.insert("session".to_string(), json!("test-e2e"));
+            execute_browser_tool(&a, &policy)
+        };
+
+        let (output, is_error) = run(json!({"action": "navigate", "url": url}));
+        assert!(!is_error, "{}", output);
+        assert!(output.contains("Title: Fixture"));
+
+        let (output, is_error) = run(json!({"action": "type", "selector": "#name", "text": "Ada"}));
+        assert!(!is_error, "{}", output);
+
+        let (output, is_error) = run(json!({"action": "click", "selector": "#go"}));
+        assert!(!is_error, "{}", output);
+
+        let (output, is_error) = run(
+            json!({"action": "evaluate", "script": "document.getElementById('out').textContent"}),
+        );
+        assert!(!is_error, "{}", output);
+        assert_eq!(output, "Hello Ada");
+
+        let (output, _) =
+            run(json!({"action": "wait_for", "selector": "#out", "timeout_ms": 2000}));
+        assert!(output.contains("Hello Ada"));
+
+        let (output, _) = run(json!({"action": "console"}));
+        assert!(output.contains("[log] clicked 42"), "{}", output);
+
+        let (output, is_error) = run(json!({"action": "accessibility_tree"}));
+        assert!(!is_error, "{}", output);
+        assert!(output.contains("button \"Greet\""), "{}", output);
+
+        let (output, is_error) = run(json!({"action": "close"}));
+        assert!(!is_error, "{}", output);
+    }
+}
@@ -0,0 +1,313 @@
+//! Token and cost budgets.
+//!
+//! Limits come from `budgets:` in config.yaml. A check compares the spend of
+//! one scope (a session, a loop run, a subagent or the current UTC day) with
+//! its limit: crossing `warn_at` yields a one-time warning, reaching the limit
+//! is a hard stop that callers report through the `Stop` hook.
+
+use crate::config::{Budget, BudgetsConfig};
+use crate::hooks::{HookEngine, HookEvent, HookInput};
+use crate::memory::{MemoryDb, UsageSummary};
+use crate::stream::TokenUsage;
+use crate::usage::{format_cost, format_tokens};
+use serde_json::json;
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::{LazyLock, Mutex};
+
+/// Scopes that already warned, as `scope:key`
+static WARNED: LazyLock<Mutex<HashSet<String>>> = LazyLock::new(|| Mutex::new(HashSet::new()));
+
+/// What a budget applies to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BudgetScope {
+    Session,
+    Loop,
+    Subagent,
+    Daily,
+}
+
+impl BudgetScope {
+    pub fn label(&self) -> &'static str {
+        match self {
+            BudgetScope::Session => "session",
+            BudgetScope::Loop => "loop",
+            BudgetScope::Subagent => "subagent",
+            BudgetScope::Daily => "daily",
+        }
+    }
+
+    /// The configured limit for this scope
+    pub fn limit(&self, config: &BudgetsConfig) -> Budget {
+        match self {
+            BudgetScope::Session => config.session,
+            BudgetScope::Loop => config.loop_run,
+            BudgetScope::Subagent => config.subagent,
+            BudgetScope::Daily => config.daily,
+        }
+    }
+}
+
+/// Tokens and money spent in a scope
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Spend {
+    pub tokens: u64,
+    pub cost_usd: f64,
+}
+
+impl Spend {
+    pub fn new(usage: &TokenUsage, cost_usd: f64) -> Self {
+        Self {
+            tokens: usage.total_tokens(),
+            cost_usd,
+        }
+    }
+}
+
+impl From<&UsageSummary> for Spend {
+    fn from(summary: &UsageSummary) -> Self {
+        Self::new(&summary.usage, summary.cost_usd)
+    }
+}
+
+impl std::ops::AddAssign for Spend {
+    fn add_assign(&mut self, other: Self) {
+        self.tokens += other.tokens;
+        self.cost_usd += other.cost_usd;
+    }
+}
+
+/// Outcome of a budget check
+#[derive(Debug, Clone, PartialEq)]
+pub enum BudgetStatus {
+    Ok,
+    /// First time the scope crossed `warn_at`
+    Warning(String),
+    /// The limit is reached; the caller must stop
+    Exceeded {
+        scope: BudgetScope,
+        message: String,
+    },
+}
+
+/// Largest fraction of a limit used, or None when the budget is unlimited
+pub fn used_fraction(budget: &Budget, spend: &Spend) -> Option<f64> {
+    let tokens = budget
+        .max_tokens
+        .map(|max| spend.tokens as f64 / max.max(1) as f64);
+    let cost = budget.max_cost_usd.map(|max| {
+        if max > 0.0 {
+            spend.cost_usd / max
+        } else if spend.cost_usd > 0.0 {
+            f64::INFINITY
+        } else {
+            0.0
+        }
+    });
+    match (tokens, cost) {
+        (Some(tokens), Some(cost)) => Some(tokens.max(cost)),
+        (tokens, cost) => tokens.or(cost),
+    }
+}
+
+/// Spend against each configured limit, e.g. `1.6M of 2.0M tokens, $4.10 of $5.00`
+fn describe(budget: &Budget, spend: &Spend) -> String {
+    let mut parts = Vec::new();
+    if let Some(max) = budget.max_tokens {
+        parts.push(format!(
+            "{} of {} tokens",
+            format_tokens(spend.tokens),
+            format_tokens(max)
+        ));
+    }
+    if let Some(max) = budget.max_cost_usd {
+        parts.push(format!(
+            "{} of {}",
+            format_cost(spend.cost_usd),
+            format_cost(max)
+        ));
+    }
+    parts.join(", ")
+}
+
+/// What is left of a budget, e.g. `400.0k tokens, $0.90 left`.
+/// Returns None when the budget is unlimited.
+pub fn remaining(budget: &Budget, spend: &Spend) -> Option<String> {
+    let mut parts = Vec::new();
+    if let Some(max) = budget.max_tokens {
+        parts.push(format!(
+            "{} tokens",
+            format_tokens(max.saturating_sub(spend.tokens))
+        ));
+    }
+    if let Some(max) = budget.max_cost_usd {
+        parts.push(format_cost((max - spend.cost_usd).max(0.0)));
+    }
+    if parts.is_empty() {
+        None
+    } else {
+        Some(format!("{} left", parts.join(", ")))
+    }
+}
+
+/// Compare a scope's spend with its limit. `key` identifies the session, run,
+/// agent or day, so each one warns only once.
+pub fn check(scope: BudgetScope, key: &str, config: &BudgetsConfig, spend: &Spend) -> BudgetStatus {
+    let budget = scope.limit(config);
+    let Some(used) = used_fraction(&budget, spend) else {
+        return BudgetStatus::Ok;
+    };
+    if used >= 1.0 {
+        return BudgetStatus::Exceeded {
+            scope,
+            message: format!(
+                "{} budget exceeded: {}",
+                scope.label(),
+                describe(&budget, spend)
+            ),
+        };
+    }
+    if used >= config.warn_at {
+        let first = WARNED
+            .lock()
+            .map(|mut warned| warned.insert(format!("{}:{}", scope.label(), key)))
+            .unwrap_or(false);
+        if first {
+            return BudgetStatus::Warning(format!(
+                "{} budget {:.0}% used: {}",
+                scope.label(),
+                used * 100.0,
+                describe(&budget, spend)
+            ));
+        }
+    }
+    BudgetStatus::Ok
+}
+
+/// Spend recorded in the project database today (UTC)
+pub fn daily_spend(project_dir: &Path) -> Spend {
+    let midnight = chrono::Utc::now()
+        .date_naive()
+        .and_hms_opt(0, 0, 0)
+        .expect("midnight is a valid time")
+        .and_utc();
+    MemoryDb::open_for_project(project_dir)
+        .and_then(|db| db.usage_since(midnight))
+        .map(|summary| Spend::from(&summary))
+        .unwrap_or_default()
+}
+
+/// Check the daily budget of the project in `project_dir`
+pub fn check_daily(config: &BudgetsConfig, project_dir: &Path) -> BudgetStatus {
+    if config.daily == Budget::default() {
+        return BudgetStatus::Ok;
+    }
+    let today = chrono::Utc::now().format("%Y-%m-%d").to_string();
+    check(
+        BudgetScope::Daily,
+        &today,
+        config,
+        &daily_spend(project_dir),
+    )
+}
+
+/// Fire the `Stop` hook for a budget hard stop
+pub async fn run_stop_hook(
+    hook_engine: &HookEngine,
+    session_id: &str,
+    scope: BudgetScope,
+    message: &str,
+) {
+    let input = HookInput::new(HookEvent::Stop)
+        .with_session_id(session_id)
+        .with_extra("reason", json!("budget"))
+        .with_extra("budget_scope", json!(scope.label()))
+        .with_extra("message", json!(message));
+    hook_engine.run(HookEvent::Stop, &input).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> BudgetsConfig {
+        BudgetsConfig {
+            session: Budget {
+                max_tokens: Some(1_000),
+                max_cost_usd: Some(1.0),
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_used_fraction() {
+        let spend = Spend {
+            tokens: 500,
+            cost_usd: 0.9,
+        };
+        assert_eq!(used_fraction(&Budget::default(), &spend), None);
+        assert_eq!(used_fraction(&config().session, &spend), Some(0.9));
+
+        let free = Budget {
+            max_tokens: None,
+            max_cost_usd: Some(0.0),
+        };
+        assert_eq!(used_fraction(&free, &Spend::default()), Some(0.0));
+        assert!(used_fraction(&free, &spend).unwrap() >= 1.0);
+    }
+
+    #[test]
+    fn test_check_warns_once_then_stops() {
+        let config = config();
+        let below = Spend {
+            tokens: 100,
+            cost_usd: 0.1,
+        };
+        let near = Spend {
+            tokens: 850,
+            cost_usd: 0.2,
+        };
+        let over = Spend {
+            tokens: 1_200,
+            cost_usd: 0.2,
+        };
+
+        assert_eq!(
+            check(BudgetScope::Session, "warn-test", &config, &below),
+            BudgetStatus::Ok
+        );
+        assert!(matches!(
+            check(BudgetScope::Session, "warn-test", &config, &near),
+            BudgetStatus::Warning(msg) if msg.starts_with("session budget 85% used")
+        ));
+        assert_eq!(
+            check(BudgetScope::Session, "warn-test", &config, &near),
+            BudgetStatus::Ok
+        );
+        assert!(matches!(
+            check(BudgetScope::Session, "warn-test", &config, &over),
+            BudgetStatus::Exceeded { scope: BudgetScope::Session, message }
+                if message == "session budget exceeded: 1.2k of 1.0k tokens, $0.20 of $1.00"
+        ));
+
+        // Unlimited scopes never stop
+        assert_eq!(
+            check(BudgetScope::Loop, "warn-test", &config, &over),
+            BudgetStatus::Ok
+        );
+    }
+
+    #[test]
+    fn test_remaining() {
+        let spend = Spend {
+            tokens: 600,
+            cost_usd: 1.5,
+        };
+        assert_eq!(
+            remaining(&config().session, &spend).as_deref(),
+            Some("400 tokens, $0.00 left")
+        );
+        assert_eq!(remaining(&Budget::default(), &spend), None);
+    }
+}
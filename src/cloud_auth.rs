@@ -0,0 +1,655 @@
+//! Cloud Credentials - AWS SigV4 request signing and Google OAuth access tokens.
+//!
+//! Bedrock requests are signed with AWS Signature Version 4 using keys from the
+//! provider config, the standard `AWS_*` environment variables or a profile in
+//! the shared credentials file. Vertex AI requests carry an OAuth access token,
+//! minted from a service-account key (a signed JWT exchanged at the token
+//! endpoint) or from `gcloud` user credentials (a refresh token), and cached
+//! until shortly before it expires.
+//!
+//! Credential files and token endpoints are configurable so both flows can run
+//! against a local mock server.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use reqwest::Url;
+use ring::{digest, hmac, signature};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use thiserror::Error;
+
+use crate::config::{AwsConfig, VertexConfig};
+
+/// OAuth scope for Vertex AI
+const CLOUD_PLATFORM_SCOPE: &str = "https://www.googleapis.com/auth/cloud-platform";
+
+/// Token endpoint used when the credentials file names none
+const GOOGLE_TOKEN_URL: &str = "https://oauth2.googleapis.com/token";
+
+/// Tokens are refreshed this long before they expire
+const TOKEN_REFRESH_MARGIN: Duration = Duration::from_secs(60);
+
+/// Access tokens by credentials file and token endpoint
+static TOKENS: LazyLock<Mutex<HashMap<String, CachedToken>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+struct CachedToken {
+    token: String,
+    expires: Instant,
+}
+
+/// Errors resolving cloud credentials or minting tokens
+#[derive(Error, Debug)]
+pub enum CloudAuthError {
+    #[error("No AWS credentials found (set aws.access_key_id/secret_access_key, AWS_ACCESS_KEY_ID or a credentials file profile)")]
+    NoAwsCredentials,
+
+    #[error("No Google credentials found (set vertex.credentials_file or GOOGLE_APPLICATION_CREDENTIALS)")]
+    NoGoogleCredentials,
+
+    #[error("No Vertex AI project (set vertex.project_id or GOOGLE_CLOUD_PROJECT)")]
+    NoVertexProject,
+
+    #[error("Failed to read {0}: {1}")]
+    CredentialsFile(String, String),
+
+    #[error("Invalid credentials: {0}")]
+    InvalidCredentials(String),
+
+    #[error("Token request failed: {0}")]
+    TokenRequest(String),
+}
+
+/// Expand a leading `~/` to the home directory
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+fn env_var(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|v| !v.is_empty())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    hex(digest::digest(&digest::SHA256, data).as_ref())
+}
+
+fn hmac_sha256(key: &[u8], data: &str) -> hmac::Tag {
+    hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, key), data.as_bytes())
+}
+
+// ============================================================================
+// AWS
+// ============================================================================
+
+/// Keys for signing AWS requests
+#[derive(Debug, Clone, PartialEq)]
+pub struct AwsCredentials {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    pub session_token: Option<String>,
+}
+
+impl AwsCredentials {
+    /// Keys from the config, then the environment, then the credentials file
+    pub fn resolve(config: &AwsConfig) -> Result<Self, CloudAuthError> {
+        if let (Some(id), Some(secret)) = (&config.access_key_id, &config.secret_access_key) {
+            return Ok(Self {
+                access_key_id: id.clone(),
+                secret_access_key: secret.clone(),
+                session_token: config.session_token.clone(),
+            });
+        }
+        if let (Some(id), Some(secret)) = (
+            env_var("AWS_ACCESS_KEY_ID"),
+            env_var("AWS_SECRET_ACCESS_KEY"),
+        ) {
+            return Ok(Self {
+                access_key_id: id,
+                secret_access_key: secret,
+                session_token: env_var("AWS_SESSION_TOKEN"),
+            });
+        }
+
+        let path = config
+            .credentials_file
+            .clone()
+            .or_else(|| env_var("AWS_SHARED_CREDENTIALS_FILE"))
+            .map(|p| expand_home(&p))
+            .or_else(|| dirs::home_dir().map(|home| home.join(".aws/credentials")))
+            .ok_or(CloudAuthError::NoAwsCredentials)?;
+        let Ok(text) = std::fs::read_to_string(&path) else {
+            return Err(CloudAuthError::NoAwsCredentials);
+        };
+        let profile = config
+            .profile
+            .clone()
+            .or_else(|| env_var("AWS_PROFILE"))
+            .unwrap_or_else(|| "default".to_string());
+        Self::from_profile(&text, &profile).ok_or(CloudAuthError::NoAwsCredentials)
+    }
+
+    /// Keys of `profile` in a shared credentials file (INI format)
+    fn from_profile(text: &str, profile: &str) -> Option<Self> {
+        let mut in_profile = false;
+        let mut values: HashMap<&str, &str> = HashMap::new();
+        for line in text.lines().map(str::trim) {
+            if let Some(section) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                in_profile = section.trim() == profile;
+            } else if let Some((key, value)) = line.split_once('=').filter(|_| in_profile) {
+                values.insert(key.trim(), value.trim());
+            }
+        }
+        Some(Self {
+            access_key_id: values.get("aws_access_key_id")?.to_string(),
+            secret_access_key: values.get("aws_secret_access_key")?.to_string(),
+            session_token: values.get("aws_session_token").map(|t| t.to_string()),
+        })
+    }
+}
+
+/// Signing region: the config, then `AWS_REGION`, then the region in a
+/// `bedrock-runtime.<region>.amazonaws.com` base URL, then `us-east-1`
+pub fn aws_region(config: &AwsConfig, base_url: &str) -> String {
+    config
+        .region
+        .clone()
+        .or_else(|| env_var("AWS_REGION"))
+        .or_else(|| env_var("AWS_DEFAULT_REGION"))
+        .or_else(|| {
+            let host = Url::parse(base_url).ok()?.host_str()?.to_string();
+            let rest = host.strip_prefix("bedrock-runtime.")?;
+            Some(rest.split('.').next()?.to_string())
+        })
+        .unwrap_or_else(|| "us-east-1".to_string())
+}
+
+/// URI-encode a path segment or query component the way SigV4 expects
+fn aws_encode(value: &str) -> String {
+    urlencoding::encode(value).into_owned()
+}
+
+/// Sign a request with AWS Signature Version 4, returning the headers to add
+/// (`x-amz-date`, `x-amz-security-token` and `authorization`).
+///
+/// `headers` are the request's own headers; `host` and any `content-type` and
+/// `x-amz-*` headers among them are signed. Path segments are encoded once
+/// more for the canonical URI, as every AWS service except S3 requires.
+#[allow(clippy::too_many_arguments)]
+pub fn sign_v4(
+    method: &str,
+    url: &Url,
+    headers: &[(String, String)],
+    body: &[u8],
+    credentials: &AwsCredentials,
+    region: &str,
+    service: &str,
+    now: DateTime<Utc>,
+) -> Vec<(String, String)> {
+    let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let date = now.format("%Y%m%d").to_string();
+
+    let mut added = vec![("x-amz-date".to_string(), amz_date.clone())];
+    if let Some(token) = &credentials.session_token {
+        added.push(("x-amz-security-token".to_string(), token.clone()));
+    }
+
+    let host = match url.port() {
+        Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+        None => url.host_str().unwrap_or_default().to_string(),
+    };
+    let mut signed: Vec<(String, String)> = headers
+        .iter()
+        .chain(&added)
+        .map(|(k, v)| (k.to_lowercase(), v.trim().to_string()))
+        .filter(|(k, _)| k == "content-type" || k.starts_with("x-amz-"))
+        .chain(std::iter::once(("host".to_string(), host)))
+        .collect();
+    signed.sort();
+    signed.dedup_by(|a, b| a.0 == b.0);
+
+    let canonical_uri = url
+        .path()
+        .split('/')
+        .map(aws_encode)
+        .collect::<Vec<_>>()
+        .join("/");
+    let mut query: Vec<(String, String)> = url
+        .query_pairs()
+        .map(|(k, v)| (aws_encode(&k), aws_encode(&v)))
+        .collect();
+    query.sort();
+    let canonical_query = query
+        .iter()
+        .map(|(k, v)| format!("{}={}", k, v))
+        .collect::<Vec<_>>()
+        .join("&");
+    let canonical_headers: String = signed
+        .iter()
+        .map(|(k, v)| format!("{}:{}\n", k, v))
+        .collect();
+    let signed_headers = signed
+        .iter()
+        .map(|(k, _)| k.as_str())
+        .collect::<Vec<_>>()
+        .join(";");
+
+    let canonical_request = format!(
+        "{}\n{}\n{}\n{}\n{}\n{}",
+        method,
+        canonical_uri,
+        canonical_query,
+        canonical_headers,
+        signed_headers,
+        sha256_hex(body)
+    );
+    let scope = format!("{}/{}/{}/aws4_request", date, region, service);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        amz_date,
+        scope,
+        sha256_hex(canonical_request.as_bytes())
+    );
+
+    let key = format!("AWS4{}", credentials.secret_access_key);
+    let key = hmac_sha256(key.as_bytes(), &date);
+    let key = hmac_sha256(key.as_ref(), region);
+    let key = hmac_sha256(key.as_ref(), service);
+    let key = hmac_sha256(key.as_ref(), "aws4_request");
+    let signature = hex(hmac_sha256(key.as_ref(), &string_to_sign).as_ref());
+
+    added.push((
+        "authorization".to_string(),
+        format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            credentials.access_key_id, scope, signed_headers, signature
+        ),
+    ));
+    added
+}
+
+// ============================================================================
+// Google
+// ============================================================================
+
+/// The fields of a service-account key or `authorized_user` credentials file
+/// that token requests need
+#[derive(Debug, Deserialize)]
+struct GoogleCredentials {
+    #[serde(rename = "type", default)]
+    kind: String,
+    #[serde(default)]
+    project_id: Option<String>,
+    #[serde(default)]
+    quota_project_id: Option<String>,
+    #[serde(default)]
+    token_uri: Option<String>,
+    // service_account
+    #[serde(default)]
+    client_email: Option<String>,
+    #[serde(default)]
+    private_key: Option<String>,
+    // authorized_user
+    #[serde(default)]
+    client_id: Option<String>,
+    #[serde(default)]
+    client_secret: Option<String>,
+    #[serde(default)]
+    refresh_token: Option<String>,
+}
+
+/// Credentials file: the config, then `GOOGLE_APPLICATION_CREDENTIALS`, then
+/// the gcloud application default credentials if present
+fn google_credentials_path(config: &VertexConfig) -> Option<PathBuf> {
+    if let Some(path) = config
+        .credentials_file
+        .clone()
+        .or_else(|| env_var("GOOGLE_APPLICATION_CREDENTIALS"))
+    {
+        return Some(expand_home(&path));
+    }
+    let adc = dirs::config_dir()?.join("gcloud/application_default_credentials.json");
+    adc.exists().then_some(adc)
+}
+
+fn load_google_credentials(path: &PathBuf) -> Result<GoogleCredentials, CloudAuthError> {
+    let display = path.display().to_string();
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| CloudAuthError::CredentialsFile(display.clone(), e.to_string()))?;
+    serde_json::from_str(&text).map_err(|e| CloudAuthError::CredentialsFile(display, e.to_string()))
+}
+
+/// Vertex project: the config, then `GOOGLE_CLOUD_PROJECT`, then the
+/// credentials file
+pub fn vertex_project(config: &VertexConfig) -> Option<String> {
+    config
+        .project_id
+        .clone()
+        .or_else(|| env_var("GOOGLE_CLOUD_PROJECT"))
+        .or_else(|| {
+            let credentials = load_google_credentials(&google_credentials_path(config)?).ok()?;
+            credentials.project_id.or(credentials.quota_project_id)
+        })
+}
+
+/// Vertex region: the config, then `GOOGLE_CLOUD_LOCATION`, then `us-central1`
+pub fn vertex_region(config: &VertexConfig) -> String {
+    config
+        .region
+        .clone()
+        .or_else(|| env_var("GOOGLE_CLOUD_LOCATION"))
+        .unwrap_or_else(|| "us-central1".to_string())
+}
+
+/// RS256-signed JWT asserting a service account, for the `jwt-bearer` grant
+fn service_account_jwt(
+    client_email: &str,
+    private_key_pem: &str,
+    audience: &str,
+    now: DateTime<Utc>,
+) -> Result<String, CloudAuthError> {
+    let der = private_key_pem
+        .lines()
+        .filter(|line| !line.starts_with("-----"))
+        .collect::<String>();
+    let der = STANDARD
+        .decode(der.trim())
+        .map_err(|e| CloudAuthError::InvalidCredentials(format!("private_key: {}", e)))?;
+    let key = signature::RsaKeyPair::from_pkcs8(&der)
+        .map_err(|e| CloudAuthError::InvalidCredentials(format!("private_key: {}", e)))?;
+
+    let header = json!({"alg": "RS256", "typ": "JWT"});
+    let claims = json!({
+        "iss": client_email,
+        "scope": CLOUD_PLATFORM_SCOPE,
+        "aud": audience,
+        "iat": now.timestamp(),
+        "exp": now.timestamp() + 3600
+    });
+    let signing_input = format!(
+        "{}.{}",
+        URL_SAFE_NO_PAD.encode(header.to_string()),
+        URL_SAFE_NO_PAD.encode(claims.to_string())
+    );
+
+    let mut sig = vec![0; key.public().modulus_len()];
+    key.sign(
+        &signature::RSA_PKCS1_SHA256,
+        &ring::rand::SystemRandom::new(),
+        signing_input.as_bytes(),
+        &mut sig,
+    )
+    .map_err(|_| CloudAuthError::InvalidCredentials("failed to sign JWT".to_string()))?;
+    Ok(format!("{}.{}", signing_input, URL_SAFE_NO_PAD.encode(sig)))
+}
+
+/// An OAuth access token for Vertex AI, from the cache while it is fresh
+pub async fn google_access_token(
+    client: &reqwest::Client,
+    config: &VertexConfig,
+) -> Result<String, CloudAuthError> {
+    let path = google_credentials_path(config).ok_or(CloudAuthError::NoGoogleCredentials)?;
+    let cache_key = format!(
+        "{}|{}",
+        path.display(),
+        config.token_url.as_deref().unwrap_or_default()
+    );
+    if let Ok(tokens) = TOKENS.lock() {
+        if let Some(cached) = tokens
+            .get(&cache_key)
+            .filter(|t| t.expires > Instant::now())
+        {
+            return Ok(cached.token.clone());
+        }
+    }
+
+    let credentials = load_google_credentials(&path)?;
+    let token_url = config
+        .token_url
+        .clone()
+        .or_else(|| credentials.token_uri.clone())
+        .unwrap_or_else(|| GOOGLE_TOKEN_URL.to_string());
+    let missing = |field: &str| CloudAuthError::InvalidCredentials(format!("missing {}", field));
+
+    let form: Vec<(&str, String)> = match credentials.kind.as_str() {
+        "service_account" => {
+            let email = credentials
+                .client_email
+                .as_deref()
+                .ok_or(missing("client_email"))?;
+            let key = credentials
+                .private_key
+                .as_deref()
+                .ok_or(missing("private_key"))?;
+            vec![
+                (
+                    "grant_type",
+                    "urn:ietf:params:oauth:grant-type:jwt-bearer".to_string(),
+                ),
+                (
+                    "assertion",
+                    service_account_jwt(email, key, &token_url, Utc::now())?,
+                ),
+            ]
+        }
+        "authorized_user" => vec![
+            ("grant_type", "refresh_token".to_string()),
+            (
+                "client_id",
+                credentials.client_id.ok_or(missing("client_id"))?,
+            ),
+            (
+                "client_secret",
+                credentials.client_secret.ok_or(missing("client_secret"))?,
+            ),
+            (
+                "refresh_token",
+                credentials.refresh_token.ok_or(missing("refresh_token"))?,
+            ),
+        ],
+        other => {
+            return Err(CloudAuthError::InvalidCredentials(format!(
+                "unsupported credentials type '{}'",
+                other
+            )))
+        }
+    };
+
+    let response = client
+        .post(&token_url)
+        .form(&form)
+        .send()
+        .await
+        .map_err(|e| CloudAuthError::TokenRequest(e.to_string()))?;
+    let status = response.status();
+    let body: Value = response
+        .json()
+        .await
+        .map_err(|e| CloudAuthError::TokenRequest(e.to_string()))?;
+    let Some(token) = body["access_token"]
+        .as_str()
+        .filter(|_| status.is_success())
+    else {
+        return Err(CloudAuthError::TokenRequest(format!(
+            "HTTP {}: {}",
+            status, body
+        )));
+    };
+
+    let lifetime = Duration::from_secs(body["expires_in"].as_u64().unwrap_or(3600));
+    if let Ok(mut tokens) = TOKENS.lock() {
+        tokens.insert(
+            cache_key,
+            CachedToken {
+                token: token.to_string(),
+                expires: Instant::now() + lifetime.saturating_sub(TOKEN_REFRESH_MARGIN),
+            },
+        );
+    }
+    Ok(token.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{body_string_contains, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    const TEST_KEY: &str = include_str!("../tests/fixtures/cloud/service_account_key.pem");
+
+    #[test]
+    fn test_sign_v4_vanilla() {
+        // "get-vanilla" from the AWS Signature Version 4 test suite
+        let credentials = AwsCredentials {
+            access_key_id: "AKIDEXAMPLE".to_string(),
+            secret_access_key: "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY".to_string(),
+            session_token: None,
+        };
+        let now = DateTime::parse_from_rfc3339("2015-08-30T12:36:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let url = Url::parse("https://example.amazonaws.com/").unwrap();
+
+        let headers = sign_v4(
+            "GET",
+            &url,
+            &[],
+            b"",
+            &credentials,
+            "us-east-1",
+            "service",
+            now,
+        );
+        assert_eq!(
+            headers[0],
+            ("x-amz-date".to_string(), "20150830T123600Z".to_string())
+        );
+        assert_eq!(
+            headers[1].1,
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, \
+             SignedHeaders=host;x-amz-date, \
+             Signature=5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31"
+        );
+    }
+
+    #[test]
+    fn test_aws_credentials_profile() {
+        let file = "[default]\naws_access_key_id = AKIA1\naws_secret_access_key = s1\n\n\
+                    [work]\naws_access_key_id=AKIA2\naws_secret_access_key=s2\naws_session_token=t2\n";
+        let work = AwsCredentials::from_profile(file, "work").unwrap();
+        assert_eq!(work.access_key_id, "AKIA2");
+        assert_eq!(work.session_token.as_deref(), Some("t2"));
+        assert!(AwsCredentials::from_profile(file, "missing").is_none());
+    }
+
+    #[test]
+    fn test_aws_region() {
+        let url = "https://bedrock-runtime.eu-west-3.amazonaws.com";
+        let config = AwsConfig {
+            region: Some("ap-south-1".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(aws_region(&config, url), "ap-south-1");
+        if env_var("AWS_REGION").is_none() && env_var("AWS_DEFAULT_REGION").is_none() {
+            assert_eq!(aws_region(&AwsConfig::default(), url), "eu-west-3");
+            assert_eq!(
+                aws_region(&AwsConfig::default(), "http://127.0.0.1:9000"),
+                "us-east-1"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_service_account_token() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/token"))
+            .and(body_string_contains("jwt-bearer"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "access_token": "ya29.mock",
+                "expires_in": 3599,
+                "token_type": "Bearer"
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let key_path = dir.path().join("sa.json");
+        std::fs::write(
+            &key_path,
+            json!({
+                "type": "service_account",
+                "project_id": "sa-project",
+                "client_email": "agent@sa-project.iam.gserviceaccount.com",
+                "private_key": TEST_KEY,
+                "token_uri": format!("{}/token", server.uri())
+            })
+            .to_string(),
+        )
+        .unwrap();
+        let config = VertexConfig {
+            credentials_file: Some(key_path.display().to_string()),
+            ..Default::default()
+        };
+
+        let client = reqwest::Client::new();
+        assert_eq!(
+            google_access_token(&client, &config).await.unwrap(),
+            "ya29.mock"
+        );
+        // Served from the cache the second time (the mock expects one call)
+        assert_eq!(
+            google_access_token(&client, &config).await.unwrap(),
+            "ya29.mock"
+        );
+        if std::env::var("GOOGLE_CLOUD_PROJECT").is_err() {
+            assert_eq!(vertex_project(&config).as_deref(), Some("sa-project"));
+        }
+    }
+
+    #[test]
+    fn test_service_account_jwt() {
+        let now = Utc::now();
+        let jwt = service_account_jwt("a@b.iam", TEST_KEY, "https://token", now).unwrap();
+        let parts: Vec<&str> = jwt.split('.').collect();
+        assert_eq!(parts.len(), 3);
+
+        let claims: Value =
+            serde_json::from_slice(&URL_SAFE_NO_PAD.decode(parts[1]).unwrap()).unwrap();
+        assert_eq!(claims["iss"], "a@b.iam");
+        assert_eq!(claims["aud"], "https://token");
+        assert_eq!(claims["exp"], now.timestamp() + 3600);
+
+        let der = STANDARD
+            .decode(
+                TEST_KEY
+                    .lines()
+                    .filter(|l| !l.starts_with("-----"))
+                    .collect::<String>(),
+            )
+            .unwrap();
+        let key = signature::RsaKeyPair::from_pkcs8(&der).unwrap();
+        signature::UnparsedPublicKey::new(
+            &signature::RSA_PKCS1_2048_8192_SHA256,
+            key.public().as_ref(),
+        )
+        .verify(
+            format!("{}.{}", parts[0], parts[1]).as_bytes(),
+            &URL_SAFE_NO_PAD.decode(parts[2]).unwrap(),
+        )
+        .unwrap();
+    }
+}
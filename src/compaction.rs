@@ -1,27 +1,19 @@
 //! Context Compaction - Manages context window limits for long-running sessions.
 //!
 //! Features:
-//! - Token estimation for messages
+//! - Token counting for messages via the model's tokenizer
 //! - Context window limit detection
 //! - PreCompact hook triggering
 //! - Conversation summarization
 //! - Critical information preservation
 
 use crate::hooks::{HookEngine, HookEvent, HookInput};
+use crate::models;
 use crate::proxy::{ChatCompletionRequest, ChatMessage, MessageContent};
+use crate::tokenizer::{self, Tokenizer};
 use serde::{Deserialize, Serialize};
 use tracing::{debug, info, warn};
 
-/// Context window sizes for different models (in tokens)
-const CLAUDE_OPUS_CONTEXT: usize = 200_000;
-const CLAUDE_SONNET_CONTEXT: usize = 200_000;
-const CLAUDE_HAIKU_CONTEXT: usize = 200_000;
-const GPT4_CONTEXT: usize = 128_000;
-const GPT4O_CONTEXT: usize = 128_000;
-const GPT35_CONTEXT: usize = 16_385;
-const GEMINI_PRO_CONTEXT: usize = 1_000_000;
-const DEFAULT_CONTEXT: usize = 128_000;
-
 /// Safety margin - trigger compaction before hitting the limit
 const COMPACTION_THRESHOLD: f32 = 0.85;
 
@@ -48,7 +40,7 @@ pub struct CompactionConfig {
 impl Default for CompactionConfig {
     fn default() -> Self {
         Self {
-            max_context_tokens: DEFAULT_CONTEXT,
+            max_context_tokens: models::DEFAULT_CONTEXT_WINDOW,
             threshold: COMPACTION_THRESHOLD,
             preserve_recent: 4,
             preserve_system: true,
@@ -69,58 +61,40 @@ impl CompactionConfig {
     }
 }
 
-/// Get context window size for a model
+/// Get context window size for a model from the model registry
 pub fn get_context_window(model: &str) -> usize {
-    let model_lower = model.to_lowercase();
-
-    if model_lower.contains("opus") {
-        CLAUDE_OPUS_CONTEXT
-    } else if model_lower.contains("sonnet") {
-        CLAUDE_SONNET_CONTEXT
-    } else if model_lower.contains("haiku") {
-        CLAUDE_HAIKU_CONTEXT
-    } else if model_lower.contains("claude") {
-        CLAUDE_SONNET_CONTEXT // Default Claude
-    } else if model_lower.contains("gpt-4o") {
-        GPT4O_CONTEXT
-    } else if model_lower.contains("gpt-4") {
-        GPT4_CONTEXT
-    } else if model_lower.contains("gpt-3.5") {
-        GPT35_CONTEXT
-    } else if model_lower.contains("gemini") {
-        GEMINI_PRO_CONTEXT
-    } else if model_lower.contains("o1") || model_lower.contains("o3") {
-        GPT4O_CONTEXT
-    } else {
-        DEFAULT_CONTEXT
-    }
+    models::context_window(model)
 }
 
-/// Estimate token count for a string (approximate: ~4 chars per token)
+/// Count tokens in a string when the model is unknown (cl100k)
 pub fn estimate_tokens(text: &str) -> usize {
-    // More accurate estimation considering whitespace and punctuation
-    let char_count = text.chars().count();
-    let word_count = text.split_whitespace().count();
+    tokenizer::CL100K.count(text)
+}
 
-    // Use a weighted average of character-based and word-based estimation
-    // Most tokenizers use subword units, so this approximates that
-    let char_estimate = char_count / 4;
-    let word_estimate = (word_count as f32 * 1.3) as usize;
+/// Count tokens in a message when the model is unknown (cl100k)
+pub fn estimate_message_tokens(message: &ChatMessage) -> usize {
+    count_message_tokens(&tokenizer::CL100K, message)
+}
 
-    // Take the average, biased toward character count
-    (char_estimate * 2 + word_estimate) / 3
+/// PDFs cost ~1500 tokens a page; without parsing, assume a page per 50 KB
+fn document_tokens(part: &crate::proxy::ContentPart) -> usize {
+    match (&part.file, part.inline_data()) {
+        (Some(_), Some((_, data))) => (data.len() * 3 / 4 / 50_000).max(1) * 1500,
+        _ => 0,
+    }
 }
 
-/// Estimate token count for a message
-pub fn estimate_message_tokens(message: &ChatMessage) -> usize {
+/// Count tokens in a message with a specific tokenizer
+pub fn count_message_tokens(tokenizer: &dyn Tokenizer, message: &ChatMessage) -> usize {
     let content_tokens = match &message.content {
-        MessageContent::Text(text) => estimate_tokens(text),
+        MessageContent::Text(text) => tokenizer.count(text),
         MessageContent::Parts(parts) => {
             parts
                 .iter()
                 .map(|p| {
-                    p.text.as_ref().map(|t| estimate_tokens(t)).unwrap_or(0)
+                    p.text.as_ref().map(|t| tokenizer.count(t)).unwrap_or(0)
                         + if p.image_url.is_some() { 1000 } else { 0 } // Images cost ~1000 tokens
+                        + document_tokens(p)
                 })
                 .sum()
         }
@@ -130,7 +104,7 @@ pub fn estimate_message_tokens(message: &ChatMessage) -> usize {
     let overhead = 4 + message
         .name
         .as_ref()
-        .map(|n| estimate_tokens(n))
+        .map(|n| tokenizer.count(n))
         .unwrap_or(0);
 
     // Tool calls add significant tokens
@@ -140,7 +114,7 @@ pub fn estimate_message_tokens(message: &ChatMessage) -> usize {
         .map(|calls| {
             calls
                 .iter()
-                .map(|c| estimate_tokens(&c.to_string()))
+                .map(|c| tokenizer.count(&c.to_string()))
                 .sum::<usize>()
         })
         .unwrap_or(0);
@@ -148,9 +122,18 @@ pub fn estimate_message_tokens(message: &ChatMessage) -> usize {
     content_tokens + overhead + tool_tokens
 }
 
-/// Estimate total token count for a request
+/// Estimate total token count for a request with its model's calibrated tokenizer
 pub fn estimate_request_tokens(request: &ChatCompletionRequest) -> usize {
-    let message_tokens: usize = request.messages.iter().map(estimate_message_tokens).sum();
+    count_request_tokens(&tokenizer::for_model(&request.model), request)
+}
+
+/// Count tokens in a request with a specific tokenizer
+pub fn count_request_tokens(tokenizer: &dyn Tokenizer, request: &ChatCompletionRequest) -> usize {
+    let message_tokens: usize = request
+        .messages
+        .iter()
+        .map(|m| count_message_tokens(tokenizer, m))
+        .sum();
 
     // Add tool definitions if present
     let tool_tokens = request
@@ -159,7 +142,7 @@ pub fn estimate_request_tokens(request: &ChatCompletionRequest) -> usize {
         .map(|tools| {
             tools
                 .iter()
-                .map(|t| estimate_tokens(&t.to_string()))
+                .map(|t| tokenizer.count(&t.to_string()))
                 .sum::<usize>()
         })
         .unwrap_or(0);
@@ -527,6 +510,7 @@ mod tests {
             stream: None,
             tools: None,
             tool_choice: None,
+            response_format: None,
             extra: HashMap::new(),
         }
     }
@@ -546,19 +530,16 @@ mod tests {
 
     #[test]
     fn test_get_context_window() {
+        assert_eq!(get_context_window("claude-3-opus-20240229"), 200_000);
+        assert_eq!(get_context_window("claude-3-5-sonnet-20241022"), 200_000);
+        assert_eq!(get_context_window("gpt-4o"), 128_000);
+        assert_eq!(get_context_window("gpt-4"), 8_192);
+        assert_eq!(get_context_window("gpt-3.5-turbo"), 16_385);
+        assert_eq!(get_context_window("gemini-pro"), 1_000_000);
         assert_eq!(
-            get_context_window("claude-3-opus-20240229"),
-            CLAUDE_OPUS_CONTEXT
-        );
-        assert_eq!(
-            get_context_window("claude-3-5-sonnet-20241022"),
-            CLAUDE_SONNET_CONTEXT
+            get_context_window("unknown-model"),
+            models::DEFAULT_CONTEXT_WINDOW
         );
-        assert_eq!(get_context_window("gpt-4o"), GPT4O_CONTEXT);
-        assert_eq!(get_context_window("gpt-4"), GPT4_CONTEXT);
-        assert_eq!(get_context_window("gpt-3.5-turbo"), GPT35_CONTEXT);
-        assert_eq!(get_context_window("gemini-pro"), GEMINI_PRO_CONTEXT);
-        assert_eq!(get_context_window("unknown-model"), DEFAULT_CONTEXT);
     }
 
     #[test]
@@ -640,6 +621,7 @@ mod tests {
 
     #[test]
     fn test_generate_summary() {
+        #[allow(clippy::useless_vec)]
         let messages = vec![
             create_test_message("user", "What is Rust?"),
             create_test_message("assistant", "Rust is a systems programming language."),
@@ -722,13 +704,13 @@ mod tests {
     #[test]
     fn test_compaction_config_for_model() {
         let config = CompactionConfig::for_model("claude-3-opus");
-        assert_eq!(config.max_context_tokens, CLAUDE_OPUS_CONTEXT);
+        assert_eq!(config.max_context_tokens, 200_000);
 
         let config = CompactionConfig::for_model("gpt-4o-mini");
-        assert_eq!(config.max_context_tokens, GPT4O_CONTEXT);
+        assert_eq!(config.max_context_tokens, 128_000);
 
         let config = CompactionConfig::for_model("gemini-1.5-pro");
-        assert_eq!(config.max_context_tokens, GEMINI_PRO_CONTEXT);
+        assert_eq!(config.max_context_tokens, 2_097_152);
     }
 
     #[test]
@@ -768,11 +750,13 @@ mod tests {
                     content_type: "text".to_string(),
                     text: Some("Hello world".to_string()),
                     image_url: None,
+                    file: None,
                 },
                 ContentPart {
                     content_type: "text".to_string(),
                     text: Some("How are you?".to_string()),
                     image_url: None,
+                    file: None,
                 },
             ]),
             name: None,
@@ -796,6 +780,7 @@ mod tests {
                 image_url: Some(serde_json::json!({
                     "url": "data:image/png;base64,iVBORw0..."
                 })),
+                file: None,
             }]),
             name: None,
             tool_calls: None,
@@ -915,6 +900,7 @@ mod tests {
 
     #[test]
     fn test_generate_summary_with_tool_markers() {
+        #[allow(clippy::useless_vec)]
         let messages = vec![
             create_test_message("user", "Run ls command"),
             ChatMessage {
@@ -972,7 +958,7 @@ mod tests {
     #[test]
     fn test_compaction_config_default() {
         let config = CompactionConfig::default();
-        assert_eq!(config.max_context_tokens, DEFAULT_CONTEXT);
+        assert_eq!(config.max_context_tokens, models::DEFAULT_CONTEXT_WINDOW);
         assert_eq!(config.threshold, COMPACTION_THRESHOLD);
         assert_eq!(config.preserve_recent, 4);
         assert!(config.preserve_system);
@@ -1013,7 +999,7 @@ mod tests {
 
         // Analysis should have valid values
         assert!(analysis.current_tokens > 0);
-        assert_eq!(analysis.max_tokens, DEFAULT_CONTEXT);
+        assert_eq!(analysis.max_tokens, models::DEFAULT_CONTEXT_WINDOW);
         assert!(!analysis.needs_compaction); // Small request
         assert_eq!(analysis.tokens_to_free, 0);
 
@@ -1024,12 +1010,12 @@ mod tests {
     #[test]
     fn test_get_context_window_edge_cases() {
         // Test model name variations
-        assert_eq!(get_context_window("CLAUDE-3-OPUS"), CLAUDE_OPUS_CONTEXT);
-        assert_eq!(get_context_window("Claude-Sonnet"), CLAUDE_SONNET_CONTEXT);
-        assert_eq!(get_context_window("GPT-4O-2024-05-13"), GPT4O_CONTEXT);
-        assert_eq!(get_context_window("gpt-3.5-turbo-16k"), GPT35_CONTEXT);
-        assert_eq!(get_context_window("o1-preview"), GPT4O_CONTEXT);
-        assert_eq!(get_context_window("o3-mini"), GPT4O_CONTEXT);
+        assert_eq!(get_context_window("CLAUDE-3-OPUS"), 200_000);
+        assert_eq!(get_context_window("Claude-Sonnet"), 200_000);
+        assert_eq!(get_context_window("GPT-4O-2024-05-13"), 128_000);
+        assert_eq!(get_context_window("gpt-3.5-turbo-16k"), 16_385);
+        assert_eq!(get_context_window("o1-preview"), 128_000);
+        assert_eq!(get_context_window("o3-mini"), 200_000);
     }
 
     #[test]
@@ -1,18 +1,31 @@
 //! Configuration loader with environment variable substitution.
 //!
-//! Loads configuration from:
+//! Loads configuration from layers, each overriding the ones before:
 //! 1. Default values
-//! 2. `.openclaudia/config.yaml` in project directory
-//! 3. `~/.openclaudia/config.yaml` in home directory
-//! 4. Environment variables with `OPENCLAUDIA_` prefix
+//! 2. `~/.openclaudia/config.yaml` in home directory (user)
+//! 3. `.openclaudia/config.yaml` in project directory (project)
+//! 4. `.openclaudia/config.local.yaml`, git-ignored (local)
+//! 5. Environment variables with `OPENCLAUDIA_` prefix and provider API key
+//!    variables such as `ANTHROPIC_API_KEY`
+//! 6. Command-line flags
+//!
+//! Strings in config files may reference environment variables as `${VAR}`
+//! or `${VAR:-default}`.
+
+use config::{Config, ConfigError, File, FileFormat};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
-use config::{Config, ConfigError, Environment, File};
-use serde::Deserialize;
-use std::collections::HashMap;
-use std::path::PathBuf;
+use crate::config_schema;
+use crate::credentials;
 
 /// Main configuration structure
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Clone, JsonSchema)]
 pub struct AppConfig {
     pub proxy: ProxyConfig,
     pub providers: HashMap<String, ProviderConfig>,
@@ -22,10 +35,32 @@ pub struct AppConfig {
     pub session: SessionConfig,
     #[serde(default)]
     pub keybindings: KeybindingsConfig,
+    #[serde(default)]
+    pub web: WebToolsConfig,
+    #[serde(default)]
+    pub issues: IssuesConfig,
+    /// Per-model prices overriding the built-in table, keyed by model name or prefix
+    #[serde(default)]
+    pub pricing: HashMap<String, ModelPrice>,
+    /// Token and cost limits for sessions, loop runs, subagents and days
+    #[serde(default)]
+    pub budgets: BudgetsConfig,
+    /// Model catalog entries overriding or extending the built-in registry
+    #[serde(default)]
+    pub models: HashMap<String, ModelOverride>,
+    /// Where API keys and OAuth tokens are stored
+    #[serde(default)]
+    pub credentials: CredentialsConfig,
+    /// Which Claude Max account the proxy uses per request
+    #[serde(default)]
+    pub oauth: OAuthConfig,
+    /// Model aliases and rules picking the provider and model per request
+    #[serde(default)]
+    pub routing: RoutingConfig,
 }
 
 /// Proxy server configuration
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Clone, JsonSchema)]
 pub struct ProxyConfig {
     #[serde(default = "default_port")]
     pub port: u16,
@@ -33,6 +68,12 @@ pub struct ProxyConfig {
     pub host: String,
     #[serde(default = "default_target")]
     pub target: String,
+    /// Retry policy for upstream provider calls
+    #[serde(default)]
+    pub retry: RetryConfig,
+    /// Providers tried in order when the target stays unavailable after retries
+    #[serde(default)]
+    pub fallback: Vec<FallbackProvider>,
 }
 
 fn default_port() -> u16 {
@@ -47,8 +88,77 @@ fn default_target() -> String {
     "anthropic".to_string()
 }
 
+/// Retry policy for rate limits, overloads and transient connection errors
+///
+/// Example:
+/// ```yaml
+/// proxy:
+///   retry:
+///     max_retries: 5
+///     initial_backoff_ms: 1000
+/// ```
+#[derive(Debug, Deserialize, Clone, JsonSchema)]
+pub struct RetryConfig {
+    /// Retries per provider after the first attempt (0 disables retries)
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+    /// Delay before the first retry; doubles on each further retry
+    #[serde(default = "default_initial_backoff_ms")]
+    pub initial_backoff_ms: u64,
+    /// Upper bound for any single delay, including `retry-after`
+    #[serde(default = "default_max_backoff_ms")]
+    pub max_backoff_ms: u64,
+}
+
+fn default_max_retries() -> u32 {
+    3
+}
+
+fn default_initial_backoff_ms() -> u64 {
+    500
+}
+
+fn default_max_backoff_ms() -> u64 {
+    30_000
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: default_max_retries(),
+            initial_backoff_ms: default_initial_backoff_ms(),
+            max_backoff_ms: default_max_backoff_ms(),
+        }
+    }
+}
+
+/// A fallback provider and the model to request from it
+///
+/// Example:
+/// ```yaml
+/// proxy:
+///   target: anthropic
+///   fallback:
+///     - provider: openrouter
+///       models:
+///         claude-sonnet-4-20250514: anthropic/claude-sonnet-4
+///     - provider: ollama
+///       model: qwen3:8b
+/// ```
+#[derive(Debug, Deserialize, Clone, JsonSchema)]
+pub struct FallbackProvider {
+    /// Name of a configured provider
+    pub provider: String,
+    /// Model to use for any requested model without a mapping
+    #[serde(default)]
+    pub model: Option<String>,
+    /// Requested model -> model name on this provider
+    #[serde(default)]
+    pub models: HashMap<String, String>,
+}
+
 /// Thinking/reasoning mode configuration
-#[derive(Debug, Deserialize, Clone, Default)]
+#[derive(Debug, Deserialize, Clone, Default, JsonSchema)]
 pub struct ThinkingConfig {
     /// Enable thinking mode (default: true for supported providers)
     #[serde(default = "default_thinking_enabled")]
@@ -72,7 +182,7 @@ fn default_thinking_enabled() -> bool {
 }
 
 /// Provider configuration (Anthropic, OpenAI, Google, etc.)
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Clone, Default, JsonSchema)]
 pub struct ProviderConfig {
     pub api_key: Option<String>,
     pub base_url: String,
@@ -82,10 +192,290 @@ pub struct ProviderConfig {
     pub headers: HashMap<String, String>,
     #[serde(default)]
     pub thinking: ThinkingConfig,
+    /// Adapter to use instead of the one named after the provider
+    /// (e.g. `responses` for the OpenAI Responses API)
+    #[serde(default)]
+    pub adapter: Option<String>,
+    /// Amazon Bedrock region and credentials
+    #[serde(default)]
+    pub aws: Option<AwsConfig>,
+    /// Google Vertex AI project, region and credentials
+    #[serde(default)]
+    pub vertex: Option<VertexConfig>,
+    /// Chat endpoint path replacing the adapter's; `{model}` is substituted
+    /// (e.g. `/openai/deployments/{model}/chat/completions`)
+    #[serde(default)]
+    pub path: Option<String>,
+    /// Query parameters added to every request (e.g. `api-version`)
+    #[serde(default)]
+    pub query: HashMap<String, String>,
+    /// How the API key is sent (default: the adapter's own scheme)
+    #[serde(default)]
+    pub auth: Option<AuthStyle>,
+    /// Anthropic prompt caching breakpoints and lifetime
+    #[serde(default)]
+    pub cache: PromptCacheConfig,
+}
+
+/// How a provider's API key is sent upstream
+///
+/// Example, for a gateway in front of an OpenAI-compatible server:
+/// ```yaml
+/// providers:
+///   gateway:
+///     base_url: https://llm.internal.example.com
+///     adapter: openai
+///     path: /serving/{model}/v1/chat/completions
+///     query:
+///       team: platform
+///     auth: x-api-key
+///     headers:
+///       X-Request-Source: openclaudia
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum AuthStyle {
+    /// `Authorization: Bearer <key>`
+    Bearer,
+    /// `api-key: <key>` (Azure OpenAI)
+    ApiKey,
+    /// `x-api-key: <key>`
+    XApiKey,
+    /// Send no key at all
+    None,
+}
+
+/// Prompt caching for the `anthropic` adapter. Breakpoints go on the system
+/// prompt, the last tool definition and the most recent user turns (tool
+/// results included), so each request reads the transcript cached by the one
+/// before. Anthropic allows at most 4 breakpoints per request.
+///
+/// Example:
+/// ```yaml
+/// providers:
+///   anthropic:
+///     base_url: https://api.anthropic.com
+///     cache:
+///       breakpoints: 2
+///       ttl: 1h
+/// ```
+#[derive(Debug, Deserialize, Clone, PartialEq, JsonSchema)]
+pub struct PromptCacheConfig {
+    /// Mark anything for caching at all (default: true)
+    #[serde(default = "default_cache_enabled")]
+    pub enabled: bool,
+    /// Rolling breakpoints on the latest user turns, within the 4-breakpoint
+    /// limit (default: 2)
+    #[serde(default = "default_cache_breakpoints")]
+    pub breakpoints: usize,
+    /// Cache lifetime; `1h` writes cost twice the base input price instead
+    /// of 1.25 times
+    #[serde(default)]
+    pub ttl: CacheTtl,
+}
+
+fn default_cache_enabled() -> bool {
+    true
+}
+
+fn default_cache_breakpoints() -> usize {
+    2
+}
+
+impl Default for PromptCacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_cache_enabled(),
+            breakpoints: default_cache_breakpoints(),
+            ttl: CacheTtl::default(),
+        }
+    }
+}
+
+/// How long Anthropic keeps a cached prompt prefix after its last use
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, JsonSchema)]
+pub enum CacheTtl {
+    #[default]
+    #[serde(rename = "5m")]
+    FiveMinutes,
+    #[serde(rename = "1h")]
+    OneHour,
+}
+
+/// Amazon Bedrock settings for the `bedrock` (Converse) and `bedrock-invoke`
+/// (InvokeModel) adapters. Unset keys fall back to `AWS_ACCESS_KEY_ID`,
+/// `AWS_SECRET_ACCESS_KEY` and `AWS_SESSION_TOKEN`, then to a profile in the
+/// shared credentials file. A provider `api_key` is sent as a Bedrock API key
+/// instead of signing requests.
+///
+/// Example:
+/// ```yaml
+/// providers:
+///   bedrock:
+///     base_url: https://bedrock-runtime.us-east-1.amazonaws.com
+///     aws:
+///       region: us-east-1
+///       profile: work
+/// ```
+#[derive(Debug, Deserialize, Clone, Default, PartialEq, JsonSchema)]
+pub struct AwsConfig {
+    /// Signing region (default: `AWS_REGION`, then the `base_url` host)
+    #[serde(default)]
+    pub region: Option<String>,
+    /// Profile in the credentials file (default: `AWS_PROFILE`, then `default`)
+    #[serde(default)]
+    pub profile: Option<String>,
+    /// Shared credentials file (default: `~/.aws/credentials`)
+    #[serde(default)]
+    pub credentials_file: Option<String>,
+    #[serde(default)]
+    pub access_key_id: Option<String>,
+    #[serde(default)]
+    pub secret_access_key: Option<String>,
+    #[serde(default)]
+    pub session_token: Option<String>,
+}
+
+/// Google Vertex AI settings for the `vertex` adapter. Requests carry an
+/// OAuth token minted from a service-account key or `gcloud` user credentials;
+/// a provider `api_key` is sent as a ready-made access token instead.
+///
+/// Example:
+/// ```yaml
+/// providers:
+///   vertex:
+///     base_url: https://us-east5-aiplatform.googleapis.com
+///     vertex:
+///       project_id: my-project
+///       region: us-east5
+///       credentials_file: ~/keys/vertex-sa.json
+/// ```
+#[derive(Debug, Deserialize, Clone, Default, PartialEq, JsonSchema)]
+pub struct VertexConfig {
+    /// GCP project (default: `GOOGLE_CLOUD_PROJECT`, then the credentials file)
+    #[serde(default)]
+    pub project_id: Option<String>,
+    /// Region in request paths (default: `GOOGLE_CLOUD_LOCATION`, then `us-central1`)
+    #[serde(default)]
+    pub region: Option<String>,
+    /// Service-account key or `authorized_user` JSON (default:
+    /// `GOOGLE_APPLICATION_CREDENTIALS`, then the gcloud application default)
+    #[serde(default)]
+    pub credentials_file: Option<String>,
+    /// Token endpoint overriding the `token_uri` of the credentials file
+    #[serde(default)]
+    pub token_url: Option<String>,
+}
+
+/// Model price in USD per million tokens
+///
+/// Example:
+/// ```yaml
+/// pricing:
+///   claude-sonnet-4: { input: 3.0, output: 15.0, cache_read: 0.3, cache_write: 3.75 }
+///   qwen3: { input: 0.0, output: 0.0 }
+/// ```
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, JsonSchema)]
+pub struct ModelPrice {
+    pub input: f64,
+    pub output: f64,
+    /// Cached input reads (defaults to the input price)
+    #[serde(default)]
+    pub cache_read: Option<f64>,
+    /// Cache writes (defaults to the input price)
+    #[serde(default)]
+    pub cache_write: Option<f64>,
+}
+
+/// Model registry override. Unset fields keep the catalog values; models
+/// missing from the catalog inherit from the closest family (e.g. `claude`).
+///
+/// Example:
+/// ```yaml
+/// models:
+///   claude-sonnet-4-5: { context_window: 1000000 }
+///   my-finetune: { provider: openai, context_window: 32768, max_output: 4096, tools: true }
+///   llama3: { emulate_tools: true }
+/// ```
+#[derive(Debug, Deserialize, Clone, Default, PartialEq, JsonSchema)]
+pub struct ModelOverride {
+    #[serde(default)]
+    pub provider: Option<String>,
+    #[serde(default)]
+    pub context_window: Option<usize>,
+    #[serde(default)]
+    pub max_output: Option<usize>,
+    #[serde(default)]
+    pub tools: Option<bool>,
+    #[serde(default)]
+    pub vision: Option<bool>,
+    #[serde(default)]
+    pub thinking: Option<bool>,
+    /// Describe tools in the prompt and parse calls from the reply instead
+    /// of using native function calling (default: when `tools` is false)
+    #[serde(default)]
+    pub emulate_tools: Option<bool>,
+    #[serde(default)]
+    pub pricing: Option<ModelPrice>,
+}
+
+/// Token and cost budgets. Unset limits are unlimited.
+///
+/// Example:
+/// ```yaml
+/// budgets:
+///   warn_at: 0.8
+///   session: { max_cost_usd: 5.0 }
+///   loop: { max_tokens: 5000000, max_cost_usd: 20.0 }
+///   subagent: { max_tokens: 1000000 }
+///   daily: { max_cost_usd: 50.0 }
+/// ```
+#[derive(Debug, Deserialize, Clone, JsonSchema)]
+pub struct BudgetsConfig {
+    /// Fraction of a limit at which to warn
+    #[serde(default = "default_budget_warn_at")]
+    pub warn_at: f64,
+    /// Per chat or proxy session
+    #[serde(default)]
+    pub session: Budget,
+    /// Per `openclaudia loop` run
+    #[serde(default, rename = "loop")]
+    pub loop_run: Budget,
+    /// Per subagent
+    #[serde(default)]
+    pub subagent: Budget,
+    /// Per UTC day across everything recorded in the project
+    #[serde(default)]
+    pub daily: Budget,
+}
+
+fn default_budget_warn_at() -> f64 {
+    0.8
+}
+
+impl Default for BudgetsConfig {
+    fn default() -> Self {
+        Self {
+            warn_at: default_budget_warn_at(),
+            session: Budget::default(),
+            loop_run: Budget::default(),
+            subagent: Budget::default(),
+            daily: Budget::default(),
+        }
+    }
+}
+
+/// Limits for one budget scope. Tokens count input, cached input and output.
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, JsonSchema)]
+pub struct Budget {
+    #[serde(default)]
+    pub max_tokens: Option<u64>,
+    #[serde(default)]
+    pub max_cost_usd: Option<f64>,
 }
 
 /// Hooks configuration
-#[derive(Debug, Deserialize, Clone, Default)]
+#[derive(Debug, Deserialize, Clone, Default, JsonSchema)]
 pub struct HooksConfig {
     #[serde(default)]
     pub session_start: Vec<HookEntry>,
@@ -102,7 +492,7 @@ pub struct HooksConfig {
 }
 
 /// Individual hook entry
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Clone, JsonSchema)]
 pub struct HookEntry {
     #[serde(default)]
     pub matcher: Option<String>,
@@ -110,7 +500,7 @@ pub struct HookEntry {
 }
 
 /// Hook definition
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Clone, JsonSchema)]
 #[serde(tag = "type")]
 pub enum Hook {
     #[serde(rename = "command")]
@@ -136,7 +526,7 @@ fn default_prompt_timeout() -> u64 {
 }
 
 /// Session configuration
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Clone, JsonSchema)]
 pub struct SessionConfig {
     #[serde(default = "default_timeout_minutes")]
     pub timeout_minutes: u64,
@@ -145,7 +535,7 @@ pub struct SessionConfig {
 }
 
 /// Keybinding action names
-#[derive(Debug, Clone, PartialEq, Eq, Hash, serde::Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, serde::Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum KeyAction {
     /// Start a new session
@@ -184,7 +574,7 @@ pub enum KeyAction {
 
 /// Keybindings configuration
 /// Maps key combinations to actions. Use "none" to disable a keybinding.
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Clone, JsonSchema)]
 pub struct KeybindingsConfig {
     /// Map of key combination strings to action names
     /// Example: { "ctrl-x n": "new_session", "f2": "models", "tab": "none" }
@@ -255,92 +645,946 @@ impl Default for SessionConfig {
     }
 }
 
+/// Web tools configuration (`web:` section of config.yaml)
+#[derive(Debug, Deserialize, Clone, Default, JsonSchema)]
+pub struct WebToolsConfig {
+    #[serde(default)]
+    pub search: WebSearchConfig,
+    #[serde(default)]
+    pub network: NetworkPolicyConfig,
+}
+
+/// Outbound network policy for web tools (SSRF protection)
+///
+/// Example:
+/// ```yaml
+/// web:
+///   network:
+///     blocked_domains: [internal.example.com]
+///     max_response_bytes: 5242880
+///     max_redirects: 3
+/// ```
+#[derive(Debug, Deserialize, Clone, JsonSchema)]
+pub struct NetworkPolicyConfig {
+    /// Allow requests to private, loopback and link-local addresses
+    #[serde(default)]
+    pub allow_private_networks: bool,
+    /// If non-empty, only these domains (and subdomains) may be fetched
+    #[serde(default)]
+    pub allowed_domains: Vec<String>,
+    /// These domains (and subdomains) are never fetched
+    #[serde(default)]
+    pub blocked_domains: Vec<String>,
+    /// Maximum response body size in bytes
+    #[serde(default = "default_max_response_bytes")]
+    pub max_response_bytes: u64,
+    /// Maximum number of redirects to follow
+    #[serde(default = "default_max_redirects")]
+    pub max_redirects: usize,
+}
+
+fn default_max_response_bytes() -> u64 {
+    10 * 1024 * 1024
+}
+
+fn default_max_redirects() -> usize {
+    5
+}
+
+impl Default for NetworkPolicyConfig {
+    fn default() -> Self {
+        Self {
+            allow_private_networks: false,
+            allowed_domains: Vec::new(),
+            blocked_domains: Vec::new(),
+            max_response_bytes: default_max_response_bytes(),
+            max_redirects: default_max_redirects(),
+        }
+    }
+}
+
+/// Backend used by the `issue_*` tools
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum IssueBackend {
+    /// Built-in store in `.openclaudia/memory.db`
+    #[default]
+    Native,
+    /// External `chainlink` CLI (must be on PATH)
+    Chainlink,
+}
+
+/// Issue tracker configuration
+///
+/// Example:
+/// ```yaml
+/// issues:
+///   backend: chainlink
+/// ```
+#[derive(Debug, Deserialize, Clone, Default, JsonSchema)]
+pub struct IssuesConfig {
+    #[serde(default)]
+    pub backend: IssueBackend,
+}
+
+/// Storage for API keys and OAuth tokens
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum CredentialBackend {
+    /// `~/.openclaudia/credentials.enc`, encrypted with a machine key or
+    /// `OPENCLAUDIA_PASSPHRASE`
+    #[default]
+    File,
+    /// Secret Service keyring (Linux, needs the `keyring` build feature)
+    Keyring,
+}
+
+/// Credential store configuration
+///
+/// Example:
+/// ```yaml
+/// credentials:
+///   backend: keyring
+/// ```
+#[derive(Debug, Deserialize, Clone, Default, JsonSchema)]
+pub struct CredentialsConfig {
+    #[serde(default)]
+    pub backend: CredentialBackend,
+}
+
+/// OAuth account selection for proxied requests
+///
+/// A request's account comes from the `x-openclaudia-account` header, else
+/// the matching `routing.rules` entry's `account`, else `default_account`,
+/// else the account named `default`, else the most recently signed-in one.
+///
+/// Example:
+/// ```yaml
+/// oauth:
+///   default_account: personal
+/// ```
+#[derive(Debug, Deserialize, Clone, Default, JsonSchema)]
+pub struct OAuthConfig {
+    /// Account used when no header or routing rule picks one
+    #[serde(default)]
+    pub default_account: Option<String>,
+}
+
+/// Proxy model routing (see [`crate::routing`])
+///
+/// Rules are tried in order against the request as the client sent it; the
+/// first match sets the provider, model and/or Claude Max account. Otherwise
+/// an alias maps the requested name, and the provider follows from the model.
+///
+/// Example:
+/// ```yaml
+/// routing:
+///   aliases:
+///     fast: claude-haiku-4-5
+///     smart: claude-opus-4-5
+///     local: { provider: ollama, model: llama3.2 }
+///   rules:
+///     - name: large-context
+///       when: { min_prompt_tokens: 150000 }
+///       provider: google
+///       model: gemini-2.5-pro
+///     - when: { headers: { x-task: summarize }, has_tools: false }
+///       provider: ollama
+///       model: llama3.2
+///     - when: { client: cursor }
+///       account: work
+/// ```
+#[derive(Debug, Deserialize, Clone, Default, JsonSchema)]
+pub struct RoutingConfig {
+    /// Model names clients may request, listed by `/v1/models`
+    #[serde(default)]
+    pub aliases: HashMap<String, ModelAlias>,
+    /// Rules tried in order; the first match wins
+    #[serde(default)]
+    pub rules: Vec<RouteRule>,
+}
+
+/// Target of a model alias: a model name, or a provider and model
+#[derive(Debug, Deserialize, Clone, PartialEq, JsonSchema)]
+#[serde(untagged)]
+pub enum ModelAlias {
+    Model(String),
+    Route {
+        /// Provider to send to; defaults to the model's provider
+        #[serde(default)]
+        provider: Option<String>,
+        model: String,
+    },
+}
+
+impl ModelAlias {
+    pub fn model(&self) -> &str {
+        match self {
+            ModelAlias::Model(model) | ModelAlias::Route { model, .. } => model,
+        }
+    }
+
+    pub fn provider(&self) -> Option<&str> {
+        match self {
+            ModelAlias::Model(_) => None,
+            ModelAlias::Route { provider, .. } => provider.as_deref(),
+        }
+    }
+}
+
+/// Routing rule: where to send requests matching `when`
+#[derive(Debug, Deserialize, Clone, JsonSchema)]
+pub struct RouteRule {
+    /// Shown in logs when the rule matches
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Conditions, all of which must hold; empty matches every request
+    #[serde(default)]
+    pub when: RouteMatch,
+    /// Provider to send to; defaults to the model's provider
+    #[serde(default)]
+    pub provider: Option<String>,
+    /// Model to request; defaults to the requested model (or its alias)
+    #[serde(default)]
+    pub model: Option<String>,
+    /// Claude Max account for requests that stay on Anthropic (see `oauth`)
+    #[serde(default)]
+    pub account: Option<String>,
+}
+
+/// Request properties a routing rule matches on
+#[derive(Debug, Deserialize, Clone, Default, JsonSchema)]
+pub struct RouteMatch {
+    /// Requested model or alias; a trailing `*` matches by prefix
+    #[serde(default)]
+    pub model: Option<String>,
+    /// Whether the request offers tools
+    #[serde(default)]
+    pub has_tools: Option<bool>,
+    /// Whether any message contains an image
+    #[serde(default)]
+    pub has_images: Option<bool>,
+    /// Estimated prompt tokens at least this many
+    #[serde(default)]
+    pub min_prompt_tokens: Option<usize>,
+    /// Estimated prompt tokens at most this many
+    #[serde(default)]
+    pub max_prompt_tokens: Option<usize>,
+    /// Request headers that must have exactly these values
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// Case-insensitive substring of the client's User-Agent
+    #[serde(default)]
+    pub client: Option<String>,
+}
+
+/// Search backend used by the `web_search` tool
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchBackend {
+    /// Try every configured backend in order: SearXNG, DuckDuckGo, Tavily, Brave
+    #[default]
+    Auto,
+    /// Self-hosted SearXNG instance (requires `searxng_url`)
+    Searxng,
+    /// DuckDuckGo via headless Chrome (requires the `browser` feature)
+    Duckduckgo,
+    /// Tavily API (requires `tavily_api_key` or TAVILY_API_KEY)
+    Tavily,
+    /// Brave Search API (requires `brave_api_key` or BRAVE_API_KEY)
+    Brave,
+}
+
+/// Web search configuration
+///
+/// Example:
+/// ```yaml
+/// web:
+///   search:
+///     backend: searxng
+///     searxng_url: http://localhost:8888
+///     blocked_domains: [pinterest.com]
+///     cache_ttl_minutes: 120
+/// ```
+#[derive(Debug, Deserialize, Clone, JsonSchema)]
+pub struct WebSearchConfig {
+    #[serde(default)]
+    pub backend: SearchBackend,
+    /// Base URL of a SearXNG instance with the JSON format enabled
+    #[serde(default)]
+    pub searxng_url: Option<String>,
+    /// Tavily API key (falls back to TAVILY_API_KEY)
+    #[serde(default)]
+    pub tavily_api_key: Option<String>,
+    /// Brave Search API key (falls back to BRAVE_API_KEY)
+    #[serde(default)]
+    pub brave_api_key: Option<String>,
+    /// Only return results from these domains (subdomains included)
+    #[serde(default)]
+    pub allowed_domains: Vec<String>,
+    /// Never return results from these domains (subdomains included)
+    #[serde(default)]
+    pub blocked_domains: Vec<String>,
+    /// Cache search results on disk
+    #[serde(default = "default_search_cache")]
+    pub cache: bool,
+    /// How long cached results stay valid
+    #[serde(default = "default_search_cache_ttl")]
+    pub cache_ttl_minutes: u64,
+    /// Cache directory (default: `<cache dir>/openclaudia/search`)
+    #[serde(default)]
+    pub cache_dir: Option<PathBuf>,
+}
+
+fn default_search_cache() -> bool {
+    true
+}
+
+fn default_search_cache_ttl() -> u64 {
+    60
+}
+
+impl Default for WebSearchConfig {
+    fn default() -> Self {
+        Self {
+            backend: SearchBackend::default(),
+            searxng_url: None,
+            tavily_api_key: None,
+            brave_api_key: None,
+            allowed_domains: Vec::new(),
+            blocked_domains: Vec::new(),
+            cache: default_search_cache(),
+            cache_ttl_minutes: default_search_cache_ttl(),
+            cache_dir: None,
+        }
+    }
+}
+
 impl Default for ProxyConfig {
     fn default() -> Self {
         Self {
             port: default_port(),
             host: default_host(),
             target: default_target(),
+            retry: RetryConfig::default(),
+            fallback: Vec::new(),
+        }
+    }
+}
+
+/// Values every configuration starts from
+const DEFAULTS: &[(&str, &str)] = &[
+    ("proxy.port", "8080"),
+    ("proxy.host", "127.0.0.1"),
+    ("proxy.target", "anthropic"),
+    ("session.timeout_minutes", "30"),
+    ("session.persist_path", ".openclaudia/session"),
+    ("providers.anthropic.base_url", "https://api.anthropic.com"),
+    ("providers.openai.base_url", "https://api.openai.com"),
+    (
+        "providers.google.base_url",
+        "https://generativelanguage.googleapis.com",
+    ),
+    // Z.AI/GLM (OpenAI-compatible)
+    (
+        "providers.zai.base_url",
+        "https://api.z.ai/api/coding/paas/v4",
+    ),
+    // DeepSeek (OpenAI-compatible)
+    ("providers.deepseek.base_url", "https://api.deepseek.com"),
+    // Qwen/Alibaba (OpenAI-compatible)
+    (
+        "providers.qwen.base_url",
+        "https://dashscope.aliyuncs.com/compatible-mode",
+    ),
+];
+
+/// Standard API key variables and the providers they configure
+const PROVIDER_KEY_VARS: &[(&str, &str)] = &[
+    ("ANTHROPIC_API_KEY", "anthropic"),
+    ("OPENAI_API_KEY", "openai"),
+    ("GOOGLE_API_KEY", "google"),
+    ("ZAI_API_KEY", "zai"),
+    ("DEEPSEEK_API_KEY", "deepseek"),
+    ("QWEN_API_KEY", "qwen"),
+];
+
+/// A config file layer. Later scopes override earlier ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigScope {
+    /// `~/.openclaudia/config.yaml`
+    User,
+    /// `.openclaudia/config.yaml`, shared with the project
+    Project,
+    /// `.openclaudia/config.local.yaml`, personal and git-ignored
+    Local,
+}
+
+impl ConfigScope {
+    /// All scopes, lowest precedence first
+    pub const ALL: [ConfigScope; 3] = [ConfigScope::User, ConfigScope::Project, ConfigScope::Local];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ConfigScope::User => "user",
+            ConfigScope::Project => "project",
+            ConfigScope::Local => "local",
+        }
+    }
+
+    /// The layer's file; None for the user layer without a home directory
+    pub fn path(self) -> Option<PathBuf> {
+        match self {
+            ConfigScope::User => dirs::home_dir().map(|home| home.join(".openclaudia/config.yaml")),
+            ConfigScope::Project => Some(PathBuf::from(".openclaudia/config.yaml")),
+            ConfigScope::Local => Some(PathBuf::from(".openclaudia/config.local.yaml")),
         }
     }
 }
 
-/// Load configuration from all sources
+impl fmt::Display for ConfigScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.name())
+    }
+}
+
+impl FromStr for ConfigScope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ConfigScope::ALL
+            .into_iter()
+            .find(|scope| scope.name() == s)
+            .ok_or_else(|| format!("unknown scope '{}' (expected user, project or local)", s))
+    }
+}
+
+/// Where a configuration value came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigLayer {
+    Default,
+    File(ConfigScope),
+    /// The credential store
+    Store,
+    /// An environment variable, by name
+    Env(String),
+    /// A command-line flag
+    Cli,
+}
+
+impl fmt::Display for ConfigLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigLayer::Default => f.write_str("default"),
+            ConfigLayer::File(scope) => match scope.path() {
+                Some(path) => write!(f, "{} ({})", scope, path.display()),
+                None => write!(f, "{}", scope),
+            },
+            ConfigLayer::Store => f.write_str("credential store"),
+            ConfigLayer::Env(var) => write!(f, "env ({})", var),
+            ConfigLayer::Cli => f.write_str("command line"),
+        }
+    }
+}
+
+/// A value as set by the layer that wins for its key
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValueOrigin {
+    pub value: String,
+    pub layer: ConfigLayer,
+}
+
+/// The merged configuration with the origin of every explicitly set key.
+/// Keys are dotted and lowercase (`providers.anthropic.api_key`).
+#[derive(Debug, Clone)]
+pub struct LayeredConfig {
+    pub config: AppConfig,
+    pub origins: BTreeMap<String, ValueOrigin>,
+}
+
+/// Load configuration from all layers:
+/// defaults < user < project < local < environment
 pub fn load_config() -> Result<AppConfig, ConfigError> {
+    load_config_with(&[])
+}
+
+/// Load configuration with command-line overrides such as
+/// `("proxy.port", "9090")` on top of every other layer
+pub fn load_config_with(cli: &[(&str, String)]) -> Result<AppConfig, ConfigError> {
+    load_layered(cli).map(|layered| layered.config)
+}
+
+/// Load configuration and record which layer set each value
+pub fn load_layered(cli: &[(&str, String)]) -> Result<LayeredConfig, ConfigError> {
+    let files: Vec<(ConfigScope, PathBuf)> = ConfigScope::ALL
+        .into_iter()
+        .filter_map(|scope| Some((scope, scope.path()?)))
+        .collect();
+    load_files(&files, cli)
+}
+
+/// Load configuration from the given files in place of the standard
+/// locations, with the other layers as in [`load_layered`]
+pub fn load_files(
+    files: &[(ConfigScope, PathBuf)],
+    cli: &[(&str, String)],
+) -> Result<LayeredConfig, ConfigError> {
+    load_layers(files, cli, &credentials::store().config_secrets())
+}
+
+/// Merge the layers. `stored` holds credential store values, which fill keys
+/// no other layer sets.
+fn load_layers(
+    files: &[(ConfigScope, PathBuf)],
+    cli: &[(&str, String)],
+    stored: &BTreeMap<String, String>,
+) -> Result<LayeredConfig, ConfigError> {
     let mut builder = Config::builder();
+    let mut origins = BTreeMap::new();
+    let mut record = |key: &str, value: String, layer: ConfigLayer| {
+        origins.insert(key.to_lowercase(), ValueOrigin { value, layer });
+    };
+
+    for (key, value) in DEFAULTS {
+        builder = builder.set_default(*key, *value)?;
+        record(key, value.to_string(), ConfigLayer::Default);
+    }
 
-    // Set defaults
-    builder = builder
-        .set_default("proxy.port", 8080)?
-        .set_default("proxy.host", "127.0.0.1")?
-        .set_default("proxy.target", "anthropic")?
-        .set_default("session.timeout_minutes", 30)?
-        .set_default("session.persist_path", ".openclaudia/session")?;
-
-    // Add default providers
-    builder = builder
-        .set_default("providers.anthropic.base_url", "https://api.anthropic.com")?
-        .set_default("providers.openai.base_url", "https://api.openai.com")?
-        .set_default(
-            "providers.google.base_url",
-            "https://generativelanguage.googleapis.com",
-        )?
-        // Z.AI/GLM (OpenAI-compatible)
-        .set_default(
-            "providers.zai.base_url",
-            "https://api.z.ai/api/coding/paas/v4",
-        )?
-        // DeepSeek (OpenAI-compatible)
-        .set_default("providers.deepseek.base_url", "https://api.deepseek.com")?
-        // Qwen/Alibaba (OpenAI-compatible)
-        .set_default(
-            "providers.qwen.base_url",
-            "https://dashscope.aliyuncs.com/compatible-mode",
-        )?;
-
-    // Load from project config file
-    let project_config = PathBuf::from(".openclaudia/config.yaml");
-    if project_config.exists() {
-        builder = builder.add_source(File::from(project_config).required(false));
-    }
-
-    // Load from home directory config file
-    if let Some(home) = dirs::home_dir() {
-        let home_config: PathBuf = home.join(".openclaudia/config.yaml");
-        if home_config.exists() {
-            builder = builder.add_source(File::from(home_config).required(false));
+    for (scope, path) in files {
+        let Some((text, tree)) = read_layer(path)? else {
+            continue;
+        };
+        config_schema::warn_once(&config_schema::check(path, &text, &tree));
+        for (key, value) in flatten(&tree) {
+            record(&key, value, ConfigLayer::File(*scope));
         }
+        let yaml = serde_yaml::to_string(&tree)
+            .map_err(|e| ConfigError::Message(format!("{}: {}", path.display(), e)))?;
+        builder = builder.add_source(File::from_str(&yaml, FileFormat::Yaml));
     }
 
     // Load from environment variables with OPENCLAUDIA_ prefix
     // e.g., OPENCLAUDIA_PROXY_PORT=9090, OPENCLAUDIA_PROVIDERS_ANTHROPIC_API_KEY=sk-...
-    builder = builder.add_source(
-        Environment::with_prefix("OPENCLAUDIA")
-            .separator("_")
-            .try_parsing(true),
-    );
+    let mut environment: Vec<(String, String)> = std::env::vars()
+        .filter(|(var, _)| var.starts_with("OPENCLAUDIA_"))
+        .collect();
+    environment.sort();
+    for (var, value) in environment {
+        let Some(key) = config_schema::env_key(&var["OPENCLAUDIA_".len()..]) else {
+            continue;
+        };
+        record(&key, value.clone(), ConfigLayer::Env(var));
+        builder = builder.set_override(key, value)?;
+    }
 
     // Also check for provider API keys from standard env vars
-    if let Ok(key) = std::env::var("ANTHROPIC_API_KEY") {
-        builder = builder.set_override("providers.anthropic.api_key", key)?;
+    for (var, provider) in PROVIDER_KEY_VARS {
+        if let Ok(key) = std::env::var(var) {
+            let path = format!("providers.{}.api_key", provider);
+            record(&path, key.clone(), ConfigLayer::Env(var.to_string()));
+            builder = builder.set_override(path, key)?;
+        }
     }
-    if let Ok(key) = std::env::var("OPENAI_API_KEY") {
-        builder = builder.set_override("providers.openai.api_key", key)?;
+
+    for (key, value) in cli {
+        record(key, value.clone(), ConfigLayer::Cli);
+        builder = builder.set_override(*key, value.as_str())?;
     }
-    if let Ok(key) = std::env::var("GOOGLE_API_KEY") {
-        builder = builder.set_override("providers.google.api_key", key)?;
+
+    for (key, value) in stored {
+        // A key for a provider no layer defines would add one without a base_url
+        let provider_known = match key.strip_prefix("providers.") {
+            Some(rest) => {
+                let section = format!("providers.{}.", rest.split('.').next().unwrap_or(rest));
+                origins.keys().any(|k| k.starts_with(&section))
+            }
+            None => true,
+        };
+        if provider_known && !origins.contains_key(key) {
+            origins.insert(
+                key.clone(),
+                ValueOrigin {
+                    value: value.clone(),
+                    layer: ConfigLayer::Store,
+                },
+            );
+            builder = builder.set_override(key.as_str(), value.as_str())?;
+        }
     }
-    if let Ok(key) = std::env::var("ZAI_API_KEY") {
-        builder = builder.set_override("providers.zai.api_key", key)?;
+    for (key, origin) in &origins {
+        if is_secret_key(key) {
+            credentials::register_secret(&origin.value);
+        }
     }
-    if let Ok(key) = std::env::var("DEEPSEEK_API_KEY") {
-        builder = builder.set_override("providers.deepseek.api_key", key)?;
+
+    let config = builder.build()?.try_deserialize()?;
+    Ok(LayeredConfig { config, origins })
+}
+
+/// Read a config file and its `${VAR}`-expanded tree, or None when the
+/// file does not exist or is empty
+fn read_layer(path: &Path) -> Result<Option<(String, serde_yaml::Value)>, ConfigError> {
+    if !path.exists() {
+        return Ok(None);
     }
-    if let Ok(key) = std::env::var("QWEN_API_KEY") {
-        builder = builder.set_override("providers.qwen.api_key", key)?;
+    let located = |e: String| ConfigError::Message(format!("{}: {}", path.display(), e));
+    let text = std::fs::read_to_string(path).map_err(|e| located(e.to_string()))?;
+    let tree = parse_layer(&text).map_err(located)?;
+    Ok(tree.map(|tree| (text, tree)))
+}
+
+/// Parse config file text with its `${VAR}` references expanded, or None
+/// when it is empty
+pub fn parse_layer(text: &str) -> Result<Option<serde_yaml::Value>, String> {
+    let mut tree: serde_yaml::Value = serde_yaml::from_str(text).map_err(|e| e.to_string())?;
+    if tree.is_null() {
+        return Ok(None);
+    }
+    // Hook commands are left for the shell to expand when they run
+    let hooks = tree.as_mapping_mut().and_then(|map| map.remove("hooks"));
+    expand_env(&mut tree)?;
+    if let (Some(map), Some(hooks)) = (tree.as_mapping_mut(), hooks) {
+        map.insert("hooks".into(), hooks);
     }
+    Ok(Some(tree))
+}
 
-    builder.build()?.try_deserialize()
+/// Expand `${VAR}` and `${VAR:-default}` in a config value; `$${` is a
+/// literal `${`. Unset variables without a default expand to nothing.
+pub fn interpolate(text: &str) -> Result<String, String> {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('$') {
+        out.push_str(&rest[..start]);
+        let tail = &rest[start..];
+        if let Some(after) = tail.strip_prefix("$${") {
+            out.push_str("${");
+            rest = after;
+            continue;
+        }
+        let Some(reference) = tail.strip_prefix("${") else {
+            out.push('$');
+            rest = &tail[1..];
+            continue;
+        };
+        let end = reference
+            .find('}')
+            .ok_or_else(|| format!("unterminated ${{ in \"{}\"", text))?;
+        let (name, default) = match reference[..end].split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (&reference[..end], None),
+        };
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(format!(
+                "invalid variable name \"{}\" in \"{}\"",
+                name, text
+            ));
+        }
+        match std::env::var(name) {
+            Ok(value) if !value.is_empty() => out.push_str(&value),
+            _ => out.push_str(default.unwrap_or_default()),
+        }
+        rest = &reference[end + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Interpolate every string in a config tree. A value that expands to
+/// nothing, like `api_key: ${UNSET_KEY}`, counts as not set.
+fn expand_env(value: &mut serde_yaml::Value) -> Result<(), String> {
+    match value {
+        serde_yaml::Value::String(text) => *text = interpolate(text)?,
+        serde_yaml::Value::Sequence(items) => {
+            for item in items {
+                expand_env(item)?;
+            }
+        }
+        serde_yaml::Value::Mapping(map) => {
+            let mut unset = Vec::new();
+            for (key, item) in map.iter_mut() {
+                let referenced = item.as_str().is_some_and(|s| s.contains("${"));
+                expand_env(item)?;
+                if referenced && item.as_str() == Some("") {
+                    unset.push(key.clone());
+                }
+            }
+            for key in unset {
+                map.remove(&key);
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Dotted keys and display values of the leaves of a config tree
+fn flatten(tree: &serde_yaml::Value) -> Vec<(String, String)> {
+    fn walk(prefix: &str, value: &Value, out: &mut Vec<(String, String)>) {
+        match value {
+            Value::Object(map) if !map.is_empty() => {
+                for (key, item) in map {
+                    let path = if prefix.is_empty() {
+                        key.clone()
+                    } else {
+                        format!("{}.{}", prefix, key)
+                    };
+                    walk(&path, item, out);
+                }
+            }
+            Value::String(text) => out.push((prefix.to_string(), text.clone())),
+            other => out.push((prefix.to_string(), other.to_string())),
+        }
+    }
+    let mut out = Vec::new();
+    if let Ok(value) = serde_json::to_value(tree) {
+        walk("", &value, &mut out);
+    }
+    out
+}
+
+/// Whether a dotted key holds a credential that should not be printed
+pub fn is_secret_key(key: &str) -> bool {
+    let leaf = key.rsplit('.').next().unwrap_or(key);
+    leaf.ends_with("api_key")
+        || leaf.ends_with("secret_access_key")
+        || leaf.ends_with("session_token")
+        || leaf.ends_with("password")
+}
+
+/// Set `key` (dotted, e.g. `proxy.port`) in the file of `scope`, creating
+/// it if needed. The value is parsed as YAML, so `9090` is a number and
+/// `[a, b]` a list. The file is rewritten, which drops its comments.
+pub fn set_value(scope: ConfigScope, key: &str, value: &str) -> Result<PathBuf, String> {
+    let path = scope_path(scope)?;
+    set_in_file(&path, key, value)?;
+    if scope == ConfigScope::Local {
+        if let Some(dir) = path.parent() {
+            ensure_local_ignored(dir)
+                .map_err(|e| format!("Failed to update {}/.gitignore: {}", dir.display(), e))?;
+        }
+    }
+    Ok(path)
+}
+
+/// Remove `key` from the file of `scope`, keeping the file's comments.
+/// Returns whether it was set there.
+pub fn unset_value(scope: ConfigScope, key: &str) -> Result<bool, String> {
+    unset_in_file(&scope_path(scope)?, key)
+}
+
+/// Add `config.local.yaml` to the `.gitignore` in `dir`
+pub fn ensure_local_ignored(dir: &Path) -> std::io::Result<()> {
+    let gitignore = dir.join(".gitignore");
+    let existing = std::fs::read_to_string(&gitignore).unwrap_or_default();
+    if existing
+        .lines()
+        .any(|line| line.trim() == "config.local.yaml")
+    {
+        return Ok(());
+    }
+    let separator = if existing.is_empty() || existing.ends_with('\n') {
+        ""
+    } else {
+        "\n"
+    };
+    std::fs::write(
+        &gitignore,
+        format!("{}{}config.local.yaml\n", existing, separator),
+    )
+}
+
+fn scope_path(scope: ConfigScope) -> Result<PathBuf, String> {
+    scope
+        .path()
+        .ok_or_else(|| format!("No file for the {} scope: home directory not found", scope))
+}
+
+fn key_segments(key: &str) -> Result<Vec<&str>, String> {
+    let segments: Vec<&str> = key.split('.').collect();
+    if segments.iter().any(|s| s.is_empty()) {
+        return Err(format!("Invalid key '{}'", key));
+    }
+    Ok(segments)
+}
+
+fn read_mapping(path: &Path) -> Result<serde_yaml::Mapping, String> {
+    if !path.exists() {
+        return Ok(serde_yaml::Mapping::new());
+    }
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    match serde_yaml::from_str(&text) {
+        Ok(serde_yaml::Value::Mapping(map)) => Ok(map),
+        Ok(serde_yaml::Value::Null) => Ok(serde_yaml::Mapping::new()),
+        Ok(_) => Err(format!("{} is not a YAML mapping", path.display())),
+        Err(e) => Err(format!("Failed to parse {}: {}", path.display(), e)),
+    }
+}
+
+fn write_mapping(path: &Path, map: serde_yaml::Mapping) -> Result<(), String> {
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    let yaml = serde_yaml::to_string(&serde_yaml::Value::Mapping(map))
+        .map_err(|e| format!("Failed to serialize {}: {}", path.display(), e))?;
+    std::fs::write(path, yaml).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// A command-line value as YAML, falling back to the plain string
+fn parse_value(text: &str) -> serde_yaml::Value {
+    match serde_yaml::from_str::<serde_yaml::Value>(text) {
+        Ok(serde_yaml::Value::Null) if !matches!(text.trim(), "~" | "null") => {
+            serde_yaml::Value::String(text.to_string())
+        }
+        Ok(value) => value,
+        Err(_) => serde_yaml::Value::String(text.to_string()),
+    }
+}
+
+fn set_in_file(path: &Path, key: &str, value: &str) -> Result<(), String> {
+    let segments = key_segments(key)?;
+    let mut root = read_mapping(path)?;
+    let (leaf, sections) = segments
+        .split_last()
+        .expect("split always yields a segment");
+    let mut map = &mut root;
+    for (depth, section) in sections.iter().enumerate() {
+        let entry = map
+            .entry(serde_yaml::Value::from(*section))
+            .or_insert_with(|| serde_yaml::Value::Mapping(serde_yaml::Mapping::new()));
+        map = entry.as_mapping_mut().ok_or_else(|| {
+            format!(
+                "'{}' is a value, not a section",
+                segments[..=depth].join(".")
+            )
+        })?;
+    }
+    map.insert(serde_yaml::Value::from(*leaf), parse_value(value));
+    write_mapping(path, root)
+}
+
+/// Remove `key` from a config file. Block-style entries are cut out of the
+/// text so comments survive; anything else rewrites the file.
+pub fn unset_in_file(path: &Path, key: &str) -> Result<bool, String> {
+    fn remove(map: &mut serde_yaml::Mapping, segments: &[&str]) -> bool {
+        let Some((first, rest)) = segments.split_first() else {
+            return false;
+        };
+        let key = serde_yaml::Value::from(*first);
+        if rest.is_empty() {
+            return map.remove(&key).is_some();
+        }
+        let Some(section) = map.get_mut(&key).and_then(|v| v.as_mapping_mut()) else {
+            return false;
+        };
+        let removed = remove(section, rest);
+        // Drop sections left empty
+        if removed && section.is_empty() {
+            map.remove(&key);
+        }
+        removed
+    }
+
+    let segments = key_segments(key)?;
+    if !path.exists() {
+        return Ok(false);
+    }
+    let mut root = read_mapping(path)?;
+    let removed = remove(&mut root, &segments);
+    if removed {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        // Keep the text edit only if it means exactly the removal
+        match remove_from_text(&text, &segments).filter(|edited| {
+            match serde_yaml::from_str(edited) {
+                Ok(serde_yaml::Value::Mapping(map)) => map == root,
+                Ok(serde_yaml::Value::Null) => root.is_empty(),
+                _ => false,
+            }
+        }) {
+            Some(edited) => std::fs::write(path, edited)
+                .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?,
+            None => write_mapping(path, root)?,
+        }
+    }
+    Ok(removed)
+}
+
+/// Cut the block-style entry at `segments` out of YAML text, along with
+/// sections it leaves without entries. Other lines, comments included, are
+/// kept as they are. None when the entry is not found as a block key.
+fn remove_from_text(text: &str, segments: &[&str]) -> Option<String> {
+    let lines: Vec<&str> = text.lines().collect();
+    let indent = |line: &str| line.len() - line.trim_start().len();
+    let is_entry = |line: &str| {
+        let trimmed = line.trim_start();
+        !trimmed.is_empty() && !trimmed.starts_with('#')
+    };
+    let key_of = |line: &str| {
+        let (key, _) = line.trim_start().split_once(':')?;
+        let key = key.trim_end();
+        let unquoted = key
+            .strip_prefix('"')
+            .and_then(|k| k.strip_suffix('"'))
+            .or_else(|| key.strip_prefix('\'').and_then(|k| k.strip_suffix('\'')));
+        Some(unquoted.unwrap_or(key).to_string())
+    };
+    // End of the block starting at `start`: after its last more-indented entry
+    let block_end = |start: usize| {
+        let own = indent(lines[start]);
+        let mut end = start + 1;
+        for (i, line) in lines.iter().enumerate().skip(start + 1) {
+            if is_entry(line) {
+                if indent(line) <= own {
+                    break;
+                }
+                end = i + 1;
+            }
+        }
+        end
+    };
+
+    // Header line of each section on the way down, then the entry itself
+    let mut path = Vec::new();
+    let (mut from, mut to) = (0, lines.len());
+    for segment in segments {
+        let child_indent = lines[from..to]
+            .iter()
+            .find(|line| is_entry(line))
+            .map(|line| indent(line))?;
+        let found = (from..to).find(|&i| {
+            is_entry(lines[i])
+                && indent(lines[i]) == child_indent
+                && key_of(lines[i]).as_deref() == Some(*segment)
+        })?;
+        path.push(found);
+        from = found + 1;
+        to = block_end(found);
+    }
+
+    let mut removed = vec![false; lines.len()];
+    let leaf = *path.last()?;
+    removed[leaf..block_end(leaf)].fill(true);
+    for &section in path.iter().rev().skip(1) {
+        let empty = (section + 1..block_end(section)).all(|i| removed[i] || !is_entry(lines[i]));
+        if !empty {
+            break;
+        }
+        removed[section] = true;
+    }
+
+    let mut out: String = lines
+        .iter()
+        .zip(&removed)
+        .filter(|(_, removed)| !**removed)
+        .map(|(line, _)| format!("{}\n", line))
+        .collect();
+    if !text.ends_with('\n') {
+        out.pop();
+    }
+    Some(out)
 }
 
 /// Get the active provider configuration
@@ -441,6 +1685,91 @@ mod tests {
         assert_eq!(config.persist_path, PathBuf::from("/custom/path"));
     }
 
+    #[test]
+    fn test_budgets_config_from_json() {
+        let json = r#"{
+            "loop": {"max_cost_usd": 20.0},
+            "subagent": {"max_tokens": 100000}
+        }"#;
+
+        let config: BudgetsConfig = serde_json::from_str(json).unwrap();
+        assert_eq!(config.warn_at, 0.8);
+        assert_eq!(config.loop_run.max_cost_usd, Some(20.0));
+        assert_eq!(config.subagent.max_tokens, Some(100_000));
+        assert_eq!(config.session, Budget::default());
+    }
+
+    #[test]
+    fn test_model_override_from_json() {
+        let json = r#"{
+            "claude-sonnet-4-5": {"context_window": 1000000},
+            "my-finetune": {"provider": "openai", "tools": true}
+        }"#;
+
+        let models: HashMap<String, ModelOverride> = serde_json::from_str(json).unwrap();
+        assert_eq!(models["claude-sonnet-4-5"].context_window, Some(1_000_000));
+        assert_eq!(models["claude-sonnet-4-5"].provider, None);
+        assert_eq!(models["my-finetune"].provider.as_deref(), Some("openai"));
+        assert_eq!(models["my-finetune"].tools, Some(true));
+    }
+
+    // ========================================================================
+    // WebSearchConfig Tests
+    // ========================================================================
+
+    #[test]
+    fn test_web_search_config_default() {
+        let config = WebToolsConfig::default();
+        assert_eq!(config.search.backend, SearchBackend::Auto);
+        assert!(config.search.cache);
+        assert_eq!(config.search.cache_ttl_minutes, 60);
+        assert!(config.search.allowed_domains.is_empty());
+    }
+
+    #[test]
+    fn test_web_search_config_from_yaml() {
+        let yaml = r#"
+search:
+  backend: searxng
+  searxng_url: http://localhost:8888
+  blocked_domains: [pinterest.com]
+  cache: false
+"#;
+
+        let config: WebToolsConfig = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(config.search.backend, SearchBackend::Searxng);
+        assert_eq!(
+            config.search.searxng_url.as_deref(),
+            Some("http://localhost:8888")
+        );
+        assert_eq!(config.search.blocked_domains, vec!["pinterest.com"]);
+        assert!(!config.search.cache);
+        assert_eq!(config.search.cache_ttl_minutes, 60);
+    }
+
+    #[test]
+    fn test_network_policy_config_default() {
+        let config = NetworkPolicyConfig::default();
+        assert!(!config.allow_private_networks);
+        assert_eq!(config.max_response_bytes, 10 * 1024 * 1024);
+        assert_eq!(config.max_redirects, 5);
+
+        let config: WebToolsConfig =
+            serde_yaml::from_str("network:\n  max_redirects: 2\n  allow_private_networks: true")
+                .unwrap();
+        assert!(config.network.allow_private_networks);
+        assert_eq!(config.network.max_redirects, 2);
+        assert!(config.search.cache);
+    }
+
+    #[test]
+    fn test_issues_config() {
+        assert_eq!(IssuesConfig::default().backend, IssueBackend::Native);
+
+        let config: IssuesConfig = serde_yaml::from_str("backend: chainlink").unwrap();
+        assert_eq!(config.backend, IssueBackend::Chainlink);
+    }
+
     // ========================================================================
     // KeyAction Tests
     // ========================================================================
@@ -679,6 +2008,43 @@ mod tests {
         assert_eq!(config.thinking.budget_tokens, Some(5000));
     }
 
+    #[test]
+    fn test_provider_config_endpoint_overrides() {
+        let json = r#"{
+            "base_url": "https://gateway.example.com",
+            "path": "/serving/{model}/chat",
+            "query": {"api-version": "2024-10-21"},
+            "auth": "x-api-key"
+        }"#;
+
+        let config: ProviderConfig = serde_json::from_str(json).unwrap();
+        assert_eq!(config.path.as_deref(), Some("/serving/{model}/chat"));
+        assert_eq!(config.query["api-version"], "2024-10-21");
+        assert_eq!(config.auth, Some(AuthStyle::XApiKey));
+
+        let none: ProviderConfig =
+            serde_json::from_str(r#"{"base_url": "http://localhost", "auth": "none"}"#).unwrap();
+        assert_eq!(none.auth, Some(AuthStyle::None));
+    }
+
+    #[test]
+    fn test_provider_config_prompt_cache() {
+        let config: ProviderConfig =
+            serde_json::from_str(r#"{"base_url": "https://api.anthropic.com"}"#).unwrap();
+        assert!(config.cache.enabled);
+        assert_eq!(config.cache.breakpoints, 2);
+        assert_eq!(config.cache.ttl, CacheTtl::FiveMinutes);
+
+        let json = r#"{
+            "base_url": "https://api.anthropic.com",
+            "cache": {"breakpoints": 3, "ttl": "1h"}
+        }"#;
+        let config: ProviderConfig = serde_json::from_str(json).unwrap();
+        assert!(config.cache.enabled);
+        assert_eq!(config.cache.breakpoints, 3);
+        assert_eq!(config.cache.ttl, CacheTtl::OneHour);
+    }
+
     // ========================================================================
     // AppConfig Tests
     // ========================================================================
@@ -694,6 +2060,13 @@ mod tests {
                 model: None,
                 headers: HashMap::new(),
                 thinking: ThinkingConfig::default(),
+                adapter: None,
+                aws: None,
+                vertex: None,
+                path: None,
+                query: HashMap::new(),
+                auth: None,
+                cache: PromptCacheConfig::default(),
             },
         );
 
@@ -706,6 +2079,14 @@ mod tests {
             hooks: HooksConfig::default(),
             session: SessionConfig::default(),
             keybindings: KeybindingsConfig::default(),
+            web: WebToolsConfig::default(),
+            issues: IssuesConfig::default(),
+            pricing: HashMap::new(),
+            budgets: BudgetsConfig::default(),
+            models: HashMap::new(),
+            credentials: CredentialsConfig::default(),
+            oauth: OAuthConfig::default(),
+            routing: RoutingConfig::default(),
         };
 
         let active = config.active_provider();
@@ -724,6 +2105,13 @@ mod tests {
                 model: None,
                 headers: HashMap::new(),
                 thinking: ThinkingConfig::default(),
+                adapter: None,
+                aws: None,
+                vertex: None,
+                path: None,
+                query: HashMap::new(),
+                auth: None,
+                cache: PromptCacheConfig::default(),
             },
         );
         providers.insert(
@@ -734,6 +2122,13 @@ mod tests {
                 model: None,
                 headers: HashMap::new(),
                 thinking: ThinkingConfig::default(),
+                adapter: None,
+                aws: None,
+                vertex: None,
+                path: None,
+                query: HashMap::new(),
+                auth: None,
+                cache: PromptCacheConfig::default(),
             },
         );
 
@@ -743,6 +2138,14 @@ mod tests {
             hooks: HooksConfig::default(),
             session: SessionConfig::default(),
             keybindings: KeybindingsConfig::default(),
+            web: WebToolsConfig::default(),
+            issues: IssuesConfig::default(),
+            pricing: HashMap::new(),
+            budgets: BudgetsConfig::default(),
+            models: HashMap::new(),
+            credentials: CredentialsConfig::default(),
+            oauth: OAuthConfig::default(),
+            routing: RoutingConfig::default(),
         };
 
         assert!(config.get_provider("openai").is_some());
@@ -761,8 +2164,170 @@ mod tests {
             hooks: HooksConfig::default(),
             session: SessionConfig::default(),
             keybindings: KeybindingsConfig::default(),
+            web: WebToolsConfig::default(),
+            issues: IssuesConfig::default(),
+            pricing: HashMap::new(),
+            budgets: BudgetsConfig::default(),
+            models: HashMap::new(),
+            credentials: CredentialsConfig::default(),
+            oauth: OAuthConfig::default(),
+            routing: RoutingConfig::default(),
         };
 
         assert!(config.active_provider().is_none());
     }
+
+    // ========================================================================
+    // Layering Tests
+    // ========================================================================
+
+    #[test]
+    fn test_interpolate() {
+        std::env::set_var("OC_CONFIG_TEST_HOST", "example.com");
+        std::env::remove_var("OC_CONFIG_TEST_MISSING");
+
+        assert_eq!(
+            interpolate("https://${OC_CONFIG_TEST_HOST}/v1").unwrap(),
+            "https://example.com/v1"
+        );
+        assert_eq!(
+            interpolate("${OC_CONFIG_TEST_MISSING:-fallback}").unwrap(),
+            "fallback"
+        );
+        assert_eq!(interpolate("${OC_CONFIG_TEST_MISSING}").unwrap(), "");
+        assert_eq!(
+            interpolate("cost: $5, $${HOME}").unwrap(),
+            "cost: $5, ${HOME}"
+        );
+        assert!(interpolate("${OC_CONFIG_TEST_HOST").is_err());
+        assert!(interpolate("${not a name}").is_err());
+    }
+
+    #[test]
+    fn test_layer_precedence() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |name: &str, yaml: &str| {
+            let path = dir.path().join(name);
+            std::fs::write(&path, yaml).unwrap();
+            path
+        };
+        let user = write(
+            "user.yaml",
+            "proxy:\n  port: 1000\n  host: user-host\nproviders:\n  anthropic:\n    model: user-model\n",
+        );
+        let project = write(
+            "project.yaml",
+            "proxy:\n  port: 2000\n  target: ${OC_CONFIG_TEST_TARGET:-openai}\n\
+             hooks:\n  stop:\n    - hooks:\n        - type: command\n          command: echo ${OC_CONFIG_TEST_TARGET}\n",
+        );
+        let local = write(
+            "local.yaml",
+            "proxy:\n  port: 3000\nproviders:\n  custom:\n    base_url: http://localhost\n    api_key: ${OC_CONFIG_TEST_UNSET_KEY}\n",
+        );
+        std::env::remove_var("OC_CONFIG_TEST_TARGET");
+        std::env::remove_var("OC_CONFIG_TEST_UNSET_KEY");
+
+        let files = [
+            (ConfigScope::User, user),
+            (ConfigScope::Project, project),
+            (ConfigScope::Local, local),
+        ];
+        let layered = load_layers(
+            &files,
+            &[("proxy.host", "cli-host".to_string())],
+            &BTreeMap::from([
+                (
+                    "web.search.brave_api_key".to_string(),
+                    "stored-key".to_string(),
+                ),
+                (
+                    "providers.missing.api_key".to_string(),
+                    "stored-missing".to_string(),
+                ),
+            ]),
+        )
+        .unwrap();
+        let config = &layered.config;
+        assert_eq!(config.proxy.port, 3000);
+        assert_eq!(config.proxy.host, "cli-host");
+        assert_eq!(config.proxy.target, "openai");
+        assert_eq!(
+            config.providers["anthropic"].model.as_deref(),
+            Some("user-model")
+        );
+        assert!(config.providers["custom"].api_key.is_none());
+        // Hook commands keep their references for the shell
+        match &config.hooks.stop[0].hooks[0] {
+            Hook::Command { command, .. } => assert_eq!(command, "echo ${OC_CONFIG_TEST_TARGET}"),
+            other => panic!("unexpected hook {:?}", other),
+        }
+
+        let layer = |key: &str| layered.origins[key].layer.clone();
+        assert_eq!(layer("proxy.port"), ConfigLayer::File(ConfigScope::Local));
+        assert_eq!(
+            layer("proxy.target"),
+            ConfigLayer::File(ConfigScope::Project)
+        );
+        assert_eq!(
+            layer("providers.anthropic.model"),
+            ConfigLayer::File(ConfigScope::User)
+        );
+        assert_eq!(layer("proxy.host"), ConfigLayer::Cli);
+        assert_eq!(layer("providers.anthropic.base_url"), ConfigLayer::Default);
+        assert_eq!(layered.origins["proxy.target"].value, "openai");
+        // Stored secrets fill unset keys, but never invent a provider
+        assert_eq!(layer("web.search.brave_api_key"), ConfigLayer::Store);
+        assert_eq!(
+            config.web.search.brave_api_key.as_deref(),
+            Some("stored-key")
+        );
+        assert!(!config.providers.contains_key("missing"));
+    }
+
+    #[test]
+    fn test_set_and_unset_in_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".openclaudia/config.yaml");
+
+        set_in_file(&path, "proxy.port", "9090").unwrap();
+        set_in_file(&path, "providers.gateway.headers.X-Team", "core").unwrap();
+        set_in_file(&path, "providers.gateway.api_key", "${GATEWAY_KEY}").unwrap();
+        let written: serde_yaml::Value =
+            serde_yaml::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(written["proxy"]["port"], 9090);
+        assert_eq!(written["providers"]["gateway"]["headers"]["X-Team"], "core");
+        assert_eq!(written["providers"]["gateway"]["api_key"], "${GATEWAY_KEY}");
+        assert!(set_in_file(&path, "proxy.port.value", "1").is_err());
+        assert!(set_in_file(&path, "proxy..port", "1").is_err());
+
+        assert!(unset_in_file(&path, "providers.gateway.headers.X-Team").unwrap());
+        assert!(unset_in_file(&path, "providers.gateway.api_key").unwrap());
+        assert!(!unset_in_file(&path, "providers.gateway.api_key").unwrap());
+        let written: serde_yaml::Value =
+            serde_yaml::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert!(written.get("providers").is_none());
+        assert_eq!(written["proxy"]["port"], 9090);
+
+        // Removing a key keeps the file's comments
+        std::fs::write(
+            &path,
+            "# $schema header\nproxy:\n  port: 9090 # custom port\nproviders:\n  # main provider\n  anthropic:\n    api_key: sk-ant-123\n    base_url: https://api.anthropic.com\n  openai:\n    api_key: 'sk-456'\n",
+        )
+        .unwrap();
+        assert!(unset_in_file(&path, "providers.anthropic.api_key").unwrap());
+        assert!(unset_in_file(&path, "providers.openai.api_key").unwrap());
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "# $schema header\nproxy:\n  port: 9090 # custom port\nproviders:\n  # main provider\n  anthropic:\n    base_url: https://api.anthropic.com\n"
+        );
+
+        ensure_local_ignored(dir.path()).unwrap();
+        ensure_local_ignored(dir.path()).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join(".gitignore")).unwrap(),
+            "config.local.yaml\n"
+        );
+        assert_eq!("local".parse::<ConfigScope>(), Ok(ConfigScope::Local));
+        assert!("global".parse::<ConfigScope>().is_err());
+    }
 }
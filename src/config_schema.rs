@@ -0,0 +1,783 @@
+//! Config Schema - JSON Schema of config.yaml and diagnostics for config files.
+//!
+//! The schema is generated from [`AppConfig`] and then adjusted for checking
+//! a single layer:
+//! - Objects with known keys reject any other key, so a misspelled key such
+//!   as `pre_tool_uses` is reported instead of being silently ignored
+//! - `required` is kept only inside list items; a layer may set part of a
+//!   section (`providers.anthropic.api_key`) that another layer completes
+//!
+//! Every config file is checked when the configuration loads, and problems
+//! are logged once per process with their file, line and column. `openclaudia
+//! doctor` lists them, and `openclaudia config schema` prints the schema for
+//! editors (it is also published as `schema/config.schema.json`).
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
+
+use jsonschema::error::{TypeKind, ValidationErrorKind};
+use jsonschema::{JsonType, ValidationError, Validator};
+use regex::Regex;
+use serde_json::{json, Value};
+use yaml_rust2::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust2::scanner::Marker;
+
+use crate::config::{self, AppConfig, ConfigScope};
+
+/// File name editors are pointed at by `openclaudia init`
+pub const SCHEMA_FILE: &str = "config.schema.json";
+
+static SCHEMA: LazyLock<Value> = LazyLock::new(generate);
+
+/// Compiled schema shared by every check
+static VALIDATOR: LazyLock<Validator> = LazyLock::new(|| {
+    jsonschema::validator_for(schema()).expect("generated config schema is valid")
+});
+
+/// Diagnostics already shown, so repeated config loads stay quiet
+static REPORTED: LazyLock<Mutex<HashSet<String>>> = LazyLock::new(|| Mutex::new(HashSet::new()));
+
+/// A problem in a config file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub file: PathBuf,
+    /// 1-based line and column, when the problem can be located
+    pub position: Option<(usize, usize)>,
+    /// Dotted key the problem is at; empty for the whole file
+    pub key: String,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.file.display())?;
+        if let Some((line, column)) = self.position {
+            write!(f, ":{}:{}", line, column)?;
+        }
+        if self.key.is_empty() {
+            write!(f, ": {}", self.message)
+        } else {
+            write!(f, ": {}: {}", self.key, self.message)
+        }
+    }
+}
+
+/// JSON Schema of a config file
+pub fn schema() -> &'static Value {
+    &SCHEMA
+}
+
+fn generate() -> Value {
+    let mut schema =
+        serde_json::to_value(schemars::schema_for!(AppConfig)).expect("config schema serializes");
+    close_objects(&mut schema);
+    relax_required(&mut schema);
+    schema["title"] = json!("OpenClaudia configuration");
+    schema["description"] = json!(
+        "~/.openclaudia/config.yaml, .openclaudia/config.yaml or .openclaudia/config.local.yaml"
+    );
+    schema
+}
+
+/// Reject unknown keys in every object that lists its keys
+fn close_objects(schema: &mut Value) {
+    match schema {
+        Value::Object(map) => {
+            if map.contains_key("properties") && !map.contains_key("additionalProperties") {
+                map.insert("additionalProperties".to_string(), Value::Bool(false));
+            }
+            for value in map.values_mut() {
+                close_objects(value);
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(close_objects),
+        _ => {}
+    }
+}
+
+/// Drop `required` everywhere except in list items, which one layer always
+/// sets in full. A definition used both in and outside lists is relaxed.
+fn relax_required(schema: &mut Value) {
+    /// Strip `required` outside lists and collect the definitions referenced,
+    /// with whether the reference is outside a list
+    fn walk(value: &mut Value, in_list: bool, refs: &mut Vec<(String, bool)>) {
+        match value {
+            Value::Object(map) => {
+                if !in_list {
+                    map.remove("required");
+                }
+                if let Some(name) = map
+                    .get("$ref")
+                    .and_then(Value::as_str)
+                    .and_then(|r| r.strip_prefix("#/$defs/"))
+                {
+                    refs.push((name.to_string(), !in_list));
+                }
+                for (key, child) in map.iter_mut() {
+                    match key.as_str() {
+                        "$defs" => {}
+                        "items" | "prefixItems" => walk(child, true, refs),
+                        _ => walk(child, in_list, refs),
+                    }
+                }
+            }
+            Value::Array(items) => items.iter_mut().for_each(|v| walk(v, in_list, refs)),
+            _ => {}
+        }
+    }
+
+    let mut defs = match schema.as_object_mut().and_then(|s| s.remove("$defs")) {
+        Some(Value::Object(defs)) => defs,
+        _ => serde_json::Map::new(),
+    };
+    let mut refs = Vec::new();
+    walk(schema, false, &mut refs);
+
+    // A definition reached outside a list is walked (again) to relax it
+    let mut visited: HashMap<String, bool> = HashMap::new();
+    while let Some((name, outside)) = refs.pop() {
+        match visited.get(&name) {
+            Some(true) => continue,
+            Some(false) if !outside => continue,
+            _ => {}
+        }
+        visited.insert(name.clone(), outside);
+        if let Some(def) = defs.get_mut(&name) {
+            walk(def, !outside, &mut refs);
+        }
+    }
+
+    if !defs.is_empty() {
+        schema["$defs"] = Value::Object(defs);
+    }
+}
+
+/// Check a config layer: `tree` is the parsed file with `${VAR}` references
+/// expanded and `text` its source, for positions
+pub fn check(file: &Path, text: &str, tree: &serde_yaml::Value) -> Vec<Diagnostic> {
+    let positions = Positions::index(text);
+    let diagnostic = |pointer: &str, message: String| Diagnostic {
+        file: file.to_path_buf(),
+        position: positions.find(pointer),
+        key: dotted(pointer),
+        message,
+    };
+
+    let instance = match serde_json::to_value(tree) {
+        Ok(instance) => instance,
+        Err(e) => return vec![diagnostic("", e.to_string())],
+    };
+    let mut problems = Vec::new();
+    for error in VALIDATOR.iter_errors(&instance) {
+        explain(&error, &mut problems);
+    }
+    problems.extend(matcher_problems(&instance));
+
+    let mut diagnostics: Vec<Diagnostic> = problems
+        .into_iter()
+        .map(|(pointer, message)| diagnostic(&pointer, message))
+        .collect();
+    diagnostics.sort_by_key(|d| d.position);
+    diagnostics.dedup();
+    diagnostics
+}
+
+/// Check a config file on disk, including whether it parses at all
+pub fn check_file(file: &Path) -> Vec<Diagnostic> {
+    let diagnostic = |position, message: String| Diagnostic {
+        file: file.to_path_buf(),
+        position,
+        key: String::new(),
+        message,
+    };
+    let text = match std::fs::read_to_string(file) {
+        Ok(text) => text,
+        Err(e) => return vec![diagnostic(None, e.to_string())],
+    };
+    if let Err(e) = serde_yaml::from_str::<serde_yaml::Value>(&text) {
+        let position = e.location().map(|l| (l.line(), l.column()));
+        return vec![diagnostic(position, format!("invalid YAML: {}", e))];
+    }
+    match config::parse_layer(&text) {
+        Ok(Some(tree)) => check(file, &text, &tree),
+        Ok(None) => Vec::new(),
+        Err(e) => vec![diagnostic(None, e)],
+    }
+}
+
+/// Check every config file that exists. The diagnostics returned are not
+/// logged again when the configuration loads.
+pub fn check_layers() -> Vec<Diagnostic> {
+    let diagnostics: Vec<Diagnostic> = ConfigScope::ALL
+        .into_iter()
+        .filter_map(|scope| scope.path())
+        .filter(|path| path.exists())
+        .flat_map(|path| check_file(&path))
+        .collect();
+    if let Ok(mut reported) = REPORTED.lock() {
+        reported.extend(diagnostics.iter().map(ToString::to_string));
+    }
+    diagnostics
+}
+
+/// Log diagnostics that were not shown before
+pub fn warn_once(diagnostics: &[Diagnostic]) {
+    let Ok(mut reported) = REPORTED.lock() else {
+        return;
+    };
+    for diagnostic in diagnostics {
+        let line = diagnostic.to_string();
+        if reported.insert(line.clone()) {
+            tracing::warn!("{}", line);
+        }
+    }
+}
+
+/// Turn a validation error into `(JSON pointer, message)` problems
+fn explain(error: &ValidationError, out: &mut Vec<(String, String)>) {
+    let pointer = error.instance_path().as_str();
+    match error.kind() {
+        ValidationErrorKind::AdditionalProperties { unexpected } => {
+            let known = known_keys(pointer);
+            for key in unexpected {
+                let message = match closest(key, &known) {
+                    Some(suggestion) => format!("unknown key (did you mean `{}`?)", suggestion),
+                    None => "unknown key".to_string(),
+                };
+                out.push((format!("{}/{}", pointer, escape(key)), message));
+            }
+        }
+        ValidationErrorKind::Type { kind } => {
+            if !coercible(error.instance(), kind) {
+                out.push((pointer.to_string(), type_message(error.instance(), kind)));
+            }
+        }
+        ValidationErrorKind::OneOfNotValid { context } | ValidationErrorKind::AnyOf { context } => {
+            explain_branches(pointer, context, out)
+        }
+        _ => out.push((pointer.to_string(), error.to_string())),
+    }
+}
+
+/// Explain a value that matches none of several schemas: a list of allowed
+/// values, or the errors of the one variant whose tag matched
+fn explain_branches(
+    pointer: &str,
+    branches: &[Vec<ValidationError<'static>>],
+    out: &mut Vec<(String, String)>,
+) {
+    let mut choices = Vec::new();
+    // Tagged variants whose tag (e.g. a hook's `type`) did not match
+    let mut tags: Vec<(String, Value)> = Vec::new();
+    let mut candidates = Vec::new();
+    for branch in branches {
+        if let [only] = branch.as_slice() {
+            if only.instance_path().as_str() == pointer {
+                match only.kind() {
+                    ValidationErrorKind::Constant { expected_value } => {
+                        choices.push(expected_value.clone());
+                        continue;
+                    }
+                    ValidationErrorKind::Enum { options } => {
+                        choices.extend(options.as_array().into_iter().flatten().cloned());
+                        continue;
+                    }
+                    // The value is not of this variant's type at all
+                    ValidationErrorKind::Type { .. } => continue,
+                    _ => {}
+                }
+            }
+        }
+        let tag = branch.iter().find_map(|e| match e.kind() {
+            ValidationErrorKind::Constant { expected_value }
+                if parent(e.instance_path().as_str()) == pointer =>
+            {
+                Some((
+                    e.instance_path().as_str().to_string(),
+                    expected_value.clone(),
+                ))
+            }
+            _ => None,
+        });
+        match tag {
+            Some(tag) => tags.push(tag),
+            None => candidates.push(branch),
+        }
+    }
+
+    match candidates.as_slice() {
+        [] if !choices.is_empty() => out.push((pointer.to_string(), one_of(&choices))),
+        [] if !tags.is_empty() => {
+            let (tag_pointer, _) = tags[0].clone();
+            let values: Vec<Value> = tags.into_iter().map(|(_, value)| value).collect();
+            out.push((tag_pointer, one_of(&values)));
+        }
+        [branch] => {
+            for error in branch.iter() {
+                explain(error, out);
+            }
+        }
+        _ => out.push((
+            pointer.to_string(),
+            "does not match any allowed form".to_string(),
+        )),
+    }
+}
+
+fn one_of(values: &[Value]) -> String {
+    let names: Vec<String> = values
+        .iter()
+        .map(|v| v.as_str().map_or_else(|| v.to_string(), str::to_string))
+        .collect();
+    format!("expected one of: {}", names.join(", "))
+}
+
+fn type_message(instance: &Value, kind: &TypeKind) -> String {
+    let expected = match kind {
+        TypeKind::Single(ty) => ty.to_string(),
+        TypeKind::Multiple(types) => types
+            .iter()
+            .filter(|ty| *ty != JsonType::Null)
+            .map(|ty| ty.to_string())
+            .collect::<Vec<_>>()
+            .join(" or "),
+    };
+    let found = match instance {
+        Value::Null => "nothing",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "a list",
+        Value::Object(_) => "a mapping",
+    };
+    format!("expected {}, found {}", expected, found)
+}
+
+/// Whether the config loader converts this scalar to the expected type anyway,
+/// like `port: "9090"` or `model: 4`
+fn coercible(instance: &Value, kind: &TypeKind) -> bool {
+    let accepts = |ty: JsonType| match kind {
+        TypeKind::Single(single) => *single == ty,
+        TypeKind::Multiple(types) => types.contains(ty),
+    };
+    match instance {
+        Value::Bool(_) | Value::Number(_) => accepts(JsonType::String),
+        Value::String(text) => {
+            let text = text.trim();
+            (accepts(JsonType::Integer) && text.parse::<i64>().is_ok())
+                || (accepts(JsonType::Number) && text.parse::<f64>().is_ok())
+                || (accepts(JsonType::Boolean)
+                    && matches!(
+                        text.to_lowercase().as_str(),
+                        "true" | "false" | "yes" | "no" | "on" | "off" | "1" | "0"
+                    ))
+        }
+        _ => false,
+    }
+}
+
+/// Hook matchers that are not valid regular expressions
+fn matcher_problems(instance: &Value) -> Vec<(String, String)> {
+    let mut problems = Vec::new();
+    let Some(events) = instance.get("hooks").and_then(Value::as_object) else {
+        return problems;
+    };
+    for (event, entries) in events {
+        for (index, entry) in entries.as_array().into_iter().flatten().enumerate() {
+            let Some(matcher) = entry.get("matcher").and_then(Value::as_str) else {
+                continue;
+            };
+            if matcher.is_empty() {
+                continue;
+            }
+            if let Err(e) = Regex::new(matcher) {
+                // The last line of a regex error says what is wrong
+                let reason = e.to_string();
+                let reason = reason.lines().last().unwrap_or_default();
+                problems.push((
+                    format!("/hooks/{}/{}/matcher", escape(event), index),
+                    format!(
+                        "invalid matcher regex: {}",
+                        reason.trim_start_matches("error: ")
+                    ),
+                ));
+            }
+        }
+    }
+    problems
+}
+
+fn deref<'a>(root: &'a Value, node: &'a Value) -> &'a Value {
+    match node
+        .get("$ref")
+        .and_then(Value::as_str)
+        .and_then(|r| r.strip_prefix("#/$defs/"))
+    {
+        Some(name) => deref(root, &root["$defs"][name]),
+        None => node,
+    }
+}
+
+/// The node and every variant under it
+fn variants<'a>(root: &'a Value, node: &'a Value) -> Vec<&'a Value> {
+    let node = deref(root, node);
+    let mut out = vec![node];
+    for keyword in ["oneOf", "anyOf", "allOf"] {
+        for branch in node
+            .get(keyword)
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            out.extend(variants(root, branch));
+        }
+    }
+    out
+}
+
+/// Dotted config key named by an `OPENCLAUDIA_` variable with the prefix
+/// removed, e.g. `PROVIDERS_ANTHROPIC_API_KEY` is `providers.anthropic.api_key`.
+/// Underscores separate keys and also appear inside them, so the name is
+/// matched against the schema, preferring the longest key at each level.
+/// Map entries such as provider names take one segment. None when the name
+/// is not a config key.
+pub fn env_key(name: &str) -> Option<String> {
+    fn resolve(root: &Value, node: &Value, segments: &[String]) -> Option<Vec<String>> {
+        let nodes = variants(root, node);
+        if segments.is_empty() {
+            // Sections like `proxy` cannot be set from a single variable
+            let section = nodes.iter().any(|n| n.get("properties").is_some());
+            return (!section).then(Vec::new);
+        }
+        for len in (1..=segments.len()).rev() {
+            let key = segments[..len].join("_");
+            for child in nodes
+                .iter()
+                .filter_map(|n| n.get("properties").and_then(|p| p.get(&key)))
+            {
+                if let Some(mut rest) = resolve(root, child, &segments[len..]) {
+                    rest.insert(0, key);
+                    return Some(rest);
+                }
+            }
+        }
+        for child in nodes
+            .iter()
+            .filter_map(|n| n.get("additionalProperties").filter(|a| a.is_object()))
+        {
+            if let Some(mut rest) = resolve(root, child, &segments[1..]) {
+                rest.insert(0, segments[0].clone());
+                return Some(rest);
+            }
+        }
+        None
+    }
+
+    let segments: Vec<String> = name.to_lowercase().split('_').map(String::from).collect();
+    if segments.iter().any(String::is_empty) {
+        return None;
+    }
+    let root = schema();
+    resolve(root, root, &segments).map(|path| path.join("."))
+}
+
+/// Keys the schema allows in the object at a JSON pointer
+fn known_keys(pointer: &str) -> Vec<String> {
+    let root = schema();
+    let mut nodes = vec![root];
+    for segment in pointer.split('/').skip(1) {
+        let segment = unescape(segment);
+        nodes = nodes
+            .into_iter()
+            .flat_map(|node| variants(root, node))
+            .filter_map(|node| {
+                node.get("properties")
+                    .and_then(|p| p.get(&segment))
+                    .or_else(|| node.get("additionalProperties").filter(|a| a.is_object()))
+                    .or_else(|| node.get("items"))
+            })
+            .collect();
+    }
+    let mut keys: Vec<String> = nodes
+        .into_iter()
+        .flat_map(|node| variants(root, node))
+        .filter_map(|node| node.get("properties").and_then(Value::as_object))
+        .flat_map(|properties| properties.keys().cloned())
+        .collect();
+    keys.sort();
+    keys.dedup();
+    keys
+}
+
+/// The known key closest to a misspelled one, if any is close enough
+fn closest<'a>(key: &str, known: &'a [String]) -> Option<&'a str> {
+    known
+        .iter()
+        .map(|candidate| (edit_distance(key, candidate), candidate))
+        .filter(|(distance, candidate)| *distance <= (candidate.len() / 3).max(1))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate.as_str())
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// `/hooks/pre_tool_use/0/matcher` as `hooks.pre_tool_use.0.matcher`
+fn dotted(pointer: &str) -> String {
+    pointer
+        .split('/')
+        .skip(1)
+        .map(unescape)
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+fn parent(pointer: &str) -> &str {
+    pointer.rsplit_once('/').map_or("", |(parent, _)| parent)
+}
+
+fn escape(segment: &str) -> String {
+    segment.replace('~', "~0").replace('/', "~1")
+}
+
+fn unescape(segment: &str) -> String {
+    segment.replace("~1", "/").replace("~0", "~")
+}
+
+/// Line and column of every mapping key and list item in a YAML document,
+/// by JSON pointer
+#[derive(Default)]
+struct Positions {
+    positions: HashMap<String, (usize, usize)>,
+    stack: Vec<Frame>,
+}
+
+enum Frame {
+    Mapping {
+        pointer: String,
+        key: Option<String>,
+    },
+    Sequence {
+        pointer: String,
+        next: usize,
+    },
+}
+
+impl Positions {
+    fn index(text: &str) -> Self {
+        let mut positions = Positions::default();
+        // A file that does not parse has no positions; the parse error is
+        // reported on its own
+        let _ = Parser::new_from_str(text).load(&mut positions, false);
+        positions
+    }
+
+    /// Position of a pointer, or of its closest located ancestor
+    fn find(&self, mut pointer: &str) -> Option<(usize, usize)> {
+        loop {
+            if let Some(position) = self.positions.get(pointer) {
+                return Some(*position);
+            }
+            if pointer.is_empty() {
+                return None;
+            }
+            pointer = parent(pointer);
+        }
+    }
+}
+
+impl MarkedEventReceiver for Positions {
+    fn on_event(&mut self, event: Event, mark: Marker) {
+        let position = (mark.line(), mark.col() + 1);
+        match event {
+            Event::MappingEnd | Event::SequenceEnd => {
+                self.stack.pop();
+                return;
+            }
+            Event::Scalar(..)
+            | Event::Alias(_)
+            | Event::MappingStart(..)
+            | Event::SequenceStart(..) => {}
+            _ => return,
+        }
+
+        let pointer = match self.stack.last_mut() {
+            None => String::new(),
+            Some(Frame::Sequence { pointer, next }) => {
+                let item = format!("{}/{}", pointer, next);
+                *next += 1;
+                self.positions.insert(item.clone(), position);
+                item
+            }
+            Some(Frame::Mapping { pointer, key }) => match key.take() {
+                Some(key) => format!("{}/{}", pointer, key),
+                None => {
+                    let name = match &event {
+                        Event::Scalar(text, ..) => escape(text),
+                        // A list or mapping as a key: nothing to locate
+                        _ => "?".to_string(),
+                    };
+                    let child = format!("{}/{}", pointer, name);
+                    self.positions.entry(child.clone()).or_insert(position);
+                    *key = Some(name);
+                    if !matches!(event, Event::MappingStart(..) | Event::SequenceStart(..)) {
+                        return;
+                    }
+                    child
+                }
+            },
+        };
+        match event {
+            Event::MappingStart(..) => self.stack.push(Frame::Mapping { pointer, key: None }),
+            Event::SequenceStart(..) => self.stack.push(Frame::Sequence { pointer, next: 0 }),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check_yaml(text: &str) -> Vec<Diagnostic> {
+        let tree = config::parse_layer(text).unwrap().unwrap();
+        check(Path::new("config.yaml"), text, &tree)
+    }
+
+    #[test]
+    fn test_valid_config_has_no_diagnostics() {
+        let text = r#"
+proxy:
+  port: "9090"
+  target: anthropic
+providers:
+  anthropic:
+    api_key: sk-test
+    thinking:
+      budget_tokens: 4096
+hooks:
+  pre_tool_use:
+    - matcher: "Write|Edit"
+      hooks:
+        - type: command
+          command: ./check.sh
+keybindings:
+  f2: models
+session:
+  timeout_minutes: 10
+"#;
+        assert_eq!(check_yaml(text), Vec::new());
+    }
+
+    #[test]
+    fn test_diagnostics_are_located() {
+        let text = r#"proxy:
+  port: many
+hooks:
+  pre_tool_uses: []
+  stop:
+    - matcher: "Write("
+      hooks:
+        - type: script
+          command: ./stop.sh
+providers:
+  anthropic:
+    thinkng:
+      enabled: true
+keybindings:
+  f2: modles
+"#;
+        let diagnostics = check_yaml(text);
+        let found: Vec<(usize, &str, &str)> = diagnostics
+            .iter()
+            .map(|d| (d.position.unwrap().0, d.key.as_str(), d.message.as_str()))
+            .collect();
+        assert_eq!(
+            found[0],
+            (2, "proxy.port", "expected integer, found a string")
+        );
+        assert_eq!(
+            found[1],
+            (
+                4,
+                "hooks.pre_tool_uses",
+                "unknown key (did you mean `pre_tool_use`?)"
+            )
+        );
+        assert_eq!(found[2].0, 6);
+        assert_eq!(found[2].1, "hooks.stop.0.matcher");
+        assert!(
+            found[2].2.starts_with("invalid matcher regex"),
+            "{}",
+            found[2].2
+        );
+        assert_eq!(
+            found[3],
+            (
+                8,
+                "hooks.stop.0.hooks.0.type",
+                "expected one of: command, prompt"
+            )
+        );
+        assert_eq!(
+            found[4],
+            (
+                12,
+                "providers.anthropic.thinkng",
+                "unknown key (did you mean `thinking`?)"
+            )
+        );
+        assert_eq!(found[5].1, "keybindings.f2");
+        assert!(
+            found[5].2.starts_with("expected one of: new_session"),
+            "{}",
+            found[5].2
+        );
+        assert_eq!(found.len(), 6);
+
+        assert_eq!(
+            diagnostics[0].to_string(),
+            "config.yaml:2:3: proxy.port: expected integer, found a string"
+        );
+    }
+
+    #[test]
+    fn test_env_key() {
+        assert_eq!(env_key("PROXY_PORT").as_deref(), Some("proxy.port"));
+        assert_eq!(
+            env_key("PROVIDERS_ANTHROPIC_API_KEY").as_deref(),
+            Some("providers.anthropic.api_key")
+        );
+        assert_eq!(
+            env_key("PROVIDERS_OPENAI_BASE_URL").as_deref(),
+            Some("providers.openai.base_url")
+        );
+        assert_eq!(env_key("PASSPHRASE"), None);
+        assert_eq!(env_key("PROXY"), None);
+        assert_eq!(env_key("PROXY__PORT"), None);
+    }
+
+    #[test]
+    fn test_published_schema_is_current() {
+        let published = include_str!("../schema/config.schema.json");
+        let current = serde_json::to_string_pretty(schema()).unwrap() + "\n";
+        assert!(
+            published == current,
+            "schema/config.schema.json is out of date; regenerate it with \
+             `openclaudia config schema > schema/config.schema.json`"
+        );
+    }
+}
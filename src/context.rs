@@ -121,6 +121,7 @@ impl ContextInjector {
                     content_type: "text".to_string(),
                     text: Some(content.to_string()),
                     image_url: None,
+                    file: None,
                 });
             }
         }
@@ -166,6 +167,7 @@ mod tests {
             stream: None,
             tools: None,
             tool_choice: None,
+            response_format: None,
             extra: std::collections::HashMap::new(),
         }
     }
@@ -296,6 +298,7 @@ mod tests {
             stream: None,
             tools: None,
             tool_choice: None,
+            response_format: None,
             extra: std::collections::HashMap::new(),
         };
 
@@ -322,6 +325,7 @@ mod tests {
             stream: None,
             tools: None,
             tool_choice: None,
+            response_format: None,
             extra: std::collections::HashMap::new(),
         };
 
@@ -397,6 +401,7 @@ mod tests {
                 content_type: "text".to_string(),
                 text: Some("Original part".to_string()),
                 image_url: None,
+                file: None,
             }]),
             name: None,
             tool_calls: None,
@@ -487,6 +492,7 @@ mod tests {
             stream: None,
             tools: None,
             tool_choice: None,
+            response_format: None,
             extra: std::collections::HashMap::new(),
         };
 
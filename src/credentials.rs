@@ -0,0 +1,822 @@
+//! Credential Store - API keys and OAuth tokens kept out of plaintext files.
+//!
+//! Secrets are stored by their config key (`providers.anthropic.api_key`),
+//! plus `oauth.sessions` for Claude Max sessions. Stored keys fill config
+//! keys that no config layer sets. Two backends:
+//!
+//! - `file` (default): `~/.openclaudia/credentials.enc`, encrypted with
+//!   AES-256-GCM. The key is derived from `OPENCLAUDIA_PASSPHRASE` when it is
+//!   set, otherwise from a random machine key in `~/.openclaudia/credentials.key`
+//!   combined with the OS machine id. Both files are readable by the owner only.
+//! - `keyring`: the Secret Service (GNOME Keyring, KWallet) on Linux, when
+//!   built with the `keyring` feature.
+//!
+//! The backend is chosen by `credentials.backend` in a config file or
+//! `OPENCLAUDIA_CREDENTIALS_BACKEND`. [`migrate`] moves plaintext secrets out
+//! of config files, and [`redact`] masks secrets in text such as log lines.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::{self, Write};
+use std::num::NonZeroU32;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex, RwLock};
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use regex::Regex;
+use ring::aead::{self, Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM};
+use ring::rand::{SecureRandom, SystemRandom};
+use ring::{hkdf, pbkdf2};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::warn;
+use tracing_subscriber::fmt::MakeWriter;
+
+use crate::config::{self, ConfigScope, CredentialBackend};
+
+/// Environment variable holding the passphrase for the encrypted file
+pub const PASSPHRASE_VAR: &str = "OPENCLAUDIA_PASSPHRASE";
+
+/// Environment variable selecting the backend, overriding config files
+const BACKEND_VAR: &str = "OPENCLAUDIA_CREDENTIALS_BACKEND";
+
+/// Store key of the persisted OAuth sessions
+pub const OAUTH_SESSIONS_KEY: &str = "oauth.sessions";
+
+/// PBKDF2 rounds for passphrase-derived keys
+const PBKDF2_ROUNDS: u32 = 600_000;
+
+/// Bound into every ciphertext so it cannot be reused elsewhere
+const AAD: &[u8] = b"openclaudia-credentials-v1";
+
+/// Files holding the OS machine id, in order of preference
+const MACHINE_ID_FILES: &[&str] = &["/etc/machine-id", "/var/lib/dbus/machine-id"];
+
+/// The store selected by the config, opened on first use
+static STORE: LazyLock<CredentialStore> = LazyLock::new(CredentialStore::configured);
+
+/// Passphrase-derived keys by salt, so PBKDF2 runs once per process
+static DERIVED_KEYS: LazyLock<Mutex<BTreeMap<Vec<u8>, [u8; 32]>>> =
+    LazyLock::new(|| Mutex::new(BTreeMap::new()));
+
+/// Secret values seen so far, masked by [`redact`]
+static KNOWN_SECRETS: LazyLock<RwLock<BTreeSet<String>>> =
+    LazyLock::new(|| RwLock::new(BTreeSet::new()));
+
+/// Token shapes masked even when their value was never registered
+static SECRET_PATTERNS: LazyLock<Vec<Regex>> = LazyLock::new(|| {
+    [
+        r"sk-[A-Za-z0-9_\-]{16,}",
+        r"AIza[0-9A-Za-z_\-]{30,}",
+        r"(?i)\bbearer\s+[A-Za-z0-9\-._~+/]{16,}=*",
+    ]
+    .iter()
+    .map(|pattern| Regex::new(pattern).expect("valid secret pattern"))
+    .collect()
+});
+
+/// Errors reading or writing stored credentials
+#[derive(Error, Debug)]
+pub enum CredentialError {
+    #[error("Failed to access {0}: {1}")]
+    Io(String, io::Error),
+
+    #[error("Credentials file {0} is encrypted with a passphrase; set {PASSPHRASE_VAR}")]
+    PassphraseRequired(String),
+
+    #[error("Cannot decrypt {0}: wrong passphrase or machine key")]
+    Decrypt(String),
+
+    #[error("Invalid credentials file {0}: {1}")]
+    Format(String, String),
+
+    #[error("Encryption failed")]
+    Encrypt,
+
+    #[error("Keyring error: {0}")]
+    Keyring(String),
+}
+
+/// Where secrets are kept
+trait Backend: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn load(&self) -> Result<BTreeMap<String, String>, CredentialError>;
+    fn save(&self, secrets: &BTreeMap<String, String>) -> Result<(), CredentialError>;
+    /// Take a lock other processes respect until the returned guard drops
+    fn lock(&self) -> Result<Option<fs::File>, CredentialError> {
+        Ok(None)
+    }
+}
+
+/// Secrets by key, in the configured backend
+pub struct CredentialStore {
+    backend: Box<dyn Backend>,
+    /// Serializes load-modify-save cycles within this process
+    writing: Mutex<()>,
+}
+
+/// The configured credential store
+pub fn store() -> &'static CredentialStore {
+    &STORE
+}
+
+impl CredentialStore {
+    /// Encrypted file store at `path`, with its machine key at `key_path`
+    pub fn file(path: PathBuf, key_path: PathBuf) -> Self {
+        Self {
+            backend: Box::new(EncryptedFile { path, key_path }),
+            writing: Mutex::new(()),
+        }
+    }
+
+    fn configured() -> Self {
+        let dir = dirs::home_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join(".openclaudia");
+        let file = || Self::file(dir.join("credentials.enc"), dir.join("credentials.key"));
+        match configured_backend() {
+            CredentialBackend::File => file(),
+            #[cfg(all(target_os = "linux", feature = "keyring"))]
+            CredentialBackend::Keyring => Self {
+                backend: Box::new(keyring_backend::SecretService),
+                writing: Mutex::new(()),
+            },
+            #[cfg(not(all(target_os = "linux", feature = "keyring")))]
+            CredentialBackend::Keyring => {
+                warn!("The keyring credential backend is not available in this build, using the encrypted file");
+                file()
+            }
+        }
+    }
+
+    /// Backend name for display, e.g. `encrypted file`
+    pub fn name(&self) -> &'static str {
+        self.backend.name()
+    }
+
+    /// Every stored secret. Values are registered for [`redact`].
+    pub fn all(&self) -> Result<BTreeMap<String, String>, CredentialError> {
+        let secrets = self.backend.load()?;
+        for value in secrets.values() {
+            register_secret(value);
+        }
+        Ok(secrets)
+    }
+
+    pub fn get(&self, key: &str) -> Result<Option<String>, CredentialError> {
+        Ok(self.all()?.remove(key))
+    }
+
+    pub fn set(&self, key: &str, value: &str) -> Result<(), CredentialError> {
+        let _writing = self.writing.lock().unwrap_or_else(|e| e.into_inner());
+        let _locked = self.backend.lock()?;
+        let mut secrets = self.backend.load()?;
+        secrets.insert(key.to_string(), value.to_string());
+        register_secret(value);
+        self.backend.save(&secrets)
+    }
+
+    /// Remove a secret. Returns whether it was stored.
+    pub fn delete(&self, key: &str) -> Result<bool, CredentialError> {
+        let _writing = self.writing.lock().unwrap_or_else(|e| e.into_inner());
+        let _locked = self.backend.lock()?;
+        let mut secrets = self.backend.load()?;
+        if secrets.remove(key).is_none() {
+            return Ok(false);
+        }
+        self.backend.save(&secrets)?;
+        Ok(true)
+    }
+
+    /// Stored secrets that are config keys, to fill in when loading config.
+    /// Errors are logged and yield nothing, so a locked store never stops
+    /// the configuration from loading.
+    pub fn config_secrets(&self) -> BTreeMap<String, String> {
+        match self.all() {
+            Ok(secrets) => secrets
+                .into_iter()
+                .filter(|(key, _)| config::is_secret_key(key))
+                .collect(),
+            Err(e) => {
+                warn!("Stored credentials unavailable: {}", e);
+                BTreeMap::new()
+            }
+        }
+    }
+}
+
+/// `credentials.backend` from the environment or the last config file that
+/// sets it. Read directly because loading the config needs the store.
+fn configured_backend() -> CredentialBackend {
+    let parse = |value: serde_yaml::Value| serde_yaml::from_value::<CredentialBackend>(value).ok();
+    if let Some(backend) = std::env::var(BACKEND_VAR)
+        .ok()
+        .and_then(|name| parse(serde_yaml::Value::from(name.to_lowercase())))
+    {
+        return backend;
+    }
+    ConfigScope::ALL
+        .iter()
+        .rev()
+        .filter_map(|scope| scope.path())
+        .filter_map(|path| fs::read_to_string(path).ok())
+        .filter_map(|text| config::parse_layer(&text).ok().flatten())
+        .find_map(|tree| parse(tree.get("credentials")?.get("backend")?.clone()))
+        .unwrap_or_default()
+}
+
+// ============================================================================
+// Encrypted file backend
+// ============================================================================
+
+/// On-disk form of the encrypted file
+#[derive(Serialize, Deserialize)]
+struct Envelope {
+    version: u32,
+    /// `passphrase` or `machine`
+    key: String,
+    salt: String,
+    nonce: String,
+    data: String,
+}
+
+struct EncryptedFile {
+    path: PathBuf,
+    key_path: PathBuf,
+}
+
+impl EncryptedFile {
+    fn io_error(path: &Path) -> impl FnOnce(io::Error) -> CredentialError + '_ {
+        move |e| CredentialError::Io(path.display().to_string(), e)
+    }
+
+    fn format_error(&self, message: impl ToString) -> CredentialError {
+        CredentialError::Format(self.path.display().to_string(), message.to_string())
+    }
+
+    /// Random key kept next to the store, created on first save
+    fn machine_key(&self, create: bool) -> Result<Vec<u8>, CredentialError> {
+        let mut material = match fs::read_to_string(&self.key_path) {
+            Ok(text) => STANDARD
+                .decode(text.trim())
+                .map_err(|e| self.format_error(format!("machine key: {}", e)))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound && create => {
+                let key = random_bytes(32)?;
+                write_private(&self.key_path, STANDARD.encode(&key).as_bytes())?;
+                key
+            }
+            Err(e) => return Err(Self::io_error(&self.key_path)(e)),
+        };
+        // Tie the key to this machine, so copying both files elsewhere is
+        // not enough to decrypt them
+        if let Some(id) = MACHINE_ID_FILES
+            .iter()
+            .find_map(|path| fs::read_to_string(path).ok())
+        {
+            material.extend_from_slice(id.trim().as_bytes());
+        }
+        Ok(material)
+    }
+
+    fn cipher(&self, key: &str, salt: &[u8], create: bool) -> Result<LessSafeKey, CredentialError> {
+        let mut bytes = [0u8; 32];
+        match key {
+            "passphrase" => {
+                let passphrase = std::env::var(PASSPHRASE_VAR).map_err(|_| {
+                    CredentialError::PassphraseRequired(self.path.display().to_string())
+                })?;
+                bytes = passphrase_key(&passphrase, salt);
+            }
+            "machine" => {
+                let material = self.machine_key(create)?;
+                hkdf::Salt::new(hkdf::HKDF_SHA256, salt)
+                    .extract(&material)
+                    .expand(&[AAD], &AES_256_GCM)
+                    .and_then(|okm| okm.fill(&mut bytes))
+                    .map_err(|_| CredentialError::Encrypt)?;
+            }
+            other => return Err(self.format_error(format!("unknown key type '{}'", other))),
+        }
+        let key = UnboundKey::new(&AES_256_GCM, &bytes).map_err(|_| CredentialError::Encrypt)?;
+        Ok(LessSafeKey::new(key))
+    }
+}
+
+impl Backend for EncryptedFile {
+    fn name(&self) -> &'static str {
+        "encrypted file"
+    }
+
+    fn load(&self) -> Result<BTreeMap<String, String>, CredentialError> {
+        let text = match fs::read_to_string(&self.path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(BTreeMap::new()),
+            Err(e) => return Err(Self::io_error(&self.path)(e)),
+        };
+        let envelope: Envelope = serde_json::from_str(&text).map_err(|e| self.format_error(e))?;
+        let decode = |field: &str| STANDARD.decode(field).map_err(|e| self.format_error(e));
+        let salt = decode(&envelope.salt)?;
+        let nonce = Nonce::try_assume_unique_for_key(&decode(&envelope.nonce)?)
+            .map_err(|_| self.format_error("invalid nonce"))?;
+        let mut data = decode(&envelope.data)?;
+        let plain = self
+            .cipher(&envelope.key, &salt, false)?
+            .open_in_place(nonce, Aad::from(AAD), &mut data)
+            .map_err(|_| CredentialError::Decrypt(self.path.display().to_string()))?;
+        serde_json::from_slice(plain).map_err(|e| self.format_error(e))
+    }
+
+    fn save(&self, secrets: &BTreeMap<String, String>) -> Result<(), CredentialError> {
+        let key = if std::env::var(PASSPHRASE_VAR).is_ok_and(|p| !p.is_empty()) {
+            "passphrase"
+        } else {
+            "machine"
+        };
+        let salt = random_bytes(16)?;
+        let nonce = random_bytes(aead::NONCE_LEN)?;
+        let mut data = serde_json::to_vec(secrets).map_err(|e| self.format_error(e))?;
+        self.cipher(key, &salt, true)?
+            .seal_in_place_append_tag(
+                Nonce::try_assume_unique_for_key(&nonce).map_err(|_| CredentialError::Encrypt)?,
+                Aad::from(AAD),
+                &mut data,
+            )
+            .map_err(|_| CredentialError::Encrypt)?;
+        let envelope = Envelope {
+            version: 1,
+            key: key.to_string(),
+            salt: STANDARD.encode(&salt),
+            nonce: STANDARD.encode(&nonce),
+            data: STANDARD.encode(&data),
+        };
+        let json = serde_json::to_vec_pretty(&envelope).map_err(|e| self.format_error(e))?;
+        write_private(&self.path, &json)
+    }
+
+    /// Advisory lock on `<path>.lock`, released when the file closes
+    fn lock(&self) -> Result<Option<fs::File>, CredentialError> {
+        let path = self.path.with_extension("lock");
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).map_err(Self::io_error(dir))?;
+        }
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)
+            .map_err(Self::io_error(&path))?;
+        file.lock().map_err(Self::io_error(&path))?;
+        Ok(Some(file))
+    }
+}
+
+fn passphrase_key(passphrase: &str, salt: &[u8]) -> [u8; 32] {
+    let mut cache = DERIVED_KEYS.lock().unwrap_or_else(|e| e.into_inner());
+    *cache.entry(salt.to_vec()).or_insert_with(|| {
+        let mut key = [0u8; 32];
+        let rounds = NonZeroU32::new(PBKDF2_ROUNDS).expect("rounds are non-zero");
+        pbkdf2::derive(
+            pbkdf2::PBKDF2_HMAC_SHA256,
+            rounds,
+            salt,
+            passphrase.as_bytes(),
+            &mut key,
+        );
+        key
+    })
+}
+
+fn random_bytes(len: usize) -> Result<Vec<u8>, CredentialError> {
+    let mut bytes = vec![0u8; len];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .map_err(|_| CredentialError::Encrypt)?;
+    Ok(bytes)
+}
+
+/// Write a file only its owner can read, replacing it atomically
+pub fn write_private(path: &Path, contents: &[u8]) -> Result<(), CredentialError> {
+    let io_error = EncryptedFile::io_error(path);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| EncryptedFile::io_error(parent)(e))?;
+    }
+    let tmp = path.with_extension("tmp");
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let result = options
+        .open(&tmp)
+        .and_then(|mut file| {
+            // The mode only applies to new files
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                file.set_permissions(fs::Permissions::from_mode(0o600))?;
+            }
+            file.write_all(contents)?;
+            file.sync_all()
+        })
+        .and_then(|()| fs::rename(&tmp, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result.map_err(io_error)
+}
+
+// ============================================================================
+// Secret Service backend
+// ============================================================================
+
+#[cfg(all(target_os = "linux", feature = "keyring"))]
+mod keyring_backend {
+    use super::{Backend, CredentialError};
+    use std::collections::BTreeMap;
+
+    /// All secrets as one JSON entry in the default collection
+    pub struct SecretService;
+
+    impl SecretService {
+        fn entry() -> Result<keyring::Entry, CredentialError> {
+            keyring::Entry::new("openclaudia", "credentials")
+                .map_err(|e| CredentialError::Keyring(e.to_string()))
+        }
+    }
+
+    impl Backend for SecretService {
+        fn name(&self) -> &'static str {
+            "Secret Service keyring"
+        }
+
+        fn load(&self) -> Result<BTreeMap<String, String>, CredentialError> {
+            match Self::entry()?.get_password() {
+                Ok(json) => serde_json::from_str(&json)
+                    .map_err(|e| CredentialError::Keyring(format!("invalid entry: {}", e))),
+                Err(keyring::Error::NoEntry) => Ok(BTreeMap::new()),
+                Err(e) => Err(CredentialError::Keyring(e.to_string())),
+            }
+        }
+
+        fn save(&self, secrets: &BTreeMap<String, String>) -> Result<(), CredentialError> {
+            let json = serde_json::to_string(secrets)
+                .map_err(|e| CredentialError::Keyring(e.to_string()))?;
+            Self::entry()?
+                .set_password(&json)
+                .map_err(|e| CredentialError::Keyring(e.to_string()))
+        }
+    }
+}
+
+// ============================================================================
+// Migration of plaintext secrets
+// ============================================================================
+
+/// Move plaintext secrets into the store: credential keys in the user and
+/// local config files (values referencing `${VAR}` stay) and keys written by
+/// older versions of `/connect` to `<config dir>/openclaudia/config.yaml`.
+/// The shared project file is never rewritten; secrets in it are only
+/// warned about. Returns what moved.
+pub fn migrate(store: &CredentialStore) -> Vec<String> {
+    if let Some(path) = ConfigScope::Project.path() {
+        for (key, _) in plaintext_secrets(&path) {
+            warn!(
+                "{} is stored in plaintext in {}, which is shared with the project; \
+                 remove it there and store it with 'openclaudia config set {} <value>', \
+                 or reference an environment variable with ${{VAR}}",
+                key,
+                path.display(),
+                key
+            );
+        }
+    }
+
+    let mut moved = Vec::new();
+    for scope in [ConfigScope::User, ConfigScope::Local] {
+        let Some(path) = scope.path() else {
+            continue;
+        };
+        for (key, value) in plaintext_secrets(&path) {
+            match move_secret(store, &key, &value) {
+                Ok(true) => match config::unset_value(scope, &key) {
+                    Ok(_) => moved.push(format!(
+                        "{} from {} to the credential store ({})",
+                        key,
+                        path.display(),
+                        store.name()
+                    )),
+                    Err(e) => warn!(
+                        "Stored {} but could not remove it from {}: {}",
+                        key,
+                        path.display(),
+                        e
+                    ),
+                },
+                Ok(false) => warn!(
+                    "{} in {} differs from the stored value and was left in place",
+                    key,
+                    path.display()
+                ),
+                Err(e) => warn!("Could not move {} to the credential store: {}", key, e),
+            }
+        }
+    }
+    if let Some(path) = dirs::config_dir().map(|dir| dir.join("openclaudia").join("config.yaml")) {
+        moved.extend(migrate_connect_file(store, &path));
+    }
+    moved
+}
+
+/// Store a secret unless a different value is already stored under its key.
+/// Returns whether the plaintext copy can go.
+fn move_secret(store: &CredentialStore, key: &str, value: &str) -> Result<bool, CredentialError> {
+    match store.get(key)? {
+        Some(stored) if stored == value => Ok(true),
+        Some(_) => Ok(false),
+        None => store.set(key, value).map(|()| true),
+    }
+}
+
+/// Credential keys with literal values in a config file
+fn plaintext_secrets(path: &Path) -> Vec<(String, String)> {
+    fn walk(prefix: &str, value: &serde_yaml::Value, out: &mut Vec<(String, String)>) {
+        match value {
+            serde_yaml::Value::Mapping(map) => {
+                for (key, item) in map {
+                    let Some(key) = key.as_str() else {
+                        continue;
+                    };
+                    let path = if prefix.is_empty() {
+                        key.to_string()
+                    } else {
+                        format!("{}.{}", prefix, key)
+                    };
+                    if path != "hooks" {
+                        walk(&path, item, out);
+                    }
+                }
+            }
+            serde_yaml::Value::String(text)
+                if config::is_secret_key(prefix) && !text.is_empty() && !text.contains("${") =>
+            {
+                out.push((prefix.to_string(), text.clone()));
+            }
+            _ => {}
+        }
+    }
+    let Some(tree) = fs::read_to_string(path)
+        .ok()
+        .and_then(|text| serde_yaml::from_str::<serde_yaml::Value>(&text).ok())
+    else {
+        return Vec::new();
+    };
+    let mut out = Vec::new();
+    walk("", &tree, &mut out);
+    out
+}
+
+/// Move `<provider>_api_key` entries out of the file `/connect` used to write
+fn migrate_connect_file(store: &CredentialStore, path: &Path) -> Vec<String> {
+    let Some(map) = fs::read_to_string(path)
+        .ok()
+        .and_then(|text| serde_yaml::from_str::<serde_yaml::Mapping>(&text).ok())
+    else {
+        return Vec::new();
+    };
+    let mut moved = Vec::new();
+    let entries: Vec<(String, String)> = map
+        .iter()
+        .filter_map(|(key, value)| Some((key.as_str()?.to_string(), value.as_str()?.to_string())))
+        .filter(|(key, value)| key.ends_with("_api_key") && !value.is_empty())
+        .collect();
+    for (entry, value) in entries {
+        let provider = entry.trim_end_matches("_api_key");
+        let key = format!("providers.{}.api_key", provider);
+        match move_secret(store, &key, &value) {
+            Ok(true) => match config::unset_in_file(path, &entry) {
+                Ok(_) => moved.push(format!(
+                    "{} from {} to the credential store ({})",
+                    key,
+                    path.display(),
+                    store.name()
+                )),
+                Err(e) => warn!("Could not rewrite {}: {}", path.display(), e),
+            },
+            Ok(false) => {}
+            Err(e) => warn!("Could not move {} to the credential store: {}", key, e),
+        }
+    }
+    let emptied = fs::read_to_string(path)
+        .ok()
+        .and_then(|text| serde_yaml::from_str::<serde_yaml::Value>(&text).ok())
+        .is_some_and(|value| value.is_null());
+    if !moved.is_empty() && emptied {
+        if let Err(e) = fs::remove_file(path) {
+            warn!("Could not remove {}: {}", path.display(), e);
+        }
+    }
+    moved
+}
+
+// ============================================================================
+// Redaction
+// ============================================================================
+
+/// Remember a secret so [`redact`] masks it wherever it appears
+pub fn register_secret(value: &str) {
+    // Short values would mask ordinary words
+    if value.len() < 8 {
+        return;
+    }
+    let mut known = KNOWN_SECRETS.write().unwrap_or_else(|e| e.into_inner());
+    if !known.contains(value) {
+        known.insert(value.to_string());
+    }
+}
+
+/// A secret for display: its first characters when long enough to keep it
+/// recognizable, e.g. `sk-a********`
+pub fn mask(secret: &str) -> String {
+    if secret.chars().count() < 12 {
+        return "********".to_string();
+    }
+    let prefix: String = secret.chars().take(4).collect();
+    format!("{}********", prefix)
+}
+
+/// Mask registered secrets and anything shaped like an API key or bearer
+/// token
+pub fn redact(text: &str) -> String {
+    let mut out = text.to_string();
+    {
+        let known = KNOWN_SECRETS.read().unwrap_or_else(|e| e.into_inner());
+        // Longest first, so a secret containing another is masked whole
+        let mut secrets: Vec<&String> = known.iter().filter(|s| out.contains(s.as_str())).collect();
+        secrets.sort_by_key(|s| std::cmp::Reverse(s.len()));
+        for secret in secrets {
+            out = out.replace(secret.as_str(), &mask(secret));
+        }
+    }
+    for pattern in SECRET_PATTERNS.iter() {
+        out = pattern
+            .replace_all(&out, |caps: &regex::Captures| {
+                let matched = &caps[0];
+                match matched.split_once(char::is_whitespace) {
+                    Some((scheme, token)) => format!("{} {}", scheme, mask(token.trim_start())),
+                    None => mask(matched),
+                }
+            })
+            .into_owned();
+    }
+    out
+}
+
+/// Log writer that passes each event through [`redact`] before it reaches
+/// stdout
+pub struct RedactingWriter;
+
+impl<'a> MakeWriter<'a> for RedactingWriter {
+    type Writer = RedactedEvent;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        RedactedEvent(Vec::new())
+    }
+}
+
+/// One formatted log event, written out redacted when dropped
+pub struct RedactedEvent(Vec<u8>);
+
+impl Write for RedactedEvent {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for RedactedEvent {
+    fn drop(&mut self) {
+        let text = redact(&String::from_utf8_lossy(&self.0));
+        let _ = io::stdout().lock().write_all(text.as_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_concurrent_writers_keep_every_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let open = || {
+            CredentialStore::file(
+                dir.path().join("credentials.enc"),
+                dir.path().join("credentials.key"),
+            )
+        };
+        // Two stores on one file stand in for two processes
+        let (first, second) = (open(), open());
+        std::thread::scope(|scope| {
+            for (name, store) in [("a", &first), ("b", &second)] {
+                scope.spawn(move || {
+                    for i in 0..10 {
+                        store.set(&format!("{}.{}", name, i), "value").unwrap();
+                    }
+                });
+            }
+        });
+        let all = first.all().unwrap();
+        assert_eq!(all.len(), 20, "lost writes: {:?}", all.keys());
+    }
+
+    #[test]
+    fn test_file_store_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("credentials.enc");
+        let store = CredentialStore::file(path.clone(), dir.path().join("credentials.key"));
+
+        assert!(store.all().unwrap().is_empty());
+        store
+            .set("providers.anthropic.api_key", "sk-ant-test-0123456789")
+            .unwrap();
+        store.set(OAUTH_SESSIONS_KEY, "{}").unwrap();
+        assert_eq!(
+            store.get("providers.anthropic.api_key").unwrap().as_deref(),
+            Some("sk-ant-test-0123456789")
+        );
+        // Only credential config keys fill in config
+        let secrets = store.config_secrets();
+        assert_eq!(secrets.len(), 1);
+        assert!(secrets.contains_key("providers.anthropic.api_key"));
+
+        let on_disk = fs::read_to_string(&path).unwrap();
+        assert!(!on_disk.contains("sk-ant-test"));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            for file in [&path, &dir.path().join("credentials.key")] {
+                let mode = fs::metadata(file).unwrap().permissions().mode();
+                assert_eq!(mode & 0o777, 0o600);
+            }
+        }
+
+        assert!(store.delete("providers.anthropic.api_key").unwrap());
+        assert!(!store.delete("providers.anthropic.api_key").unwrap());
+        assert_eq!(store.all().unwrap().len(), 1);
+
+        // A different machine key cannot decrypt the file
+        fs::write(
+            dir.path().join("credentials.key"),
+            STANDARD.encode([7u8; 32]),
+        )
+        .unwrap();
+        assert!(matches!(store.all(), Err(CredentialError::Decrypt(_))));
+    }
+
+    #[test]
+    fn test_plaintext_secrets() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.yaml");
+        fs::write(
+            &path,
+            "providers:\n  anthropic:\n    api_key: sk-plain\n  openai:\n    api_key: ${OPENAI_API_KEY}\n\
+             web:\n  search:\n    brave_api_key: brave-key\nproxy:\n  port: 8080\n",
+        )
+        .unwrap();
+        let mut secrets = plaintext_secrets(&path);
+        secrets.sort();
+        assert_eq!(
+            secrets,
+            vec![
+                (
+                    "providers.anthropic.api_key".to_string(),
+                    "sk-plain".to_string()
+                ),
+                (
+                    "web.search.brave_api_key".to_string(),
+                    "brave-key".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_redact() {
+        register_secret("registered-secret-value");
+        let line = "key=registered-secret-value auth: Bearer abcdefghijklmnopqrstuvwxyz sk-ant-REDACTED";
+        let redacted = redact(line);
+        assert!(!redacted.contains("registered-secret-value"));
+        assert!(!redacted.contains("abcdefghijklmnopqrstuvwxyz"));
+        assert!(!redacted.contains("api03-abcdefghijklmnop"));
+        assert!(redacted.contains("regi********"));
+        assert!(redacted.contains("Bearer abcd********"));
+        assert_eq!(redact("nothing to hide"), "nothing to hide");
+        assert_eq!(mask("short"), "********");
+    }
+}
@@ -254,6 +254,16 @@ impl HooksConfig {
             && self.user_prompt_submit.is_empty()
             && self.stop.is_empty()
     }
+
+    /// Number of hook entries across all events
+    pub fn len(&self) -> usize {
+        self.session_start.len()
+            + self.session_end.len()
+            + self.pre_tool_use.len()
+            + self.post_tool_use.len()
+            + self.user_prompt_submit.len()
+            + self.stop.len()
+    }
 }
 
 /// Input provided to hooks via stdin
@@ -0,0 +1,574 @@
+//! Issue tracking tools (`issue_create`, `issue_list`, `issue_show`, ...).
+//!
+//! Issues live in the per-project SQLite database (`.openclaudia/memory.db`)
+//! by default, so tracking works without any external tooling and without
+//! `--stateful`. Setting `issues.backend: chainlink` in config.yaml routes the
+//! same tools through the external chainlink CLI instead.
+
+use crate::config::{self, IssueBackend};
+use crate::memory::{Issue, MemoryDb, ISSUE_PRIORITIES};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Names of the structured issue tools
+pub const ISSUE_TOOLS: &[&str] = &[
+    "issue_create",
+    "issue_list",
+    "issue_show",
+    "issue_comment",
+    "issue_close",
+    "issue_reopen",
+    "issue_label",
+    "issue_depend",
+];
+
+/// Whether a tool name is one of the issue tools
+pub fn is_issue_tool(name: &str) -> bool {
+    ISSUE_TOOLS.contains(&name)
+}
+
+/// Configured issue backend (native when no config is available)
+pub fn configured_backend() -> IssueBackend {
+    config::load_config()
+        .map(|c| c.issues.backend)
+        .unwrap_or_default()
+}
+
+/// Execute an issue tool against the configured backend.
+///
+/// Uses `memory_db` when running in stateful mode, otherwise opens the
+/// project database in the current directory.
+pub fn execute_issue_tool(
+    name: &str,
+    args: &HashMap<String, Value>,
+    memory_db: Option<&MemoryDb>,
+) -> (String, bool) {
+    match configured_backend() {
+        IssueBackend::Chainlink => execute_chainlink_issue_tool(name, args),
+        IssueBackend::Native => match memory_db {
+            Some(db) => execute_native_issue_tool(name, args, db),
+            None => match open_project_db() {
+                Ok(db) => execute_native_issue_tool(name, args, &db),
+                Err(e) => (e, true),
+            },
+        },
+    }
+}
+
+fn open_project_db() -> Result<MemoryDb, String> {
+    let cwd = std::env::current_dir().map_err(|e| format!("Failed to get cwd: {}", e))?;
+    MemoryDb::open_for_project(&cwd).map_err(|e| format!("Failed to open issue store: {}", e))
+}
+
+/// Look up an issue in the native store of the current project.
+/// Returns None with the chainlink backend or when the issue doesn't exist.
+pub fn lookup_issue(id: i64) -> Option<Issue> {
+    if configured_backend() != IssueBackend::Native {
+        return None;
+    }
+    open_project_db().ok()?.issue_get(id).ok().flatten()
+}
+
+/// Map a successful issue tool call to a short-term memory activity
+/// (`activity_type`, `"#<id>"`) so the issue shows up in the session's
+/// `issues_worked`. Read-only tools return None.
+pub fn issue_activity(
+    name: &str,
+    args: &HashMap<String, Value>,
+    output: &str,
+) -> Option<(&'static str, String)> {
+    let activity_type = match name {
+        "issue_create" => "issue_created",
+        "issue_close" => "issue_closed",
+        "issue_comment" => "issue_comment",
+        "issue_reopen" | "issue_label" | "issue_depend" => "issue_updated",
+        _ => return None,
+    };
+    let id = if name == "issue_create" {
+        parse_created_id(output)?
+    } else {
+        issue_id_arg(args, "id").ok()?
+    };
+    Some((activity_type, format!("#{}", id)))
+}
+
+/// Extract the issue number from "Created issue #N" style output
+fn parse_created_id(output: &str) -> Option<i64> {
+    let rest = &output[output.find('#')? + 1..];
+    let digits: String = rest.chars().take_while(|c| c.is_ascii_digit()).collect();
+    digits.parse().ok()
+}
+
+/// Read an issue ID argument, accepting `5`, `"5"` or `"#5"`
+fn issue_id_arg(args: &HashMap<String, Value>, key: &str) -> Result<i64, String> {
+    match args.get(key) {
+        Some(Value::Number(n)) => n
+            .as_i64()
+            .ok_or_else(|| format!("Invalid '{}' argument", key)),
+        Some(Value::String(s)) => s
+            .trim()
+            .trim_start_matches('#')
+            .parse()
+            .map_err(|_| format!("Invalid '{}' argument: {}", key, s)),
+        _ => Err(format!("Missing '{}' argument", key)),
+    }
+}
+
+fn str_arg<'a>(args: &'a HashMap<String, Value>, key: &str) -> Result<&'a str, String> {
+    args.get(key)
+        .and_then(|v| v.as_str())
+        .filter(|s| !s.trim().is_empty())
+        .ok_or_else(|| format!("Missing '{}' argument", key))
+}
+
+fn labels_arg(args: &HashMap<String, Value>) -> Vec<String> {
+    args.get("labels")
+        .and_then(|v| v.as_array())
+        .map(|arr| {
+            arr.iter()
+                .filter_map(|v| v.as_str().map(String::from))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn remove_arg(args: &HashMap<String, Value>) -> bool {
+    args.get("remove")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false)
+}
+
+// === Native backend ===
+
+fn execute_native_issue_tool(
+    name: &str,
+    args: &HashMap<String, Value>,
+    db: &MemoryDb,
+) -> (String, bool) {
+    match native_issue_tool(name, args, db) {
+        Ok(output) => (output, false),
+        Err(e) => (e, true),
+    }
+}
+
+fn native_issue_tool(
+    name: &str,
+    args: &HashMap<String, Value>,
+    db: &MemoryDb,
+) -> Result<String, String> {
+    match name {
+        "issue_create" => {
+            let title = str_arg(args, "title")?;
+            let description = args
+                .get("description")
+                .and_then(|v| v.as_str())
+                .unwrap_or("");
+            let priority = args
+                .get("priority")
+                .and_then(|v| v.as_str())
+                .unwrap_or("medium");
+            let parent = match args.get("parent") {
+                Some(Value::Null) | None => None,
+                Some(_) => Some(issue_id_arg(args, "parent")?),
+            };
+
+            let id = db
+                .issue_create(title, description, priority, parent)
+                .map_err(|e| e.to_string())?;
+            for label in labels_arg(args) {
+                db.issue_add_label(id, &label).map_err(|e| e.to_string())?;
+            }
+            Ok(format!("Created issue #{}: {}", id, title))
+        }
+        "issue_list" => {
+            let status = match args
+                .get("status")
+                .and_then(|v| v.as_str())
+                .unwrap_or("open")
+            {
+                "all" => None,
+                s @ ("open" | "closed") => Some(s),
+                other => {
+                    return Err(format!(
+                        "Invalid status '{}'. Must be 'open', 'closed' or 'all'",
+                        other
+                    ))
+                }
+            };
+            let label = args.get("label").and_then(|v| v.as_str());
+            let issues = db.issue_list(status, label).map_err(|e| e.to_string())?;
+            if issues.is_empty() {
+                return Ok(format!("No {} issues.", status.unwrap_or("tracked")));
+            }
+
+            let open_ids: Vec<i64> = db
+                .issue_list(Some("open"), None)
+                .map_err(|e| e.to_string())?
+                .iter()
+                .map(|i| i.id)
+                .collect();
+            Ok(issues
+                .iter()
+                .map(|issue| format_issue_line(issue, &open_ids))
+                .collect::<Vec<_>>()
+                .join("\n"))
+        }
+        "issue_show" => {
+            let id = issue_id_arg(args, "id")?;
+            let issue = db
+                .issue_get(id)
+                .map_err(|e| e.to_string())?
+                .ok_or_else(|| format!("Issue #{} not found", id))?;
+            let comments = db.issue_comments(id).map_err(|e| e.to_string())?;
+
+            let mut output = format!(
+                "#{} [{}] {} ({})\n",
+                issue.id, issue.status, issue.title, issue.priority
+            );
+            if let Some(parent) = issue.parent_id {
+                output.push_str(&format!("Parent: #{}\n", parent));
+            }
+            if !issue.labels.is_empty() {
+                output.push_str(&format!("Labels: {}\n", issue.labels.join(", ")));
+            }
+            if !issue.blocked_by.is_empty() {
+                let blockers: Vec<String> = issue
+                    .blocked_by
+                    .iter()
+                    .map(|b| {
+                        let status = db
+                            .issue_get(*b)
+                            .ok()
+                            .flatten()
+                            .map(|i| i.status)
+                            .unwrap_or_default();
+                        format!("#{} ({})", b, status)
+                    })
+                    .collect();
+                output.push_str(&format!("Blocked by: {}\n", blockers.join(", ")));
+            }
+            output.push_str(&format!(
+                "Created: {}  Updated: {}\n",
+                issue.created_at, issue.updated_at
+            ));
+            if let Some(closed_at) = &issue.closed_at {
+                output.push_str(&format!("Closed: {}\n", closed_at));
+            }
+            if !issue.description.is_empty() {
+                output.push_str(&format!("\n{}\n", issue.description));
+            }
+            if !comments.is_empty() {
+                output.push_str(&format!("\nComments ({}):\n", comments.len()));
+                for comment in comments {
+                    output.push_str(&format!("[{}] {}\n", comment.created_at, comment.content));
+                }
+            }
+            Ok(output.trim_end().to_string())
+        }
+        "issue_comment" => {
+            let id = issue_id_arg(args, "id")?;
+            let content = str_arg(args, "content")?;
+            db.issue_comment(id, content).map_err(|e| e.to_string())?;
+            Ok(format!("Added comment to issue #{}", id))
+        }
+        "issue_close" | "issue_reopen" => {
+            let id = issue_id_arg(args, "id")?;
+            if let Some(comment) = args.get("comment").and_then(|v| v.as_str()) {
+                db.issue_comment(id, comment).map_err(|e| e.to_string())?;
+            }
+            let (status, verb) = if name == "issue_close" {
+                ("closed", "Closed")
+            } else {
+                ("open", "Reopened")
+            };
+            if db.issue_set_status(id, status).map_err(|e| e.to_string())? {
+                Ok(format!("{} issue #{}", verb, id))
+            } else {
+                Err(format!("Issue #{} not found", id))
+            }
+        }
+        "issue_label" => {
+            let id = issue_id_arg(args, "id")?;
+            let label = str_arg(args, "label")?;
+            if remove_arg(args) {
+                db.issue_remove_label(id, label)
+                    .map_err(|e| e.to_string())?;
+                Ok(format!("Removed label '{}' from issue #{}", label, id))
+            } else {
+                db.issue_add_label(id, label).map_err(|e| e.to_string())?;
+                Ok(format!("Added label '{}' to issue #{}", label, id))
+            }
+        }
+        "issue_depend" => {
+            let id = issue_id_arg(args, "id")?;
+            let blocked_by = issue_id_arg(args, "blocked_by")?;
+            if remove_arg(args) {
+                db.issue_remove_dependency(id, blocked_by)
+                    .map_err(|e| e.to_string())?;
+                Ok(format!(
+                    "Issue #{} is no longer blocked by #{}",
+                    id, blocked_by
+                ))
+            } else {
+                db.issue_add_dependency(id, blocked_by)
+                    .map_err(|e| e.to_string())?;
+                Ok(format!("Issue #{} is now blocked by #{}", id, blocked_by))
+            }
+        }
+        _ => Err(format!("Unknown issue tool: {}", name)),
+    }
+}
+
+/// One-line summary used by `issue_list`
+fn format_issue_line(issue: &Issue, open_ids: &[i64]) -> String {
+    let mut line = format!("#{} [{}] {}", issue.id, issue.priority, issue.title);
+    if !issue.is_open() {
+        line.push_str(" (closed)");
+    }
+    if !issue.labels.is_empty() {
+        line.push_str(&format!(" [{}]", issue.labels.join(", ")));
+    }
+    let open_blockers: Vec<String> = issue
+        .blocked_by
+        .iter()
+        .filter(|b| open_ids.contains(b))
+        .map(|b| format!("#{}", b))
+        .collect();
+    if !open_blockers.is_empty() {
+        line.push_str(&format!(" (blocked by {})", open_blockers.join(", ")));
+    }
+    line
+}
+
+// === Chainlink backend ===
+
+/// Quote an argument for the shell chainlink runs under
+fn shell_quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', r"'\''"))
+}
+
+/// Translate an issue tool call into chainlink command lines
+fn chainlink_commands(name: &str, args: &HashMap<String, Value>) -> Result<Vec<String>, String> {
+    let q = shell_quote;
+    let commands = match name {
+        "issue_create" => {
+            let title = str_arg(args, "title")?;
+            let priority = args
+                .get("priority")
+                .and_then(|v| v.as_str())
+                .unwrap_or("medium");
+            if !ISSUE_PRIORITIES.contains(&priority) {
+                return Err(format!(
+                    "Invalid priority '{}'. Must be one of: {}",
+                    priority,
+                    ISSUE_PRIORITIES.join(", ")
+                ));
+            }
+            let mut cmd = match args.get("parent") {
+                Some(Value::Null) | None => format!("create {}", q(title)),
+                Some(_) => format!("subissue {} {}", issue_id_arg(args, "parent")?, q(title)),
+            };
+            cmd.push_str(&format!(" -p {}", priority));
+            if let Some(desc) = args.get("description").and_then(|v| v.as_str()) {
+                if !desc.is_empty() {
+                    cmd.push_str(&format!(" -d {}", q(desc)));
+                }
+            }
+            vec![cmd]
+        }
+        "issue_list" => {
+            let mut cmd = "list".to_string();
+            match args.get("status").and_then(|v| v.as_str()) {
+                None | Some("open") => {}
+                Some(status) => cmd.push_str(&format!(" -s {}", q(status))),
+            }
+            if let Some(label) = args.get("label").and_then(|v| v.as_str()) {
+                cmd.push_str(&format!(" -l {}", q(label)));
+            }
+            vec![cmd]
+        }
+        "issue_show" => vec![format!("show {}", issue_id_arg(args, "id")?)],
+        "issue_comment" => vec![format!(
+            "comment {} {}",
+            issue_id_arg(args, "id")?,
+            q(str_arg(args, "content")?)
+        )],
+        "issue_close" | "issue_reopen" => {
+            let id = issue_id_arg(args, "id")?;
+            let mut cmds = Vec::new();
+            if let Some(comment) = args.get("comment").and_then(|v| v.as_str()) {
+                cmds.push(format!("comment {} {}", id, q(comment)));
+            }
+            let verb = if name == "issue_close" {
+                "close"
+            } else {
+                "reopen"
+            };
+            cmds.push(format!("{} {}", verb, id));
+            cmds
+        }
+        "issue_label" => {
+            let verb = if remove_arg(args) { "unlabel" } else { "label" };
+            vec![format!(
+                "{} {} {}",
+                verb,
+                issue_id_arg(args, "id")?,
+                q(str_arg(args, "label")?)
+            )]
+        }
+        "issue_depend" => {
+            let verb = if remove_arg(args) { "unblock" } else { "block" };
+            vec![format!(
+                "{} {} {}",
+                verb,
+                issue_id_arg(args, "id")?,
+                issue_id_arg(args, "blocked_by")?
+            )]
+        }
+        _ => return Err(format!("Unknown issue tool: {}", name)),
+    };
+    Ok(commands)
+}
+
+fn execute_chainlink_issue_tool(name: &str, args: &HashMap<String, Value>) -> (String, bool) {
+    let commands = match chainlink_commands(name, args) {
+        Ok(cmds) => cmds,
+        Err(e) => return (e, true),
+    };
+
+    let mut outputs = Vec::new();
+    for cmd in commands {
+        let (output, is_error) = crate::tools::run_chainlink(&cmd);
+        if is_error {
+            return (output, true);
+        }
+        outputs.push(output);
+    }
+    let mut output = outputs.join("\n");
+
+    // chainlink has no create-time labels; apply them once the ID is known
+    if name == "issue_create" {
+        if let Some(id) = parse_created_id(&output) {
+            for label in labels_arg(args) {
+                let (label_output, is_error) =
+                    crate::tools::run_chainlink(&format!("label {} {}", id, shell_quote(&label)));
+                if is_error {
+                    return (label_output, true);
+                }
+            }
+        }
+    }
+    if output.is_empty() {
+        output = "(chainlink command completed)".to_string();
+    }
+    (output, false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    fn args(value: Value) -> HashMap<String, Value> {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_native_issue_tools() {
+        let dir = tempdir().unwrap();
+        let db = MemoryDb::open(&dir.path().join("test.db")).unwrap();
+
+        let (output, is_error) = execute_native_issue_tool(
+            "issue_create",
+            &args(json!({"title": "Fix parser", "priority": "high", "labels": ["bug"]})),
+            &db,
+        );
+        assert!(!is_error, "{}", output);
+        assert_eq!(output, "Created issue #1: Fix parser");
+        assert_eq!(parse_created_id(&output), Some(1));
+
+        execute_native_issue_tool("issue_create", &args(json!({"title": "Write docs"})), &db);
+        let (output, is_error) = execute_native_issue_tool(
+            "issue_depend",
+            &args(json!({"id": "#2", "blocked_by": 1})),
+            &db,
+        );
+        assert!(!is_error, "{}", output);
+
+        let (list, _) = execute_native_issue_tool("issue_list", &args(json!({})), &db);
+        assert_eq!(
+            list,
+            "#1 [high] Fix parser [bug]\n#2 [medium] Write docs (blocked by #1)"
+        );
+
+        execute_native_issue_tool(
+            "issue_close",
+            &args(json!({"id": 1, "comment": "Fixed in parser.rs"})),
+            &db,
+        );
+        let (show, is_error) =
+            execute_native_issue_tool("issue_show", &args(json!({"id": 1})), &db);
+        assert!(!is_error);
+        assert!(show.starts_with("#1 [closed] Fix parser (high)"));
+        assert!(show.contains("Labels: bug"));
+        assert!(show.contains("Fixed in parser.rs"));
+
+        let (list, _) = execute_native_issue_tool("issue_list", &args(json!({})), &db);
+        assert_eq!(list, "#2 [medium] Write docs");
+    }
+
+    #[test]
+    fn test_native_issue_tool_errors() {
+        let dir = tempdir().unwrap();
+        let db = MemoryDb::open(&dir.path().join("test.db")).unwrap();
+
+        let cases = [
+            ("issue_create", json!({})),
+            ("issue_create", json!({"title": "x", "priority": "urgent"})),
+            ("issue_show", json!({"id": 42})),
+            ("issue_comment", json!({"id": 42, "content": "hi"})),
+            ("issue_close", json!({"id": "abc"})),
+            ("issue_list", json!({"status": "done"})),
+        ];
+        for (name, value) in cases {
+            let (output, is_error) = execute_native_issue_tool(name, &args(value.clone()), &db);
+            assert!(is_error, "{} {} should fail: {}", name, value, output);
+        }
+    }
+
+    #[test]
+    fn test_chainlink_commands() {
+        let cmds = chainlink_commands(
+            "issue_create",
+            &args(json!({"title": "Don't panic", "priority": "low", "parent": 3})),
+        )
+        .unwrap();
+        assert_eq!(cmds, vec![r"subissue 3 'Don'\''t panic' -p low"]);
+
+        let cmds =
+            chainlink_commands("issue_close", &args(json!({"id": 5, "comment": "done"}))).unwrap();
+        assert_eq!(cmds, vec!["comment 5 'done'", "close 5"]);
+
+        let cmds =
+            chainlink_commands("issue_depend", &args(json!({"id": 2, "blocked_by": 1}))).unwrap();
+        assert_eq!(cmds, vec!["block 2 1"]);
+        assert!(chainlink_commands("issue_show", &args(json!({}))).is_err());
+    }
+
+    #[test]
+    fn test_issue_activity() {
+        assert_eq!(
+            issue_activity("issue_create", &args(json!({})), "Created issue #12: x"),
+            Some(("issue_created", "#12".to_string()))
+        );
+        assert_eq!(
+            issue_activity("issue_label", &args(json!({"id": "4"})), "ok"),
+            Some(("issue_updated", "#4".to_string()))
+        );
+        assert_eq!(
+            issue_activity("issue_show", &args(json!({"id": 4})), "ok"),
+            None
+        );
+    }
+}
@@ -5,21 +5,38 @@
 //! This library exposes the core functionality of OpenClaudia for both
 //! the CLI binary and integration testing.
 
+pub mod attachments;
+pub mod browser;
+pub mod budget;
+pub mod cloud_auth;
 pub mod compaction;
 pub mod config;
+pub mod config_schema;
 pub mod context;
+pub mod credentials;
 pub mod hooks;
+pub mod issues;
 pub mod mcp;
 pub mod memory;
+pub mod models;
+pub mod network;
 pub mod oauth;
 pub mod plugins;
 pub mod prompt;
 pub mod providers;
 pub mod proxy;
+pub mod reload;
+pub mod routing;
 pub mod rules;
 pub mod session;
+pub mod stream;
+pub mod structured_output;
 pub mod subagent;
+pub mod tokenizer;
+pub mod tool_emulation;
 pub mod tool_intercept;
 pub mod tools;
 pub mod tui;
+pub mod upstream;
+pub mod usage;
 pub mod web;
@@ -2,7 +2,13 @@
 //!
 //! Provides Claude Code-like capabilities for any AI agent.
 
-use openclaudia::{config, memory, oauth, prompt, proxy, tool_intercept, tools, tui};
+use openclaudia::budget::{self, BudgetScope, BudgetStatus, Spend};
+use openclaudia::stream::{StreamAccumulator, StreamDecoder, StreamEvent, TokenUsage};
+use openclaudia::tokenizer::{self, Tokenizer};
+use openclaudia::{
+    config, config_schema, credentials, issues, memory, models, network, oauth, prompt, proxy,
+    session, tool_intercept, tools, tui, upstream, usage,
+};
 
 use clap::{Parser, Subcommand};
 use std::fs;
@@ -48,6 +54,10 @@ enum Commands {
         /// Log out and clear stored OAuth session
         #[arg(long)]
         logout: bool,
+
+        /// Named account to sign in, show or log out (e.g. work, personal)
+        #[arg(long)]
+        account: Option<String>,
     },
 
     /// Start the OpenClaudia proxy server
@@ -65,12 +75,33 @@ enum Commands {
         target: Option<String>,
     },
 
-    /// Show current configuration
-    Config,
+    /// Show the configuration and the layer each value comes from
+    Config {
+        #[command(subcommand)]
+        action: Option<ConfigAction>,
+    },
 
     /// Check configuration and connectivity
     Doctor,
 
+    /// Report token usage and cost for this project
+    Usage {
+        /// Number of days to include
+        #[arg(short, long, default_value = "30")]
+        days: u32,
+    },
+
+    /// List known models and their capabilities
+    Models {
+        /// Fetch current model lists from the configured providers first
+        #[arg(short, long)]
+        refresh: bool,
+
+        /// Only show (and refresh) this provider
+        #[arg(short, long)]
+        provider: Option<String>,
+    },
+
     /// Run in iteration/loop mode with Stop hooks
     Loop {
         /// Maximum number of iterations (0 = unlimited)
@@ -87,6 +118,34 @@ enum Commands {
     },
 }
 
+#[derive(Subcommand)]
+enum ConfigAction {
+    /// Print a value (or a section) and the layer it comes from
+    Get { key: String },
+
+    /// Set a value in a config file, e.g. `config set proxy.port 9090`.
+    /// API keys and other credentials go to the credential store instead.
+    Set {
+        key: String,
+        /// Parsed as YAML: numbers, booleans and lists keep their type
+        value: String,
+        /// File to write: user, project or local (git-ignored)
+        #[arg(long, default_value = "project")]
+        scope: config::ConfigScope,
+    },
+
+    /// Remove a value from a config file (and credentials from the store)
+    Unset {
+        key: String,
+        /// File to edit: user, project or local
+        #[arg(long, default_value = "project")]
+        scope: config::ConfigScope,
+    },
+
+    /// Print the JSON Schema of config files, for editor autocompletion
+    Schema,
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
@@ -102,16 +161,27 @@ async fn main() -> anyhow::Result<()> {
         .with(
             tracing_subscriber::EnvFilter::try_from_default_env().unwrap_or_else(|_| filter.into()),
         )
-        .with(tracing_subscriber::fmt::layer())
+        .with(tracing_subscriber::fmt::layer().with_writer(credentials::RedactingWriter))
         .init();
 
+    // Move plaintext API keys out of config files
+    for moved in credentials::migrate(credentials::store()) {
+        info!("Moved {}", moved);
+    }
+
     match cli.command {
         None => cmd_chat(cli.model, cli.stateful).await,
         Some(Commands::Init { force }) => cmd_init(force),
-        Some(Commands::Auth { status, logout }) => cmd_auth(status, logout).await,
+        Some(Commands::Auth {
+            status,
+            logout,
+            account,
+        }) => cmd_auth(status, logout, account).await,
         Some(Commands::Start { port, host, target }) => cmd_start(port, host, target).await,
-        Some(Commands::Config) => cmd_config(),
+        Some(Commands::Config { action }) => cmd_config(action),
         Some(Commands::Doctor) => cmd_doctor().await,
+        Some(Commands::Usage { days }) => cmd_usage(days),
+        Some(Commands::Models { refresh, provider }) => cmd_models(refresh, provider).await,
         Some(Commands::Loop {
             max_iterations,
             port,
@@ -135,10 +205,16 @@ fn cmd_init(force: bool) -> anyhow::Result<()> {
     fs::create_dir_all(config_dir.join("hooks"))?;
     fs::create_dir_all(config_dir.join("rules"))?;
     fs::create_dir_all(config_dir.join("plugins"))?;
+    config::ensure_local_ignored(&config_dir)?;
 
     // Write default config
-    let default_config = r#"# OpenClaudia Configuration
+    let default_config = r#"# yaml-language-server: $schema=config.schema.json
+# OpenClaudia Configuration
 # https://github.com/yourusername/openclaudia
+#
+# Overrides ~/.openclaudia/config.yaml; personal settings that should not be
+# committed go in .openclaudia/config.local.yaml. Strings may use ${VAR} or
+# ${VAR:-default} to read environment variables.
 
 proxy:
   port: 8080
@@ -209,6 +285,11 @@ session:
 "#;
 
     fs::write(&config_file, default_config)?;
+    // Lets YAML-aware editors complete and check config.yaml
+    fs::write(
+        config_dir.join(config_schema::SCHEMA_FILE),
+        serde_json::to_string_pretty(config_schema::schema())? + "\n",
+    )?;
 
     // Write example hook
     let example_hook = r#"#!/usr/bin/env python3
@@ -262,6 +343,7 @@ These rules are injected into every conversation.
 
     info!("Initialized OpenClaudia configuration in .openclaudia/");
     info!("  config.yaml  - Main configuration");
+    info!("  config.schema.json - Schema for editor autocompletion");
     info!("  hooks/       - Hook scripts");
     info!("  rules/       - Markdown rules");
     info!("  plugins/     - Plugin directory");
@@ -276,7 +358,7 @@ These rules are injected into every conversation.
 }
 
 /// Authenticate with Claude Max subscription via OAuth
-async fn cmd_auth(status: bool, logout: bool) -> anyhow::Result<()> {
+async fn cmd_auth(status: bool, logout: bool, account: Option<String>) -> anyhow::Result<()> {
     use openclaudia::oauth::{parse_auth_code, OAuthClient, OAuthStore, PkceParams};
     use std::io::{self, Write};
 
@@ -284,48 +366,38 @@ async fn cmd_auth(status: bool, logout: bool) -> anyhow::Result<()> {
 
     // Handle --status flag
     if status {
-        let sessions: Vec<_> = {
-            // Check if any sessions exist by trying to load from disk
-            let _store = OAuthStore::new();
-            // We can't easily enumerate sessions, so just check persistence path
-            let persist_path =
-                dirs::data_local_dir().map(|d| d.join("openclaudia").join("oauth_sessions.json"));
-
-            if let Some(path) = persist_path {
-                if path.exists() {
-                    if let Ok(content) = std::fs::read_to_string(&path) {
-                        if let Ok(sessions) = serde_json::from_str::<
-                            std::collections::HashMap<String, serde_json::Value>,
-                        >(&content)
-                        {
-                            sessions.into_iter().collect()
-                        } else {
-                            vec![]
-                        }
-                    } else {
-                        vec![]
-                    }
-                } else {
-                    vec![]
-                }
-            } else {
-                vec![]
-            }
-        };
-
+        let sessions: Vec<_> = store
+            .sessions()
+            .into_iter()
+            .filter(|s| account.as_ref().is_none_or(|a| &s.account == a))
+            .collect();
         if sessions.is_empty() {
             println!("Not authenticated with Claude Max.");
-            println!("Run 'openclaudia auth' to authenticate.");
+            match &account {
+                Some(name) => {
+                    println!("Run 'openclaudia auth --account {}' to authenticate.", name)
+                }
+                None => println!("Run 'openclaudia auth' to authenticate."),
+            }
         } else {
             println!("Authenticated with Claude Max.");
-            println!("Sessions: {}", sessions.len());
-            for (id, data) in &sessions {
-                let expires = data
-                    .get("credentials")
-                    .and_then(|c| c.get("expires_at"))
-                    .and_then(|e| e.as_str())
-                    .unwrap_or("unknown");
-                println!("  {} (expires: {})", &id[..8], expires);
+            println!("Accounts: {}", sessions.len());
+            for session in &sessions {
+                let credentials = &session.credentials;
+                let state = if !credentials.is_expired() {
+                    "valid"
+                } else if credentials.refresh_token.is_some() {
+                    "expired, refreshed on next use"
+                } else {
+                    "expired"
+                };
+                println!(
+                    "  {} - session {} ({}, expires: {})",
+                    session.account,
+                    &session.id[..8],
+                    state,
+                    credentials.expires_at.to_rfc3339()
+                );
             }
         }
         return Ok(());
@@ -333,15 +405,20 @@ async fn cmd_auth(status: bool, logout: bool) -> anyhow::Result<()> {
 
     // Handle --logout flag
     if logout {
-        let persist_path =
-            dirs::data_local_dir().map(|d| d.join("openclaudia").join("oauth_sessions.json"));
-
-        if let Some(path) = persist_path {
-            if path.exists() {
-                std::fs::remove_file(&path)?;
-                println!("Logged out. OAuth sessions cleared.");
-            } else {
-                println!("No OAuth sessions to clear.");
+        match &account {
+            Some(name) => {
+                if store.remove_account(name) > 0 {
+                    println!("Logged out of account '{}'.", name);
+                } else {
+                    println!("No OAuth session for account '{}'.", name);
+                }
+            }
+            None => {
+                if store.clear() > 0 {
+                    println!("Logged out. OAuth sessions cleared.");
+                } else {
+                    println!("No OAuth sessions to clear.");
+                }
             }
         }
         return Ok(());
@@ -410,6 +487,9 @@ async fn cmd_auth(status: bool, logout: bool) -> anyhow::Result<()> {
 
     // Create session from token response
     let mut session = crate::oauth::OAuthSession::from_token_response(token_response);
+    if let Some(name) = account {
+        session.account = name;
+    }
 
     // Try to create API key only if we have the required scope
     // Personal Claude Max accounts don't get org:create_api_key, so they use Bearer token directly
@@ -435,10 +515,12 @@ async fn cmd_auth(status: bool, logout: bool) -> anyhow::Result<()> {
     }
 
     let session_id = session.id.clone();
+    let account = session.account.clone();
     let auth_mode = session.auth_mode.clone();
     store.store_session(session);
 
     println!("\n✓ Authentication successful!");
+    println!("  Account: {}", account);
     println!("  Session ID: {}", &session_id[..8]);
     match auth_mode {
         crate::oauth::AuthMode::ApiKey => {
@@ -531,6 +613,18 @@ struct ChatSession {
     /// Undo stack for undone message pairs (user + assistant)
     #[serde(default)]
     undo_stack: Vec<(serde_json::Value, serde_json::Value)>,
+    /// Todo list maintained by the todo_write tool
+    #[serde(default)]
+    todos: Vec<tools::TodoItem>,
+    /// Provider and model that answered each request, after retries and fallback
+    #[serde(default)]
+    served_by: Vec<session::ServedTurn>,
+    /// Token usage reported by the provider, summed over all requests
+    #[serde(default)]
+    usage: TokenUsage,
+    /// Cost of `usage` for models with a known price
+    #[serde(default)]
+    cost_usd: f64,
 }
 
 impl ChatSession {
@@ -546,6 +640,10 @@ impl ChatSession {
             mode: AgentMode::default(),
             messages: Vec::new(),
             undo_stack: Vec::new(),
+            todos: Vec::new(),
+            served_by: Vec::new(),
+            usage: TokenUsage::default(),
+            cost_usd: 0.0,
         }
     }
 
@@ -587,7 +685,11 @@ impl ChatSession {
             .iter()
             .find(|m| m.get("role").and_then(|r| r.as_str()) == Some("user"))
         {
-            if let Some(content) = first_user.get("content").and_then(|c| c.as_str()) {
+            if let Some(content) = first_user
+                .get("content")
+                .map(openclaudia::attachments::content_text)
+            {
+                let content = content.as_str();
                 let title = if content.len() > 50 {
                     format!("{}...", &content[..47])
                 } else {
@@ -644,6 +746,7 @@ fn list_chat_sessions() -> Vec<ChatSession> {
     }
 
     // Sort by updated_at descending (most recent first)
+    #[allow(clippy::unnecessary_sort_by)]
     sessions.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
     sessions
 }
@@ -680,40 +783,25 @@ enum SlashCommandResult {
     Memory(String),
     /// Activity command to show recent session activities
     Activity(String),
+    /// Attach a content part (a pasted image) to the next message
+    Attach(serde_json::Value),
+    /// Re-read config, hooks, rules and plugins
+    Reload,
     /// Show help message (already printed)
     Handled,
 }
 
 /// Get available models for a provider
-fn get_available_models(provider: &str) -> Vec<&'static str> {
-    match provider {
-        "anthropic" => vec![
-            "claude-sonnet-4-20250514",
-            "claude-opus-4-20250514",
-            "claude-3-5-sonnet-20241022",
-            "claude-3-5-haiku-20241022",
-            "claude-3-opus-20240229",
-        ],
-        "openai" => vec![
-            "gpt-4",
-            "gpt-4-turbo",
-            "gpt-4o",
-            "gpt-4o-mini",
-            "gpt-3.5-turbo",
-            "o1-preview",
-            "o1-mini",
-        ],
-        "google" => vec![
-            "gemini-pro",
-            "gemini-1.5-pro",
-            "gemini-1.5-flash",
-            "gemini-2.0-flash-exp",
-        ],
-        "zai" => vec!["glm-4.7", "glm-4-plus", "glm-4-air", "glm-4-flash"],
-        "deepseek" => vec!["deepseek-chat", "deepseek-coder", "deepseek-reasoner"],
-        "qwen" => vec!["qwen-turbo", "qwen-plus", "qwen-max", "qwen-long"],
-        _ => vec!["gpt-4"],
-    }
+/// Context, output limit and capabilities of a model, e.g. `200.0k ctx, 64.0k out, tools vision`
+fn format_model_summary(info: &models::ModelInfo) -> String {
+    let capabilities = info.capabilities();
+    format!(
+        "{} ctx, {} out{}{}",
+        usage::format_tokens(info.context_window as u64),
+        usage::format_tokens(info.max_output as u64),
+        if capabilities.is_empty() { "" } else { ", " },
+        capabilities.join(" ")
+    )
 }
 
 /// Prompt user for permission to perform a sensitive operation
@@ -821,18 +909,34 @@ fn execute_shell_command_internal(cmd: &str) {
     println!();
 }
 
-/// Estimate tokens in a chat session (rough: ~4 chars per token)
-fn estimate_session_tokens(session: &ChatSession) -> usize {
-    session
-        .messages
+/// Count tokens in chat messages with the given tokenizer
+fn count_messages_tokens(tokenizer: &dyn Tokenizer, messages: &[serde_json::Value]) -> usize {
+    messages
         .iter()
         .map(|msg| {
-            let content = msg.get("content").and_then(|c| c.as_str()).unwrap_or("");
-            content.len() / 4 + 4 // content tokens + overhead
+            let content = match msg.get("content") {
+                Some(serde_json::Value::String(text)) => tokenizer.count(text),
+                Some(serde_json::Value::Array(parts)) => parts
+                    .iter()
+                    .filter_map(|p| p.get("text").and_then(|t| t.as_str()))
+                    .map(|text| tokenizer.count(text))
+                    .sum(),
+                _ => 0,
+            };
+            let tool_calls = msg
+                .get("tool_calls")
+                .map(|calls| tokenizer.count(&calls.to_string()))
+                .unwrap_or(0);
+            content + tool_calls + 4 // role and message framing
         })
         .sum()
 }
 
+/// Estimate tokens in a chat session with the model's calibrated tokenizer
+fn estimate_session_tokens(session: &ChatSession) -> usize {
+    count_messages_tokens(&tokenizer::for_model(&session.model), &session.messages)
+}
+
 /// Open external editor for composing a message
 fn open_external_editor() -> Option<String> {
     use std::process::Command;
@@ -898,8 +1002,10 @@ fn open_external_editor() -> Option<String> {
     }
 }
 
-/// Expand @file references in input to include file contents
-fn expand_file_references(input: &str) -> String {
+/// Expand @file references in input to include file contents. Images and
+/// PDFs are returned as content parts to attach when the model has vision.
+fn expand_file_references(input: &str, vision: bool) -> (String, Vec<serde_json::Value>) {
+    use openclaudia::attachments;
     use regex::Regex;
 
     // Match @path patterns (supports paths with spaces in quotes)
@@ -907,11 +1013,30 @@ fn expand_file_references(input: &str) -> String {
 
     let mut result = input.to_string();
     let mut replacements = Vec::new();
+    let mut attached = Vec::new();
 
     for cap in re.captures_iter(input) {
         let full_match = cap.get(0).unwrap().as_str();
         let path = cap.get(1).or(cap.get(2)).unwrap().as_str();
 
+        // Images and PDFs are attached as content parts, or noted for models
+        // that can't take them
+        if attachments::media_type(std::path::Path::new(path)).is_some() {
+            if !vision {
+                eprintln!(
+                    "Warning: {} not attached: model does not accept images",
+                    path
+                );
+                replacements.push((full_match.to_string(), attachments::placeholder(path)));
+                continue;
+            }
+            match attachments::load(std::path::Path::new(path)) {
+                Ok(part) => attached.push(part),
+                Err(e) => eprintln!("Warning: {}", e),
+            }
+            continue;
+        }
+
         // Try to read the file
         match fs::read_to_string(path) {
             Ok(content) => {
@@ -930,7 +1055,7 @@ fn expand_file_references(input: &str) -> String {
         result = result.replace(&from, &to);
     }
 
-    result
+    (result, attached)
 }
 
 /// Convert a crossterm KeyEvent to a keybinding string format
@@ -1035,7 +1160,10 @@ fn compact_chat_session(session: &mut ChatSession) -> (usize, usize) {
     let mut summary_parts = Vec::new();
     for msg in session.messages.iter().take(to_summarize) {
         let role = msg.get("role").and_then(|r| r.as_str()).unwrap_or("?");
-        let content = msg.get("content").and_then(|c| c.as_str()).unwrap_or("");
+        let content = msg
+            .get("content")
+            .map(openclaudia::attachments::content_text)
+            .unwrap_or_default();
 
         // Truncate long messages in summary
         let preview = if content.len() > 200 {
@@ -1099,7 +1227,10 @@ fn export_chat_session(session: &ChatSession) {
             .get("role")
             .and_then(|r| r.as_str())
             .unwrap_or("unknown");
-        let msg_content = msg.get("content").and_then(|c| c.as_str()).unwrap_or("");
+        let msg_content = &msg
+            .get("content")
+            .map(openclaudia::attachments::content_text)
+            .unwrap_or_default();
 
         match role {
             "user" => {
@@ -1126,6 +1257,22 @@ fn export_chat_session(session: &ChatSession) {
     }
 }
 
+/// Record web requests refused by the network policy as session activity
+fn log_blocked_network_requests(session_id: &str, memory_db: Option<&memory::MemoryDb>) {
+    // Always drain so the queue doesn't grow when not in stateful mode
+    let blocked = network::take_blocked_requests();
+    if let Some(db) = memory_db {
+        for request in blocked {
+            let _ = db.log_activity(
+                session_id,
+                "network_blocked",
+                &request.url,
+                Some(&request.reason),
+            );
+        }
+    }
+}
+
 /// Save session summary to short-term memory for continuity across restarts
 fn save_session_to_short_term_memory(session: &ChatSession, memory_db: Option<&memory::MemoryDb>) {
     let db = match memory_db {
@@ -1143,7 +1290,10 @@ fn save_session_to_short_term_memory(session: &ChatSession, memory_db: Option<&m
 
     for msg in &session.messages {
         let role = msg.get("role").and_then(|r| r.as_str()).unwrap_or("");
-        let content = msg.get("content").and_then(|c| c.as_str()).unwrap_or("");
+        let content = msg
+            .get("content")
+            .map(openclaudia::attachments::content_text)
+            .unwrap_or_default();
 
         if role == "user" && !content.is_empty() {
             // Keep first line of each user message as a request summary
@@ -1241,10 +1391,12 @@ fn handle_slash_command(
             println!("  /models          - List available models");
             println!("  /model <name>    - Switch to a different model");
             println!("  /copy            - Copy last assistant response to clipboard");
+            println!("  /paste           - Attach the clipboard image to your next message");
             println!("  /init            - Generate project rules from codebase");
             println!("  /review          - Review uncommitted git changes");
             println!("  /review <branch> - Compare current branch against <branch>");
             println!("  /status          - Show session status (model, tokens, etc.)");
+            println!("  /reload          - Re-read config, hooks, rules and plugins");
             println!("  /connect         - Configure API keys for providers");
             println!("  /theme           - List available color themes");
             println!("  /theme <name>    - Switch to a color theme");
@@ -1355,7 +1507,10 @@ fn handle_slash_command(
                 println!("\nConversation History ({} messages):", messages.len());
                 for (i, msg) in messages.iter().enumerate() {
                     let role = msg.get("role").and_then(|r| r.as_str()).unwrap_or("?");
-                    let content = msg.get("content").and_then(|c| c.as_str()).unwrap_or("");
+                    let content = msg
+                        .get("content")
+                        .map(openclaudia::attachments::content_text)
+                        .unwrap_or_default();
                     let preview = if content.len() > 60 {
                         format!("{}...", &content[..57])
                     } else {
@@ -1373,23 +1528,42 @@ fn handle_slash_command(
                 println!("\nCurrent model: {}", current_model);
                 println!("Provider: {}\n", provider);
             } else {
-                // Switch to the specified model
+                // Switch to the specified model. Any name is allowed since
+                // providers serve more models than the catalog lists.
                 let new_model = args.trim().to_string();
-                let available = get_available_models(provider);
-                if available.contains(&new_model.as_str()) || !available.is_empty() {
-                    // Allow any model name (provider might support more than we list)
-                    println!("\nSwitching to model: {}\n", new_model);
-                    return Some(SlashCommandResult::SwitchModel(new_model));
+                match models::lookup(&new_model) {
+                    Some(info) => println!(
+                        "\nSwitching to model: {} ({})\n",
+                        new_model,
+                        format_model_summary(&info)
+                    ),
+                    None => println!(
+                        "\nSwitching to model: {} (not in the model catalog, assuming a {} context)\n",
+                        new_model,
+                        usage::format_tokens(models::DEFAULT_CONTEXT_WINDOW as u64)
+                    ),
                 }
+                return Some(SlashCommandResult::SwitchModel(new_model));
             }
             Some(SlashCommandResult::Handled)
         }
         "models" => {
-            let available = get_available_models(provider);
+            let available = models::list(Some(provider));
+            if available.is_empty() {
+                println!("\nNo models known for {}.", provider);
+                println!("Run `openclaudia models --refresh` to fetch them from the provider.\n");
+                return Some(SlashCommandResult::Handled);
+            }
             println!("\nAvailable models for {}:", provider);
             for (i, model) in available.iter().enumerate() {
-                let marker = if *model == current_model { " *" } else { "" };
-                println!("  {}. {}{}", i + 1, model, marker);
+                let marker = if model.id == current_model { " *" } else { "" };
+                println!(
+                    "  {}. {}{} ({})",
+                    i + 1,
+                    model.id,
+                    marker,
+                    format_model_summary(model)
+                );
             }
             println!("\nUse /model <name> to switch models.\n");
             Some(SlashCommandResult::Handled)
@@ -1429,6 +1603,13 @@ fn handle_slash_command(
             }
             Some(SlashCommandResult::Handled)
         }
+        "paste" => match openclaudia::attachments::clipboard_image() {
+            Ok(part) => Some(SlashCommandResult::Attach(part)),
+            Err(e) => {
+                eprintln!("\n{}\n", e);
+                Some(SlashCommandResult::Handled)
+            }
+        },
         "init" => {
             init_project_rules();
             Some(SlashCommandResult::Handled)
@@ -1438,6 +1619,7 @@ fn handle_slash_command(
             Some(SlashCommandResult::Handled)
         }
         "status" | "info" => Some(SlashCommandResult::Status),
+        "reload" => Some(SlashCommandResult::Reload),
         "connect" | "auth" => {
             configure_provider_api_key();
             Some(SlashCommandResult::Handled)
@@ -1477,18 +1659,10 @@ fn handle_slash_command(
             println!("Messages:     {}", messages.len());
             println!();
             println!("Configuration Paths:");
-            println!("  Project:    .openclaudia/config.yaml");
-            if let Some(home) = dirs::home_dir() {
-                println!(
-                    "  User:       {}",
-                    home.join(".openclaudia/config.yaml").display()
-                );
-            }
-            if let Some(config_dir) = dirs::config_dir() {
-                println!(
-                    "  System:     {}",
-                    config_dir.join("openclaudia/config.yaml").display()
-                );
+            for scope in config::ConfigScope::ALL {
+                if let Some(path) = scope.path() {
+                    println!("  {:<11} {}", format!("{}:", scope), path.display());
+                }
             }
             println!();
             println!("Data Directories:");
@@ -1514,6 +1688,17 @@ fn handle_slash_command(
                 println!("  {}: {}", var, status);
             }
             println!();
+            println!("Credential Store ({}):", credentials::store().name());
+            match credentials::store().all() {
+                Ok(secrets) if secrets.is_empty() => println!("  (empty)"),
+                Ok(secrets) => {
+                    for (key, value) in &secrets {
+                        println!("  {}: {}", key, credentials::mask(value));
+                    }
+                }
+                Err(e) => println!("  unavailable: {}", e),
+            }
+            println!();
             Some(SlashCommandResult::Handled)
         }
         "memory" | "mem" => {
@@ -1771,6 +1956,8 @@ fn handle_activity_command(
                                 "issue_created" => "🎫",
                                 "issue_closed" => "✅",
                                 "issue_comment" => "💬",
+                                "issue_updated" => "🏷️",
+                                "network_blocked" => "🚫",
                                 _ => "•",
                             };
                             let details = activity.details.as_deref().unwrap_or("");
@@ -2242,47 +2429,16 @@ fn configure_provider_api_key() {
         return;
     }
 
-    // Save to config file
-    let config_dir = dirs::config_dir()
-        .unwrap_or_else(|| std::path::PathBuf::from("."))
-        .join("openclaudia");
-
-    if let Err(e) = fs::create_dir_all(&config_dir) {
-        eprintln!("Failed to create config directory: {}\n", e);
-        return;
-    }
-
-    let config_path = config_dir.join("config.yaml");
-
-    // Read existing config or create new
-    let mut config_content = if config_path.exists() {
-        fs::read_to_string(&config_path).unwrap_or_default()
-    } else {
-        String::new()
-    };
-
-    // Simple approach: append or update the provider section
-    // For a production app, you'd want to properly parse and modify YAML
-    let provider_section = format!(
-        "\n# {} configuration\n{}_api_key: \"{}\"\n",
-        provider_name, provider_id, api_key
-    );
-
-    // Check if provider already configured
-    let key_pattern = format!("{}_api_key:", provider_id);
-    if config_content.contains(&key_pattern) {
-        println!("\nProvider already configured in config file.");
-        println!("Edit {} to update.\n", config_path.display());
-    } else {
-        config_content.push_str(&provider_section);
-
-        match fs::write(&config_path, &config_content) {
-            Ok(()) => {
-                println!("\nSaved API key to: {}", config_path.display());
-                println!("Restart the chat to use the new configuration.\n");
-            }
-            Err(e) => eprintln!("\nFailed to save config: {}\n", e),
+    let key = format!("providers.{}.api_key", provider_id);
+    match credentials::store().set(&key, api_key) {
+        Ok(()) => {
+            println!(
+                "\nSaved API key to the credential store ({}).",
+                credentials::store().name()
+            );
+            println!("Restart the chat to use the new configuration.\n");
         }
+        Err(e) => eprintln!("\nFailed to save API key: {}\n", e),
     }
 }
 
@@ -2432,7 +2588,7 @@ async fn start_builtin_oauth_flow(config: &config::AppConfig) -> Option<OAuthFlo
         // Start our own proxy in a background task
         let config_clone = config.clone();
         tokio::spawn(async move {
-            if let Err(e) = crate::proxy::start_server(config_clone).await {
+            if let Err(e) = crate::proxy::start_server(config_clone, &[]).await {
                 tracing::error!("Proxy server error: {}", e);
             }
         });
@@ -2546,14 +2702,360 @@ async fn start_builtin_oauth_flow(config: &config::AppConfig) -> Option<OAuthFlo
     None
 }
 
+/// Build a chat request for the configured provider through its adapter, so
+/// message/tool conversion and thinking params match the proxy and subagents
+fn build_chat_request(
+    adapter: &dyn openclaudia::providers::ProviderAdapter,
+    provider: &config::ProviderConfig,
+    model: &str,
+    messages: &[serde_json::Value],
+    tools: &serde_json::Value,
+) -> Result<serde_json::Value, String> {
+    let messages = messages
+        .iter()
+        .map(|m| serde_json::from_value(m.clone()))
+        .collect::<Result<Vec<openclaudia::proxy::ChatMessage>, _>>()
+        .map_err(|e| format!("Invalid message in session: {}", e))?;
+    let request = openclaudia::proxy::ChatCompletionRequest {
+        model: model.to_string(),
+        messages,
+        temperature: None,
+        max_tokens: Some(4096),
+        stream: Some(true),
+        tools: tools.as_array().cloned(),
+        tool_choice: None,
+        response_format: None,
+        extra: std::collections::HashMap::new(),
+    };
+    adapter
+        .transform_request_with_thinking(&request, &provider.thinking)
+        .map_err(|e| e.to_string())
+}
+
+/// An assistant turn streamed to the terminal
+struct StreamedTurn {
+    turn: StreamAccumulator,
+    cancelled: bool,
+    pending_action: Option<SlashCommandResult>,
+}
+
+/// Stream a response to the terminal and collect it into a turn.
+/// Thinking is shown dimmed and kept out of the transcript. Configured
+/// keybindings are honored while streaming: Cancel stops immediately and
+/// other actions are queued for after the turn.
+async fn stream_chat_turn(
+    response: reqwest::Response,
+    mut decoder: Box<dyn StreamDecoder>,
+    keybindings: &config::KeybindingsConfig,
+) -> StreamedTurn {
+    use crossterm::event::{self, Event, KeyEventKind};
+    use futures::StreamExt;
+    use std::io::Write;
+
+    let mut streamed = StreamedTurn {
+        turn: StreamAccumulator::new(),
+        cancelled: false,
+        pending_action: None,
+    };
+    let mut in_thinking = false;
+    let mut show = |event: StreamEvent, turn: &mut StreamAccumulator| {
+        match &event {
+            StreamEvent::ThinkingDelta(text) => {
+                if !in_thinking {
+                    print!("\x1b[90m");
+                    in_thinking = true;
+                }
+                print!("{}", text);
+            }
+            StreamEvent::TextDelta(text) => {
+                if in_thinking {
+                    print!("\x1b[0m\n\n");
+                    in_thinking = false;
+                }
+                print!("{}", text);
+            }
+            StreamEvent::Error(message) => eprintln!("\nStream error: {}", message),
+            _ => {}
+        }
+        std::io::stdout().flush().ok();
+        turn.push(&event);
+    };
+
+    let mut stream = response.bytes_stream();
+    while let Some(chunk_result) = stream.next().await {
+        if event::poll(std::time::Duration::from_millis(1)).unwrap_or(false) {
+            if let Ok(Event::Key(key_event)) = event::read() {
+                if key_event.kind == KeyEventKind::Press {
+                    if let Some(key_str) = key_event_to_string(&key_event, false) {
+                        if keybindings.is_bound(&key_str) {
+                            let action = keybindings.get_action_or_default(&key_str);
+                            if action == config::KeyAction::Cancel {
+                                streamed.cancelled = true;
+                                print!(" (cancelled)");
+                                break;
+                            }
+                            if let Some(result) = execute_key_action(&action) {
+                                streamed.pending_action = Some(result);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        match chunk_result {
+            Ok(chunk) => {
+                for event in decoder.decode(&chunk) {
+                    show(event, &mut streamed.turn);
+                }
+            }
+            Err(e) => {
+                eprintln!("\nStream error: {}", e);
+                break;
+            }
+        }
+    }
+    if !streamed.cancelled {
+        for event in decoder.finish() {
+            show(event, &mut streamed.turn);
+        }
+    }
+
+    if in_thinking {
+        print!("\x1b[0m");
+    }
+    println!();
+    streamed
+}
+
+/// Headers for the built-in OAuth proxy. The Cookie carries the session ID so
+/// the proxy uses the stored OAuth credentials.
+fn oauth_proxy_headers(session: Option<&oauth::OAuthSession>) -> Vec<(String, String)> {
+    let mut headers = vec![
+        ("anthropic-version".to_string(), "2023-06-01".to_string()),
+        ("content-type".to_string(), "application/json".to_string()),
+    ];
+    if let Some(session) = session {
+        tracing::debug!("Proxy mode - sending the OAuth session cookie");
+        headers.push((
+            "Cookie".to_string(),
+            format!("anthropic_session={}", session.id),
+        ));
+    } else {
+        tracing::debug!("Proxy mode - no session, proxy will use any stored session");
+    }
+    headers
+}
+
+/// Build one attempt of a chat request for `route`. With `oauth_proxy` set
+/// (endpoint and headers), the minimal proxy request goes there instead.
+fn chat_attempt(
+    client: &reqwest::Client,
+    route: &upstream::Route,
+    oauth_proxy: Option<&(String, Vec<(String, String)>)>,
+    messages: &[serde_json::Value],
+    tools: &serde_json::Value,
+) -> Result<reqwest::RequestBuilder, String> {
+    let (endpoint, headers, body) = match oauth_proxy {
+        Some((endpoint, headers)) => (
+            endpoint.clone(),
+            headers.clone(),
+            build_oauth_proxy_request(&route.model, messages),
+        ),
+        None => {
+            let adapter = route.adapter();
+            let body = build_chat_request(
+                adapter.as_ref(),
+                &route.provider,
+                &route.model,
+                messages,
+                tools,
+            )?;
+            let api_key = route.provider.api_key.clone().unwrap_or_default();
+            let mut headers = adapter.get_headers(&api_key);
+            headers.extend(route.provider.headers.clone());
+            let endpoint = format!(
+                "{}{}",
+                route.provider.base_url,
+                adapter.chat_path(&route.model, true)
+            );
+            (endpoint, headers, body)
+        }
+    };
+
+    let mut req = client.post(&endpoint).json(&body);
+    for (key, value) in &headers {
+        req = req.header(key, value);
+    }
+    Ok(req)
+}
+
+/// Note which provider answered, telling the user when it was a fallback
+fn record_served(chat_session: &mut ChatSession, route: &upstream::Route) {
+    if route.fallback {
+        eprintln!(
+            "\x1b[33m(served by fallback {} / {})\x1b[0m",
+            route.provider_name, route.model
+        );
+    }
+    chat_session.served_by.push(route.served());
+}
+
+/// One-line summary of reported token usage for `/status`
+fn format_session_usage(total: &TokenUsage) -> String {
+    let mut line = format!(
+        "{} in, {} out",
+        usage::format_tokens(total.input_tokens),
+        usage::format_tokens(total.output_tokens)
+    );
+    if total.cache_read_tokens + total.cache_write_tokens > 0 {
+        line.push_str(&format!(
+            ", {} cache read, {} cache write",
+            usage::format_tokens(total.cache_read_tokens),
+            usage::format_tokens(total.cache_write_tokens)
+        ));
+    }
+    if total.reasoning_tokens > 0 {
+        line.push_str(&format!(
+            " ({} reasoning)",
+            usage::format_tokens(total.reasoning_tokens)
+        ));
+    }
+    line
+}
+
+/// Prompt-cache hit rate for `/status`, with the savings when the model is
+/// priced. None until the provider reports any cache activity.
+fn format_cache_usage(
+    total: &TokenUsage,
+    model: &str,
+    pricing: &std::collections::HashMap<String, config::ModelPrice>,
+    ttl: config::CacheTtl,
+) -> Option<String> {
+    if total.cache_read_tokens + total.cache_write_tokens == 0 {
+        return None;
+    }
+    let mut line = format!(
+        "{:.0}% of input read from cache",
+        usage::cache_hit_rate(total)? * 100.0
+    );
+    if let Some(price) = usage::price_for(model, pricing) {
+        line.push_str(&format!(
+            ", saved ~{}",
+            usage::format_cost(usage::cache_savings_usd(total, &price, ttl))
+        ));
+    }
+    Some(line)
+}
+
+/// Check the session and daily budgets, printing first-time warnings.
+/// Returns the scope and message of a limit that has been reached.
+fn chat_budget_exceeded(
+    chat_session: &ChatSession,
+    budgets: &config::BudgetsConfig,
+) -> Option<(BudgetScope, String)> {
+    let spend = Spend::new(&chat_session.usage, chat_session.cost_usd);
+    let cwd = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+    for status in [
+        budget::check(BudgetScope::Session, &chat_session.id, budgets, &spend),
+        budget::check_daily(budgets, &cwd),
+    ] {
+        match status {
+            BudgetStatus::Ok => {}
+            BudgetStatus::Warning(message) => eprintln!("\x1b[33mWarning: {}\x1b[0m", message),
+            BudgetStatus::Exceeded { scope, message } => return Some((scope, message)),
+        }
+    }
+    None
+}
+
+/// Remaining session and daily budget for `/status`, if any limit is set
+fn format_budget_remaining(
+    chat_session: &ChatSession,
+    budgets: &config::BudgetsConfig,
+) -> Option<String> {
+    let spend = Spend::new(&chat_session.usage, chat_session.cost_usd);
+    let mut parts = Vec::new();
+    if let Some(left) = budget::remaining(&budgets.session, &spend) {
+        parts.push(format!("session {}", left));
+    }
+    if budgets.daily != config::Budget::default() {
+        let cwd = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+        if let Some(left) = budget::remaining(&budgets.daily, &budget::daily_spend(&cwd)) {
+            parts.push(format!("today {}", left));
+        }
+    }
+    if parts.is_empty() {
+        None
+    } else {
+        Some(parts.join("; "))
+    }
+}
+
+/// Add a response's reported token usage to the session totals and the project usage log.
+/// When the request was built from the session messages and `sent_tools`, the reported
+/// prompt size also calibrates the model's tokenizer.
+fn record_usage(
+    chat_session: &mut ChatSession,
+    route: &upstream::Route,
+    turn: &StreamAccumulator,
+    sent_tools: Option<&serde_json::Value>,
+    pricing: &std::collections::HashMap<String, config::ModelPrice>,
+) {
+    let Some(turn_usage) = turn.usage else {
+        return;
+    };
+    if let Some(tools) = sent_tools {
+        let base = tokenizer::base_for_model(&route.model);
+        let estimated =
+            count_messages_tokens(base, &chat_session.messages) + base.count(&tools.to_string());
+        tokenizer::calibrate(&route.model, estimated, turn_usage.prompt_tokens());
+    }
+    chat_session.usage += turn_usage;
+    let cwd = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+    if let Some(cost) = usage::record(
+        &cwd,
+        &chat_session.id,
+        &route.provider_name,
+        &route.model,
+        &turn_usage,
+        pricing,
+        route.provider.cache.ttl,
+    ) {
+        chat_session.cost_usd += cost;
+    }
+}
+
+/// Build the minimal Anthropic request sent through the built-in OAuth proxy.
+/// OAuth sessions do not accept custom tools, so none are included.
+fn build_oauth_proxy_request(model: &str, messages: &[serde_json::Value]) -> serde_json::Value {
+    use openclaudia::providers::convert_messages_to_anthropic;
+
+    // Extract system message to top-level (Claude API requirement)
+    let system_msg = messages
+        .iter()
+        .find(|m| m.get("role").and_then(|r| r.as_str()) == Some("system"))
+        .and_then(|m| m.get("content").and_then(|c| c.as_str()))
+        .map(String::from);
+
+    // Proxy mode still talks to Anthropic API, so needs proper format
+    let mut req = serde_json::json!({
+        "model": model,
+        "messages": convert_messages_to_anthropic(messages),
+        "max_tokens": 4096,
+        "stream": true
+    });
+    if let Some(sys) = system_msg {
+        req["system"] = serde_json::json!(sys);
+    }
+    req
+}
+
 /// Interactive chat mode (default command)
 async fn cmd_chat(model_override: Option<String>, stateful: bool) -> anyhow::Result<()> {
     use indicatif::{ProgressBar, ProgressStyle};
-    use openclaudia::hooks::{
-        load_claude_code_hooks, merge_hooks_config, HookEngine, HookEvent, HookInput,
-    };
-    use openclaudia::providers::{convert_messages_to_anthropic, convert_tools_to_anthropic, get_adapter};
-    use openclaudia::rules::RulesEngine;
+    use openclaudia::hooks::{HookEvent, HookInput};
+    use openclaudia::reload::{self, Live};
     use rustyline::error::ReadlineError;
     use rustyline::DefaultEditor;
 
@@ -2586,13 +3088,12 @@ async fn cmd_chat(model_override: Option<String>, stateful: bool) -> anyhow::Res
 
     let api_key = if config.proxy.target == "anthropic" && provider.api_key.is_none() {
         // No API key configured - use built-in OAuth proxy (AUTOMATIC)
-        eprintln!("[debug] Anthropic provider with no API key - starting OAuth flow...");
+        tracing::debug!("Anthropic provider with no API key - starting OAuth flow");
         match start_builtin_oauth_flow(&config).await {
             Some(result) => {
                 // Store proxy URL and create session with actual session ID
                 eprintln!("✓ Connected via OpenClaudia proxy");
-                eprintln!("[debug] Proxy URL: {}", result.proxy_url);
-                eprintln!("[debug] Session ID: {}", result.session_id);
+                tracing::debug!("Proxy URL: {}", result.proxy_url);
                 proxy_url = Some(result.proxy_url);
                 let proxy_session = crate::oauth::OAuthSession {
                     id: result.session_id, // ACTUAL session ID, not proxy URL!
@@ -2606,6 +3107,7 @@ async fn cmd_chat(model_override: Option<String>, stateful: bool) -> anyhow::Res
                     granted_scopes: vec![],
                     created_at: chrono::Utc::now(),
                     user_id: None,
+                    account: crate::oauth::DEFAULT_ACCOUNT.to_string(),
                 };
                 oauth_session = Some(proxy_session);
                 "proxy-session".to_string()
@@ -2618,6 +3120,11 @@ async fn cmd_chat(model_override: Option<String>, stateful: bool) -> anyhow::Res
     } else if let Some(k) = &provider.api_key {
         // API key configured - use it directly
         k.clone()
+    } else if !openclaudia::providers::adapter_for(&config.proxy.target, provider)
+        .requires_api_key()
+    {
+        // Cloud and local providers authenticate without an API key
+        String::new()
     } else {
         // Non-Anthropic provider with no API key
         let env_var = match config.proxy.target.as_str() {
@@ -2648,16 +3155,16 @@ async fn cmd_chat(model_override: Option<String>, stateful: bool) -> anyhow::Res
             _ => "gpt-4".to_string(),
         });
 
-    let adapter = get_adapter(&config.proxy.target);
     let client = reqwest::Client::new();
 
-    // Initialize hook engine with merged hooks (config + Claude Code hooks)
-    let claude_hooks = load_claude_code_hooks();
-    let merged_hooks = merge_hooks_config(config.hooks.clone(), claude_hooks);
-    let hook_engine = HookEngine::new(merged_hooks);
-
-    // Initialize rules engine
-    let rules_engine = RulesEngine::new(".openclaudia/rules");
+    // Config, hooks (config + Claude Code hooks) and rules, reloaded when
+    // their files change or on /reload. The provider stays the one chat
+    // started with.
+    let target = config.proxy.target.clone();
+    let live = Live::start(config.clone(), &[], false).await;
+    let _watcher = reload::watch(live.clone())
+        .map_err(|e| tracing::warn!("Could not watch config files: {}", e))
+        .ok();
 
     // Initialize rustyline editor with history
     let mut rl = DefaultEditor::new()?;
@@ -2735,10 +3242,13 @@ async fn cmd_chat(model_override: Option<String>, stateful: bool) -> anyhow::Res
 
     // Initialize permissions cache for sensitive operations
     let mut permissions: std::collections::HashSet<String> = std::collections::HashSet::new();
+    // Images pasted with /paste, sent with the next message
+    let mut pending_attachments: Vec<serde_json::Value> = Vec::new();
 
     loop {
         // Show input hints before prompt
         let mode_str = chat_session.mode.display().to_lowercase();
+        let _ = tui::render_todo_status(&chat_session.todos);
         let _ = tui::render_input_prompt(&mode_str);
 
         let readline = rl.readline("> ");
@@ -2770,13 +3280,18 @@ async fn cmd_chat(model_override: Option<String>, stateful: bool) -> anyhow::Res
                 let _ = rl.add_history_entry(&input);
                 let input = input.as_str();
 
+                // Config, hooks and rules for this turn
+                let current = live.current();
+                let config = &current.config;
+                let hook_engine = &current.hook_engine;
+                let rules_engine = &current.rules_engine;
+                let provider = config.get_provider(&target).unwrap_or(provider);
+                let api_key = provider.api_key.clone().unwrap_or_else(|| api_key.clone());
+
                 // Handle slash commands
-                if let Some(result) = handle_slash_command(
-                    input,
-                    &mut chat_session.messages,
-                    &config.proxy.target,
-                    &model,
-                ) {
+                if let Some(result) =
+                    handle_slash_command(input, &mut chat_session.messages, &target, &model)
+                {
                     match result {
                         SlashCommandResult::Exit => {
                             // Save session to short-term memory before exiting
@@ -2786,7 +3301,7 @@ async fn cmd_chat(model_override: Option<String>, stateful: bool) -> anyhow::Res
                         SlashCommandResult::Clear => {
                             // Save current session before starting new one
                             save_session_to_short_term_memory(&chat_session, memory_db.as_ref());
-                            chat_session = ChatSession::new(&model, &config.proxy.target);
+                            chat_session = ChatSession::new(&model, &target);
                             continue;
                         }
                         SlashCommandResult::LoadSession(session_id) => {
@@ -2818,15 +3333,17 @@ async fn cmd_chat(model_override: Option<String>, stateful: bool) -> anyhow::Res
                         }
                         SlashCommandResult::EditorInput(editor_content) => {
                             // Process editor content and send as message
-                            let expanded = if editor_content.contains('@') {
-                                expand_file_references(&editor_content)
+                            let vision = models::supports_vision(&model);
+                            let (expanded, mut attached) = if editor_content.contains('@') {
+                                expand_file_references(&editor_content, vision)
                             } else {
-                                editor_content
+                                (editor_content, Vec::new())
                             };
+                            attached.append(&mut pending_attachments);
                             // Add user message from editor
                             chat_session.messages.push(serde_json::json!({
                                 "role": "user",
-                                "content": expanded
+                                "content": openclaudia::attachments::message_content(&expanded, attached)
                             }));
                             chat_session.update_title();
                             chat_session.touch();
@@ -2887,7 +3404,32 @@ async fn cmd_chat(model_override: Option<String>, stateful: bool) -> anyhow::Res
                                 chat_session.mode.description()
                             );
                             println!("  Messages:   {}", msg_count);
-                            println!("  Est tokens: ~{}", tokens);
+                            println!(
+                                "  Est tokens: ~{} ({})",
+                                tokens,
+                                tokenizer::for_model(&chat_session.model).name()
+                            );
+                            println!(
+                                "  Usage:      {}",
+                                format_session_usage(&chat_session.usage)
+                            );
+                            println!(
+                                "  Cost:       {}",
+                                usage::format_cost(chat_session.cost_usd)
+                            );
+                            if let Some(cache) = format_cache_usage(
+                                &chat_session.usage,
+                                &chat_session.model,
+                                &config.pricing,
+                                provider.cache.ttl,
+                            ) {
+                                println!("  Cache:      {}", cache);
+                            }
+                            if let Some(left) =
+                                format_budget_remaining(&chat_session, &config.budgets)
+                            {
+                                println!("  Budget:     {}", left);
+                            }
                             println!("  Duration:   {} min", mins);
                             println!(
                                 "  Created:    {}",
@@ -2926,6 +3468,31 @@ async fn cmd_chat(model_override: Option<String>, stateful: bool) -> anyhow::Res
                             handle_activity_command(&args, &chat_session.id, memory_db.as_ref());
                             continue;
                         }
+                        SlashCommandResult::Reload => {
+                            match live.reload().await {
+                                Ok(report) => {
+                                    println!("\nReloaded: {}", report);
+                                    for warning in &report.warnings {
+                                        eprintln!("  \x1b[33m{}\x1b[0m", warning);
+                                    }
+                                    println!();
+                                }
+                                Err(e) => eprintln!(
+                                    "\nReload failed, keeping the previous configuration: {}\n",
+                                    e
+                                ),
+                            }
+                            continue;
+                        }
+                        SlashCommandResult::Attach(part) => {
+                            if models::supports_vision(&model) {
+                                pending_attachments.push(part);
+                                println!("\nImage attached to your next message.\n");
+                            } else {
+                                eprintln!("\n{} does not accept images.\n", model);
+                            }
+                            continue;
+                        }
                         SlashCommandResult::Handled => {
                             continue;
                         }
@@ -2963,15 +3530,17 @@ async fn cmd_chat(model_override: Option<String>, stateful: bool) -> anyhow::Res
                 // Add user message (skip if already added from editor)
                 if !editor_message_added {
                     // Expand @file references in input
-                    let expanded_input = if input.contains('@') {
-                        expand_file_references(input)
+                    let vision = models::supports_vision(&model);
+                    let (expanded_input, mut attached) = if input.contains('@') {
+                        expand_file_references(input, vision)
                     } else {
-                        input.to_string()
+                        (input.to_string(), Vec::new())
                     };
+                    attached.append(&mut pending_attachments);
 
                     chat_session.messages.push(serde_json::json!({
                         "role": "user",
-                        "content": expanded_input.clone()
+                        "content": openclaudia::attachments::message_content(&expanded_input, attached)
                     }));
                     chat_session.update_title();
                     chat_session.touch();
@@ -2997,6 +3566,18 @@ async fn cmd_chat(model_override: Option<String>, stateful: bool) -> anyhow::Res
                         continue;
                     }
 
+                    if let Some((scope, message)) =
+                        chat_budget_exceeded(&chat_session, &config.budgets)
+                    {
+                        eprintln!("\n\x1b[31mStopped: {}\x1b[0m", message);
+                        eprintln!(
+                            "Raise `budgets` in config.yaml or start a new session with /new.\n"
+                        );
+                        budget::run_stop_hook(hook_engine, &chat_session.id, scope, &message).await;
+                        chat_session.messages.pop();
+                        continue;
+                    }
+
                     // Inject hook context into messages if any
                     for output in &hook_result.outputs {
                         if let Some(sys_msg) = &output.system_message {
@@ -3090,125 +3671,32 @@ async fn cmd_chat(model_override: Option<String>, stateful: bool) -> anyhow::Res
                     .unwrap_or(false);
 
                 // Build request - proxy mode sends minimal request (no tools, no extra fields)
-                // This matches exactly what the working curl command sends
-                let request_body = if using_proxy {
-                    // Extract system message to top-level (Claude API requirement)
-                    let system_msg = chat_session
-                        .messages
-                        .iter()
-                        .find(|m| m.get("role").and_then(|r| r.as_str()) == Some("system"))
-                        .and_then(|m| m.get("content").and_then(|c| c.as_str()))
-                        .map(String::from);
-
-                    // Convert messages to Anthropic format (handles tool_calls and tool results)
-                    // Proxy mode still talks to Anthropic API, so needs proper format
-                    let anthropic_messages = convert_messages_to_anthropic(&chat_session.messages);
-
-                    // NOTE: Proxy mode (OAuth credentials) does NOT support custom tools!
-                    // The OAuth session is restricted to Claude Code's native format.
-                    // Claude will use its built-in sandbox environment for tool execution.
-                    // For local tool execution, use direct API mode with ANTHROPIC_API_KEY.
-                    let mut req = serde_json::json!({
-                        "model": model,
-                        "messages": anthropic_messages,
-                        "max_tokens": 4096,
-                        "stream": true
-                    });
-
-                    // Add system as top-level parameter if present
-                    if let Some(sys) = system_msg {
-                        req["system"] = serde_json::json!(sys);
-                    }
-
-                    req
-                } else if config.proxy.target == "anthropic" {
-                    // Anthropic direct API mode - need proper Anthropic format
-                    // Extract system message to top-level (Anthropic API requirement)
-                    let system_msg = chat_session
-                        .messages
-                        .iter()
-                        .find(|m| m.get("role").and_then(|r| r.as_str()) == Some("system"))
-                        .and_then(|m| m.get("content").and_then(|c| c.as_str()))
-                        .map(String::from);
-
-                    // Convert messages to Anthropic format (handles tool_calls and tool results)
-                    let anthropic_messages = convert_messages_to_anthropic(&chat_session.messages);
-
-                    // Get tools in OpenAI format and convert to Anthropic format
-                    let openai_tools = tools::get_all_tool_definitions(stateful, true);
-                    let anthropic_tools =
-                        convert_tools_to_anthropic(openai_tools.as_array().unwrap_or(&vec![]));
-
-                    let mut req = serde_json::json!({
-                        "model": model,
-                        "messages": anthropic_messages,
-                        "max_tokens": 4096,
-                        "stream": true,
-                        "tools": anthropic_tools
-                    });
-
-                    // Add system as top-level parameter with cache_control for prompt caching
-                    if let Some(sys) = system_msg {
-                        req["system"] = serde_json::json!([{
-                            "type": "text",
-                            "text": sys,
-                            "cache_control": {"type": "ephemeral"}
-                        }]);
-                    }
-
-                    req
-                } else {
-                    // OpenAI-compatible format for other providers
-                    serde_json::json!({
-                        "model": model,
-                        "messages": chat_session.messages,
-                        "max_tokens": 4096,
-                        "stream": true,
-                        "tools": tools::get_all_tool_definitions(stateful, true)
-                    })
-                };
-
-                // Get endpoint - proxy mode uses our local proxy, which handles OAuth internally
-                let endpoint = if using_proxy {
-                    // Use the stored proxy_url for the endpoint
-                    if let Some(ref url) = proxy_url {
+                // This matches exactly what the working curl command sends.
+                // NOTE: Proxy mode (OAuth credentials) does NOT support custom tools!
+                // The OAuth session is restricted to Claude Code's native format.
+                // Claude will use its built-in sandbox environment for tool execution.
+                // For local tool execution, use direct API mode with ANTHROPIC_API_KEY.
+                let tool_definitions = tools::get_all_tool_definitions(stateful, true);
+
+                // Proxy mode sends every request to our local proxy, which handles OAuth
+                // internally. Tool interception there assumes Anthropic, so no fallbacks.
+                let oauth_proxy = if using_proxy {
+                    proxy_url.as_ref().map(|url| {
                         eprintln!("[debug] Using built-in proxy at: {}", url);
-                        format!("{}/v1/messages", url)
-                    } else {
-                        format!("{}{}", provider.base_url, adapter.chat_endpoint())
-                    }
+                        (
+                            format!("{}/v1/messages", url),
+                            oauth_proxy_headers(oauth_session.as_ref()),
+                        )
+                    })
                 } else {
-                    format!("{}{}", provider.base_url, adapter.chat_endpoint())
+                    None
                 };
-
-                // Build headers based on auth mode
-                let headers: Vec<(String, String)> = if using_proxy {
-                    // Proxy mode: send Cookie header with session ID so proxy uses stored OAuth
-                    if let Some(ref session) = oauth_session {
-                        eprintln!(
-                            "[debug] Proxy mode - sending Cookie: anthropic_session={}",
-                            session.id
-                        );
-                        vec![
-                            ("anthropic-version".to_string(), "2023-06-01".to_string()),
-                            ("content-type".to_string(), "application/json".to_string()),
-                            (
-                                "Cookie".to_string(),
-                                format!("anthropic_session={}", session.id),
-                            ),
-                        ]
-                    } else {
-                        eprintln!(
-                            "[debug] Proxy mode - no session, proxy will use any stored session"
-                        );
-                        vec![
-                            ("anthropic-version".to_string(), "2023-06-01".to_string()),
-                            ("content-type".to_string(), "application/json".to_string()),
-                        ]
-                    }
+                let mut routes = upstream::fallback_chain(config, &target, provider, &model);
+                if oauth_proxy.is_some() {
+                    routes.truncate(1);
                 } else {
-                    adapter.get_headers(&api_key)
-                };
+                    routes[0].provider.api_key = Some(api_key.clone());
+                }
 
                 // Show spinner while connecting
                 let spinner = ProgressBar::new_spinner();
@@ -3220,133 +3708,44 @@ async fn cmd_chat(model_override: Option<String>, stateful: bool) -> anyhow::Res
                 spinner.set_message("Connecting...");
                 spinner.enable_steady_tick(std::time::Duration::from_millis(80));
 
-                // Send request
-                let mut req = client.post(&endpoint).json(&request_body);
-                for (key, value) in &headers {
-                    req = req.header(key, value);
-                }
-
-                match req.send().await {
-                    Ok(response) => {
+                // Send request, retrying and falling back as configured
+                let sent = upstream::send_with_fallback(&routes, &config.proxy.retry, |route| {
+                    chat_attempt(
+                        &client,
+                        route,
+                        oauth_proxy.as_ref(),
+                        &chat_session.messages,
+                        &tool_definitions,
+                    )
+                })
+                .await;
+
+                match sent {
+                    Ok(upstream::Served { response, route }) => {
                         spinner.finish_and_clear();
 
                         if response.status().is_success() {
-                            // Stream the response
-                            use crossterm::event::{self, Event, KeyEventKind};
-                            use futures::StreamExt;
-                            use std::io::Write;
-
-                            println!();
-                            let mut full_content = String::new();
-                            let mut stream = response.bytes_stream();
-                            let mut buffer = String::new();
-                            let mut cancelled = false;
-                            let mut pending_action: Option<SlashCommandResult> = None;
-                            let mut tool_accumulator = tools::ToolCallAccumulator::new();
-
-                            while let Some(chunk_result) = stream.next().await {
-                                // Check for configured keybindings during streaming
-                                if event::poll(std::time::Duration::from_millis(1)).unwrap_or(false)
-                                {
-                                    if let Ok(Event::Key(key_event)) = event::read() {
-                                        if key_event.kind == KeyEventKind::Press {
-                                            // Convert key event to binding string and look up action
-                                            if let Some(key_str) =
-                                                key_event_to_string(&key_event, false)
-                                            {
-                                                if config.keybindings.is_bound(&key_str) {
-                                                    let action = config
-                                                        .keybindings
-                                                        .get_action_or_default(&key_str);
-                                                    // Cancel immediately stops streaming
-                                                    if action == config::KeyAction::Cancel {
-                                                        cancelled = true;
-                                                        print!(" (cancelled)");
-                                                        std::io::stdout().flush().ok();
-                                                        break;
-                                                    }
-                                                    // Other actions queued for after streaming completes
-                                                    if let Some(result) =
-                                                        execute_key_action(&action)
-                                                    {
-                                                        pending_action = Some(result);
-                                                    }
-                                                }
-                                            }
-                                        }
-                                    }
-                                }
-
-                                match chunk_result {
-                                    Ok(chunk) => {
-                                        // Append chunk to buffer
-                                        buffer.push_str(&String::from_utf8_lossy(&chunk));
-
-                                        // Process complete SSE lines
-                                        while let Some(line_end) = buffer.find('\n') {
-                                            let line = buffer[..line_end].trim().to_string();
-                                            buffer = buffer[line_end + 1..].to_string();
-
-                                            // Skip empty lines and comments
-                                            if line.is_empty() || line.starts_with(':') {
-                                                continue;
-                                            }
-
-                                            // Parse SSE data lines
-                                            if let Some(data) = line.strip_prefix("data: ") {
-                                                // Check for stream end
-                                                if data == "[DONE]" {
-                                                    break;
-                                                }
-
-                                                // Parse JSON
-                                                if let Ok(json) =
-                                                    serde_json::from_str::<serde_json::Value>(data)
-                                                {
-                                                    // Anthropic format: content_block_delta with delta.text
-                                                    if json.get("type").and_then(|t| t.as_str())
-                                                        == Some("content_block_delta")
-                                                    {
-                                                        if let Some(text) = json
-                                                            .get("delta")
-                                                            .and_then(|d| d.get("text"))
-                                                            .and_then(|t| t.as_str())
-                                                        {
-                                                            print!("{}", text);
-                                                            std::io::stdout().flush().ok();
-                                                            full_content.push_str(text);
-                                                        }
-                                                    }
-                                                    // OpenAI format: choices[0].delta.content
-                                                    else if let Some(delta) = json
-                                                        .get("choices")
-                                                        .and_then(|c| c.get(0))
-                                                        .and_then(|c| c.get("delta"))
-                                                    {
-                                                        // Handle text content
-                                                        if let Some(content) = delta
-                                                            .get("content")
-                                                            .and_then(|c| c.as_str())
-                                                        {
-                                                            print!("{}", content);
-                                                            std::io::stdout().flush().ok();
-                                                            full_content.push_str(content);
-                                                        }
-                                                        // Accumulate tool calls
-                                                        tool_accumulator.process_delta(delta);
-                                                    }
-                                                }
-                                            }
-                                        }
-                                    }
-                                    Err(e) => {
-                                        eprintln!("\nStream error: {}", e);
-                                        break;
-                                    }
-                                }
-                            }
-
+                            record_served(&mut chat_session, route);
                             println!();
+                            let StreamedTurn {
+                                turn,
+                                cancelled,
+                                mut pending_action,
+                            } = stream_chat_turn(
+                                response,
+                                route.adapter().stream_decoder(),
+                                &config.keybindings,
+                            )
+                            .await;
+                            record_usage(
+                                &mut chat_session,
+                                route,
+                                &turn,
+                                oauth_proxy.is_none().then_some(&tool_definitions),
+                                &config.pricing,
+                            );
+                            let mut full_content = turn.text;
+                            let mut tool_accumulator = turn.tool_calls;
 
                             // If cancelled, append note to content
                             if cancelled && !full_content.is_empty() {
@@ -3384,10 +3783,24 @@ async fn cmd_chat(model_override: Option<String>, stateful: bool) -> anyhow::Res
                                     }
 
                                     // Execute tools locally
-                                    let results = tool_intercept::execute_intercepted_tools(
-                                        &intercepted_tools,
-                                        memory_db.as_ref(),
+                                    let todo_scope = chat_session.id.clone();
+                                    let results = tools::with_owned_todos(
+                                        &todo_scope,
+                                        &mut chat_session.todos,
+                                        || {
+                                            tool_intercept::execute_intercepted_tools(
+                                                &intercepted_tools,
+                                                memory_db.as_ref(),
+                                            )
+                                        },
                                     );
+                                    if intercepted_tools
+                                        .iter()
+                                        .any(|t| t.to_tool_call().function.name == "todo_write")
+                                    {
+                                        let _ = tui::render_todo_panel(&chat_session.todos);
+                                    }
+                                    log_blocked_network_requests(&chat_session.id, memory_db.as_ref());
 
                                     // Format results for Claude and add as user message
                                     let results_xml = tool_intercept::format_tool_results_xml(&results);
@@ -3399,77 +3812,26 @@ async fn cmd_chat(model_override: Option<String>, stateful: bool) -> anyhow::Res
                                     // Send follow-up request
                                     println!("\n\x1b[90m(Sending tool results to Claude...)\x1b[0m");
 
-                                    let anthropic_messages = convert_messages_to_anthropic(&chat_session.messages);
-                                    let system_msg = chat_session
-                                        .messages
-                                        .iter()
-                                        .find(|m| m.get("role").and_then(|r| r.as_str()) == Some("system"))
-                                        .and_then(|m| m.get("content").and_then(|c| c.as_str()))
-                                        .map(String::from);
-
-                                    let mut followup_req = serde_json::json!({
-                                        "model": model,
-                                        "messages": anthropic_messages,
-                                        "max_tokens": 4096,
-                                        "stream": true
-                                    });
-                                    if let Some(sys) = system_msg {
-                                        followup_req["system"] = serde_json::json!(sys);
-                                    }
-
-                                    let mut req = client.post(&endpoint).json(&followup_req);
-                                    for (key, value) in &headers {
-                                        req = req.header(key, value);
-                                    }
+                                    let sent = upstream::send_with_fallback(&routes, &config.proxy.retry, |route| {
+                                        chat_attempt(&client, route, oauth_proxy.as_ref(), &chat_session.messages, &tool_definitions)
+                                    })
+                                    .await;
 
-                                    match req.send().await {
-                                        Ok(response) if response.status().is_success() => {
-                                            use futures::StreamExt;
-
-                                            let mut stream = response.bytes_stream();
-                                            let mut buffer = String::new();
-                                            let mut followup_content = String::new();
-
-                                            while let Some(chunk_result) = stream.next().await {
-                                                match chunk_result {
-                                                    Ok(chunk) => {
-                                                        buffer.push_str(&String::from_utf8_lossy(&chunk));
-                                                        while let Some(line_end) = buffer.find('\n') {
-                                                            let line = buffer[..line_end].trim().to_string();
-                                                            buffer = buffer[line_end + 1..].to_string();
-                                                            if line.is_empty() || line.starts_with(':') {
-                                                                continue;
-                                                            }
-                                                            if let Some(data) = line.strip_prefix("data: ") {
-                                                                if data == "[DONE]" {
-                                                                    break;
-                                                                }
-                                                                if let Ok(json) = serde_json::from_str::<serde_json::Value>(data) {
-                                                                    if json.get("type").and_then(|t| t.as_str()) == Some("content_block_delta") {
-                                                                        if let Some(text) = json.get("delta").and_then(|d| d.get("text")).and_then(|t| t.as_str()) {
-                                                                            print!("{}", text);
-                                                                            std::io::stdout().flush().ok();
-                                                                            followup_content.push_str(text);
-                                                                        }
-                                                                    }
-                                                                }
-                                                            }
-                                                        }
-                                                    }
-                                                    Err(e) => {
-                                                        eprintln!("\nStream error: {}", e);
-                                                        break;
-                                                    }
-                                                }
-                                            }
+                                    match sent {
+                                        Ok(served) if served.response.status().is_success() => {
+                                            record_served(&mut chat_session, served.route);
+                                            // Proxy mode sends no tools, so only the text matters here
+                                            let followup = stream_chat_turn(served.response, served.route.adapter().stream_decoder(), &config.keybindings).await;
+                                            record_usage(&mut chat_session, served.route, &followup.turn, None, &config.pricing);
+                                            let followup_content = followup.turn.text;
 
                                             // Check if follow-up contains more tool calls
                                             tool_interceptor.clear();
                                             tool_interceptor.push(&followup_content);
                                             full_content = followup_content;
                                         }
-                                        Ok(response) => {
-                                            eprintln!("\nFollow-up request failed: {}", response.status());
+                                        Ok(served) => {
+                                            eprintln!("\nFollow-up request failed: {}", served.response.status());
                                             break;
                                         }
                                         Err(e) => {
@@ -3496,10 +3858,10 @@ async fn cmd_chat(model_override: Option<String>, stateful: bool) -> anyhow::Res
                             let mut iteration = 0;
                             let mut current_content = full_content;
 
-                            // Check for tool calls
-                            let has_tools = tool_accumulator.has_tool_calls();
-
-                            while has_tools && !cancelled && iteration < max_iterations {
+                            while tool_accumulator.has_tool_calls()
+                                && !cancelled
+                                && iteration < max_iterations
+                            {
                                 iteration += 1;
 
                                 // Get tool calls
@@ -3534,11 +3896,21 @@ async fn cmd_chat(model_override: Option<String>, stateful: bool) -> anyhow::Res
                                     );
 
                                     // Use appropriate tool executor based on stateful mode
-                                    let result = if let Some(ref db) = memory_db {
-                                        tools::execute_tool_with_memory(tool_call, Some(db))
-                                    } else {
-                                        tools::execute_tool(tool_call)
-                                    };
+                                    let todo_scope = chat_session.id.clone();
+                                    let result = tools::with_owned_todos(
+                                        &todo_scope,
+                                        &mut chat_session.todos,
+                                        || {
+                                            if let Some(ref db) = memory_db {
+                                                tools::execute_tool_with_memory(tool_call, Some(db))
+                                            } else {
+                                                tools::execute_tool(tool_call)
+                                            }
+                                        },
+                                    );
+                                    if tool_call.function.name == "todo_write" && !result.is_error {
+                                        let _ = tui::render_todo_panel(&chat_session.todos);
+                                    }
 
                                     // Log activity for short-term memory
                                     if let Some(ref db) = memory_db {
@@ -3591,6 +3963,23 @@ async fn cmd_chat(model_override: Option<String>, stateful: bool) -> anyhow::Res
                                             tool_call.function.name.clone()
                                         };
 
+                                        // Issue tools log the issue number so it lands in issues_worked
+                                        let issue_activity = if result.is_error {
+                                            None
+                                        } else {
+                                            serde_json::from_str(&tool_call.function.arguments)
+                                                .ok()
+                                                .and_then(|args| {
+                                                    issues::issue_activity(
+                                                        &tool_call.function.name,
+                                                        &args,
+                                                        &result.content,
+                                                    )
+                                                })
+                                        };
+                                        let (activity_type, target) =
+                                            issue_activity.unwrap_or((activity_type, target));
+
                                         let _ = db.log_activity(
                                             &chat_session.id,
                                             activity_type,
@@ -3598,6 +3987,10 @@ async fn cmd_chat(model_override: Option<String>, stateful: bool) -> anyhow::Res
                                             if result.is_error { Some("error") } else { None },
                                         );
                                     }
+                                    log_blocked_network_requests(
+                                        &chat_session.id,
+                                        memory_db.as_ref(),
+                                    );
 
                                     // Show result preview
                                     let preview: String = result
@@ -3620,174 +4013,75 @@ async fn cmd_chat(model_override: Option<String>, stateful: bool) -> anyhow::Res
                                     }
 
                                     // Add tool result to messages
-                                    chat_session.messages.push(serde_json::json!({
-                                        "role": "tool",
-                                        "tool_call_id": result.tool_call_id,
-                                        "content": result.content
-                                    }));
+                                    chat_session
+                                        .messages
+                                        .push(result.to_message(models::supports_vision(&model)));
                                 }
 
                                 // Clear accumulator for next iteration
                                 tool_accumulator.clear();
 
+                                if let Some((scope, message)) =
+                                    chat_budget_exceeded(&chat_session, &config.budgets)
+                                {
+                                    eprintln!("\n\x1b[31mStopped: {}\x1b[0m\n", message);
+                                    budget::run_stop_hook(
+                                        hook_engine,
+                                        &chat_session.id,
+                                        scope,
+                                        &message,
+                                    )
+                                    .await;
+                                    // The text before the tool calls is already in history
+                                    current_content.clear();
+                                    break;
+                                }
+
                                 // Continue the conversation - send tool results back to model
                                 println!("\n\x1b[90mContinuing with tool results...\x1b[0m\n");
 
-                                // Build new request with tool results
-                                // Use minimal format for proxy, native Anthropic for direct, OpenAI for others
-                                let request_body = if using_proxy {
-                                    // Extract system message to top-level
-                                    let system_msg = chat_session
-                                        .messages
-                                        .iter()
-                                        .find(|m| {
-                                            m.get("role").and_then(|r| r.as_str()) == Some("system")
-                                        })
-                                        .and_then(|m| m.get("content").and_then(|c| c.as_str()))
-                                        .map(String::from);
-
-                                    // Convert messages to Anthropic format (handles tool_calls and tool results)
-                                    // Proxy mode still talks to Anthropic API, so needs proper format
-                                    let anthropic_messages = convert_messages_to_anthropic(&chat_session.messages);
-
-                                    // NOTE: Proxy mode (OAuth) does NOT support custom tools - see above
-                                    let mut req = serde_json::json!({
-                                        "model": model,
-                                        "messages": anthropic_messages,
-                                        "max_tokens": 4096,
-                                        "stream": true
-                                    });
-
-                                    if let Some(sys) = system_msg {
-                                        req["system"] = serde_json::json!(sys);
-                                    }
-
-                                    req
-                                } else if config.proxy.target == "anthropic" {
-                                    // Anthropic direct API - convert messages to Anthropic format
-                                    let system_msg = chat_session
-                                        .messages
-                                        .iter()
-                                        .find(|m| {
-                                            m.get("role").and_then(|r| r.as_str()) == Some("system")
-                                        })
-                                        .and_then(|m| m.get("content").and_then(|c| c.as_str()))
-                                        .map(String::from);
-
-                                    // Convert messages with proper tool_use/tool_result handling
-                                    let anthropic_messages = convert_messages_to_anthropic(&chat_session.messages);
-
-                                    let openai_tools = tools::get_all_tool_definitions(stateful, true);
-                                    let anthropic_tools = convert_tools_to_anthropic(openai_tools.as_array().unwrap_or(&vec![]));
-
-                                    let mut req = serde_json::json!({
-                                        "model": model,
-                                        "messages": anthropic_messages,
-                                        "max_tokens": 4096,
-                                        "stream": true,
-                                        "tools": anthropic_tools
-                                    });
-
-                                    if let Some(sys) = system_msg {
-                                        req["system"] = serde_json::json!([{
-                                            "type": "text",
-                                            "text": sys,
-                                            "cache_control": {"type": "ephemeral"}
-                                        }]);
-                                    }
-
-                                    req
-                                } else {
-                                    // OpenAI-compatible format for other providers
-                                    serde_json::json!({
-                                        "model": model,
-                                        "messages": chat_session.messages,
-                                        "max_tokens": 4096,
-                                        "stream": true,
-                                        "tools": tools::get_all_tool_definitions(stateful, true)
-                                    })
-                                };
-
-                                // Send follow-up request
-                                let mut req = client.post(&endpoint).json(&request_body);
-                                for (key, value) in &headers {
-                                    req = req.header(key, value);
-                                }
+                                // Send follow-up request with tool results
+                                let sent = upstream::send_with_fallback(
+                                    &routes,
+                                    &config.proxy.retry,
+                                    |route| {
+                                        chat_attempt(
+                                            &client,
+                                            route,
+                                            oauth_proxy.as_ref(),
+                                            &chat_session.messages,
+                                            &tool_definitions,
+                                        )
+                                    },
+                                )
+                                .await;
 
                                 current_content = String::new();
 
-                                if let Ok(response) = req.send().await {
-                                    if response.status().is_success() {
-                                        let mut stream = response.bytes_stream();
-                                        let mut buffer = String::new();
-
-                                        while let Some(chunk_result) = stream.next().await {
-                                            if let Ok(chunk) = chunk_result {
-                                                buffer.push_str(&String::from_utf8_lossy(&chunk));
-
-                                                while let Some(line_end) = buffer.find('\n') {
-                                                    let line =
-                                                        buffer[..line_end].trim().to_string();
-                                                    buffer = buffer[line_end + 1..].to_string();
-
-                                                    if line.is_empty() || line.starts_with(':') {
-                                                        continue;
-                                                    }
-
-                                                    if let Some(data) = line.strip_prefix("data: ")
-                                                    {
-                                                        if data == "[DONE]" {
-                                                            break;
-                                                        }
-
-                                                        if let Ok(json) = serde_json::from_str::<
-                                                            serde_json::Value,
-                                                        >(
-                                                            data
-                                                        ) {
-                                                            // Anthropic format: content_block_delta
-                                                            if json
-                                                                .get("type")
-                                                                .and_then(|t| t.as_str())
-                                                                == Some("content_block_delta")
-                                                            {
-                                                                if let Some(text) = json
-                                                                    .get("delta")
-                                                                    .and_then(|d| d.get("text"))
-                                                                    .and_then(|t| t.as_str())
-                                                                {
-                                                                    print!("{}", text);
-                                                                    std::io::stdout().flush().ok();
-                                                                    current_content.push_str(text);
-                                                                }
-                                                            }
-                                                            // OpenAI format: choices[0].delta.content
-                                                            else if let Some(delta) = json
-                                                                .get("choices")
-                                                                .and_then(|c| c.get(0))
-                                                                .and_then(|c| c.get("delta"))
-                                                            {
-                                                                // Handle text content
-                                                                if let Some(content) = delta
-                                                                    .get("content")
-                                                                    .and_then(|c| c.as_str())
-                                                                {
-                                                                    print!("{}", content);
-                                                                    std::io::stdout().flush().ok();
-                                                                    current_content
-                                                                        .push_str(content);
-                                                                }
-                                                                // Accumulate tool calls for next iteration
-                                                                tool_accumulator
-                                                                    .process_delta(delta);
-                                                            }
-                                                        }
-                                                    }
-                                                }
-                                            }
+                                if let Ok(served) = sent {
+                                    if served.response.status().is_success() {
+                                        record_served(&mut chat_session, served.route);
+                                        let followup = stream_chat_turn(
+                                            served.response,
+                                            served.route.adapter().stream_decoder(),
+                                            &config.keybindings,
+                                        )
+                                        .await;
+                                        record_usage(
+                                            &mut chat_session,
+                                            served.route,
+                                            &followup.turn,
+                                            oauth_proxy.is_none().then_some(&tool_definitions),
+                                            &config.pricing,
+                                        );
+                                        current_content = followup.turn.text;
+                                        tool_accumulator = followup.turn.tool_calls;
+                                        if followup.pending_action.is_some() {
+                                            pending_action = followup.pending_action;
+                                        }
+                                        if followup.cancelled {
+                                            break;
                                         }
-
-                                        println!();
                                     }
                                 }
                             }
@@ -3840,10 +4134,11 @@ async fn cmd_chat(model_override: Option<String>, stateful: bool) -> anyhow::Res
                                         let duration = chrono::Utc::now()
                                             .signed_duration_since(chat_session.created_at);
                                         println!(
-                                            "\n[{}] {} | ~{} tokens | {} min\n",
+                                            "\n[{}] {} | ~{} tokens | {} | {} min\n",
                                             chat_session.mode.display(),
                                             chat_session.model,
                                             tokens,
+                                            usage::format_cost(chat_session.cost_usd),
                                             duration.num_minutes()
                                         );
                                     }
@@ -3865,7 +4160,7 @@ async fn cmd_chat(model_override: Option<String>, stateful: bool) -> anyhow::Res
                     }
                     Err(e) => {
                         spinner.finish_and_clear();
-                        eprintln!("\nRequest failed: {}\n", e);
+                        eprintln!("\n{}\n", e);
                         // Remove the failed user message
                         chat_session.messages.pop();
                     }
@@ -3908,22 +4203,23 @@ async fn cmd_start(
     host: Option<String>,
     target: Option<String>,
 ) -> anyhow::Result<()> {
-    let mut config = config::load_config()?;
-
-    // Apply command-line overrides
-    if let Some(p) = port {
-        config.proxy.port = p;
-    }
-    if let Some(h) = host {
-        config.proxy.host = h;
-    }
-    if let Some(t) = target {
-        config.proxy.target = t;
-    }
+    // Command-line flags override every config layer
+    let overrides: Vec<(&str, String)> = [
+        ("proxy.port", port.map(|p| p.to_string())),
+        ("proxy.host", host),
+        ("proxy.target", target),
+    ]
+    .into_iter()
+    .filter_map(|(key, value)| Some((key, value?)))
+    .collect();
+    let config = config::load_config_with(&overrides)?;
 
     // Check for API key - warn if missing but allow startup for OAuth mode
     if let Some(provider) = config.active_provider() {
-        if provider.api_key.is_none() {
+        if provider.api_key.is_none()
+            && openclaudia::providers::adapter_for(&config.proxy.target, provider)
+                .requires_api_key()
+        {
             let env_var = match config.proxy.target.as_str() {
                 "anthropic" => "ANTHROPIC_API_KEY",
                 "openai" => "OPENAI_API_KEY",
@@ -3963,43 +4259,217 @@ async fn cmd_start(
         config.proxy.port
     );
 
-    proxy::start_server(config).await
+    proxy::start_server(config, &overrides).await
 }
 
 /// Show current configuration
-fn cmd_config() -> anyhow::Result<()> {
-    match config::load_config() {
-        Ok(config) => {
-            println!("OpenClaudia Configuration\n");
-            println!("Proxy:");
-            println!("  Host: {}", config.proxy.host);
-            println!("  Port: {}", config.proxy.port);
-            println!("  Target: {}", config.proxy.target);
-            println!();
-            println!("Providers:");
-            for (name, provider) in &config.providers {
-                let has_key = provider.api_key.is_some();
+fn cmd_config(action: Option<ConfigAction>) -> anyhow::Result<()> {
+    match action {
+        None => show_config(),
+        Some(ConfigAction::Get { key }) => {
+            let layered = config::load_layered(&[])?;
+            let key = key.to_lowercase();
+            let section = format!("{}.", key);
+            let matches: Vec<_> = layered
+                .origins
+                .iter()
+                .filter(|(k, _)| **k == key || k.starts_with(&section))
+                .collect();
+            if matches.is_empty() {
+                anyhow::bail!("{} is not set in any layer", key);
+            }
+            for (key, origin) in matches {
                 println!(
-                    "  {}: {} (API key: {})",
-                    name,
-                    provider.base_url,
-                    if has_key { "configured" } else { "not set" }
+                    "{} = {}  ({})",
+                    key,
+                    display_config_value(key, &origin.value),
+                    origin.layer
                 );
             }
-            println!();
-            println!("Session:");
-            println!("  Timeout: {} minutes", config.session.timeout_minutes);
-            println!("  Persist path: {:?}", config.session.persist_path);
+            Ok(())
+        }
+        Some(ConfigAction::Set { key, value, .. })
+            if config::is_secret_key(&key) && !value.contains("${") =>
+        {
+            // Credentials go to the store, whatever the scope
+            credentials::store().set(&key.to_lowercase(), &value)?;
+            println!(
+                "Stored {} in the credential store ({})",
+                key,
+                credentials::store().name()
+            );
+            Ok(())
+        }
+        Some(ConfigAction::Set { key, value, scope }) => {
+            let path = config::set_value(scope, &key, &value).map_err(anyhow::Error::msg)?;
+            println!("Set {} in {}", key, path.display());
+            Ok(())
         }
+        Some(ConfigAction::Unset { key, scope }) => {
+            let stored =
+                config::is_secret_key(&key) && credentials::store().delete(&key.to_lowercase())?;
+            if stored {
+                println!("Removed {} from the credential store", key);
+            }
+            if config::unset_value(scope, &key).map_err(anyhow::Error::msg)? {
+                println!("Removed {} from the {} config", key, scope);
+            } else if !stored {
+                println!("{} is not set in the {} config", key, scope);
+            }
+            Ok(())
+        }
+        Some(ConfigAction::Schema) => {
+            println!("{}", serde_json::to_string_pretty(config_schema::schema())?);
+            Ok(())
+        }
+    }
+}
+
+/// A config value for printing, with credentials masked
+fn display_config_value(key: &str, value: &str) -> String {
+    if config::is_secret_key(key) && !value.is_empty() {
+        credentials::mask(value)
+    } else {
+        value.to_string()
+    }
+}
+
+/// Print every set value with the layer it comes from
+fn show_config() -> anyhow::Result<()> {
+    let layered = match config::load_layered(&[]) {
+        Ok(layered) => layered,
         Err(e) => {
             error!("Failed to load configuration: {}", e);
             info!("Run 'openclaudia init' to create a configuration file.");
+            return Ok(());
+        }
+    };
+
+    println!("OpenClaudia Configuration\n");
+    println!("Layers (later override earlier):");
+    println!("  default");
+    for scope in config::ConfigScope::ALL {
+        if let Some(path) = scope.path() {
+            let state = if path.exists() { "" } else { " (not found)" };
+            println!("  {:<8} {}{}", scope, path.display(), state);
         }
     }
+    println!("  env      OPENCLAUDIA_* and provider API key variables");
+    println!("  command line flags");
+    println!();
+
+    let width = layered.origins.keys().map(|k| k.len()).max().unwrap_or(0);
+    for (key, origin) in &layered.origins {
+        println!(
+            "  {:<width$}  {}  [{}]",
+            key,
+            display_config_value(key, &origin.value),
+            origin.layer,
+            width = width
+        );
+    }
     Ok(())
 }
 
 /// Check configuration and connectivity
+/// Print token usage and cost per model and per day from the project database
+fn cmd_usage(days: u32) -> anyhow::Result<()> {
+    let cwd = std::env::current_dir()?;
+    let db = memory::MemoryDb::open_for_project(&cwd)?;
+    let by_model = db.usage_by_model(days)?;
+    if by_model.is_empty() {
+        println!("No token usage recorded in the last {} days.", days);
+        return Ok(());
+    }
+
+    let print_table = |heading: &str, rows: &[memory::UsageSummary]| {
+        println!(
+            "{:<32} {:>6} {:>9} {:>9} {:>11} {:>11} {:>10}",
+            heading, "Turns", "Input", "Output", "Cache read", "Cache write", "Cost"
+        );
+        for row in rows {
+            let cost = usage::format_cost(row.cost_usd);
+            println!(
+                "{:<32} {:>6} {:>9} {:>9} {:>11} {:>11} {:>10}{}",
+                row.key,
+                row.turns,
+                usage::format_tokens(row.usage.input_tokens),
+                usage::format_tokens(row.usage.output_tokens),
+                usage::format_tokens(row.usage.cache_read_tokens),
+                usage::format_tokens(row.usage.cache_write_tokens),
+                cost,
+                if row.unpriced_turns > 0 { " *" } else { "" }
+            );
+        }
+        println!();
+    };
+
+    println!("Token usage, last {} days\n", days);
+    print_table("Model", &by_model);
+    print_table("Day (UTC)", &db.usage_by_day(days)?);
+
+    let total_cost: f64 = by_model.iter().map(|row| row.cost_usd).sum();
+    let total_turns: u64 = by_model.iter().map(|row| row.turns).sum();
+    println!(
+        "Total: {} requests, {}",
+        total_turns,
+        usage::format_cost(total_cost)
+    );
+    if by_model.iter().any(|row| row.unpriced_turns > 0) {
+        println!("* includes requests for models without a price; add them under `pricing:` in config.yaml");
+    }
+    Ok(())
+}
+
+/// List the model registry, optionally refreshing it from the providers first
+async fn cmd_models(refresh: bool, provider: Option<String>) -> anyhow::Result<()> {
+    if refresh {
+        let config = config::load_config()?;
+        let client = reqwest::Client::new();
+        let results = models::refresh(&client, &config, provider.as_deref()).await;
+        if results.is_empty() {
+            anyhow::bail!(
+                "Provider '{}' is not configured",
+                provider.as_deref().unwrap_or_default()
+            );
+        }
+        println!("Refreshed model lists:");
+        for (name, result) in results {
+            match result {
+                Ok(count) => println!("  {:<12} {} models", name, count),
+                Err(e) => println!("  {:<12} {}", name, e),
+            }
+        }
+        println!();
+    }
+
+    let list = models::list(provider.as_deref());
+    if list.is_empty() {
+        println!("No models known. Run `openclaudia models --refresh` to fetch them.");
+        return Ok(());
+    }
+    println!(
+        "{:<28} {:<10} {:>8} {:>8}  {:<20} Price (in/out per 1M)",
+        "Model", "Provider", "Context", "Output", "Capabilities"
+    );
+    for m in list {
+        let price = m
+            .pricing
+            .map(|p| format!("${} / ${}", p.input, p.output))
+            .unwrap_or_else(|| "-".to_string());
+        println!(
+            "{:<28} {:<10} {:>8} {:>8}  {:<20} {}",
+            m.id,
+            m.provider,
+            usage::format_tokens(m.context_window as u64),
+            usage::format_tokens(m.max_output as u64),
+            m.capabilities().join(" "),
+            price
+        );
+    }
+    Ok(())
+}
+
 async fn cmd_doctor() -> anyhow::Result<()> {
     use openclaudia::mcp::McpManager;
     use openclaudia::plugins::{PluginError, PluginManager};
@@ -4010,6 +4480,29 @@ async fn cmd_doctor() -> anyhow::Result<()> {
 
     println!("OpenClaudia Doctor\n");
 
+    // Check config files against the schema
+    print!("Config files... ");
+    let diagnostics = config_schema::check_layers();
+    if diagnostics.is_empty() {
+        println!("OK");
+    } else {
+        println!("{} problem(s)", diagnostics.len());
+        for diagnostic in &diagnostics {
+            println!("  {}", diagnostic);
+        }
+    }
+
+    // Check the credential store can be opened
+    print!("Credential store... ");
+    match credentials::store().all() {
+        Ok(secrets) => println!(
+            "OK ({}, {} stored)",
+            credentials::store().name(),
+            secrets.len()
+        ),
+        Err(e) => println!("FAILED: {}", e),
+    }
+
     // Check configuration
     print!("Configuration... ");
     match config::load_config() {
@@ -4044,7 +4537,11 @@ async fn cmd_doctor() -> anyhow::Result<()> {
         }
         Err(e) => {
             println!("FAILED: {}", e);
-            println!("\nRun 'openclaudia init' to create a configuration file.");
+            if diagnostics.is_empty() {
+                println!("\nRun 'openclaudia init' to create a configuration file.");
+            } else {
+                println!("\nFix the config file problems listed above.");
+            }
         }
     }
 
@@ -4326,19 +4823,22 @@ async fn cmd_loop(
     use openclaudia::session::SessionManager;
     use tokio::sync::watch;
 
-    let mut config = config::load_config()?;
-
-    // Apply command-line overrides
-    if let Some(p) = port {
-        config.proxy.port = p;
-    }
-    if let Some(t) = target {
-        config.proxy.target = t;
-    }
+    // Command-line flags override every config layer
+    let overrides: Vec<(&str, String)> = [
+        ("proxy.port", port.map(|p| p.to_string())),
+        ("proxy.target", target),
+    ]
+    .into_iter()
+    .filter_map(|(key, value)| Some((key, value?)))
+    .collect();
+    let config = config::load_config_with(&overrides)?;
 
     // Validate API key
     if let Some(provider) = config.active_provider() {
-        if provider.api_key.is_none() {
+        if provider.api_key.is_none()
+            && openclaudia::providers::adapter_for(&config.proxy.target, provider)
+                .requires_api_key()
+        {
             let env_var = match config.proxy.target.as_str() {
                 "anthropic" => "ANTHROPIC_API_KEY",
                 "openai" => "OPENAI_API_KEY",
@@ -4395,6 +4895,13 @@ async fn cmd_loop(
         }
     });
 
+    // The proxy checks the loop budget before every upstream request and
+    // stops the run once it is spent
+    let loop_run = proxy::LoopRun {
+        started: chrono::Utc::now(),
+        stop: shutdown_tx.clone(),
+    };
+
     let mut iteration: u32 = 0;
     let mut shutdown_rx_loop = shutdown_rx.clone();
 
@@ -4422,10 +4929,18 @@ async fn cmd_loop(
 
         // Start the proxy server for this iteration
         let config_clone = config.clone();
+        let overrides_clone = overrides.clone();
         let shutdown_rx_server = shutdown_rx.clone();
+        let loop_run_clone = loop_run.clone();
 
         let server_handle = tokio::spawn(async move {
-            proxy::start_server_with_shutdown(config_clone, shutdown_rx_server).await
+            proxy::start_server_with_shutdown(
+                config_clone,
+                &overrides_clone,
+                shutdown_rx_server,
+                Some(loop_run_clone),
+            )
+            .await
         });
 
         // Wait for the server to complete (client disconnects or shutdown)
@@ -4473,6 +4988,9 @@ async fn cmd_loop(
         "Loop mode completed after {} iterations.\nSession ended at iteration {}.",
         iteration, iteration
     );
+    if let Some(session) = session_manager.get_session_mut() {
+        session.set_todos(tools::get_todo_list());
+    }
     session_manager.end_session(Some(&handoff));
 
     info!("Loop mode ended after {} iterations", iteration);
@@ -587,11 +587,31 @@ impl McpManager {
         Ok(())
     }
 
+    /// Remove a connection without closing it
+    pub fn take(&mut self, name: &str) -> Option<McpServer> {
+        self.servers.remove(name)
+    }
+
+    /// Move the connections of `other` into this manager. Connections they
+    /// replace are returned still open, to be closed outside any lock.
+    pub fn merge(&mut self, other: McpManager) -> Vec<McpServer> {
+        other
+            .servers
+            .into_iter()
+            .filter_map(|(name, server)| self.servers.insert(name, server))
+            .collect()
+    }
+
     /// Get the number of connected servers
     pub fn server_count(&self) -> usize {
         self.servers.len()
     }
 
+    /// Names of the connected servers
+    pub fn server_names(&self) -> Vec<String> {
+        self.servers.keys().cloned().collect()
+    }
+
     /// Check if a server is connected
     pub fn is_connected(&self, name: &str) -> bool {
         self.servers.contains_key(name)
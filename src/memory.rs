@@ -7,11 +7,13 @@ use anyhow::{Context, Result};
 use rusqlite::{params, Connection, OptionalExtension};
 use std::path::{Path, PathBuf};
 
+use crate::stream::TokenUsage;
+
 /// Memory database file name
 const MEMORY_DB_NAME: &str = "memory.db";
 
 /// Current schema version - increment when adding migrations
-const SCHEMA_VERSION: i64 = 2;
+const SCHEMA_VERSION: i64 = 4;
 
 /// Short-term memory expiration (hours)
 const SHORT_TERM_EXPIRY_HOURS: i64 = 48;
@@ -54,6 +56,41 @@ pub struct ArchivalMemory {
     pub updated_at: String,
 }
 
+/// Issue priority values accepted by the issue store
+pub const ISSUE_PRIORITIES: &[&str] = &["low", "medium", "high", "critical"];
+
+/// A tracked issue in the native issue store
+#[derive(Debug, Clone)]
+pub struct Issue {
+    pub id: i64,
+    pub title: String,
+    pub description: String,
+    pub status: String, // "open" or "closed"
+    pub priority: String,
+    pub parent_id: Option<i64>,
+    pub labels: Vec<String>,
+    /// Issues that must be closed before this one can be worked
+    pub blocked_by: Vec<i64>,
+    pub created_at: String,
+    pub updated_at: String,
+    pub closed_at: Option<String>,
+}
+
+impl Issue {
+    pub fn is_open(&self) -> bool {
+        self.status == "open"
+    }
+}
+
+/// A comment on an issue
+#[derive(Debug, Clone)]
+pub struct IssueComment {
+    pub id: i64,
+    pub issue_id: i64,
+    pub content: String,
+    pub created_at: String,
+}
+
 /// Core memory block (always in context)
 #[derive(Debug, Clone)]
 pub struct CoreMemory {
@@ -145,6 +182,16 @@ impl MemoryDb {
             self.migrate_v2()?;
         }
 
+        // Version 3: Add native issue tracker tables
+        if from_version < 3 {
+            self.migrate_v3()?;
+        }
+
+        // Version 4: Add token usage accounting
+        if from_version < 4 {
+            self.migrate_v4()?;
+        }
+
         // Record current version
         self.conn.execute(
             "INSERT OR REPLACE INTO schema_version (version) VALUES (?1)",
@@ -252,6 +299,79 @@ impl MemoryDb {
         Ok(())
     }
 
+    /// Migration v3: Add native issue tracker tables
+    fn migrate_v3(&mut self) -> Result<()> {
+        tracing::debug!("Running migration v3: issue tracker tables");
+        self.conn
+            .execute_batch(
+                r#"
+            CREATE TABLE IF NOT EXISTS issues (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                title TEXT NOT NULL,
+                description TEXT DEFAULT '',
+                status TEXT NOT NULL DEFAULT 'open',
+                priority TEXT NOT NULL DEFAULT 'medium',
+                parent_id INTEGER REFERENCES issues(id),
+                created_at TEXT DEFAULT (datetime('now')),
+                updated_at TEXT DEFAULT (datetime('now')),
+                closed_at TEXT
+            );
+            CREATE INDEX IF NOT EXISTS idx_issues_status ON issues(status);
+
+            CREATE TABLE IF NOT EXISTS issue_labels (
+                issue_id INTEGER NOT NULL REFERENCES issues(id),
+                label TEXT NOT NULL,
+                PRIMARY KEY (issue_id, label)
+            );
+
+            CREATE TABLE IF NOT EXISTS issue_comments (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                issue_id INTEGER NOT NULL REFERENCES issues(id),
+                content TEXT NOT NULL,
+                created_at TEXT DEFAULT (datetime('now'))
+            );
+            CREATE INDEX IF NOT EXISTS idx_issue_comments_issue ON issue_comments(issue_id);
+
+            CREATE TABLE IF NOT EXISTS issue_dependencies (
+                issue_id INTEGER NOT NULL REFERENCES issues(id),
+                blocked_by INTEGER NOT NULL REFERENCES issues(id),
+                PRIMARY KEY (issue_id, blocked_by)
+            );
+            "#,
+            )
+            .context("Failed to create v3 schema (issue tracker)")?;
+
+        Ok(())
+    }
+
+    /// Migration v4: Add token usage accounting
+    fn migrate_v4(&mut self) -> Result<()> {
+        tracing::debug!("Running migration v4: token usage");
+        self.conn
+            .execute_batch(
+                r#"
+            CREATE TABLE IF NOT EXISTS token_usage (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                session_id TEXT NOT NULL,
+                provider TEXT NOT NULL,
+                model TEXT NOT NULL,
+                input_tokens INTEGER NOT NULL DEFAULT 0,
+                output_tokens INTEGER NOT NULL DEFAULT 0,
+                cache_read_tokens INTEGER NOT NULL DEFAULT 0,
+                cache_write_tokens INTEGER NOT NULL DEFAULT 0,
+                reasoning_tokens INTEGER NOT NULL DEFAULT 0,
+                cost_usd REAL,
+                created_at TEXT DEFAULT (datetime('now'))
+            );
+            CREATE INDEX IF NOT EXISTS idx_token_usage_session ON token_usage(session_id);
+            CREATE INDEX IF NOT EXISTS idx_token_usage_created ON token_usage(created_at);
+            "#,
+            )
+            .context("Failed to create v4 schema (token usage)")?;
+
+        Ok(())
+    }
+
     // === Archival Memory Operations ===
 
     /// Save a new memory entry
@@ -584,7 +704,7 @@ impl MemoryDb {
     pub fn get_session_issues(&self, session_id: &str) -> Result<Vec<String>> {
         let mut stmt = self.conn.prepare(
             r#"SELECT DISTINCT target FROM recent_activity
-               WHERE session_id = ?1 AND activity_type IN ('issue_created', 'issue_closed', 'issue_comment')
+               WHERE session_id = ?1 AND activity_type IN ('issue_created', 'issue_closed', 'issue_comment', 'issue_updated')
                ORDER BY target"#,
         )?;
 
@@ -651,6 +771,344 @@ impl MemoryDb {
         Ok(output)
     }
 
+    // === Issue Tracker Operations ===
+
+    /// Create a new open issue, returning its ID
+    pub fn issue_create(
+        &self,
+        title: &str,
+        description: &str,
+        priority: &str,
+        parent_id: Option<i64>,
+    ) -> Result<i64> {
+        if !ISSUE_PRIORITIES.contains(&priority) {
+            anyhow::bail!(
+                "Invalid priority '{}'. Must be one of: {}",
+                priority,
+                ISSUE_PRIORITIES.join(", ")
+            );
+        }
+        if let Some(parent) = parent_id {
+            self.require_issue(parent)?;
+        }
+
+        self.conn.execute(
+            "INSERT INTO issues (title, description, priority, parent_id) VALUES (?1, ?2, ?3, ?4)",
+            params![title, description, priority, parent_id],
+        )?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    /// Get an issue by ID, including labels and blockers
+    pub fn issue_get(&self, id: i64) -> Result<Option<Issue>> {
+        let issue = self
+            .conn
+            .query_row(
+                r#"SELECT id, title, description, status, priority, parent_id, created_at, updated_at, closed_at
+                   FROM issues WHERE id = ?1"#,
+                params![id],
+                Self::issue_from_row,
+            )
+            .optional()?;
+
+        match issue {
+            Some(mut issue) => {
+                self.load_issue_relations(&mut issue)?;
+                Ok(Some(issue))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// List issues, optionally filtered by status ("open", "closed") and label
+    pub fn issue_list(&self, status: Option<&str>, label: Option<&str>) -> Result<Vec<Issue>> {
+        let mut stmt = self.conn.prepare(
+            r#"SELECT id, title, description, status, priority, parent_id, created_at, updated_at, closed_at
+               FROM issues
+               WHERE (?1 IS NULL OR status = ?1)
+                 AND (?2 IS NULL OR id IN (SELECT issue_id FROM issue_labels WHERE label = ?2))
+               ORDER BY CASE priority
+                   WHEN 'critical' THEN 0 WHEN 'high' THEN 1 WHEN 'medium' THEN 2 ELSE 3 END,
+                   id"#,
+        )?;
+
+        let mut issues = stmt
+            .query_map(params![status, label], Self::issue_from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        for issue in &mut issues {
+            self.load_issue_relations(issue)?;
+        }
+
+        Ok(issues)
+    }
+
+    /// Set an issue's status to "open" or "closed"
+    pub fn issue_set_status(&self, id: i64, status: &str) -> Result<bool> {
+        let closed_at = if status == "closed" {
+            "datetime('now')"
+        } else if status == "open" {
+            "NULL"
+        } else {
+            anyhow::bail!("Invalid status '{}'. Must be 'open' or 'closed'", status);
+        };
+        let rows = self.conn.execute(
+            &format!(
+                "UPDATE issues SET status = ?1, closed_at = {}, updated_at = datetime('now') WHERE id = ?2",
+                closed_at
+            ),
+            params![status, id],
+        )?;
+        Ok(rows > 0)
+    }
+
+    /// Add a comment to an issue
+    pub fn issue_comment(&self, id: i64, content: &str) -> Result<i64> {
+        self.require_issue(id)?;
+        self.conn.execute(
+            "INSERT INTO issue_comments (issue_id, content) VALUES (?1, ?2)",
+            params![id, content],
+        )?;
+        let comment_id = self.conn.last_insert_rowid();
+        self.touch_issue(id)?;
+        Ok(comment_id)
+    }
+
+    /// Get all comments on an issue, oldest first
+    pub fn issue_comments(&self, id: i64) -> Result<Vec<IssueComment>> {
+        let mut stmt = self.conn.prepare(
+            r#"SELECT id, issue_id, content, created_at FROM issue_comments
+               WHERE issue_id = ?1 ORDER BY id"#,
+        )?;
+
+        let comments = stmt
+            .query_map(params![id], |row| {
+                Ok(IssueComment {
+                    id: row.get(0)?,
+                    issue_id: row.get(1)?,
+                    content: row.get(2)?,
+                    created_at: row.get(3)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(comments)
+    }
+
+    /// Add a label to an issue. Returns false if it was already present.
+    pub fn issue_add_label(&self, id: i64, label: &str) -> Result<bool> {
+        self.require_issue(id)?;
+        let rows = self.conn.execute(
+            "INSERT OR IGNORE INTO issue_labels (issue_id, label) VALUES (?1, ?2)",
+            params![id, label],
+        )?;
+        self.touch_issue(id)?;
+        Ok(rows > 0)
+    }
+
+    /// Remove a label from an issue. Returns false if it wasn't present.
+    pub fn issue_remove_label(&self, id: i64, label: &str) -> Result<bool> {
+        let rows = self.conn.execute(
+            "DELETE FROM issue_labels WHERE issue_id = ?1 AND label = ?2",
+            params![id, label],
+        )?;
+        self.touch_issue(id)?;
+        Ok(rows > 0)
+    }
+
+    /// Record that `id` is blocked by `blocked_by`. Rejects self-references and cycles.
+    pub fn issue_add_dependency(&self, id: i64, blocked_by: i64) -> Result<bool> {
+        self.require_issue(id)?;
+        self.require_issue(blocked_by)?;
+        if id == blocked_by {
+            anyhow::bail!("Issue #{} cannot block itself", id);
+        }
+        if self.issue_depends_on(blocked_by, id)? {
+            anyhow::bail!(
+                "Issue #{} already depends on #{}; adding this would create a cycle",
+                blocked_by,
+                id
+            );
+        }
+
+        let rows = self.conn.execute(
+            "INSERT OR IGNORE INTO issue_dependencies (issue_id, blocked_by) VALUES (?1, ?2)",
+            params![id, blocked_by],
+        )?;
+        self.touch_issue(id)?;
+        Ok(rows > 0)
+    }
+
+    /// Remove a dependency. Returns false if it didn't exist.
+    pub fn issue_remove_dependency(&self, id: i64, blocked_by: i64) -> Result<bool> {
+        let rows = self.conn.execute(
+            "DELETE FROM issue_dependencies WHERE issue_id = ?1 AND blocked_by = ?2",
+            params![id, blocked_by],
+        )?;
+        self.touch_issue(id)?;
+        Ok(rows > 0)
+    }
+
+    /// Whether `id` transitively depends on `target`
+    fn issue_depends_on(&self, id: i64, target: i64) -> Result<bool> {
+        let found: Option<i64> = self
+            .conn
+            .query_row(
+                r#"WITH RECURSIVE deps(id) AS (
+                       SELECT blocked_by FROM issue_dependencies WHERE issue_id = ?1
+                       UNION
+                       SELECT d.blocked_by FROM issue_dependencies d JOIN deps ON d.issue_id = deps.id
+                   )
+                   SELECT id FROM deps WHERE id = ?2"#,
+                params![id, target],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(found.is_some())
+    }
+
+    fn require_issue(&self, id: i64) -> Result<()> {
+        let exists: bool = self.conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM issues WHERE id = ?1)",
+            params![id],
+            |row| row.get(0),
+        )?;
+        if !exists {
+            anyhow::bail!("Issue #{} not found", id);
+        }
+        Ok(())
+    }
+
+    fn touch_issue(&self, id: i64) -> Result<()> {
+        self.conn.execute(
+            "UPDATE issues SET updated_at = datetime('now') WHERE id = ?1",
+            params![id],
+        )?;
+        Ok(())
+    }
+
+    fn issue_from_row(row: &rusqlite::Row) -> rusqlite::Result<Issue> {
+        Ok(Issue {
+            id: row.get(0)?,
+            title: row.get(1)?,
+            description: row.get::<_, Option<String>>(2)?.unwrap_or_default(),
+            status: row.get(3)?,
+            priority: row.get(4)?,
+            parent_id: row.get(5)?,
+            labels: Vec::new(),
+            blocked_by: Vec::new(),
+            created_at: row.get(6)?,
+            updated_at: row.get(7)?,
+            closed_at: row.get(8)?,
+        })
+    }
+
+    fn load_issue_relations(&self, issue: &mut Issue) -> Result<()> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT label FROM issue_labels WHERE issue_id = ?1 ORDER BY label")?;
+        issue.labels = stmt
+            .query_map(params![issue.id], |row| row.get(0))?
+            .collect::<Result<Vec<String>, _>>()?;
+
+        let mut stmt = self.conn.prepare(
+            "SELECT blocked_by FROM issue_dependencies WHERE issue_id = ?1 ORDER BY blocked_by",
+        )?;
+        issue.blocked_by = stmt
+            .query_map(params![issue.id], |row| row.get(0))?
+            .collect::<Result<Vec<i64>, _>>()?;
+
+        Ok(())
+    }
+
+    // === Token Usage Operations ===
+
+    /// Record the usage of one model response. `cost_usd` is None for unpriced models.
+    pub fn usage_record(
+        &self,
+        session_id: &str,
+        provider: &str,
+        model: &str,
+        usage: &TokenUsage,
+        cost_usd: Option<f64>,
+    ) -> Result<i64> {
+        self.conn.execute(
+            r#"INSERT INTO token_usage (session_id, provider, model, input_tokens, output_tokens,
+                   cache_read_tokens, cache_write_tokens, reasoning_tokens, cost_usd)
+               VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)"#,
+            params![
+                session_id,
+                provider,
+                model,
+                usage.input_tokens as i64,
+                usage.output_tokens as i64,
+                usage.cache_read_tokens as i64,
+                usage.cache_write_tokens as i64,
+                usage.reasoning_tokens as i64,
+                cost_usd
+            ],
+        )?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    /// Totals for one session
+    pub fn usage_for_session(&self, session_id: &str) -> Result<UsageSummary> {
+        let mut totals = self.usage_grouped("session_id", "session_id = ?1", session_id)?;
+        Ok(totals.pop().unwrap_or_else(|| UsageSummary {
+            key: session_id.to_string(),
+            ..Default::default()
+        }))
+    }
+
+    /// Totals over everything recorded at or after `since`
+    pub fn usage_since(&self, since: chrono::DateTime<chrono::Utc>) -> Result<UsageSummary> {
+        let since = since.format("%Y-%m-%d %H:%M:%S").to_string();
+        let mut totals = self.usage_grouped("'total'", "created_at >= ?1", &since)?;
+        Ok(totals.pop().unwrap_or_else(|| UsageSummary {
+            key: "total".to_string(),
+            ..Default::default()
+        }))
+    }
+
+    /// Totals per model over the last `days` days, most expensive first
+    pub fn usage_by_model(&self, days: u32) -> Result<Vec<UsageSummary>> {
+        let mut totals = self.usage_grouped("model", SINCE_DAYS, &format!("-{} days", days))?;
+        totals.sort_by(|a, b| b.cost_usd.total_cmp(&a.cost_usd));
+        Ok(totals)
+    }
+
+    /// Totals per UTC day over the last `days` days, oldest first
+    pub fn usage_by_day(&self, days: u32) -> Result<Vec<UsageSummary>> {
+        self.usage_grouped("date(created_at)", SINCE_DAYS, &format!("-{} days", days))
+    }
+
+    fn usage_grouped(&self, key: &str, filter: &str, arg: &str) -> Result<Vec<UsageSummary>> {
+        let mut stmt = self.conn.prepare(&format!(
+            r#"SELECT {key}, COUNT(*), SUM(input_tokens), SUM(output_tokens),
+                   SUM(cache_read_tokens), SUM(cache_write_tokens), SUM(reasoning_tokens),
+                   COALESCE(SUM(cost_usd), 0), SUM(cost_usd IS NULL)
+               FROM token_usage WHERE {filter} GROUP BY {key} ORDER BY {key}"#
+        ))?;
+        let rows = stmt
+            .query_map(params![arg], |row| {
+                let count = |i| row.get::<_, i64>(i).map(|n| n as u64);
+                Ok(UsageSummary {
+                    key: row.get(0)?,
+                    turns: count(1)?,
+                    usage: TokenUsage {
+                        input_tokens: count(2)?,
+                        output_tokens: count(3)?,
+                        cache_read_tokens: count(4)?,
+                        cache_write_tokens: count(5)?,
+                        reasoning_tokens: count(6)?,
+                    },
+                    cost_usd: row.get(7)?,
+                    unpriced_turns: count(8)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+
     /// Reset everything including core memory and short-term memory
     pub fn reset_all(&self) -> Result<()> {
         self.conn.execute_batch(
@@ -669,6 +1127,22 @@ impl MemoryDb {
     }
 }
 
+/// Filter for usage rows newer than a `datetime('now', ?)` offset
+const SINCE_DAYS: &str = "created_at >= datetime('now', ?1)";
+
+/// Aggregated token usage for a session, model or day
+#[derive(Debug, Clone, Default)]
+pub struct UsageSummary {
+    /// Session ID, model name or `YYYY-MM-DD`
+    pub key: String,
+    pub turns: u64,
+    pub usage: TokenUsage,
+    /// Cost of the priced turns
+    pub cost_usd: f64,
+    /// Turns for models without a known price
+    pub unpriced_turns: u64,
+}
+
 /// Memory statistics
 #[derive(Debug, Clone)]
 pub struct MemoryStats {
@@ -848,4 +1322,121 @@ mod tests {
         assert!(formatted.contains("#50"));
         assert!(formatted.contains("</recent_sessions>"));
     }
+
+    #[test]
+    fn test_issue_lifecycle() {
+        let dir = tempdir().unwrap();
+        let db = MemoryDb::open(&dir.path().join("test.db")).unwrap();
+
+        let id = db
+            .issue_create("Fix login bug", "Users can't log in", "high", None)
+            .unwrap();
+        let sub = db.issue_create("Add test", "", "low", Some(id)).unwrap();
+        assert!(db.issue_create("Bad", "", "urgent", None).is_err());
+        assert!(db.issue_create("Orphan", "", "low", Some(999)).is_err());
+
+        assert!(db.issue_add_label(id, "bug").unwrap());
+        assert!(!db.issue_add_label(id, "bug").unwrap());
+        db.issue_comment(id, "Reproduced on staging").unwrap();
+
+        let issue = db.issue_get(id).unwrap().unwrap();
+        assert_eq!(issue.title, "Fix login bug");
+        assert_eq!(issue.priority, "high");
+        assert_eq!(issue.labels, vec!["bug"]);
+        assert!(issue.is_open());
+        assert_eq!(db.issue_get(sub).unwrap().unwrap().parent_id, Some(id));
+        assert_eq!(
+            db.issue_comments(id).unwrap()[0].content,
+            "Reproduced on staging"
+        );
+
+        // Higher priority sorts first; label filter narrows the list
+        let open = db.issue_list(Some("open"), None).unwrap();
+        assert_eq!(open.iter().map(|i| i.id).collect::<Vec<_>>(), vec![id, sub]);
+        assert_eq!(db.issue_list(None, Some("bug")).unwrap().len(), 1);
+
+        assert!(db.issue_set_status(id, "closed").unwrap());
+        let closed = db.issue_get(id).unwrap().unwrap();
+        assert!(!closed.is_open());
+        assert!(closed.closed_at.is_some());
+        assert_eq!(db.issue_list(Some("open"), None).unwrap().len(), 1);
+
+        assert!(db.issue_set_status(id, "open").unwrap());
+        assert!(db.issue_get(id).unwrap().unwrap().closed_at.is_none());
+        assert!(!db.issue_set_status(999, "closed").unwrap());
+        assert!(db.issue_set_status(id, "done").is_err());
+
+        assert!(db.issue_remove_label(id, "bug").unwrap());
+        assert!(db.issue_get(id).unwrap().unwrap().labels.is_empty());
+    }
+
+    #[test]
+    fn test_issue_dependencies() {
+        let dir = tempdir().unwrap();
+        let db = MemoryDb::open(&dir.path().join("test.db")).unwrap();
+
+        let a = db.issue_create("A", "", "medium", None).unwrap();
+        let b = db.issue_create("B", "", "medium", None).unwrap();
+        let c = db.issue_create("C", "", "medium", None).unwrap();
+
+        assert!(db.issue_add_dependency(a, b).unwrap());
+        assert!(db.issue_add_dependency(b, c).unwrap());
+        assert_eq!(db.issue_get(a).unwrap().unwrap().blocked_by, vec![b]);
+
+        // Self-references and cycles are rejected
+        assert!(db.issue_add_dependency(a, a).is_err());
+        assert!(db.issue_add_dependency(c, a).is_err());
+        assert!(db.issue_add_dependency(a, 999).is_err());
+
+        assert!(db.issue_remove_dependency(b, c).unwrap());
+        assert!(db.issue_add_dependency(c, a).unwrap());
+    }
+
+    #[test]
+    fn test_issue_activity_counts_as_session_issue() {
+        let dir = tempdir().unwrap();
+        let db = MemoryDb::open(&dir.path().join("test.db")).unwrap();
+
+        db.log_activity("session-1", "issue_updated", "#7", None)
+            .unwrap();
+        assert_eq!(db.get_session_issues("session-1").unwrap(), vec!["#7"]);
+    }
+
+    #[test]
+    fn test_usage_totals() {
+        let dir = tempdir().unwrap();
+        let db = MemoryDb::open(&dir.path().join("test.db")).unwrap();
+
+        let usage = TokenUsage {
+            input_tokens: 100,
+            output_tokens: 20,
+            cache_read_tokens: 1000,
+            ..Default::default()
+        };
+        db.usage_record("s1", "anthropic", "claude-sonnet-4", &usage, Some(0.01))
+            .unwrap();
+        db.usage_record("s1", "anthropic", "claude-sonnet-4", &usage, Some(0.02))
+            .unwrap();
+        db.usage_record("s2", "ollama", "qwen3:8b", &usage, None)
+            .unwrap();
+
+        let session = db.usage_for_session("s1").unwrap();
+        assert_eq!(session.turns, 2);
+        assert_eq!(session.usage.cache_read_tokens, 2000);
+        assert!((session.cost_usd - 0.03).abs() < 1e-9);
+        assert_eq!(db.usage_for_session("missing").unwrap().turns, 0);
+
+        let by_model = db.usage_by_model(30).unwrap();
+        assert_eq!(by_model[0].key, "claude-sonnet-4");
+        assert_eq!(by_model[1].unpriced_turns, 1);
+
+        let by_day = db.usage_by_day(30).unwrap();
+        assert_eq!(by_day.len(), 1);
+        assert_eq!(by_day[0].turns, 3);
+
+        let hour_ago = chrono::Utc::now() - chrono::Duration::hours(1);
+        assert_eq!(db.usage_since(hour_ago).unwrap().turns, 3);
+        let later = chrono::Utc::now() + chrono::Duration::hours(1);
+        assert_eq!(db.usage_since(later).unwrap().turns, 0);
+    }
 }
@@ -0,0 +1,560 @@
+//! Model Registry - What each model can do and what it costs.
+//!
+//! The registry is built from three layers, later ones winning:
+//! 1. The bundled catalog (`assets/models.json`)
+//! 2. Models discovered from each provider's models endpoint by
+//!    `openclaudia models --refresh`, cached in `~/.openclaudia/models-cache.json`
+//! 3. `models:` overrides in config.yaml
+//!
+//! Lookups match the longest catalog id that prefixes the model name, so dated
+//! snapshots like `claude-sonnet-4-5-20250929` resolve to their base entry.
+//! Families (`claude`, `gpt`, ...) catch unknown models of a known vendor but
+//! are never listed.
+
+use crate::config::{AppConfig, ModelOverride, ModelPrice, ProviderConfig};
+use crate::providers::{adapter_for, ProviderAdapter};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{LazyLock, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+/// Bundled catalog
+const CATALOG: &str = include_str!("../assets/models.json");
+
+/// Context window assumed for models the registry does not know
+pub const DEFAULT_CONTEXT_WINDOW: usize = 128_000;
+
+/// Output limit assumed for models the registry does not know
+pub const DEFAULT_MAX_OUTPUT: usize = 4_096;
+
+/// Timeout for a provider's models endpoint
+const REFRESH_TIMEOUT: Duration = Duration::from_secs(15);
+
+static REGISTRY: LazyLock<Mutex<Registry>> = LazyLock::new(|| Mutex::new(Registry::load()));
+
+/// Capabilities and pricing of one model (or model family)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ModelInfo {
+    pub id: String,
+    pub provider: String,
+    /// Input context window in tokens
+    pub context_window: usize,
+    /// Maximum output tokens per response
+    pub max_output: usize,
+    /// Native function calling; None when the catalog does not say
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tools: Option<bool>,
+    #[serde(default)]
+    pub vision: bool,
+    /// Extended thinking / reasoning support
+    #[serde(default)]
+    pub thinking: bool,
+    /// Text-protocol tool calling, overriding the default of emulating tools
+    /// only for models listed without native support
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub emulate_tools: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pricing: Option<ModelPrice>,
+}
+
+impl ModelInfo {
+    /// An entry with default limits and no known capabilities
+    fn unknown(id: &str, provider: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            provider: provider.to_string(),
+            context_window: DEFAULT_CONTEXT_WINDOW,
+            max_output: DEFAULT_MAX_OUTPUT,
+            tools: None,
+            vision: false,
+            thinking: false,
+            emulate_tools: None,
+            pricing: None,
+        }
+    }
+
+    /// Whether tools are sent as a text protocol rather than natively
+    pub fn emulates_tools(&self) -> bool {
+        self.emulate_tools.unwrap_or(self.tools == Some(false))
+    }
+
+    /// Names of the supported optional features
+    pub fn capabilities(&self) -> Vec<&'static str> {
+        [
+            (self.tools == Some(true), "tools"),
+            (self.vision, "vision"),
+            (self.thinking, "thinking"),
+        ]
+        .into_iter()
+        .filter_map(|(supported, name)| supported.then_some(name))
+        .collect()
+    }
+
+    fn apply(&mut self, o: &ModelOverride) {
+        if let Some(provider) = &o.provider {
+            self.provider = provider.clone();
+        }
+        self.context_window = o.context_window.unwrap_or(self.context_window);
+        self.max_output = o.max_output.unwrap_or(self.max_output);
+        self.tools = o.tools.or(self.tools);
+        self.vision = o.vision.unwrap_or(self.vision);
+        self.thinking = o.thinking.unwrap_or(self.thinking);
+        if o.emulate_tools.is_some() {
+            self.emulate_tools = o.emulate_tools;
+        }
+        if o.pricing.is_some() {
+            self.pricing = o.pricing;
+        }
+    }
+}
+
+/// A model reported by a provider's models endpoint
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListedModel {
+    pub id: String,
+    pub context_window: Option<usize>,
+    pub max_output: Option<usize>,
+}
+
+impl ListedModel {
+    pub fn new(id: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            context_window: None,
+            max_output: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct Catalog {
+    models: Vec<ModelInfo>,
+    #[serde(default)]
+    families: Vec<ModelInfo>,
+}
+
+/// Longest entry id that prefixes `model` (case-insensitive)
+fn longest_match<'a>(
+    model: &str,
+    entries: impl IntoIterator<Item = &'a ModelInfo>,
+) -> Option<&'a ModelInfo> {
+    let model = model.to_lowercase();
+    entries
+        .into_iter()
+        .filter(|m| model.starts_with(&m.id.to_lowercase()))
+        .max_by_key(|m| m.id.len())
+}
+
+/// The layered model catalog
+#[derive(Debug, Clone)]
+pub struct Registry {
+    builtin: Catalog,
+    discovered: Vec<ModelInfo>,
+    overrides: HashMap<String, ModelOverride>,
+    /// Provider for overridden models that match no known entry
+    default_provider: String,
+    /// Merged listable models
+    models: Vec<ModelInfo>,
+}
+
+impl Registry {
+    pub fn new(
+        discovered: Vec<ModelInfo>,
+        overrides: HashMap<String, ModelOverride>,
+        default_provider: &str,
+    ) -> Self {
+        let builtin: Catalog =
+            serde_json::from_str(CATALOG).expect("bundled model catalog is valid JSON");
+        let mut registry = Self {
+            builtin,
+            discovered,
+            overrides,
+            default_provider: default_provider.to_string(),
+            models: Vec::new(),
+        };
+        registry.merge();
+        registry
+    }
+
+    /// Build from the discovery cache and config.yaml
+    fn load() -> Self {
+        let discovered = cache_path()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+        match crate::config::load_config() {
+            Ok(config) => Self::new(discovered, config.models, &config.proxy.target),
+            Err(e) => {
+                tracing::debug!("Using model catalog without config overrides: {}", e);
+                Self::new(discovered, HashMap::new(), "anthropic")
+            }
+        }
+    }
+
+    /// Best built-in entry for a model, trying the part after the last `/`
+    /// for router-style names like `anthropic/claude-sonnet-4`
+    fn builtin_match(&self, model: &str) -> Option<&ModelInfo> {
+        let bare = model.rsplit('/').next().unwrap_or(model);
+        [model, bare].into_iter().find_map(|name| {
+            longest_match(
+                name,
+                self.builtin.models.iter().chain(&self.builtin.families),
+            )
+        })
+    }
+
+    fn upsert(&mut self, info: ModelInfo) {
+        match self
+            .models
+            .iter_mut()
+            .find(|m| m.id.eq_ignore_ascii_case(&info.id))
+        {
+            Some(existing) => *existing = info,
+            None => self.models.push(info),
+        }
+    }
+
+    /// Recompute the merged list from the three layers
+    fn merge(&mut self) {
+        self.models = self.builtin.models.clone();
+        for info in self.discovered.clone() {
+            self.upsert(info);
+        }
+        let mut overrides: Vec<_> = self.overrides.clone().into_iter().collect();
+        overrides.sort_by(|a, b| a.0.cmp(&b.0));
+        for (id, o) in overrides {
+            let mut info = match self.models.iter().find(|m| m.id.eq_ignore_ascii_case(&id)) {
+                Some(existing) => existing.clone(),
+                None => self.inherit(&id, &self.default_provider),
+            };
+            info.apply(&o);
+            self.upsert(info);
+        }
+    }
+
+    /// A new entry for `id` based on its closest built-in match
+    fn inherit(&self, id: &str, provider: &str) -> ModelInfo {
+        let mut info = self
+            .builtin_match(id)
+            .cloned()
+            .unwrap_or_else(|| ModelInfo::unknown(id, provider));
+        info.id = id.to_string();
+        info
+    }
+
+    /// Replace the models discovered for `provider`
+    pub fn set_discovered(&mut self, provider: &str, listed: Vec<ListedModel>) {
+        self.discovered.retain(|m| m.provider != provider);
+        for model in listed {
+            let mut info = self.inherit(&model.id, provider);
+            info.provider = provider.to_string();
+            info.context_window = model.context_window.unwrap_or(info.context_window);
+            info.max_output = model.max_output.unwrap_or(info.max_output);
+            self.discovered.push(info);
+        }
+        self.merge();
+    }
+
+    /// Everything known about a model, None when no entry or family matches
+    pub fn lookup(&self, model: &str) -> Option<ModelInfo> {
+        let bare = model.rsplit('/').next().unwrap_or(model);
+        [model, bare]
+            .into_iter()
+            .find_map(|name| longest_match(name, self.models.iter().chain(&self.builtin.families)))
+            .cloned()
+    }
+
+    /// Listable models, optionally only those served by `provider`
+    pub fn list(&self, provider: Option<&str>) -> Vec<ModelInfo> {
+        self.models
+            .iter()
+            .filter(|m| provider.is_none_or(|p| m.provider == p))
+            .cloned()
+            .collect()
+    }
+}
+
+fn registry() -> MutexGuard<'static, Registry> {
+    REGISTRY.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Where discovered models are cached between runs
+fn cache_path() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join(".openclaudia/models-cache.json"))
+}
+
+/// Re-apply the `models:` overrides of a (re)loaded config, keeping the
+/// discovered models
+pub fn apply_config(config: &AppConfig) {
+    let mut registry = registry();
+    let discovered = std::mem::take(&mut registry.discovered);
+    *registry = Registry::new(discovered, config.models.clone(), &config.proxy.target);
+}
+
+/// Everything known about a model
+pub fn lookup(model: &str) -> Option<ModelInfo> {
+    registry().lookup(model)
+}
+
+/// Context window of a model, or [`DEFAULT_CONTEXT_WINDOW`] when unknown
+pub fn context_window(model: &str) -> usize {
+    lookup(model).map_or(DEFAULT_CONTEXT_WINDOW, |m| m.context_window)
+}
+
+/// Whether a model gets emulated tool calling. Models the registry does not
+/// know are assumed to support tools natively.
+pub fn emulates_tools(model: &str) -> bool {
+    lookup(model).is_some_and(|m| m.emulates_tools())
+}
+
+/// Whether a model accepts images and documents. Models the registry does
+/// not know are assumed not to.
+pub fn supports_vision(model: &str) -> bool {
+    lookup(model).is_some_and(|m| m.vision)
+}
+
+/// Provider that serves a model
+pub fn provider_for(model: &str) -> Option<String> {
+    lookup(model).map(|m| m.provider)
+}
+
+/// Listable models, optionally only those served by `provider`
+pub fn list(provider: Option<&str>) -> Vec<ModelInfo> {
+    registry().list(provider)
+}
+
+/// Fetch the model list of one provider
+async fn fetch_models(
+    client: &reqwest::Client,
+    provider: &ProviderConfig,
+    adapter: &dyn ProviderAdapter,
+    api_key: &str,
+) -> Result<Vec<ListedModel>, String> {
+    let url = format!(
+        "{}{}",
+        provider.base_url.trim_end_matches('/'),
+        adapter.models_path()
+    );
+    let mut request = client.get(&url).timeout(REFRESH_TIMEOUT);
+    for (name, value) in adapter
+        .get_headers(api_key)
+        .into_iter()
+        .chain(provider.headers.iter().map(|(k, v)| (k.clone(), v.clone())))
+    {
+        request = request.header(name, value);
+    }
+    let request = request
+        .build()
+        .map_err(|e| format!("request failed: {}", e))?;
+    let request = adapter
+        .authorize(client, request)
+        .await
+        .map_err(|e| e.to_string())?;
+    let response = client
+        .execute(request)
+        .await
+        .map_err(|e| format!("request failed: {}", e))?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(format!("HTTP {} from {}", status, url));
+    }
+    let body: Value = response
+        .json()
+        .await
+        .map_err(|e| format!("invalid response: {}", e))?;
+    Ok(adapter.parse_models(&body))
+}
+
+/// Refresh the registry from the models endpoints of the configured providers
+/// (or just `only`) and update the discovery cache. Providers without an API
+/// key are skipped, except Ollama which needs none. Returns the number of
+/// models found per provider.
+pub async fn refresh(
+    client: &reqwest::Client,
+    config: &AppConfig,
+    only: Option<&str>,
+) -> Vec<(String, Result<usize, String>)> {
+    let mut names: Vec<&String> = config
+        .providers
+        .keys()
+        .filter(|name| only.is_none_or(|o| o == name.as_str()))
+        .collect();
+    names.sort();
+
+    let mut results = Vec::new();
+    for name in names {
+        let provider = &config.providers[name];
+        let adapter = adapter_for(name, provider);
+        let api_key = provider.api_key.clone().unwrap_or_default();
+        if api_key.is_empty() && adapter.requires_api_key() {
+            results.push((name.clone(), Err("skipped: no API key".to_string())));
+            continue;
+        }
+        let result = fetch_models(client, provider, adapter.as_ref(), &api_key).await;
+        let count = result.map(|listed| {
+            let count = listed.len();
+            registry().set_discovered(name, listed);
+            count
+        });
+        results.push((name.clone(), count));
+    }
+
+    if let Some(path) = cache_path() {
+        let discovered = registry().discovered.clone();
+        let saved = serde_json::to_string_pretty(&discovered)
+            .map_err(|e| e.to_string())
+            .and_then(|json| {
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+                }
+                std::fs::write(&path, json).map_err(|e| e.to_string())
+            });
+        if let Err(e) = saved {
+            tracing::warn!("Failed to write model cache {}: {}", path.display(), e);
+        }
+    }
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registry() -> Registry {
+        Registry::new(Vec::new(), HashMap::new(), "anthropic")
+    }
+
+    #[test]
+    fn test_lookup_longest_prefix() {
+        let registry = registry();
+        let sonnet = registry.lookup("claude-sonnet-4-5-20250929").unwrap();
+        assert_eq!(sonnet.id, "claude-sonnet-4-5");
+        assert_eq!(sonnet.max_output, 64_000);
+        assert!(sonnet.thinking && sonnet.vision && sonnet.tools == Some(true));
+
+        assert_eq!(registry.lookup("gpt-4o-mini").unwrap().id, "gpt-4o-mini");
+        assert_eq!(registry.lookup("GPT-4O-2024-05-13").unwrap().id, "gpt-4o");
+        assert_eq!(
+            registry
+                .lookup("anthropic/claude-opus-4-5")
+                .unwrap()
+                .provider,
+            "anthropic"
+        );
+
+        // Families catch unknown models but are not listed
+        let family = registry.lookup("claude-next").unwrap();
+        assert_eq!(family.id, "claude");
+        assert!(registry.list(None).iter().all(|m| m.id != "claude"));
+        assert!(registry.lookup("mystery-model").is_none());
+    }
+
+    #[test]
+    fn test_list_by_provider() {
+        let registry = registry();
+        let anthropic = registry.list(Some("anthropic"));
+        assert!(anthropic.iter().any(|m| m.id == "claude-sonnet-4-5"));
+        assert!(anthropic.iter().all(|m| m.provider == "anthropic"));
+        assert!(registry.list(Some("ollama")).is_empty());
+    }
+
+    #[test]
+    fn test_discovered_models() {
+        let mut registry = registry();
+        registry.set_discovered(
+            "ollama",
+            vec![ListedModel::new("qwen3:8b"), ListedModel::new("llama3.2")],
+        );
+        registry.set_discovered(
+            "google",
+            vec![ListedModel {
+                id: "gemini-3-pro-preview".to_string(),
+                context_window: Some(2_000_000),
+                max_output: None,
+            }],
+        );
+
+        // Discovered models route to the provider that listed them
+        let qwen = registry.lookup("qwen3:8b").unwrap();
+        assert_eq!(qwen.provider, "ollama");
+        assert_eq!(qwen.context_window, 131_072);
+        assert_eq!(
+            registry.lookup("llama3.2:latest").unwrap().context_window,
+            DEFAULT_CONTEXT_WINDOW
+        );
+        assert_eq!(registry.list(Some("ollama")).len(), 2);
+        // Models no catalog family describes keep native tools
+        let llama = registry.lookup("llama3.2").unwrap();
+        assert_eq!(llama.tools, None);
+        assert!(!llama.emulates_tools());
+        assert!(!qwen.emulates_tools());
+
+        let gemini = registry.lookup("gemini-3-pro-preview").unwrap();
+        assert_eq!(gemini.context_window, 2_000_000);
+        assert_eq!(gemini.max_output, 8192);
+
+        // A refresh replaces the provider's previous list
+        registry.set_discovered("ollama", vec![ListedModel::new("llama3.2")]);
+        assert_eq!(registry.list(Some("ollama")).len(), 1);
+        assert_eq!(registry.lookup("qwen3:8b").unwrap().provider, "qwen");
+    }
+
+    #[test]
+    fn test_config_overrides() {
+        let mut overrides = HashMap::new();
+        overrides.insert(
+            "claude-sonnet-4-5".to_string(),
+            ModelOverride {
+                context_window: Some(1_000_000),
+                ..Default::default()
+            },
+        );
+        overrides.insert(
+            "my-finetune".to_string(),
+            ModelOverride {
+                provider: Some("openai".to_string()),
+                tools: Some(true),
+                ..Default::default()
+            },
+        );
+        let registry = Registry::new(Vec::new(), overrides, "anthropic");
+
+        let sonnet = registry.lookup("claude-sonnet-4-5").unwrap();
+        assert_eq!(sonnet.context_window, 1_000_000);
+        assert_eq!(sonnet.max_output, 64_000);
+
+        let custom = registry.lookup("my-finetune-v2").unwrap();
+        assert_eq!(custom.provider, "openai");
+        assert_eq!(custom.tools, Some(true));
+        assert_eq!(custom.context_window, DEFAULT_CONTEXT_WINDOW);
+        assert!(!custom.emulates_tools());
+    }
+
+    #[test]
+    fn test_emulate_tools_override() {
+        let overrides = HashMap::from([
+            (
+                "gpt-4o".to_string(),
+                ModelOverride {
+                    emulate_tools: Some(true),
+                    ..Default::default()
+                },
+            ),
+            (
+                "o1-mini".to_string(),
+                ModelOverride {
+                    emulate_tools: Some(false),
+                    ..Default::default()
+                },
+            ),
+        ]);
+        let registry = Registry::new(Vec::new(), overrides, "anthropic");
+
+        assert!(registry.lookup("gpt-4o").unwrap().emulates_tools());
+        // The catalog lists o1-mini without tools, so it is emulated unless disabled
+        assert!(registry.lookup("o1-preview").unwrap().emulates_tools());
+        assert!(!registry.lookup("o1-mini").unwrap().emulates_tools());
+    }
+}
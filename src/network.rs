@@ -0,0 +1,530 @@
+//! Outbound network policy for web tools.
+//!
+//! Guards `web_fetch`, `web_browser` and friends against SSRF: a prompt-injected
+//! page must not be able to make the agent read cloud metadata endpoints or
+//! internal dashboards. Every request is checked before it is sent:
+//! - Scheme must be http or https
+//! - Host must pass the configured allow/deny domain lists
+//! - Every resolved address must be public (private, loopback and link-local
+//!   ranges are blocked unless `allow_private_networks` is set)
+//!
+//! Redirects are followed manually so each hop is re-checked, and the checked
+//! addresses are pinned in the HTTP client to defeat DNS rebinding.
+
+use crate::config::NetworkPolicyConfig;
+use reqwest::{Client, Url};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, ToSocketAddrs};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Requests blocked by the policy, drained by the chat loop into the activity log
+static BLOCKED_REQUESTS: std::sync::LazyLock<Mutex<Vec<BlockedRequest>>> =
+    std::sync::LazyLock::new(|| Mutex::new(Vec::new()));
+
+/// A request that was refused by the network policy
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockedRequest {
+    pub url: String,
+    pub reason: String,
+}
+
+/// Take all blocked requests recorded since the last call
+pub fn take_blocked_requests() -> Vec<BlockedRequest> {
+    BLOCKED_REQUESTS
+        .lock()
+        .map(|mut blocked| std::mem::take(&mut *blocked))
+        .unwrap_or_default()
+}
+
+fn record_blocked(url: &str, reason: &str) {
+    tracing::warn!("Network policy blocked {}: {}", url, reason);
+    if let Ok(mut blocked) = BLOCKED_REQUESTS.lock() {
+        blocked.push(BlockedRequest {
+            url: url.to_string(),
+            reason: reason.to_string(),
+        });
+    }
+}
+
+/// Check whether a host equals `domain` or is one of its subdomains
+pub fn domain_matches(host: &str, domain: &str) -> bool {
+    let host = host.trim_end_matches('.').to_lowercase();
+    let domain = domain.trim().trim_start_matches("*.").to_lowercase();
+    if domain.is_empty() {
+        return false;
+    }
+    host == domain || host.ends_with(&format!(".{}", domain))
+}
+
+/// Check whether an address is outside the public internet
+///
+/// Covers loopback, RFC 1918 private, link-local (incl. cloud metadata at
+/// 169.254.169.254), CGNAT, unspecified, broadcast, multicast, documentation
+/// and benchmarking ranges, plus their IPv6 equivalents. IPv4 addresses
+/// embedded in IPv6 (mapped, NAT64 64:ff9b::/96 and 6to4 2002::/16) are
+/// checked as IPv4.
+pub fn is_non_public_ip(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => is_non_public_ipv4(v4),
+        IpAddr::V6(v6) => {
+            let segments = v6.segments();
+            let embedded = |high: u16, low: u16| {
+                Ipv4Addr::new((high >> 8) as u8, high as u8, (low >> 8) as u8, low as u8)
+            };
+            if let Some(v4) = v6.to_ipv4_mapped() {
+                return is_non_public_ipv4(&v4);
+            }
+            if segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0] {
+                return is_non_public_ipv4(&embedded(segments[6], segments[7]));
+            }
+            // IPv4-compatible ::a.b.c.d, apart from :: and ::1
+            if segments[..6] == [0; 6] && !v6.is_unspecified() && !v6.is_loopback() {
+                return is_non_public_ipv4(&embedded(segments[6], segments[7]));
+            }
+            if segments[0] == 0x2002 {
+                return is_non_public_ipv4(&embedded(segments[1], segments[2]));
+            }
+            let first = segments[0];
+            v6.is_loopback()
+                || v6.is_unspecified()
+                || v6.is_multicast()
+                // Unique local fc00::/7
+                || (first & 0xfe00) == 0xfc00
+                // Link-local fe80::/10
+                || (first & 0xffc0) == 0xfe80
+                // Documentation 2001:db8::/32
+                || (first == 0x2001 && segments[1] == 0x0db8)
+        }
+    }
+}
+
+fn is_non_public_ipv4(ip: &Ipv4Addr) -> bool {
+    let [a, b, _, _] = ip.octets();
+    ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || ip.is_documentation()
+        // "This network" 0.0.0.0/8
+        || a == 0
+        // Shared address space (CGNAT) 100.64.0.0/10
+        || (a == 100 && (b & 0xc0) == 64)
+        // Benchmarking 198.18.0.0/15
+        || (a == 198 && (b & 0xfe) == 18)
+        // Reserved 240.0.0.0/4
+        || a >= 240
+}
+
+/// A URL that passed the policy, with the addresses it resolved to
+#[derive(Debug, Clone)]
+pub struct CheckedUrl {
+    pub url: Url,
+    pub addrs: Vec<SocketAddr>,
+}
+
+/// Response body read under the policy's size limit
+#[derive(Debug, Clone)]
+pub struct PolicyResponse {
+    /// URL after following redirects
+    pub url: String,
+    pub status: u16,
+    pub content_type: Option<String>,
+    pub body: String,
+}
+
+/// Network policy applied to all outbound web tool requests
+#[derive(Debug, Clone)]
+pub struct NetworkPolicy {
+    pub allow_private_networks: bool,
+    pub allowed_domains: Vec<String>,
+    pub blocked_domains: Vec<String>,
+    pub max_response_bytes: u64,
+    pub max_redirects: usize,
+}
+
+impl Default for NetworkPolicy {
+    fn default() -> Self {
+        Self::from_config(&NetworkPolicyConfig::default())
+    }
+}
+
+impl NetworkPolicy {
+    /// Build a policy from the `web.network` config section
+    pub fn from_config(config: &NetworkPolicyConfig) -> Self {
+        Self {
+            allow_private_networks: config.allow_private_networks,
+            allowed_domains: config.allowed_domains.clone(),
+            blocked_domains: config.blocked_domains.clone(),
+            max_response_bytes: config.max_response_bytes,
+            max_redirects: config.max_redirects,
+        }
+    }
+
+    /// Load the policy from config.yaml, or the default policy if no config loads
+    pub fn load() -> Self {
+        match crate::config::load_config() {
+            Ok(config) => Self::from_config(&config.web.network),
+            Err(e) => {
+                tracing::debug!("Using default network policy: {}", e);
+                Self::default()
+            }
+        }
+    }
+
+    /// Check a host name against the allow/deny domain lists
+    fn check_domain(&self, host: &str) -> Result<(), String> {
+        if self.blocked_domains.iter().any(|d| domain_matches(host, d)) {
+            return Err(format!("domain '{}' is blocked", host));
+        }
+        if !self.allowed_domains.is_empty()
+            && !self.allowed_domains.iter().any(|d| domain_matches(host, d))
+        {
+            return Err(format!("domain '{}' is not in the allowed list", host));
+        }
+        Ok(())
+    }
+
+    /// Validate a URL and resolve its host, without recording blocked attempts
+    fn validate(&self, url: &str) -> Result<CheckedUrl, String> {
+        let parsed = Url::parse(url).map_err(|e| format!("invalid URL: {}", e))?;
+
+        if parsed.scheme() != "http" && parsed.scheme() != "https" {
+            return Err(format!("scheme '{}' is not allowed", parsed.scheme()));
+        }
+
+        let host = parsed
+            .host_str()
+            .ok_or_else(|| "URL has no host".to_string())?
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .to_string();
+
+        self.check_domain(&host)?;
+
+        let port = parsed.port_or_known_default().unwrap_or(80);
+        let addrs: Vec<SocketAddr> = match host.parse::<IpAddr>() {
+            Ok(ip) => vec![SocketAddr::new(ip, port)],
+            Err(_) => (host.as_str(), port)
+                .to_socket_addrs()
+                .map_err(|e| format!("failed to resolve '{}': {}", host, e))?
+                .collect(),
+        };
+
+        if addrs.is_empty() {
+            return Err(format!("'{}' did not resolve to any address", host));
+        }
+
+        if !self.allow_private_networks {
+            if let Some(addr) = addrs.iter().find(|a| is_non_public_ip(&a.ip())) {
+                return Err(format!(
+                    "'{}' resolves to non-public address {}",
+                    host,
+                    addr.ip()
+                ));
+            }
+        }
+
+        Ok(CheckedUrl { url: parsed, addrs })
+    }
+
+    /// Check a URL against the policy (blocking DNS lookup)
+    ///
+    /// Blocked attempts are recorded for the activity log.
+    pub fn check_url(&self, url: &str) -> Result<CheckedUrl, String> {
+        self.validate(url).map_err(|reason| {
+            record_blocked(url, &reason);
+            format!("Blocked by network policy: {}", reason)
+        })
+    }
+
+    /// Check a URL against the policy without blocking the async runtime
+    pub async fn check_url_async(&self, url: &str) -> Result<CheckedUrl, String> {
+        let policy = self.clone();
+        let owned = url.to_string();
+        tokio::task::spawn_blocking(move || policy.check_url(&owned))
+            .await
+            .map_err(|e| format!("DNS lookup task failed: {}", e))?
+    }
+
+    /// GET a URL under the policy
+    ///
+    /// Follows up to `max_redirects` redirects, checking every hop, and stops
+    /// reading once the body exceeds `max_response_bytes`.
+    pub async fn get(
+        &self,
+        url: &str,
+        headers: &[(&str, &str)],
+        timeout: Duration,
+    ) -> Result<PolicyResponse, String> {
+        use futures::StreamExt;
+
+        let mut current = url.to_string();
+        let mut redirects = 0;
+
+        loop {
+            let checked = self.check_url_async(&current).await?;
+
+            let mut builder = Client::builder()
+                .timeout(timeout)
+                .redirect(reqwest::redirect::Policy::none());
+            if let Some(host) = checked.url.host_str() {
+                builder = builder.resolve_to_addrs(host, &checked.addrs);
+            }
+            let client = builder
+                .build()
+                .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+
+            let mut request = client.get(checked.url.clone());
+            for (key, value) in headers {
+                request = request.header(*key, *value);
+            }
+
+            let response = request
+                .send()
+                .await
+                .map_err(|e| format!("Request failed: {}", e))?;
+
+            if response.status().is_redirection() {
+                let location = response
+                    .headers()
+                    .get(reqwest::header::LOCATION)
+                    .and_then(|v| v.to_str().ok())
+                    .ok_or_else(|| {
+                        format!("Redirect {} without Location header", response.status())
+                    })?;
+                let next = checked
+                    .url
+                    .join(location)
+                    .map_err(|e| format!("Invalid redirect location '{}': {}", location, e))?;
+
+                redirects += 1;
+                if redirects > self.max_redirects {
+                    let reason = format!("more than {} redirects", self.max_redirects);
+                    record_blocked(next.as_str(), &reason);
+                    return Err(format!("Blocked by network policy: {}", reason));
+                }
+                current = next.to_string();
+                continue;
+            }
+
+            let too_large = |size: u64| {
+                let reason = format!(
+                    "response exceeds {} bytes (got {})",
+                    self.max_response_bytes, size
+                );
+                record_blocked(&current, &reason);
+                format!("Blocked by network policy: {}", reason)
+            };
+
+            if let Some(len) = response.content_length() {
+                if len > self.max_response_bytes {
+                    return Err(too_large(len));
+                }
+            }
+
+            let status = response.status().as_u16();
+            let content_type = response
+                .headers()
+                .get(reqwest::header::CONTENT_TYPE)
+                .and_then(|v| v.to_str().ok())
+                .map(String::from);
+
+            let mut body = Vec::new();
+            let mut stream = response.bytes_stream();
+            while let Some(chunk) = stream.next().await {
+                let chunk = chunk.map_err(|e| format!("Failed to read response: {}", e))?;
+                body.extend_from_slice(&chunk);
+                if body.len() as u64 > self.max_response_bytes {
+                    return Err(too_large(body.len() as u64));
+                }
+            }
+
+            return Ok(PolicyResponse {
+                url: current,
+                status,
+                content_type,
+                body: String::from_utf8_lossy(&body).into_owned(),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn local_policy() -> NetworkPolicy {
+        NetworkPolicy {
+            allow_private_networks: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_non_public_ips() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+            "64:ff9b::a9fe:a9fe",
+            "64:ff9b::127.0.0.1",
+            "::127.0.0.1",
+            "::169.254.169.254",
+            "2002:a9fe:a9fe::1",
+            "2002:c0a8:0101::",
+        ] {
+            assert!(
+                is_non_public_ip(&ip.parse().unwrap()),
+                "{} should be blocked",
+                ip
+            );
+        }
+        for ip in [
+            "8.8.8.8",
+            "1.1.1.1",
+            "2606:4700:4700::1111",
+            "64:ff9b::808:808",
+            "::8.8.8.8",
+            "2002:808:808::1",
+        ] {
+            assert!(
+                !is_non_public_ip(&ip.parse().unwrap()),
+                "{} should be allowed",
+                ip
+            );
+        }
+    }
+
+    #[test]
+    fn test_domain_matches() {
+        assert!(domain_matches("docs.rs", "docs.rs"));
+        assert!(domain_matches("www.docs.rs", "docs.rs"));
+        assert!(domain_matches("WWW.Docs.RS.", "*.docs.rs"));
+        assert!(!domain_matches("notdocs.rs", "docs.rs"));
+        assert!(!domain_matches("docs.rs", ""));
+    }
+
+    #[test]
+    fn test_check_url_blocks_metadata_and_loopback() {
+        let policy = NetworkPolicy::default();
+        let err = policy
+            .check_url("http://169.254.169.254/latest/meta-data/")
+            .unwrap_err();
+        assert!(err.contains("non-public"));
+        assert!(policy.check_url("http://localhost:8080/").is_err());
+        assert!(policy.check_url("http://[::1]/").is_err());
+        assert!(policy.check_url("file:///etc/passwd").is_err());
+
+        let blocked = take_blocked_requests();
+        assert!(blocked
+            .iter()
+            .any(|b| b.url.starts_with("http://169.254.169.254")));
+    }
+
+    #[test]
+    fn test_check_url_domain_lists() {
+        let policy = NetworkPolicy {
+            allow_private_networks: true,
+            allowed_domains: vec!["127.0.0.1".to_string()],
+            blocked_domains: vec!["evil.example".to_string()],
+            ..Default::default()
+        };
+        assert!(policy.check_url("http://127.0.0.1:9/").is_ok());
+        assert!(policy
+            .check_url("http://api.evil.example/")
+            .unwrap_err()
+            .contains("blocked"));
+        assert!(policy
+            .check_url("http://10.0.0.1/")
+            .unwrap_err()
+            .contains("not in the allowed list"));
+    }
+
+    #[tokio::test]
+    async fn test_get_follows_redirects_within_limit() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/start"))
+            .respond_with(ResponseTemplate::new(302).insert_header("Location", "/end"))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/end"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("done"))
+            .mount(&server)
+            .await;
+
+        let response = local_policy()
+            .get(
+                &format!("{}/start", server.uri()),
+                &[],
+                Duration::from_secs(5),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body, "done");
+        assert!(response.url.ends_with("/end"));
+
+        let strict = NetworkPolicy {
+            max_redirects: 0,
+            ..local_policy()
+        };
+        let err = strict
+            .get(
+                &format!("{}/start", server.uri()),
+                &[],
+                Duration::from_secs(5),
+            )
+            .await
+            .unwrap_err();
+        assert!(err.contains("redirects"));
+    }
+
+    #[tokio::test]
+    async fn test_get_enforces_size_limit_and_private_block() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/big"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("x".repeat(2048)))
+            .mount(&server)
+            .await;
+
+        let policy = NetworkPolicy {
+            max_response_bytes: 1024,
+            ..local_policy()
+        };
+        let err = policy
+            .get(
+                &format!("{}/big", server.uri()),
+                &[],
+                Duration::from_secs(5),
+            )
+            .await
+            .unwrap_err();
+        assert!(err.contains("exceeds 1024 bytes"));
+
+        // Default policy refuses the loopback mock server entirely
+        let err = NetworkPolicy::default()
+            .get(
+                &format!("{}/big", server.uri()),
+                &[],
+                Duration::from_secs(5),
+            )
+            .await
+            .unwrap_err();
+        assert!(err.contains("non-public"));
+    }
+}
@@ -24,8 +24,10 @@ use sha2::{Digest, Sha256};
 use std::collections::HashMap;
 use std::fs;
 use std::path::PathBuf;
-use std::sync::RwLock;
-use tracing::{debug, error, info};
+use std::sync::{Arc, RwLock};
+use tracing::{debug, error, info, warn};
+
+use crate::credentials::{self, CredentialStore, OAUTH_SESSIONS_KEY};
 
 /// Anthropic's fixed OAuth client identifier
 pub const ANTHROPIC_CLIENT_ID: &str = "9d1c250a-e61b-44d9-88ed-5944d1962f5e";
@@ -48,6 +50,15 @@ pub const API_KEY_ENDPOINT: &str = "https://api.anthropic.com/api/oauth/claude_c
 pub const OAUTH_SCOPES: &str =
     "org:create_api_key user:profile user:inference user:sessions:claude_code";
 
+/// Access tokens are refreshed once they expire within this many seconds
+pub const REFRESH_MARGIN_SECS: i64 = 300;
+
+/// How often the background refresher checks for expiring tokens
+pub const REFRESH_INTERVAL_SECS: u64 = 60;
+
+/// Account name used when none is given
+pub const DEFAULT_ACCOUNT: &str = "default";
+
 // ============================================================================
 // PKCE (Proof Key for Code Exchange) Implementation
 // ============================================================================
@@ -133,6 +144,26 @@ impl OAuthCredentials {
     pub fn is_expired(&self) -> bool {
         Utc::now() >= self.expires_at
     }
+
+    /// Check if the token expires within [`REFRESH_MARGIN_SECS`]
+    pub fn needs_refresh(&self) -> bool {
+        Utc::now() + Duration::seconds(REFRESH_MARGIN_SECS) >= self.expires_at
+    }
+
+    /// Check if the token is still valid or can be refreshed
+    pub fn is_usable(&self) -> bool {
+        !self.is_expired() || self.refresh_token.is_some()
+    }
+
+    /// Replace the tokens with a refresh response, keeping the old
+    /// refresh token when the server doesn't rotate it
+    pub fn apply_refresh(&mut self, response: TokenExchangeResponse) {
+        self.access_token = response.access_token;
+        if response.refresh_token.is_some() {
+            self.refresh_token = response.refresh_token;
+        }
+        self.expires_at = Utc::now() + Duration::seconds(response.expires_in as i64);
+    }
 }
 
 /// Request body for token endpoint
@@ -194,6 +225,13 @@ pub struct OAuthSession {
     pub created_at: DateTime<Utc>,
     /// Optional user identifier
     pub user_id: Option<String>,
+    /// Named account the session belongs to
+    #[serde(default = "default_account")]
+    pub account: String,
+}
+
+fn default_account() -> String {
+    DEFAULT_ACCOUNT.to_string()
 }
 
 impl OAuthSession {
@@ -234,6 +272,7 @@ impl OAuthSession {
             granted_scopes,
             created_at: Utc::now(),
             user_id: None,
+            account: default_account(),
         }
     }
 
@@ -251,8 +290,10 @@ pub struct OAuthStore {
     sessions: RwLock<HashMap<String, OAuthSession>>,
     /// Pending PKCE challenges keyed by state parameter
     pending_challenges: RwLock<HashMap<String, PkceParams>>,
-    /// Path for persistent session storage
-    persist_path: Option<PathBuf>,
+    /// Credential store holding the sessions between runs (none for in-memory stores)
+    credentials: Option<&'static CredentialStore>,
+    /// Serializes token refreshes so a rotated refresh token is used only once
+    refreshing: tokio::sync::Mutex<()>,
 }
 
 impl Default for OAuthStore {
@@ -262,25 +303,29 @@ impl Default for OAuthStore {
 }
 
 impl OAuthStore {
-    /// Create new OAuth store with optional persistence
+    /// Create new OAuth store, persisted in the credential store
     pub fn new() -> Self {
-        let persist_path =
-            dirs::data_local_dir().map(|d| d.join("openclaudia").join("oauth_sessions.json"));
-
         let store = Self {
-            sessions: RwLock::new(HashMap::new()),
-            pending_challenges: RwLock::new(HashMap::new()),
-            persist_path: persist_path.clone(),
+            credentials: Some(credentials::store()),
+            ..Self::in_memory()
         };
 
         // Load persisted sessions
-        if persist_path.is_some() {
-            store.load_from_disk();
-        }
+        store.load_persisted();
 
         store
     }
 
+    /// Create a store that keeps sessions in memory only
+    pub fn in_memory() -> Self {
+        Self {
+            sessions: RwLock::new(HashMap::new()),
+            pending_challenges: RwLock::new(HashMap::new()),
+            credentials: None,
+            refreshing: tokio::sync::Mutex::new(()),
+        }
+    }
+
     /// Store PKCE challenge for pending authorization
     pub fn store_challenge(&self, pkce: PkceParams) {
         let state = pkce.state.clone();
@@ -294,15 +339,16 @@ impl OAuthStore {
         challenges.remove(state)
     }
 
-    /// Store new OAuth session
+    /// Store new OAuth session, replacing any earlier session of the same account
     pub fn store_session(&self, session: OAuthSession) {
         let id = session.id.clone();
-        {
-            let mut sessions = self.sessions.write().unwrap();
+        let account = session.account.clone();
+        register_session_secrets(&session);
+        self.update(|sessions| {
+            sessions.retain(|_, s| s.account != account);
             sessions.insert(id.clone(), session);
-        }
-        self.persist_to_disk();
-        info!("OAuth session stored: {}", id);
+        });
+        info!("OAuth session stored: {} (account {})", id, account);
     }
 
     /// Retrieve session by ID
@@ -311,82 +357,316 @@ impl OAuthStore {
         sessions.get(id).cloned()
     }
 
-    /// Get any valid (non-expired) session - used when no specific session ID is provided
+    /// All stored sessions, ordered by account name
+    pub fn sessions(&self) -> Vec<OAuthSession> {
+        let sessions = self.sessions.read().unwrap();
+        let mut all: Vec<OAuthSession> = sessions.values().cloned().collect();
+        all.sort_by(|a, b| a.account.cmp(&b.account));
+        all
+    }
+
+    /// Usable session of a named account. Falls back to re-reading the
+    /// credential store, which another process may have signed it in to.
+    pub fn account_session(&self, account: &str) -> Option<OAuthSession> {
+        self.find_account(account).or_else(|| {
+            self.reload();
+            self.find_account(account)
+        })
+    }
+
+    fn find_account(&self, account: &str) -> Option<OAuthSession> {
+        let sessions = self.sessions.read().unwrap();
+        sessions
+            .values()
+            .filter(|s| s.account == account && s.credentials.is_usable())
+            .max_by_key(|s| s.created_at)
+            .cloned()
+    }
+
+    /// Remove the sessions of a named account. Returns how many there were.
+    pub fn remove_account(&self, account: &str) -> usize {
+        let mut removed = 0;
+        self.update(|sessions| {
+            let before = sessions.len();
+            sessions.retain(|_, s| s.account != account);
+            removed = before - sessions.len();
+        });
+        removed
+    }
+
+    /// Remove every session. Returns how many there were.
+    pub fn clear(&self) -> usize {
+        let count = {
+            let mut sessions = self.sessions.write().unwrap();
+            let count = sessions.len();
+            sessions.clear();
+            count
+        };
+        if let Some(store) = self.credentials {
+            if let Err(e) = store.delete(OAUTH_SESSIONS_KEY) {
+                error!("Failed to remove stored OAuth sessions: {}", e);
+            }
+        }
+        count
+    }
+
+    /// Session used when a request names no account: the `default` account
+    /// if it is signed in, else the most recently created usable session.
+    /// The credential store is re-read when no session is usable.
     pub fn get_any_valid_session(&self) -> Option<OAuthSession> {
+        self.find_any_valid().or_else(|| {
+            self.reload();
+            self.find_any_valid()
+        })
+    }
+
+    fn find_any_valid(&self) -> Option<OAuthSession> {
+        if let Some(session) = self.find_account(DEFAULT_ACCOUNT) {
+            return Some(session);
+        }
         let sessions = self.sessions.read().unwrap();
         for (id, session) in sessions.iter() {
-            let expired = session.credentials.is_expired();
             tracing::debug!(
-                "Session {}: expired={}, expires_at={:?}",
+                "Session {} ({}): expired={}, expires_at={:?}",
                 id,
-                expired,
+                session.account,
+                session.credentials.is_expired(),
                 session.credentials.expires_at
             );
         }
         sessions
             .values()
-            .find(|s| !s.credentials.is_expired())
+            .filter(|s| s.credentials.is_usable())
+            .max_by_key(|s| s.created_at)
             .cloned()
     }
 
-    /// Load sessions from disk, filtering out expired ones
-    fn load_from_disk(&self) {
-        let Some(path) = &self.persist_path else {
-            return;
+    /// Return the session with a token that is good for at least
+    /// [`REFRESH_MARGIN_SECS`], refreshing it first if needed.
+    ///
+    /// `rejected` is an access token the API refused; the session is
+    /// refreshed if it still holds that token, even when not yet expiring.
+    pub async fn refresh_session(
+        &self,
+        client: &OAuthClient,
+        id: &str,
+        rejected: Option<&str>,
+    ) -> Result<OAuthSession> {
+        let _refreshing = self.refreshing.lock().await;
+        let session = self
+            .get_session(id)
+            .with_context(|| format!("OAuth session {} no longer exists", id))?;
+        let credentials = &session.credentials;
+        let stale = rejected == Some(credentials.access_token.as_str());
+        if !stale && !credentials.needs_refresh() {
+            return Ok(session);
+        }
+        let Some(refresh_token) = credentials.refresh_token.as_deref() else {
+            if credentials.is_expired() || stale {
+                anyhow::bail!(
+                    "OAuth session for account '{}' expired and has no refresh token; run 'openclaudia auth --account {}'",
+                    session.account,
+                    session.account
+                );
+            }
+            return Ok(session);
         };
 
-        match fs::read_to_string(path) {
-            Ok(data) => {
-                if let Ok(loaded) = serde_json::from_str::<HashMap<String, OAuthSession>>(&data) {
-                    // Filter out expired sessions during load
-                    let valid_sessions: HashMap<String, OAuthSession> = loaded
-                        .into_iter()
-                        .filter(|(id, session)| {
-                            if session.credentials.is_expired() {
-                                info!("Removing expired OAuth session: {}", id);
-                                false
-                            } else {
-                                true
-                            }
-                        })
-                        .collect();
-
-                    let mut sessions = self.sessions.write().unwrap();
-                    *sessions = valid_sessions;
-                    info!("Loaded {} OAuth sessions from disk", sessions.len());
-                }
-            }
-            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
-                debug!("No persisted OAuth sessions found");
+        let response = client.refresh_token(refresh_token).await.with_context(|| {
+            format!(
+                "Failed to refresh OAuth token for account '{}'",
+                session.account
+            )
+        })?;
+        let mut refreshed = None;
+        self.update(|sessions| {
+            if let Some(stored) = sessions.get_mut(id) {
+                stored.credentials.apply_refresh(response);
+                register_session_secrets(stored);
+                refreshed = Some(stored.clone());
             }
-            Err(e) => {
-                error!("Failed to load OAuth sessions: {}", e);
+        });
+        let refreshed =
+            refreshed.with_context(|| format!("OAuth session {} no longer exists", id))?;
+        info!(
+            "Refreshed OAuth token for account '{}' (expires {})",
+            refreshed.account, refreshed.credentials.expires_at
+        );
+        Ok(refreshed)
+    }
+
+    /// Refresh every session whose token is about to expire
+    pub async fn refresh_expiring(&self, client: &OAuthClient) {
+        let expiring: Vec<String> = {
+            let sessions = self.sessions.read().unwrap();
+            sessions
+                .values()
+                .filter(|s| s.credentials.refresh_token.is_some() && s.credentials.needs_refresh())
+                .map(|s| s.id.clone())
+                .collect()
+        };
+        for id in expiring {
+            if let Err(e) = self.refresh_session(client, &id, None).await {
+                warn!("{:#}", e);
             }
         }
     }
 
-    /// Persist sessions to disk
-    fn persist_to_disk(&self) {
-        let Some(path) = &self.persist_path else {
+    /// Refresh expiring tokens every [`REFRESH_INTERVAL_SECS`] until the
+    /// store is dropped
+    pub fn spawn_refresher(self: &Arc<Self>, client: OAuthClient) -> tokio::task::JoinHandle<()> {
+        let store = Arc::downgrade(self);
+        tokio::spawn(async move {
+            let mut interval =
+                tokio::time::interval(std::time::Duration::from_secs(REFRESH_INTERVAL_SECS));
+            loop {
+                interval.tick().await;
+                let Some(store) = store.upgrade() else {
+                    break;
+                };
+                store.refresh_expiring(&client).await;
+            }
+        })
+    }
+
+    /// Load sessions from the credential store. Sessions in the plaintext
+    /// file older versions wrote are moved over.
+    fn load_persisted(&self) {
+        let Some(store) = self.credentials else {
             return;
         };
+        let Some(mut loaded) = self.read_persisted() else {
+            return;
+        };
+        let legacy = legacy_sessions_path().filter(|path| path.exists());
+        if let Some(path) = &legacy {
+            match fs::read_to_string(path)
+                .map_err(|e| e.to_string())
+                .and_then(|data| {
+                    serde_json::from_str::<HashMap<String, OAuthSession>>(&data)
+                        .map_err(|e| e.to_string())
+                }) {
+                Ok(sessions) => loaded.extend(
+                    sessions
+                        .into_iter()
+                        .filter(|(_, session)| session.credentials.is_usable()),
+                ),
+                Err(e) => error!("Failed to read {}: {}", path.display(), e),
+            }
+        }
+        loaded.values().for_each(register_session_secrets);
+
+        let mut sessions = self.sessions.write().unwrap();
+        *sessions = loaded;
+        info!("Loaded {} OAuth sessions", sessions.len());
+
+        if let Some(path) = legacy {
+            if self.write_persisted(&sessions) {
+                match fs::remove_file(&path) {
+                    Ok(()) => info!(
+                        "Moved OAuth sessions from {} to the credential store ({})",
+                        path.display(),
+                        store.name()
+                    ),
+                    Err(e) => error!("Failed to remove {}: {}", path.display(), e),
+                }
+            }
+        }
+    }
 
-        // Ensure parent directory exists
-        if let Some(parent) = path.parent() {
-            let _ = fs::create_dir_all(parent);
+    /// Replace the in-memory sessions with the stored ones, picking up
+    /// sign-ins and logouts made by other processes
+    fn reload(&self) {
+        if let Some(stored) = self.read_persisted() {
+            stored.values().for_each(register_session_secrets);
+            *self.sessions.write().unwrap() = stored;
         }
+    }
 
-        let sessions = self.sessions.read().unwrap();
-        match serde_json::to_string_pretty(&*sessions) {
-            Ok(json) => {
-                if let Err(e) = fs::write(path, json) {
-                    error!("Failed to persist OAuth sessions: {}", e);
+    /// Usable sessions in the credential store, or `None` when there is no
+    /// store or it cannot be read. Expired sessions are kept while they can
+    /// still be refreshed.
+    fn read_persisted(&self) -> Option<HashMap<String, OAuthSession>> {
+        let store = self.credentials?;
+        let stored: HashMap<String, OAuthSession> = match store.get(OAUTH_SESSIONS_KEY) {
+            Ok(Some(data)) => match serde_json::from_str(&data) {
+                Ok(sessions) => sessions,
+                Err(e) => {
+                    error!("Failed to parse stored OAuth sessions: {}", e);
+                    return None;
                 }
+            },
+            Ok(None) => {
+                debug!("No persisted OAuth sessions found");
+                HashMap::new()
             }
             Err(e) => {
-                error!("Failed to serialize OAuth sessions: {}", e);
+                error!("Failed to load OAuth sessions: {}", e);
+                return None;
             }
+        };
+        Some(
+            stored
+                .into_iter()
+                .filter(|(id, session)| {
+                    if session.credentials.is_usable() {
+                        true
+                    } else {
+                        info!("Removing expired OAuth session: {}", id);
+                        false
+                    }
+                })
+                .collect(),
+        )
+    }
+
+    /// Apply a change to the sessions and save them. The change is applied
+    /// on top of the freshly read credential store, so sessions that another
+    /// process (e.g. `openclaudia auth --account`) added or removed are kept
+    /// that way. Returns whether saving succeeded.
+    fn update(&self, change: impl FnOnce(&mut HashMap<String, OAuthSession>)) -> bool {
+        let mut sessions = self.sessions.write().unwrap();
+        if let Some(stored) = self.read_persisted() {
+            stored.values().for_each(register_session_secrets);
+            *sessions = stored;
         }
+        change(&mut sessions);
+        self.write_persisted(&sessions)
+    }
+
+    /// Save sessions to the credential store. Returns whether it succeeded.
+    fn write_persisted(&self, sessions: &HashMap<String, OAuthSession>) -> bool {
+        let Some(store) = self.credentials else {
+            return true;
+        };
+        let result = serde_json::to_string(sessions)
+            .map_err(|e| e.to_string())
+            .and_then(|json| {
+                store
+                    .set(OAUTH_SESSIONS_KEY, &json)
+                    .map_err(|e| e.to_string())
+            });
+        if let Err(e) = &result {
+            error!("Failed to persist OAuth sessions: {}", e);
+        }
+        result.is_ok()
+    }
+}
+
+/// Plaintext session file written by earlier versions
+fn legacy_sessions_path() -> Option<PathBuf> {
+    dirs::data_local_dir().map(|d| d.join("openclaudia").join("oauth_sessions.json"))
+}
+
+/// Let log redaction mask a session's tokens
+fn register_session_secrets(session: &OAuthSession) {
+    credentials::register_secret(&session.id);
+    credentials::register_secret(&session.credentials.access_token);
+    if let Some(token) = &session.credentials.refresh_token {
+        credentials::register_secret(token);
+    }
+    if let Some(key) = &session.api_key {
+        credentials::register_secret(key);
     }
 }
 
@@ -395,8 +675,10 @@ impl OAuthStore {
 // ============================================================================
 
 /// Client for OAuth token operations
+#[derive(Clone)]
 pub struct OAuthClient {
     http: reqwest::Client,
+    token_endpoint: String,
 }
 
 impl Default for OAuthClient {
@@ -414,7 +696,16 @@ impl OAuthClient {
             .build()
             .unwrap_or_else(|_| reqwest::Client::new());
 
-        Self { http }
+        Self {
+            http,
+            token_endpoint: TOKEN_ENDPOINT.to_string(),
+        }
+    }
+
+    /// Send token requests to another endpoint
+    pub fn with_token_endpoint(mut self, url: impl Into<String>) -> Self {
+        self.token_endpoint = url.into();
+        self
     }
 
     /// Exchange authorization code for tokens
@@ -491,13 +782,13 @@ impl OAuthClient {
         &self,
         request: TokenExchangeRequest,
     ) -> Result<TokenExchangeResponse> {
-        debug!("Sending token request to {}", TOKEN_ENDPOINT);
+        debug!("Sending token request to {}", self.token_endpoint);
 
         // CRITICAL: Anthropic's OAuth endpoint requires form-urlencoded, NOT JSON
         // This is the key difference that makes anthropic-proxy work
         let response = self
             .http
-            .post(TOKEN_ENDPOINT)
+            .post(&self.token_endpoint)
             .header("Content-Type", "application/x-www-form-urlencoded")
             .form(&request)
             .send()
@@ -676,4 +967,142 @@ mod tests {
         // Already past expiry
         assert!(expired_creds.is_expired());
     }
+
+    fn session(account: &str, expires_in: i64, refresh_token: Option<&str>) -> OAuthSession {
+        let mut session = OAuthSession::from_token_response(TokenExchangeResponse {
+            access_token: format!("access-{}", account),
+            token_type: "Bearer".to_string(),
+            expires_in: 3600,
+            refresh_token: refresh_token.map(String::from),
+            scope: None,
+        });
+        session.account = account.to_string();
+        session.credentials.expires_at = Utc::now() + Duration::seconds(expires_in);
+        session
+    }
+
+    #[test]
+    fn test_needs_refresh_margin() {
+        let creds = session("a", REFRESH_MARGIN_SECS + 60, None).credentials;
+        assert!(!creds.needs_refresh());
+
+        let expiring = session("a", REFRESH_MARGIN_SECS - 60, None).credentials;
+        assert!(expiring.needs_refresh());
+        assert!(!expiring.is_expired());
+
+        // Expired tokens stay usable while they can be refreshed
+        assert!(session("a", -10, Some("refresh")).credentials.is_usable());
+        assert!(!session("a", -10, None).credentials.is_usable());
+    }
+
+    #[test]
+    fn test_account_selection() {
+        let store = OAuthStore::in_memory();
+        store.store_session(session("work", 3600, None));
+        store.store_session(session("personal", -10, None));
+        assert_eq!(store.get_any_valid_session().unwrap().account, "work");
+
+        store.store_session(session(DEFAULT_ACCOUNT, 3600, None));
+        assert_eq!(
+            store.get_any_valid_session().unwrap().account,
+            DEFAULT_ACCOUNT
+        );
+        assert_eq!(store.account_session("work").unwrap().account, "work");
+        assert!(store.account_session("personal").is_none());
+
+        // Signing in again replaces the account's previous session
+        store.store_session(session("work", 3600, None));
+        assert_eq!(store.sessions().len(), 3);
+        assert_eq!(store.remove_account("work"), 1);
+        assert!(store.account_session("work").is_none());
+    }
+
+    #[test]
+    fn test_sessions_merged_with_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let credentials: &'static CredentialStore = Box::leak(Box::new(CredentialStore::file(
+            dir.path().join("credentials.enc"),
+            dir.path().join("credentials.key"),
+        )));
+        let proxy = OAuthStore {
+            credentials: Some(credentials),
+            ..OAuthStore::in_memory()
+        };
+        let cli = OAuthStore {
+            credentials: Some(credentials),
+            ..OAuthStore::in_memory()
+        };
+        proxy.store_session(session(DEFAULT_ACCOUNT, 3600, None));
+
+        // An account signed in by another process is picked up on demand
+        // and survives the first process saving its own sessions
+        cli.store_session(session("work", 3600, None));
+        assert_eq!(proxy.account_session("work").unwrap().account, "work");
+        proxy.store_session(session("personal", 3600, None));
+        cli.reload();
+        assert_eq!(cli.sessions().len(), 3);
+
+        // A logout elsewhere is not undone either
+        cli.remove_account("work");
+        proxy.store_session(session(DEFAULT_ACCOUNT, 3600, None));
+        assert!(proxy.account_session("work").is_none());
+        assert_eq!(proxy.sessions().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_refresh_session() {
+        use wiremock::matchers::{body_string_contains, method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/oauth/token"))
+            .and(body_string_contains("refresh_token=old-refresh"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "access_token": "new-access",
+                "token_type": "Bearer",
+                "expires_in": 3600,
+            })))
+            .expect(2)
+            .mount(&server)
+            .await;
+        let client =
+            OAuthClient::new().with_token_endpoint(format!("{}/v1/oauth/token", server.uri()));
+
+        let store = OAuthStore::in_memory();
+        let fresh = session("fresh", 3600, Some("old-refresh"));
+        let expiring = session("expiring", 60, Some("old-refresh"));
+        let (fresh_id, expiring_id) = (fresh.id.clone(), expiring.id.clone());
+        store.store_session(fresh);
+        store.store_session(expiring);
+
+        // Tokens far from expiry are left alone
+        let unchanged = store
+            .refresh_session(&client, &fresh_id, None)
+            .await
+            .unwrap();
+        assert_eq!(unchanged.credentials.access_token, "access-fresh");
+
+        let refreshed = store
+            .refresh_session(&client, &expiring_id, None)
+            .await
+            .unwrap();
+        assert_eq!(refreshed.credentials.access_token, "new-access");
+        assert_eq!(
+            refreshed.credentials.refresh_token.as_deref(),
+            Some("old-refresh")
+        );
+        assert!(!refreshed.credentials.needs_refresh());
+
+        // A rejected token forces a refresh, but only once
+        store
+            .refresh_session(&client, &fresh_id, Some("access-fresh"))
+            .await
+            .unwrap();
+        let again = store
+            .refresh_session(&client, &fresh_id, Some("access-fresh"))
+            .await
+            .unwrap();
+        assert_eq!(again.credentials.access_token, "new-access");
+    }
 }
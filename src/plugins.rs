@@ -84,7 +84,7 @@ pub struct PluginCommand {
 }
 
 /// MCP server definition in a plugin manifest
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PluginMcpServer {
     /// Server name
     pub name: String,
@@ -231,6 +231,7 @@ pub enum PluginError {
 }
 
 /// Manages plugin discovery and loading
+#[derive(Clone)]
 pub struct PluginManager {
     /// Loaded plugins by name
     plugins: HashMap<String, Plugin>,
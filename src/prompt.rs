@@ -69,25 +69,36 @@ Fetch a URL and return its content as markdown.
 - Good for looking up library docs, error messages, etc.
 
 ### `web_search` - Search the Web
-Search the web for information. Requires TAVILY_API_KEY or BRAVE_API_KEY.
+Search the web for information. Backend is set in config.yaml (`web.search`).
+- Use `allowed_domains` / `blocked_domains` to restrict results to trusted sites
 - Use when you need current information beyond your training data
 - Good for finding solutions to specific errors
 
-### `chainlink` - Task and Issue Tracking (Preferred)
-Track tasks, issues, and work items for the project.
-- Create issues before starting significant work
-- Close issues when work is complete
-- Use to maintain context across sessions
-- If chainlink is not installed, use `todo_write` as a fallback
-
-### `todo_write` / `todo_read` - Simple Task List (Chainlink Fallback)
-Create and track a simple task list when chainlink is unavailable.
+### `browser` - Interactive Browser Testing
+Drive a persistent headless Chrome session (requires the `browser` build feature).
+- Start with `navigate`, then `click`, `type`, `wait_for`, `evaluate`
+- Use `console` to read logs/exceptions and `accessibility_tree` to see page structure
+- Use it to verify web apps you build; `close` the session when done
+
+### `issue_create` / `issue_list` / `issue_show` - Issue Tracking (Preferred)
+Track tasks, issues, and work items for the project. Issues persist across sessions.
+- Create issues before starting significant work (`issue_create`)
+- Record progress with `issue_comment`; close issues when work is complete (`issue_close`)
+- Organize with `issue_label`, subissues (`parent`), and `issue_depend` for blockers
+- Reference issues as `#N` so subagents and later sessions can pick them up
+
+### `chainlink` - External Issue Tracker
+Raw passthrough to the chainlink CLI, only useful when it is installed.
+- Prefer the `issue_*` tools; they use chainlink automatically when `issues.backend: chainlink` is configured
+
+### `todo_write` / `todo_read` - Simple Task List
+Track a short task list for the current session.
 - `todo_write`: Replace the todo list with a new set of tasks
 - `todo_read`: View current tasks and their status
 - Each task needs: `content` (imperative), `status`, `activeForm` (present continuous)
 - Status values: `pending`, `in_progress`, `completed`
 - Only ONE task should be `in_progress` at a time
-- Use chainlink when available - it persists across sessions
+- Use issues for work that should outlive the session
 
 ### `task` - Spawn Autonomous Subagents
 Launch a specialized subagent to handle complex tasks autonomously.
@@ -105,7 +116,7 @@ Launch a specialized subagent to handle complex tasks autonomously.
   - `run_in_background`: If true, returns agent_id immediately (default: false)
 - Use `run_in_background: true` for long tasks you want to run while doing other work
 - Subagents return a summary when complete
-- TestBuilder agents can reopen chainlink issues and alert humans when tests fail
+- TestBuilder agents can reopen issues and alert humans when tests fail
 
 ### `agent_output` - Get Subagent Results
 Retrieve results from a background subagent.
@@ -240,6 +251,7 @@ mod tests {
         assert!(prompt.contains("### `read_file`"));
         assert!(prompt.contains("### `edit_file`"));
         assert!(prompt.contains("### `chainlink`"));
+        assert!(prompt.contains("### `issue_create`"));
         assert!(prompt.contains("### `task`"));
         assert!(prompt.contains("### `agent_output`"));
     }
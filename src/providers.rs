@@ -3,22 +3,38 @@
 //! Supports:
 //! - Anthropic Messages API
 //! - OpenAI Chat Completions API
+//! - OpenAI Responses API (reasoning models, built-in tools)
 //! - Google Gemini API
 //! - DeepSeek API (with thinking/reasoning support)
 //! - Qwen/Alibaba API (with thinking support)
 //! - Z.AI/GLM API (with thinking support)
 //! - Ollama (local LLM inference)
+//! - Amazon Bedrock (Converse and InvokeModel, SigV4-signed)
+//! - Google Vertex AI (Gemini and Claude, service-account OAuth)
+//! - Azure OpenAI (deployment paths, `api-version`, `api-key` header)
 //! - Any OpenAI-compatible server (LM Studio, LocalAI, etc.)
 //!
 //! Handles message format translation and tool/function calling conversion.
+//! Each adapter also supplies a [`StreamDecoder`] that normalizes its streaming
+//! wire format into [`StreamEvent`](crate::stream::StreamEvent)s.
 
 use async_trait::async_trait;
 use serde_json::{json, Value};
+use std::sync::{LazyLock, Mutex};
 use thiserror::Error;
 use tracing::debug;
 
-use crate::config::ThinkingConfig;
-use crate::proxy::{ChatCompletionRequest, ChatMessage, MessageContent};
+use crate::cloud_auth::{self, AwsCredentials, CloudAuthError};
+use crate::config::{
+    AuthStyle, AwsConfig, CacheTtl, PromptCacheConfig, ProviderConfig, ThinkingConfig, VertexConfig,
+};
+use crate::models::ListedModel;
+use crate::proxy::{ChatCompletionRequest, ChatMessage, ContentPart, MessageContent};
+use crate::stream::{
+    AnthropicDecoder, BedrockDecoder, BedrockInvokeDecoder, GeminiDecoder, OllamaDecoder,
+    OpenAIDecoder, ResponsesDecoder, StopReason, StreamDecoder, TokenUsage, VertexDecoder,
+};
+use crate::structured_output::{self, ResponseFormat, ResponseToolDecoder};
 
 /// Errors that can occur during provider operations
 #[derive(Error, Debug)]
@@ -31,6 +47,9 @@ pub enum ProviderError {
 
     #[error("Unsupported feature: {0}")]
     Unsupported(String),
+
+    #[error("Authentication failed: {0}")]
+    Auth(#[from] CloudAuthError),
 }
 
 /// Trait for provider adapters
@@ -61,14 +80,106 @@ pub trait ProviderAdapter: Send + Sync {
 
     /// Get required headers for this provider
     fn get_headers(&self, api_key: &str) -> Vec<(String, String)>;
+
+    /// Get the request path for a chat call. Providers that encode the model or
+    /// the streaming mode in the URL (Gemini) override this.
+    fn chat_path(&self, model: &str, stream: bool) -> String {
+        let _ = (model, stream);
+        self.chat_endpoint().to_string()
+    }
+
+    /// Whether the provider already answers in OpenAI chat completions format
+    fn openai_compatible(&self) -> bool {
+        true
+    }
+
+    /// Create a decoder that turns this provider's streaming body into stream events
+    fn stream_decoder(&self) -> Box<dyn StreamDecoder> {
+        Box::new(OpenAIDecoder::default())
+    }
+
+    /// Token usage reported in a non-streaming response body
+    fn response_usage(&self, response: &Value) -> Option<TokenUsage> {
+        response.get("usage").map(TokenUsage::from_openai)
+    }
+
+    /// Whether calls need an `api_key`. Adapters that find credentials on
+    /// their own (cloud platforms) or need none (Ollama) return false.
+    fn requires_api_key(&self) -> bool {
+        true
+    }
+
+    /// Authorize a fully built request before it is sent, for credentials
+    /// that are not a static header: Bedrock signs the URL, headers and body,
+    /// and Vertex attaches a short-lived OAuth token
+    async fn authorize(
+        &self,
+        client: &reqwest::Client,
+        request: reqwest::Request,
+    ) -> Result<reqwest::Request, ProviderError> {
+        let _ = client;
+        Ok(request)
+    }
+
+    /// Path of the endpoint listing the models available to the account
+    fn models_path(&self) -> &str {
+        "/v1/models"
+    }
+
+    /// Models in a response from [`models_path`](Self::models_path).
+    /// The default reads the OpenAI list format (`data[].id`).
+    fn parse_models(&self, response: &Value) -> Vec<ListedModel> {
+        response["data"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|m| m["id"].as_str())
+            .map(ListedModel::new)
+            .collect()
+    }
 }
 
+/// Most `cache_control` breakpoints Anthropic accepts in one request
+const MAX_CACHE_BREAKPOINTS: usize = 4;
+
 /// Anthropic Messages API adapter
-pub struct AnthropicAdapter;
+pub struct AnthropicAdapter {
+    cache: PromptCacheConfig,
+}
 
 impl AnthropicAdapter {
     pub fn new() -> Self {
-        Self
+        Self::with_cache(PromptCacheConfig::default())
+    }
+
+    pub fn with_cache(cache: PromptCacheConfig) -> Self {
+        Self { cache }
+    }
+
+    /// The `cache_control` marker for a breakpoint, or None with caching off
+    fn cache_control(&self) -> Option<Value> {
+        if !self.cache.enabled {
+            return None;
+        }
+        Some(match self.cache.ttl {
+            CacheTtl::FiveMinutes => json!({"type": "ephemeral"}),
+            CacheTtl::OneHour => json!({"type": "ephemeral", "ttl": "1h"}),
+        })
+    }
+
+    /// Put rolling breakpoints on the last block of the latest `count` user
+    /// turns, so the next request reads this one's transcript from the cache.
+    /// Empty text blocks can't be marked, so turns ending in one are skipped.
+    fn add_message_breakpoints(messages: &mut [Value], count: usize, cache_control: &Value) {
+        let turns = messages.iter_mut().rev().filter(|m| m["role"] == "user");
+        let blocks = turns.filter_map(|m| {
+            m["content"].as_array_mut()?.last_mut().filter(|b| {
+                b["type"] != "text" || b["text"].as_str().is_some_and(|t| !t.is_empty())
+            })
+        });
+        for block in blocks.take(count) {
+            block["cache_control"] = cache_control.clone();
+        }
     }
 
     /// Extract system message from messages array
@@ -87,50 +198,119 @@ impl AnthropicAdapter {
     }
 
     /// Convert OpenAI messages to Anthropic format
+    ///
+    /// Assistant `tool_calls` become `tool_use` blocks and consecutive `tool`
+    /// messages are merged into a single user turn of `tool_result` blocks.
     fn convert_messages(messages: &[ChatMessage]) -> Vec<Value> {
-        messages
-            .iter()
-            .filter(|m| m.role != "system") // System is handled separately
-            .map(|m| {
-                let role = match m.role.as_str() {
-                    "assistant" => "assistant",
-                    _ => "user", // user, function, tool all become user
-                };
+        let mut result: Vec<Value> = Vec::new();
 
+        for m in messages.iter().filter(|m| m.role != "system") {
+            if m.role == "tool" {
                 let content = match &m.content {
-                    MessageContent::Text(t) => json!([{"type": "text", "text": t}]),
                     MessageContent::Parts(parts) => {
-                        let converted: Vec<Value> = parts
-                            .iter()
-                            .map(|p| {
-                                if let Some(text) = &p.text {
-                                    json!({"type": "text", "text": text})
-                                } else if let Some(image) = &p.image_url {
-                                    // Convert OpenAI image format to Anthropic
-                                    json!({
-                                        "type": "image",
-                                        "source": image
-                                    })
-                                } else {
-                                    json!({"type": "text", "text": ""})
-                                }
-                            })
-                            .collect();
-                        Value::Array(converted)
+                        json!(parts.iter().map(Self::convert_part).collect::<Vec<_>>())
                     }
+                    MessageContent::Text(t) => json!(t),
                 };
-
-                json!({
-                    "role": role,
+                let block = json!({
+                    "type": "tool_result",
+                    "tool_use_id": m.tool_call_id.as_deref().unwrap_or(""),
                     "content": content
-                })
-            })
-            .collect()
+                });
+                let merged = result.last_mut().is_some_and(|last| {
+                    let is_results = last["role"] == "user"
+                        && last["content"].as_array().is_some_and(|blocks| {
+                            blocks.iter().all(|b| b["type"] == "tool_result")
+                        });
+                    if is_results {
+                        if let Some(blocks) = last["content"].as_array_mut() {
+                            blocks.push(block.clone());
+                        }
+                    }
+                    is_results
+                });
+                if !merged {
+                    result.push(json!({"role": "user", "content": [block]}));
+                }
+                continue;
+            }
+
+            let role = match m.role.as_str() {
+                "assistant" => "assistant",
+                _ => "user", // user and function become user
+            };
+
+            let mut content = match &m.content {
+                MessageContent::Text(t) if t.is_empty() && m.tool_calls.is_some() => Vec::new(),
+                MessageContent::Text(t) => vec![json!({"type": "text", "text": t})],
+                MessageContent::Parts(parts) => parts.iter().map(Self::convert_part).collect(),
+            };
+
+            for call in m.tool_calls.iter().flatten() {
+                let func = call.get("function");
+                let arguments = func
+                    .and_then(|f| f.get("arguments"))
+                    .and_then(|a| a.as_str())
+                    .unwrap_or("{}");
+                content.push(json!({
+                    "type": "tool_use",
+                    "id": call.get("id").and_then(|v| v.as_str()).unwrap_or(""),
+                    "name": func.and_then(|f| f.get("name")).and_then(|v| v.as_str()).unwrap_or(""),
+                    "input": serde_json::from_str::<Value>(arguments).unwrap_or_else(|_| json!({}))
+                }));
+            }
+
+            result.push(json!({
+                "role": role,
+                "content": content
+            }));
+        }
+
+        result
+    }
+
+    /// Convert an OpenAI content part to an Anthropic block: images become
+    /// `image` blocks with a base64 or URL source and PDFs `document` blocks
+    fn convert_part(part: &ContentPart) -> Value {
+        if let Some(text) = &part.text {
+            return json!({"type": "text", "text": text});
+        }
+        let kind = if part.file.is_some() {
+            "document"
+        } else {
+            "image"
+        };
+        let source = match (part.inline_data(), &part.image_url) {
+            (Some((media_type, data)), _) => {
+                json!({"type": "base64", "media_type": media_type, "data": data})
+            }
+            (None, Some(image)) => json!({"type": "url", "url": image.get("url").unwrap_or(image)}),
+            (None, None) => return json!({"type": "text", "text": ""}),
+        };
+        let mut block = json!({"type": kind, "source": source});
+        if let Some(filename) = part.filename() {
+            block["title"] = json!(filename);
+        }
+        block
+    }
+
+    /// Chat-format `tool_choice` to Anthropic's `{type, name}` form
+    fn convert_tool_choice(choice: &Value) -> Option<Value> {
+        match choice.as_str() {
+            Some("auto") => Some(json!({"type": "auto"})),
+            Some("required") => Some(json!({"type": "any"})),
+            Some("none") => Some(json!({"type": "none"})),
+            Some(_) => None,
+            None => {
+                let name = choice["function"]["name"].as_str()?;
+                Some(json!({"type": "tool", "name": name}))
+            }
+        }
     }
 
     /// Convert OpenAI tools to Anthropic format with optional prompt caching
-    /// If cache_last is true, adds cache_control to the last tool for prompt caching
-    fn convert_tools(tools: &[Value], cache_last: bool) -> Vec<Value> {
+    /// If cache_control is set, it is added to the last tool for prompt caching
+    fn convert_tools(tools: &[Value], cache_control: Option<&Value>) -> Vec<Value> {
         let len = tools.len();
         tools
             .iter()
@@ -145,8 +325,8 @@ impl AnthropicAdapter {
 
                 // Add cache_control to the last tool for prompt caching
                 // This caches all tools since cache applies to everything before the marker
-                if cache_last && i == len - 1 {
-                    tool_def["cache_control"] = json!({"type": "ephemeral"});
+                if let Some(cache_control) = cache_control.filter(|_| i == len - 1) {
+                    tool_def["cache_control"] = cache_control.clone();
                 }
 
                 Some(tool_def)
@@ -160,7 +340,7 @@ impl AnthropicAdapter {
 /// OpenAI format: `{ "type": "function", "function": { "name": ..., "parameters": ... } }`
 /// Anthropic format: `{ "name": ..., "description": ..., "input_schema": ... }`
 pub fn convert_tools_to_anthropic(tools: &[Value]) -> Vec<Value> {
-    AnthropicAdapter::convert_tools(tools, true)
+    AnthropicAdapter::convert_tools(tools, Some(&json!({"type": "ephemeral"})))
 }
 
 /// Convert messages from OpenAI format to Anthropic format
@@ -185,9 +365,10 @@ pub fn convert_messages_to_anthropic(messages: &[Value]) -> Vec<Value> {
             let tool_use_id = msg.get("tool_call_id")
                 .and_then(|v| v.as_str())
                 .unwrap_or("");
-            let content = msg.get("content")
-                .and_then(|v| v.as_str())
-                .unwrap_or("");
+            let content = match msg.get("content") {
+                Some(Value::Array(parts)) => json!(convert_parts_to_anthropic(parts)),
+                other => json!(other.and_then(|v| v.as_str()).unwrap_or("")),
+            };
 
             result.push(json!({
                 "role": "user",
@@ -244,8 +425,8 @@ pub fn convert_messages_to_anthropic(messages: &[Value]) -> Vec<Value> {
             .map(|c| {
                 if c.is_string() {
                     json!([{"type": "text", "text": c.as_str().unwrap_or("")}])
-                } else if c.is_array() {
-                    c.clone()
+                } else if let Some(parts) = c.as_array() {
+                    json!(convert_parts_to_anthropic(parts))
                 } else {
                     json!([{"type": "text", "text": ""}])
                 }
@@ -261,6 +442,22 @@ pub fn convert_messages_to_anthropic(messages: &[Value]) -> Vec<Value> {
     result
 }
 
+/// Convert OpenAI `image_url` and `file` parts to Anthropic blocks, leaving
+/// parts already in Anthropic format as they are
+fn convert_parts_to_anthropic(parts: &[Value]) -> Vec<Value> {
+    parts
+        .iter()
+        .map(
+            |part| match serde_json::from_value::<ContentPart>(part.clone()) {
+                Ok(p) if p.image_url.is_some() || p.file.is_some() => {
+                    AnthropicAdapter::convert_part(&p)
+                }
+                _ => part.clone(),
+            },
+        )
+        .collect()
+}
+
 impl Default for AnthropicAdapter {
     fn default() -> Self {
         Self::new()
@@ -274,22 +471,51 @@ impl ProviderAdapter for AnthropicAdapter {
     }
 
     fn transform_request(&self, request: &ChatCompletionRequest) -> Result<Value, ProviderError> {
+        // A JSON response is the input of a forced tool call
+        if let Some(forced) = structured_output::with_response_tool(request) {
+            return self.transform_request(&forced);
+        }
+
+        let cache_control = self.cache_control();
+        let mut breakpoints = 0;
+        let mut messages = Self::convert_messages(&request.messages);
+
+        // Add system message if present - use array format with cache_control for prompt caching
+        // See: https://docs.anthropic.com/en/docs/build-with-claude/prompt-caching
+        let system = Self::extract_system(&request.messages).map(|system| {
+            let mut block = json!({"type": "text", "text": system});
+            if let Some(cache_control) = &cache_control {
+                block["cache_control"] = cache_control.clone();
+                breakpoints += 1;
+            }
+            json!([block])
+        });
+
+        // Convert tools with cache_control on last tool for prompt caching
+        let tools = request.tools.as_deref().map(|tools| {
+            let converted = Self::convert_tools(tools, cache_control.as_ref());
+            if cache_control.is_some() && !converted.is_empty() {
+                breakpoints += 1;
+            }
+            converted
+        });
+
+        // The rest of the limit goes to the latest turns
+        if let Some(cache_control) = &cache_control {
+            let count = self
+                .cache
+                .breakpoints
+                .min(MAX_CACHE_BREAKPOINTS - breakpoints);
+            Self::add_message_breakpoints(&mut messages, count, cache_control);
+        }
+
         let mut body = json!({
             "model": &request.model,
-            "messages": Self::convert_messages(&request.messages),
+            "messages": messages,
             "max_tokens": request.max_tokens.unwrap_or(4096)
         });
-
-        // Add system message if present - use array format with cache_control for prompt caching
-        // See: https://docs.anthropic.com/en/docs/build-with-claude/prompt-caching
-        if let Some(system) = Self::extract_system(&request.messages) {
-            body["system"] = json!([
-                {
-                    "type": "text",
-                    "text": system,
-                    "cache_control": {"type": "ephemeral"}
-                }
-            ]);
+        if let Some(system) = system {
+            body["system"] = system;
         }
 
         // Add temperature if specified
@@ -297,11 +523,14 @@ impl ProviderAdapter for AnthropicAdapter {
             body["temperature"] = json!(temp);
         }
 
-        // Convert tools with cache_control on last tool for prompt caching
-        if let Some(tools) = &request.tools {
-            let converted = Self::convert_tools(tools, true);
-            if !converted.is_empty() {
-                body["tools"] = json!(converted);
+        if let Some(tools) = tools.filter(|t| !t.is_empty()) {
+            body["tools"] = json!(tools);
+            if let Some(choice) = request
+                .tool_choice
+                .as_ref()
+                .and_then(Self::convert_tool_choice)
+            {
+                body["tool_choice"] = choice;
             }
         }
 
@@ -321,15 +550,25 @@ impl ProviderAdapter for AnthropicAdapter {
     ) -> Result<Value, ProviderError> {
         let mut body = self.transform_request(request)?;
 
+        // A tool result continuation must echo the signed thinking block of the
+        // tool_use turn, which OpenAI-format history does not carry, so those
+        // requests go without thinking. Neither can a forced JSON response tool.
+        let continues_tool_use = request.messages.last().is_some_and(|m| m.role == "tool");
+
         // Add Anthropic extended thinking params if enabled
         // See: https://docs.anthropic.com/en/docs/build-with-claude/extended-thinking
-        if thinking.enabled {
+        if thinking.enabled && !continues_tool_use && !structured_output::forces_tool(request) {
             // Budget tokens must be at least 1024 for Anthropic
             let budget = thinking.budget_tokens.unwrap_or(10000).max(1024);
             body["thinking"] = json!({
                 "type": "enabled",
                 "budget_tokens": budget
             });
+            // max_tokens includes the thinking budget and must exceed it
+            let max_tokens = body["max_tokens"].as_u64().unwrap_or(4096);
+            if max_tokens <= budget as u64 {
+                body["max_tokens"] = json!(max_tokens + budget as u64);
+            }
             debug!(
                 "Added Anthropic thinking params: enabled=true, budget={}",
                 budget
@@ -390,7 +629,7 @@ impl ProviderAdapter for AnthropicAdapter {
             message["tool_calls"] = json!(calls);
         }
 
-        Ok(json!({
+        let mut response = json!({
             "id": response.get("id").unwrap_or(&json!("msg_unknown")),
             "object": "chat.completion",
             "created": chrono::Utc::now().timestamp(),
@@ -405,15 +644,10 @@ impl ProviderAdapter for AnthropicAdapter {
                     _ => "stop"
                 }
             }],
-            "usage": {
-                "prompt_tokens": response.get("usage").and_then(|u| u.get("input_tokens")).unwrap_or(&json!(0)),
-                "completion_tokens": response.get("usage").and_then(|u| u.get("output_tokens")).unwrap_or(&json!(0)),
-                "total_tokens": response.get("usage").map(|u| {
-                    u.get("input_tokens").and_then(|i| i.as_u64()).unwrap_or(0) +
-                    u.get("output_tokens").and_then(|o| o.as_u64()).unwrap_or(0)
-                }).unwrap_or(0)
-            }
-        }))
+            "usage": TokenUsage::from_anthropic(&response["usage"]).to_openai()
+        });
+        structured_output::unwrap_response_tool(&mut response);
+        Ok(response)
     }
 
     fn chat_endpoint(&self) -> &str {
@@ -421,11 +655,36 @@ impl ProviderAdapter for AnthropicAdapter {
     }
 
     fn get_headers(&self, api_key: &str) -> Vec<(String, String)> {
-        vec![
+        let mut headers = vec![
             ("x-api-key".to_string(), api_key.to_string()),
             ("anthropic-version".to_string(), "2023-06-01".to_string()),
             ("content-type".to_string(), "application/json".to_string()),
-        ]
+        ];
+        if self.cache.enabled && self.cache.ttl == CacheTtl::OneHour {
+            headers.push((
+                "anthropic-beta".to_string(),
+                "extended-cache-ttl-2025-04-11".to_string(),
+            ));
+        }
+        headers
+    }
+
+    fn openai_compatible(&self) -> bool {
+        false
+    }
+
+    fn stream_decoder(&self) -> Box<dyn StreamDecoder> {
+        Box::new(ResponseToolDecoder::new(Box::new(
+            AnthropicDecoder::default(),
+        )))
+    }
+
+    fn models_path(&self) -> &str {
+        "/v1/models?limit=1000"
+    }
+
+    fn response_usage(&self, response: &Value) -> Option<TokenUsage> {
+        response.get("usage").map(TokenUsage::from_anthropic)
     }
 }
 
@@ -452,7 +711,10 @@ impl ProviderAdapter for OpenAIAdapter {
 
     fn transform_request(&self, request: &ChatCompletionRequest) -> Result<Value, ProviderError> {
         // OpenAI format is our canonical format, so minimal transformation
-        serde_json::to_value(request).map_err(|e| ProviderError::RequestFailed(e.to_string()))
+        let mut body = serde_json::to_value(request)
+            .map_err(|e| ProviderError::RequestFailed(e.to_string()))?;
+        hoist_tool_media(&mut body);
+        Ok(body)
     }
 
     fn transform_request_with_thinking(
@@ -462,6 +724,8 @@ impl ProviderAdapter for OpenAIAdapter {
     ) -> Result<Value, ProviderError> {
         let mut body = serde_json::to_value(request)
             .map_err(|e| ProviderError::RequestFailed(e.to_string()))?;
+        request_stream_usage(&mut body);
+        hoist_tool_media(&mut body);
 
         // Add OpenAI o1/o3 reasoning_effort if enabled
         // See: https://platform.openai.com/docs/guides/reasoning
@@ -500,42 +764,111 @@ impl GoogleAdapter {
         Self
     }
 
+    /// Convert an OpenAI content part to a Gemini part. Inline images and
+    /// PDFs become `inlineData`; image URLs can't be fetched, so they're text.
+    fn convert_part(part: &ContentPart) -> Value {
+        if let Some(text) = &part.text {
+            return json!({"text": text});
+        }
+        match (part.inline_data(), &part.image_url) {
+            (Some((mime_type, data)), _) => {
+                json!({"inlineData": {"mimeType": mime_type, "data": data}})
+            }
+            (None, Some(image)) => {
+                let url = image.get("url").unwrap_or(image);
+                json!({"text": format!("[image: {}]", url.as_str().unwrap_or_default())})
+            }
+            (None, None) => json!({"text": ""}),
+        }
+    }
+
     /// Convert OpenAI messages to Gemini format
+    ///
+    /// Tool calls become `functionCall` parts and tool results become
+    /// `functionResponse` parts; Gemini identifies results by function name,
+    /// so names are recovered from the earlier call with the same id.
     fn convert_messages(messages: &[ChatMessage]) -> Vec<Value> {
-        messages
-            .iter()
-            .filter(|m| m.role != "system") // System handled via systemInstruction
-            .map(|m| {
-                let role = match m.role.as_str() {
-                    "assistant" => "model",
-                    _ => "user",
-                };
-
-                let parts = match &m.content {
-                    MessageContent::Text(t) => json!([{"text": t}]),
-                    MessageContent::Parts(parts) => {
-                        let converted: Vec<Value> = parts
+        let mut call_names: std::collections::HashMap<&str, &str> =
+            std::collections::HashMap::new();
+        let mut result: Vec<Value> = Vec::new();
+
+        for m in messages.iter().filter(|m| m.role != "system") {
+            // System handled via systemInstruction
+            if m.role == "tool" {
+                let name = m
+                    .tool_call_id
+                    .as_deref()
+                    .and_then(|id| call_names.get(id))
+                    .copied()
+                    .unwrap_or_default();
+                let mut parts = vec![json!({
+                    "functionResponse": {
+                        "name": name,
+                        "response": {"content": m.content.as_text()}
+                    }
+                })];
+                // Images from the tool go in the same turn, after the response
+                if let MessageContent::Parts(content) = &m.content {
+                    parts.extend(
+                        content
                             .iter()
-                            .map(|p| {
-                                if let Some(text) = &p.text {
-                                    json!({"text": text})
-                                } else if let Some(image) = &p.image_url {
-                                    json!({"inlineData": image})
-                                } else {
-                                    json!({"text": ""})
-                                }
-                            })
-                            .collect();
-                        Value::Array(converted)
+                            .filter(|p| p.text.is_none())
+                            .map(Self::convert_part),
+                    );
+                }
+                // Responses to parallel calls must share one turn
+                match result.last_mut() {
+                    Some(last)
+                        if last["role"] == "user"
+                            && last["parts"][0].get("functionResponse").is_some() =>
+                    {
+                        if let Some(turn) = last["parts"].as_array_mut() {
+                            turn.extend(parts);
+                        }
                     }
-                };
+                    _ => result.push(json!({"role": "user", "parts": parts})),
+                }
+                continue;
+            }
 
-                json!({
-                    "role": role,
-                    "parts": parts
-                })
-            })
-            .collect()
+            let role = match m.role.as_str() {
+                "assistant" => "model",
+                _ => "user",
+            };
+
+            let mut parts: Vec<Value> = match &m.content {
+                MessageContent::Text(t) if t.is_empty() && m.tool_calls.is_some() => Vec::new(),
+                MessageContent::Text(t) => vec![json!({"text": t})],
+                MessageContent::Parts(parts) => parts.iter().map(Self::convert_part).collect(),
+            };
+
+            for call in m.tool_calls.iter().flatten() {
+                let id = call.get("id").and_then(|v| v.as_str()).unwrap_or("");
+                let func = call.get("function");
+                let name = func
+                    .and_then(|f| f.get("name"))
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("");
+                let arguments = func
+                    .and_then(|f| f.get("arguments"))
+                    .and_then(|a| a.as_str())
+                    .unwrap_or("{}");
+                call_names.insert(id, name);
+                parts.push(json!({
+                    "functionCall": {
+                        "name": name,
+                        "args": serde_json::from_str::<Value>(arguments).unwrap_or_else(|_| json!({}))
+                    }
+                }));
+            }
+
+            result.push(json!({
+                "role": role,
+                "parts": parts
+            }));
+        }
+
+        result
     }
 
     /// Convert OpenAI tools to Gemini function declarations
@@ -547,7 +880,7 @@ impl GoogleAdapter {
                 Some(json!({
                     "name": func.get("name")?,
                     "description": func.get("description").unwrap_or(&json!("")),
-                    "parameters": func.get("parameters").unwrap_or(&json!({}))
+                    "parameters": Self::convert_schema(func.get("parameters").unwrap_or(&json!({"type": "object"})))
                 }))
             })
             .collect();
@@ -555,6 +888,46 @@ impl GoogleAdapter {
         json!([{"functionDeclarations": functions}])
     }
 
+    /// Reduce a JSON schema to the OpenAPI subset Gemini accepts
+    ///
+    /// Keywords such as `additionalProperties` or `default` make the whole
+    /// request fail, so anything outside the supported set is dropped.
+    fn convert_schema(schema: &Value) -> Value {
+        const SUPPORTED: &[&str] = &[
+            "type",
+            "description",
+            "enum",
+            "format",
+            "nullable",
+            "required",
+            "items",
+            "properties",
+        ];
+        let Some(obj) = schema.as_object() else {
+            return schema.clone();
+        };
+        let mut out = serde_json::Map::new();
+        for (key, value) in obj.iter().filter(|(k, _)| SUPPORTED.contains(&k.as_str())) {
+            let converted = match key.as_str() {
+                "items" => Self::convert_schema(value),
+                "properties" => Value::Object(
+                    value
+                        .as_object()
+                        .map(|props| {
+                            props
+                                .iter()
+                                .map(|(name, prop)| (name.clone(), Self::convert_schema(prop)))
+                                .collect()
+                        })
+                        .unwrap_or_default(),
+                ),
+                _ => value.clone(),
+            };
+            out.insert(key.clone(), converted);
+        }
+        Value::Object(out)
+    }
+
     /// Extract system instruction
     fn extract_system(messages: &[ChatMessage]) -> Option<Value> {
         messages.iter().find(|m| m.role == "system").map(|m| {
@@ -601,6 +974,18 @@ impl ProviderAdapter for GoogleAdapter {
         if let Some(max_tokens) = request.max_tokens {
             gen_config["maxOutputTokens"] = json!(max_tokens);
         }
+        match &request.response_format {
+            Some(ResponseFormat::JsonSchema { json_schema }) => {
+                gen_config["responseMimeType"] = json!("application/json");
+                if let Some(schema) = &json_schema.schema {
+                    gen_config["responseSchema"] = Self::convert_schema(schema);
+                }
+            }
+            Some(ResponseFormat::JsonObject) => {
+                gen_config["responseMimeType"] = json!("application/json");
+            }
+            Some(ResponseFormat::Text) | None => {}
+        }
         if gen_config != json!({}) {
             body["generationConfig"] = gen_config;
         }
@@ -633,7 +1018,8 @@ impl ProviderAdapter for GoogleAdapter {
             }
 
             body["generationConfig"]["thinkingConfig"] = json!({
-                "thinkingBudget": budget
+                "thinkingBudget": budget,
+                "includeThoughts": true
             });
             debug!("Added Google thinking params: budget={}", budget);
         }
@@ -695,16 +1081,15 @@ impl ProviderAdapter for GoogleAdapter {
             message["tool_calls"] = json!(calls);
         }
 
-        let finish_reason = candidate
-            .get("finishReason")
-            .and_then(|r| r.as_str())
-            .map(|r| match r {
-                "STOP" => "stop",
-                "MAX_TOKENS" => "length",
-                "SAFETY" => "content_filter",
-                _ => "stop",
-            })
-            .unwrap_or("stop");
+        let finish_reason = if message.get("tool_calls").is_some() {
+            "tool_calls".to_string()
+        } else {
+            candidate
+                .get("finishReason")
+                .and_then(|r| r.as_str())
+                .map(|r| StopReason::from_gemini(r).as_openai().to_string())
+                .unwrap_or_else(|| "stop".to_string())
+        };
 
         Ok(json!({
             "id": format!("gemini-{}", uuid::Uuid::new_v4()),
@@ -716,11 +1101,7 @@ impl ProviderAdapter for GoogleAdapter {
                 "message": message,
                 "finish_reason": finish_reason
             }],
-            "usage": {
-                "prompt_tokens": response.get("usageMetadata").and_then(|u| u.get("promptTokenCount")).unwrap_or(&json!(0)),
-                "completion_tokens": response.get("usageMetadata").and_then(|u| u.get("candidatesTokenCount")).unwrap_or(&json!(0)),
-                "total_tokens": response.get("usageMetadata").and_then(|u| u.get("totalTokenCount")).unwrap_or(&json!(0))
-            }
+            "usage": TokenUsage::from_gemini(&response["usageMetadata"]).to_openai()
         }))
     }
 
@@ -735,6 +1116,51 @@ impl ProviderAdapter for GoogleAdapter {
             ("content-type".to_string(), "application/json".to_string()),
         ]
     }
+
+    fn chat_path(&self, model: &str, stream: bool) -> String {
+        if stream {
+            format!("/v1beta/models/{}:streamGenerateContent?alt=sse", model)
+        } else {
+            format!("/v1beta/models/{}:generateContent", model)
+        }
+    }
+
+    fn openai_compatible(&self) -> bool {
+        false
+    }
+
+    fn stream_decoder(&self) -> Box<dyn StreamDecoder> {
+        Box::new(GeminiDecoder::default())
+    }
+
+    fn response_usage(&self, response: &Value) -> Option<TokenUsage> {
+        response.get("usageMetadata").map(TokenUsage::from_gemini)
+    }
+
+    fn models_path(&self) -> &str {
+        "/v1beta/models?pageSize=1000"
+    }
+
+    fn parse_models(&self, response: &Value) -> Vec<ListedModel> {
+        response["models"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter(|m| {
+                m["supportedGenerationMethods"]
+                    .as_array()
+                    .is_some_and(|methods| methods.iter().any(|v| v == "generateContent"))
+            })
+            .filter_map(|m| {
+                let name = m["name"].as_str()?;
+                Some(ListedModel {
+                    id: name.trim_start_matches("models/").to_string(),
+                    context_window: m["inputTokenLimit"].as_u64().map(|n| n as usize),
+                    max_output: m["outputTokenLimit"].as_u64().map(|n| n as usize),
+                })
+            })
+            .collect()
+    }
 }
 
 /// Z.AI/GLM API adapter (OpenAI-compatible with different endpoint path)
@@ -770,6 +1196,7 @@ impl ProviderAdapter for ZaiAdapter {
     ) -> Result<Value, ProviderError> {
         let mut body = serde_json::to_value(request)
             .map_err(|e| ProviderError::RequestFailed(e.to_string()))?;
+        request_stream_usage(&mut body);
 
         // Add GLM-4.7 thinking params if enabled
         // See: https://docs.z.ai/guides/llm/glm-4.7
@@ -813,6 +1240,10 @@ impl ProviderAdapter for ZaiAdapter {
             ("content-type".to_string(), "application/json".to_string()),
         ]
     }
+
+    fn models_path(&self) -> &str {
+        "/models"
+    }
 }
 
 /// DeepSeek API adapter (OpenAI-compatible with thinking support)
@@ -847,6 +1278,7 @@ impl ProviderAdapter for DeepSeekAdapter {
     ) -> Result<Value, ProviderError> {
         let mut body = serde_json::to_value(request)
             .map_err(|e| ProviderError::RequestFailed(e.to_string()))?;
+        request_stream_usage(&mut body);
 
         // Add DeepSeek R1 thinking params if enabled
         // See: https://api-docs.deepseek.com/guides/reasoning_model
@@ -907,6 +1339,7 @@ impl ProviderAdapter for QwenAdapter {
     ) -> Result<Value, ProviderError> {
         let mut body = serde_json::to_value(request)
             .map_err(|e| ProviderError::RequestFailed(e.to_string()))?;
+        request_stream_usage(&mut body);
 
         // Add Qwen QwQ thinking params if enabled
         // See: https://help.aliyun.com/zh/model-studio/user-guide/qwq
@@ -947,23 +1380,60 @@ impl OllamaAdapter {
     }
 
     /// Convert OpenAI messages to Ollama format
+    ///
+    /// Ollama takes tool call arguments as objects rather than JSON strings.
     fn convert_messages(messages: &[ChatMessage]) -> Vec<Value> {
         messages
             .iter()
             .map(|m| {
-                let content = match &m.content {
-                    MessageContent::Text(t) => t.clone(),
-                    MessageContent::Parts(parts) => parts
+                let mut message = json!({
+                    "role": m.role,
+                    "content": m.content.as_text()
+                });
+
+                // Images go in a separate list of base64 strings; Ollama
+                // takes no documents, so those are named in the text instead
+                if let MessageContent::Parts(parts) = &m.content {
+                    let images: Vec<&str> = parts
                         .iter()
-                        .filter_map(|p| p.text.clone())
-                        .collect::<Vec<_>>()
-                        .join("\n"),
-                };
+                        .filter_map(|p| p.inline_data())
+                        .filter(|(media_type, _)| media_type.starts_with("image/"))
+                        .map(|(_, data)| data)
+                        .collect();
+                    if !images.is_empty() {
+                        message["images"] = json!(images);
+                    }
+                    for name in parts.iter().filter_map(|p| p.filename()) {
+                        let note =
+                            format!("\n[{} was not attached: documents are not supported]", name);
+                        if let Some(content) = message["content"].as_str() {
+                            message["content"] = json!(format!("{}{}", content, note));
+                        }
+                    }
+                }
 
-                json!({
-                    "role": m.role,
-                    "content": content
-                })
+                if let Some(calls) = &m.tool_calls {
+                    let converted: Vec<Value> = calls
+                        .iter()
+                        .filter_map(|call| {
+                            let func = call.get("function")?;
+                            let arguments = func
+                                .get("arguments")
+                                .and_then(|a| a.as_str())
+                                .and_then(|a| serde_json::from_str::<Value>(a).ok())
+                                .unwrap_or_else(|| json!({}));
+                            Some(json!({
+                                "function": {
+                                    "name": func.get("name")?,
+                                    "arguments": arguments
+                                }
+                            }))
+                        })
+                        .collect();
+                    message["tool_calls"] = json!(converted);
+                }
+
+                message
             })
             .collect()
     }
@@ -1000,6 +1470,16 @@ impl ProviderAdapter for OllamaAdapter {
             body["options"] = options;
         }
 
+        // Structured outputs: a JSON schema, or "json" for any JSON
+        // See: https://ollama.com/blog/structured-outputs
+        match &request.response_format {
+            Some(ResponseFormat::JsonSchema { json_schema }) => {
+                body["format"] = json_schema.schema.clone().unwrap_or_else(|| json!("json"));
+            }
+            Some(ResponseFormat::JsonObject) => body["format"] = json!("json"),
+            Some(ResponseFormat::Text) | None => {}
+        }
+
         // Convert tools to Ollama format if present
         if let Some(tools) = &request.tools {
             let ollama_tools: Vec<Value> = tools
@@ -1025,6 +1505,23 @@ impl ProviderAdapter for OllamaAdapter {
         Ok(body)
     }
 
+    fn transform_request_with_thinking(
+        &self,
+        request: &ChatCompletionRequest,
+        thinking: &ThinkingConfig,
+    ) -> Result<Value, ProviderError> {
+        let mut body = self.transform_request(request)?;
+
+        // Reasoning models (qwen3, deepseek-r1, ...) return thinking separately
+        // See: https://ollama.com/blog/thinking
+        if thinking.enabled {
+            body["think"] = json!(true);
+            debug!("Added Ollama thinking params: think=true");
+        }
+
+        Ok(body)
+    }
+
     fn transform_response(&self, response: Value, _stream: bool) -> Result<Value, ProviderError> {
         // Ollama response format:
         // {"model": "...", "message": {"role": "assistant", "content": "..."}, "done": true, ...}
@@ -1126,31 +1623,1260 @@ impl ProviderAdapter for OllamaAdapter {
         // Ollama doesn't require authentication by default
         vec![("content-type".to_string(), "application/json".to_string())]
     }
-}
 
-/// Get the appropriate adapter for a provider name
-pub fn get_adapter(provider: &str) -> Box<dyn ProviderAdapter> {
-    match provider.to_lowercase().as_str() {
-        "anthropic" => Box::new(AnthropicAdapter::new()),
-        "google" | "gemini" => Box::new(GoogleAdapter::new()),
-        "zai" | "glm" | "zhipu" => Box::new(ZaiAdapter::new()),
-        "deepseek" => Box::new(DeepSeekAdapter::new()),
-        "qwen" | "alibaba" => Box::new(QwenAdapter::new()),
-        "ollama" => Box::new(OllamaAdapter::new()),
-        // OpenAI-compatible providers (default)
-        // Includes: openai, local, lmstudio, localai, text-generation-webui, etc.
-        "openai" | "local" | "lmstudio" | "localai" => Box::new(OpenAIAdapter::new()),
-        // Default fallback for unknown providers (assume OpenAI-compatible)
-        _ => Box::new(OpenAIAdapter::new()),
+    fn openai_compatible(&self) -> bool {
+        false
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    fn stream_decoder(&self) -> Box<dyn StreamDecoder> {
+        Box::new(OllamaDecoder::default())
+    }
 
-    fn create_test_request() -> ChatCompletionRequest {
-        ChatCompletionRequest {
+    fn response_usage(&self, response: &Value) -> Option<TokenUsage> {
+        response
+            .get("eval_count")
+            .map(|_| TokenUsage::from_ollama(response))
+    }
+
+    fn requires_api_key(&self) -> bool {
+        false
+    }
+
+    fn models_path(&self) -> &str {
+        "/api/tags"
+    }
+
+    fn parse_models(&self, response: &Value) -> Vec<ListedModel> {
+        response["models"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|m| m["name"].as_str())
+            .map(ListedModel::new)
+            .collect()
+    }
+}
+
+/// A tool call id and the reasoning items that preceded the call
+type ReasoningEntry = (String, Vec<Value>);
+
+/// Reasoning items cached per tool call, at most [`REASONING_CACHE_SIZE`]
+static REASONING: LazyLock<Mutex<Vec<ReasoningEntry>>> = LazyLock::new(|| Mutex::new(Vec::new()));
+
+/// Tool calls whose reasoning is kept; older entries are dropped first
+const REASONING_CACHE_SIZE: usize = 512;
+
+/// Keep the encrypted reasoning items of a Responses API turn, keyed by the
+/// `call_id` of each function call in it. OpenAI-format history has no place
+/// for them, so [`ResponsesAdapter`] looks them up again when the tool results
+/// are sent back.
+pub fn remember_reasoning(output: &[Value]) {
+    let reasoning: Vec<Value> = output
+        .iter()
+        .filter(|item| item["type"] == "reasoning" && item.get("encrypted_content").is_some())
+        .map(|item| {
+            json!({
+                "type": "reasoning",
+                "summary": item.get("summary").cloned().unwrap_or_else(|| json!([])),
+                "encrypted_content": item["encrypted_content"]
+            })
+        })
+        .collect();
+    if reasoning.is_empty() {
+        return;
+    }
+    let Ok(mut cache) = REASONING.lock() else {
+        return;
+    };
+    for item in output.iter().filter(|item| item["type"] == "function_call") {
+        if let Some(call_id) = item["call_id"].as_str() {
+            cache.retain(|(id, _)| id != call_id);
+            cache.push((call_id.to_string(), reasoning.clone()));
+        }
+    }
+    let excess = cache.len().saturating_sub(REASONING_CACHE_SIZE);
+    cache.drain(..excess);
+}
+
+/// Reasoning items remembered for a tool call
+pub fn recall_reasoning(call_id: &str) -> Vec<Value> {
+    REASONING
+        .lock()
+        .ok()
+        .and_then(|cache| {
+            cache
+                .iter()
+                .find(|(id, _)| id == call_id)
+                .map(|(_, items)| items.clone())
+        })
+        .unwrap_or_default()
+}
+
+/// OpenAI Responses API adapter (`/v1/responses`), for reasoning models.
+/// Requests are stateless (`store: false`); encrypted reasoning is carried
+/// between tool-loop turns through [`remember_reasoning`].
+/// See: https://platform.openai.com/docs/api-reference/responses
+pub struct ResponsesAdapter;
+
+impl ResponsesAdapter {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// System messages become the `instructions`
+    fn extract_instructions(messages: &[ChatMessage]) -> Option<String> {
+        let system: Vec<String> = messages
+            .iter()
+            .filter(|m| m.role == "system")
+            .map(|m| m.content.as_text())
+            .collect();
+        (!system.is_empty()).then(|| system.join("\n\n"))
+    }
+
+    /// Convert an OpenAI content part to a Responses content item
+    fn convert_part(part: &ContentPart, text_type: &str) -> Option<Value> {
+        if let Some(text) = &part.text {
+            return Some(json!({"type": text_type, "text": text}));
+        }
+        if let Some(file) = &part.file {
+            return Some(json!({
+                "type": "input_file",
+                "filename": file["filename"],
+                "file_data": file["file_data"]
+            }));
+        }
+        let image = part.image_url.as_ref()?;
+        let url = image.get("url").unwrap_or(image);
+        Some(json!({"type": "input_image", "image_url": url}))
+    }
+
+    /// Convert OpenAI messages to Responses input items
+    ///
+    /// Assistant `tool_calls` become `function_call` items, preceded by the
+    /// reasoning that produced them, and `tool` messages become
+    /// `function_call_output` items.
+    fn convert_input(messages: &[ChatMessage]) -> Vec<Value> {
+        let mut items = Vec::new();
+
+        for m in messages.iter().filter(|m| m.role != "system") {
+            if m.role == "tool" {
+                let output = match &m.content {
+                    MessageContent::Parts(parts) => json!(parts
+                        .iter()
+                        .filter_map(|p| Self::convert_part(p, "input_text"))
+                        .collect::<Vec<_>>()),
+                    MessageContent::Text(t) => json!(t),
+                };
+                items.push(json!({
+                    "type": "function_call_output",
+                    "call_id": m.tool_call_id.as_deref().unwrap_or(""),
+                    "output": output
+                }));
+                continue;
+            }
+
+            let assistant = m.role == "assistant";
+            let text_type = if assistant {
+                "output_text"
+            } else {
+                "input_text"
+            };
+            let content: Vec<Value> = match &m.content {
+                MessageContent::Text(t) if t.is_empty() => Vec::new(),
+                MessageContent::Text(t) => vec![json!({"type": text_type, "text": t})],
+                MessageContent::Parts(parts) => parts
+                    .iter()
+                    .filter_map(|p| Self::convert_part(p, text_type))
+                    .collect(),
+            };
+            if !content.is_empty() {
+                items.push(json!({
+                    "role": if assistant { "assistant" } else { "user" },
+                    "content": content
+                }));
+            }
+
+            // Parallel calls share one set of reasoning items; send it once
+            let mut replayed = false;
+            for call in m.tool_calls.iter().flatten() {
+                let call_id = call["id"].as_str().unwrap_or("");
+                if !replayed {
+                    let reasoning = recall_reasoning(call_id);
+                    replayed = !reasoning.is_empty();
+                    items.extend(reasoning);
+                }
+                items.push(json!({
+                    "type": "function_call",
+                    "call_id": call_id,
+                    "name": call["function"]["name"].as_str().unwrap_or(""),
+                    "arguments": call["function"]["arguments"].as_str().unwrap_or("{}")
+                }));
+            }
+        }
+
+        items
+    }
+
+    /// Flatten chat-format function tools. Built-in tools (`web_search`,
+    /// `file_search`, `code_interpreter`, ...) are passed through unchanged.
+    fn convert_tools(tools: &[Value]) -> Vec<Value> {
+        tools
+            .iter()
+            .map(|tool| match tool.get("function") {
+                Some(func) if tool["type"] == "function" => json!({
+                    "type": "function",
+                    "name": func["name"],
+                    "description": func.get("description").unwrap_or(&json!("")),
+                    "parameters": func.get("parameters").unwrap_or(&json!({}))
+                }),
+                _ => tool.clone(),
+            })
+            .collect()
+    }
+
+    /// Chat-format `response_format` becomes `text.format`, with the schema
+    /// fields flattened into it
+    fn convert_response_format(format: &ResponseFormat) -> Value {
+        match format {
+            ResponseFormat::JsonSchema { json_schema } => {
+                let mut converted = json!({"type": "json_schema"});
+                if let (Some(fields), Ok(Value::Object(schema))) =
+                    (converted.as_object_mut(), serde_json::to_value(json_schema))
+                {
+                    fields.extend(schema);
+                }
+                converted
+            }
+            other => serde_json::to_value(other).unwrap_or_else(|_| json!({"type": "text"})),
+        }
+    }
+
+    /// Chat-format `tool_choice` naming a function becomes `{type, name}`
+    fn convert_tool_choice(choice: &Value) -> Value {
+        match choice["function"]["name"].as_str() {
+            Some(name) => json!({"type": "function", "name": name}),
+            None => choice.clone(),
+        }
+    }
+}
+
+impl Default for ResponsesAdapter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl ProviderAdapter for ResponsesAdapter {
+    fn name(&self) -> &str {
+        "openai-responses"
+    }
+
+    fn transform_request(&self, request: &ChatCompletionRequest) -> Result<Value, ProviderError> {
+        let mut body = json!({
+            "model": &request.model,
+            "input": Self::convert_input(&request.messages),
+            "store": false
+        });
+
+        if let Some(instructions) = Self::extract_instructions(&request.messages) {
+            body["instructions"] = json!(instructions);
+        }
+        if let Some(max_tokens) = request.max_tokens {
+            body["max_output_tokens"] = json!(max_tokens);
+        }
+        if let Some(temp) = request.temperature {
+            body["temperature"] = json!(temp);
+        }
+        if let Some(tools) = &request.tools {
+            if !tools.is_empty() {
+                body["tools"] = json!(Self::convert_tools(tools));
+            }
+        }
+        if let Some(choice) = &request.tool_choice {
+            body["tool_choice"] = Self::convert_tool_choice(choice);
+        }
+        if let Some(parallel) = request.extra.get("parallel_tool_calls") {
+            body["parallel_tool_calls"] = parallel.clone();
+        }
+        if let Some(format) = &request.response_format {
+            body["text"] = json!({"format": Self::convert_response_format(format)});
+        }
+        if request.stream.unwrap_or(false) {
+            body["stream"] = json!(true);
+        }
+
+        debug!(body = %body, "Transformed request for OpenAI Responses");
+        Ok(body)
+    }
+
+    fn transform_request_with_thinking(
+        &self,
+        request: &ChatCompletionRequest,
+        thinking: &ThinkingConfig,
+    ) -> Result<Value, ProviderError> {
+        let mut body = self.transform_request(request)?;
+
+        // Ask for encrypted reasoning so it can be replayed on the next turn
+        // See: https://platform.openai.com/docs/guides/reasoning
+        if thinking.enabled {
+            let effort = thinking.reasoning_effort.as_deref().unwrap_or("medium");
+            body["reasoning"] = json!({"effort": effort, "summary": "auto"});
+            body["include"] = json!(["reasoning.encrypted_content"]);
+            debug!("Added OpenAI Responses reasoning params: effort={}", effort);
+        }
+
+        Ok(body)
+    }
+
+    fn transform_response(&self, response: Value, _stream: bool) -> Result<Value, ProviderError> {
+        let output = response["output"].as_array().map_or(&[][..], Vec::as_slice);
+        remember_reasoning(output);
+
+        let of_type = |kind: &'static str| output.iter().filter(move |item| item["type"] == kind);
+        let content: String = of_type("message")
+            .flat_map(|item| item["content"].as_array().into_iter().flatten())
+            .filter_map(|part| part["text"].as_str().or_else(|| part["refusal"].as_str()))
+            .collect();
+        let reasoning: Vec<&str> = of_type("reasoning")
+            .flat_map(|item| item["summary"].as_array().into_iter().flatten())
+            .filter_map(|part| part["text"].as_str())
+            .collect();
+        let tool_calls: Vec<Value> = of_type("function_call")
+            .map(|item| {
+                json!({
+                    "id": item["call_id"],
+                    "type": "function",
+                    "function": {
+                        "name": item["name"],
+                        "arguments": item["arguments"]
+                    }
+                })
+            })
+            .collect();
+
+        let mut message = json!({
+            "role": "assistant",
+            "content": content
+        });
+        if !reasoning.is_empty() {
+            message["reasoning_content"] = json!(reasoning.join("\n\n"));
+        }
+        let finish_reason = if let Some(reason) = response["incomplete_details"]["reason"].as_str()
+        {
+            StopReason::from_responses_incomplete(reason)
+        } else if tool_calls.is_empty() {
+            StopReason::EndTurn
+        } else {
+            StopReason::ToolUse
+        };
+        if !tool_calls.is_empty() {
+            message["tool_calls"] = json!(tool_calls);
+        }
+
+        let created = response["created_at"]
+            .as_i64()
+            .unwrap_or_else(|| chrono::Utc::now().timestamp());
+        Ok(json!({
+            "id": response.get("id").unwrap_or(&json!("resp_unknown")),
+            "object": "chat.completion",
+            "created": created,
+            "model": response.get("model").unwrap_or(&json!("unknown")),
+            "choices": [{
+                "index": 0,
+                "message": message,
+                "finish_reason": finish_reason.as_openai()
+            }],
+            "usage": TokenUsage::from_responses(&response["usage"]).to_openai()
+        }))
+    }
+
+    fn chat_endpoint(&self) -> &str {
+        "/v1/responses"
+    }
+
+    fn get_headers(&self, api_key: &str) -> Vec<(String, String)> {
+        vec![
+            ("Authorization".to_string(), format!("Bearer {}", api_key)),
+            ("content-type".to_string(), "application/json".to_string()),
+        ]
+    }
+
+    fn openai_compatible(&self) -> bool {
+        false
+    }
+
+    fn stream_decoder(&self) -> Box<dyn StreamDecoder> {
+        Box::new(ResponsesDecoder::default())
+    }
+
+    fn response_usage(&self, response: &Value) -> Option<TokenUsage> {
+        response.get("usage").map(TokenUsage::from_responses)
+    }
+}
+
+/// Copy a built request's headers as name/value pairs
+fn request_headers(request: &reqwest::Request) -> Vec<(String, String)> {
+    request
+        .headers()
+        .iter()
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+        .collect()
+}
+
+fn insert_header(
+    request: &mut reqwest::Request,
+    name: &str,
+    value: &str,
+) -> Result<(), ProviderError> {
+    let name = reqwest::header::HeaderName::from_bytes(name.as_bytes())
+        .map_err(|e| ProviderError::RequestFailed(e.to_string()))?;
+    let value = reqwest::header::HeaderValue::from_str(value)
+        .map_err(|e| ProviderError::RequestFailed(e.to_string()))?;
+    request.headers_mut().insert(name, value);
+    Ok(())
+}
+
+/// Sign a Bedrock request with SigV4, unless it already carries a Bedrock
+/// API key as a bearer token
+fn sign_bedrock_request(
+    aws: &AwsConfig,
+    mut request: reqwest::Request,
+) -> Result<reqwest::Request, ProviderError> {
+    if request
+        .headers()
+        .contains_key(reqwest::header::AUTHORIZATION)
+    {
+        return Ok(request);
+    }
+    let credentials = AwsCredentials::resolve(aws)?;
+    let region = cloud_auth::aws_region(aws, request.url().as_str());
+    let body = request
+        .body()
+        .and_then(|b| b.as_bytes())
+        .unwrap_or_default()
+        .to_vec();
+    let signed = cloud_auth::sign_v4(
+        request.method().as_str(),
+        request.url(),
+        &request_headers(&request),
+        &body,
+        &credentials,
+        &region,
+        "bedrock",
+        chrono::Utc::now(),
+    );
+    for (name, value) in signed {
+        insert_header(&mut request, &name, &value)?;
+    }
+    Ok(request)
+}
+
+/// Headers shared by the Bedrock and Vertex adapters: the API key, when one
+/// is configured, is sent as a bearer token instead of signing the request
+fn cloud_headers(api_key: &str) -> Vec<(String, String)> {
+    let mut headers = vec![("content-type".to_string(), "application/json".to_string())];
+    if !api_key.is_empty() {
+        headers.push(("Authorization".to_string(), format!("Bearer {}", api_key)));
+    }
+    headers
+}
+
+/// Amazon Bedrock Converse API adapter. Converse has one request shape for
+/// every Bedrock model (Claude, Llama, Mistral, Nova, ...).
+/// See: https://docs.aws.amazon.com/bedrock/latest/APIReference/API_runtime_Converse.html
+pub struct BedrockAdapter {
+    aws: AwsConfig,
+}
+
+impl BedrockAdapter {
+    pub fn new(aws: AwsConfig) -> Self {
+        Self { aws }
+    }
+
+    /// Convert an OpenAI content part to a Converse block. Only inline (data
+    /// URL) images and PDFs can be sent.
+    fn convert_part(part: &ContentPart) -> Option<Value> {
+        if let Some(text) = part.text.as_deref() {
+            return (!text.is_empty()).then(|| json!({"text": text}));
+        }
+        let (media_type, data) = part.inline_data()?;
+        if let Some(format) = media_type.strip_prefix("image/") {
+            return Some(json!({"image": {"format": format, "source": {"bytes": data}}}));
+        }
+        // Document names allow only alphanumerics, spaces, hyphens, parentheses
+        // and brackets
+        let name: String = part
+            .filename()
+            .unwrap_or("document")
+            .trim_end_matches(".pdf")
+            .chars()
+            .map(|c| {
+                if c.is_alphanumeric() || " -()[]".contains(c) {
+                    c
+                } else {
+                    '-'
+                }
+            })
+            .collect();
+        (media_type == "application/pdf").then(
+            || json!({"document": {"format": "pdf", "name": name, "source": {"bytes": data}}}),
+        )
+    }
+
+    /// Convert OpenAI messages to Converse messages
+    ///
+    /// Tool calls become `toolUse` blocks and consecutive `tool` messages are
+    /// merged into a single user turn of `toolResult` blocks. Converse rejects
+    /// empty text blocks, so those are dropped.
+    fn convert_messages(messages: &[ChatMessage]) -> Vec<Value> {
+        let mut result: Vec<Value> = Vec::new();
+
+        for m in messages.iter().filter(|m| m.role != "system") {
+            if m.role == "tool" {
+                let content: Vec<Value> = match &m.content {
+                    MessageContent::Parts(parts) => {
+                        parts.iter().filter_map(Self::convert_part).collect()
+                    }
+                    MessageContent::Text(t) => vec![json!({"text": t})],
+                };
+                let block = json!({
+                    "toolResult": {
+                        "toolUseId": m.tool_call_id.as_deref().unwrap_or(""),
+                        "content": content
+                    }
+                });
+                let merged = result.last_mut().is_some_and(|last| {
+                    let blocks = last["content"]
+                        .as_array_mut()
+                        .filter(|blocks| blocks.iter().all(|b| b.get("toolResult").is_some()));
+                    blocks.map(|blocks| blocks.push(block.clone())).is_some()
+                        && last["role"] == "user"
+                });
+                if !merged {
+                    result.push(json!({"role": "user", "content": [block]}));
+                }
+                continue;
+            }
+
+            let mut content: Vec<Value> = match &m.content {
+                MessageContent::Text(t) if t.is_empty() => Vec::new(),
+                MessageContent::Text(t) => vec![json!({"text": t})],
+                MessageContent::Parts(parts) => {
+                    parts.iter().filter_map(Self::convert_part).collect()
+                }
+            };
+
+            for call in m.tool_calls.iter().flatten() {
+                let arguments = call["function"]["arguments"].as_str().unwrap_or("{}");
+                content.push(json!({
+                    "toolUse": {
+                        "toolUseId": call["id"].as_str().unwrap_or(""),
+                        "name": call["function"]["name"].as_str().unwrap_or(""),
+                        "input": serde_json::from_str::<Value>(arguments).unwrap_or_else(|_| json!({}))
+                    }
+                }));
+            }
+
+            if !content.is_empty() {
+                result.push(json!({
+                    "role": if m.role == "assistant" { "assistant" } else { "user" },
+                    "content": content
+                }));
+            }
+        }
+
+        result
+    }
+
+    /// Convert OpenAI tools to a Converse `toolConfig`
+    fn convert_tools(tools: &[Value], tool_choice: Option<&Value>) -> Option<Value> {
+        let specs: Vec<Value> = tools
+            .iter()
+            .filter_map(|tool| {
+                let func = tool.get("function")?;
+                Some(json!({
+                    "toolSpec": {
+                        "name": func.get("name")?,
+                        "description": func.get("description").unwrap_or(&json!("")),
+                        "inputSchema": {"json": func.get("parameters").unwrap_or(&json!({}))}
+                    }
+                }))
+            })
+            .collect();
+        if specs.is_empty() {
+            return None;
+        }
+
+        let mut config = json!({"tools": specs});
+        let choice = match tool_choice {
+            Some(Value::String(choice)) if choice == "required" => Some(json!({"any": {}})),
+            Some(Value::String(choice)) if choice == "auto" => Some(json!({"auto": {}})),
+            Some(choice) => choice["function"]["name"]
+                .as_str()
+                .map(|name| json!({"tool": {"name": name}})),
+            None => None,
+        };
+        if let Some(choice) = choice {
+            config["toolChoice"] = choice;
+        }
+        Some(config)
+    }
+}
+
+#[async_trait]
+impl ProviderAdapter for BedrockAdapter {
+    fn name(&self) -> &str {
+        "bedrock"
+    }
+
+    fn transform_request(&self, request: &ChatCompletionRequest) -> Result<Value, ProviderError> {
+        // A JSON response is the input of a forced tool call
+        if let Some(forced) = structured_output::with_response_tool(request) {
+            return self.transform_request(&forced);
+        }
+
+        let mut body = json!({
+            "messages": Self::convert_messages(&request.messages),
+            "inferenceConfig": {"maxTokens": request.max_tokens.unwrap_or(4096)}
+        });
+
+        let system: Vec<Value> = request
+            .messages
+            .iter()
+            .filter(|m| m.role == "system")
+            .map(|m| json!({"text": m.content.as_text()}))
+            .collect();
+        if !system.is_empty() {
+            body["system"] = json!(system);
+        }
+        if let Some(temp) = request.temperature {
+            body["inferenceConfig"]["temperature"] = json!(temp);
+        }
+        if let Some(config) = request
+            .tools
+            .as_deref()
+            .and_then(|tools| Self::convert_tools(tools, request.tool_choice.as_ref()))
+        {
+            body["toolConfig"] = config;
+        }
+
+        debug!(body = %body, "Transformed request for Bedrock Converse");
+        Ok(body)
+    }
+
+    fn transform_request_with_thinking(
+        &self,
+        request: &ChatCompletionRequest,
+        thinking: &ThinkingConfig,
+    ) -> Result<Value, ProviderError> {
+        let mut body = self.transform_request(request)?;
+
+        // Claude's extended thinking goes through additionalModelRequestFields,
+        // with the same restriction on tool result continuations as the
+        // Anthropic adapter
+        let continues_tool_use = request.messages.last().is_some_and(|m| m.role == "tool")
+            || structured_output::forces_tool(request);
+        if thinking.enabled && request.model.contains("claude") && !continues_tool_use {
+            let budget = thinking.budget_tokens.unwrap_or(10000).max(1024);
+            body["additionalModelRequestFields"] = json!({
+                "thinking": {"type": "enabled", "budget_tokens": budget}
+            });
+            let max_tokens = body["inferenceConfig"]["maxTokens"]
+                .as_u64()
+                .unwrap_or(4096);
+            if max_tokens <= budget as u64 {
+                body["inferenceConfig"]["maxTokens"] = json!(max_tokens + budget as u64);
+            }
+            debug!("Added Bedrock thinking params: budget={}", budget);
+        }
+
+        Ok(body)
+    }
+
+    fn transform_response(&self, response: Value, _stream: bool) -> Result<Value, ProviderError> {
+        let blocks = response["output"]["message"]["content"]
+            .as_array()
+            .ok_or_else(|| ProviderError::InvalidResponse("No output message".to_string()))?;
+
+        let content: String = blocks.iter().filter_map(|b| b["text"].as_str()).collect();
+        let reasoning: String = blocks
+            .iter()
+            .filter_map(|b| b["reasoningContent"]["reasoningText"]["text"].as_str())
+            .collect();
+        let tool_calls: Vec<Value> = blocks
+            .iter()
+            .filter_map(|b| b.get("toolUse"))
+            .map(|tool| {
+                json!({
+                    "id": tool["toolUseId"],
+                    "type": "function",
+                    "function": {
+                        "name": tool["name"],
+                        "arguments": tool["input"].to_string()
+                    }
+                })
+            })
+            .collect();
+
+        let mut message = json!({
+            "role": "assistant",
+            "content": content
+        });
+        if !reasoning.is_empty() {
+            message["reasoning_content"] = json!(reasoning);
+        }
+        if !tool_calls.is_empty() {
+            message["tool_calls"] = json!(tool_calls);
+        }
+        let finish_reason =
+            StopReason::from_bedrock(response["stopReason"].as_str().unwrap_or("end_turn"));
+
+        let mut response = json!({
+            "id": format!("bedrock-{}", uuid::Uuid::new_v4()),
+            "object": "chat.completion",
+            "created": chrono::Utc::now().timestamp(),
+            "model": "bedrock",
+            "choices": [{
+                "index": 0,
+                "message": message,
+                "finish_reason": finish_reason.as_openai()
+            }],
+            "usage": TokenUsage::from_bedrock(&response["usage"]).to_openai()
+        });
+        structured_output::unwrap_response_tool(&mut response);
+        Ok(response)
+    }
+
+    fn chat_endpoint(&self) -> &str {
+        // Bedrock uses the model id in the path
+        "/model/{modelId}/converse"
+    }
+
+    fn get_headers(&self, api_key: &str) -> Vec<(String, String)> {
+        cloud_headers(api_key)
+    }
+
+    fn chat_path(&self, model: &str, stream: bool) -> String {
+        let action = if stream {
+            "converse-stream"
+        } else {
+            "converse"
+        };
+        format!("/model/{}/{}", urlencoding::encode(model), action)
+    }
+
+    fn openai_compatible(&self) -> bool {
+        false
+    }
+
+    fn stream_decoder(&self) -> Box<dyn StreamDecoder> {
+        Box::new(ResponseToolDecoder::new(
+            Box::new(BedrockDecoder::default()),
+        ))
+    }
+
+    fn response_usage(&self, response: &Value) -> Option<TokenUsage> {
+        response.get("usage").map(TokenUsage::from_bedrock)
+    }
+
+    fn requires_api_key(&self) -> bool {
+        false
+    }
+
+    async fn authorize(
+        &self,
+        _client: &reqwest::Client,
+        request: reqwest::Request,
+    ) -> Result<reqwest::Request, ProviderError> {
+        sign_bedrock_request(&self.aws, request)
+    }
+}
+
+/// Amazon Bedrock InvokeModel adapter for Claude models. The body is a native
+/// Anthropic Messages request, so prompt caching and extended thinking work
+/// exactly as with the Anthropic API.
+/// See: https://docs.aws.amazon.com/bedrock/latest/userguide/model-parameters-anthropic-claude-messages.html
+pub struct BedrockInvokeAdapter {
+    aws: AwsConfig,
+}
+
+impl BedrockInvokeAdapter {
+    pub fn new(aws: AwsConfig) -> Self {
+        Self { aws }
+    }
+
+    /// Bedrock takes the model and streaming mode from the URL and wants its
+    /// own API version in the body
+    fn for_bedrock(mut body: Value) -> Value {
+        if let Some(fields) = body.as_object_mut() {
+            fields.remove("model");
+            fields.remove("stream");
+            fields.insert("anthropic_version".to_string(), json!("bedrock-2023-05-31"));
+        }
+        body
+    }
+}
+
+#[async_trait]
+impl ProviderAdapter for BedrockInvokeAdapter {
+    fn name(&self) -> &str {
+        "bedrock-invoke"
+    }
+
+    fn transform_request(&self, request: &ChatCompletionRequest) -> Result<Value, ProviderError> {
+        AnthropicAdapter::new()
+            .transform_request(request)
+            .map(Self::for_bedrock)
+    }
+
+    fn transform_request_with_thinking(
+        &self,
+        request: &ChatCompletionRequest,
+        thinking: &ThinkingConfig,
+    ) -> Result<Value, ProviderError> {
+        AnthropicAdapter::new()
+            .transform_request_with_thinking(request, thinking)
+            .map(Self::for_bedrock)
+    }
+
+    fn transform_response(&self, response: Value, stream: bool) -> Result<Value, ProviderError> {
+        AnthropicAdapter::new().transform_response(response, stream)
+    }
+
+    fn chat_endpoint(&self) -> &str {
+        "/model/{modelId}/invoke"
+    }
+
+    fn get_headers(&self, api_key: &str) -> Vec<(String, String)> {
+        cloud_headers(api_key)
+    }
+
+    fn chat_path(&self, model: &str, stream: bool) -> String {
+        let action = if stream {
+            "invoke-with-response-stream"
+        } else {
+            "invoke"
+        };
+        format!("/model/{}/{}", urlencoding::encode(model), action)
+    }
+
+    fn openai_compatible(&self) -> bool {
+        false
+    }
+
+    fn stream_decoder(&self) -> Box<dyn StreamDecoder> {
+        Box::new(ResponseToolDecoder::new(Box::new(
+            BedrockInvokeDecoder::default(),
+        )))
+    }
+
+    fn response_usage(&self, response: &Value) -> Option<TokenUsage> {
+        response.get("usage").map(TokenUsage::from_anthropic)
+    }
+
+    fn requires_api_key(&self) -> bool {
+        false
+    }
+
+    async fn authorize(
+        &self,
+        _client: &reqwest::Client,
+        request: reqwest::Request,
+    ) -> Result<reqwest::Request, ProviderError> {
+        sign_bedrock_request(&self.aws, request)
+    }
+}
+
+/// Google Vertex AI adapter for Gemini models and for Claude models published
+/// on Vertex (model ids starting with `claude`), which take an Anthropic
+/// Messages body.
+/// See: https://cloud.google.com/vertex-ai/generative-ai/docs/partner-models/claude
+pub struct VertexAdapter {
+    vertex: VertexConfig,
+}
+
+impl VertexAdapter {
+    pub fn new(vertex: VertexConfig) -> Self {
+        Self { vertex }
+    }
+
+    fn is_claude(model: &str) -> bool {
+        model.starts_with("claude")
+    }
+
+    /// Vertex takes the model from the URL and wants its own API version in
+    /// Anthropic bodies
+    fn for_vertex(mut body: Value) -> Value {
+        if let Some(fields) = body.as_object_mut() {
+            fields.remove("model");
+            fields.insert("anthropic_version".to_string(), json!("vertex-2023-10-16"));
+        }
+        body
+    }
+}
+
+#[async_trait]
+impl ProviderAdapter for VertexAdapter {
+    fn name(&self) -> &str {
+        "vertex"
+    }
+
+    fn transform_request(&self, request: &ChatCompletionRequest) -> Result<Value, ProviderError> {
+        if Self::is_claude(&request.model) {
+            AnthropicAdapter::new()
+                .transform_request(request)
+                .map(Self::for_vertex)
+        } else {
+            GoogleAdapter::new().transform_request(request)
+        }
+    }
+
+    fn transform_request_with_thinking(
+        &self,
+        request: &ChatCompletionRequest,
+        thinking: &ThinkingConfig,
+    ) -> Result<Value, ProviderError> {
+        if Self::is_claude(&request.model) {
+            AnthropicAdapter::new()
+                .transform_request_with_thinking(request, thinking)
+                .map(Self::for_vertex)
+        } else {
+            GoogleAdapter::new().transform_request_with_thinking(request, thinking)
+        }
+    }
+
+    fn transform_response(&self, response: Value, stream: bool) -> Result<Value, ProviderError> {
+        if response["type"] == "message" {
+            AnthropicAdapter::new().transform_response(response, stream)
+        } else {
+            GoogleAdapter::new().transform_response(response, stream)
+        }
+    }
+
+    fn chat_endpoint(&self) -> &str {
+        "/v1/projects/{project}/locations/{region}/publishers/google/models/{model}:generateContent"
+    }
+
+    fn get_headers(&self, api_key: &str) -> Vec<(String, String)> {
+        cloud_headers(api_key)
+    }
+
+    fn chat_path(&self, model: &str, stream: bool) -> String {
+        let (publisher, method) = match (Self::is_claude(model), stream) {
+            (true, true) => ("anthropic", "streamRawPredict"),
+            (true, false) => ("anthropic", "rawPredict"),
+            (false, true) => ("google", "streamGenerateContent?alt=sse"),
+            (false, false) => ("google", "generateContent"),
+        };
+        format!(
+            "/v1/projects/{}/locations/{}/publishers/{}/models/{}:{}",
+            cloud_auth::vertex_project(&self.vertex).unwrap_or_default(),
+            cloud_auth::vertex_region(&self.vertex),
+            publisher,
+            model,
+            method
+        )
+    }
+
+    fn openai_compatible(&self) -> bool {
+        false
+    }
+
+    fn stream_decoder(&self) -> Box<dyn StreamDecoder> {
+        Box::new(ResponseToolDecoder::new(Box::new(VertexDecoder::default())))
+    }
+
+    fn response_usage(&self, response: &Value) -> Option<TokenUsage> {
+        if let Some(usage) = response.get("usageMetadata") {
+            return Some(TokenUsage::from_gemini(usage));
+        }
+        response.get("usage").map(TokenUsage::from_anthropic)
+    }
+
+    fn requires_api_key(&self) -> bool {
+        false
+    }
+
+    async fn authorize(
+        &self,
+        client: &reqwest::Client,
+        mut request: reqwest::Request,
+    ) -> Result<reqwest::Request, ProviderError> {
+        if request
+            .headers()
+            .contains_key(reqwest::header::AUTHORIZATION)
+        {
+            return Ok(request);
+        }
+        if cloud_auth::vertex_project(&self.vertex).is_none() {
+            return Err(CloudAuthError::NoVertexProject.into());
+        }
+        let token = cloud_auth::google_access_token(client, &self.vertex).await?;
+        insert_header(&mut request, "authorization", &format!("Bearer {}", token))?;
+        Ok(request)
+    }
+}
+
+/// Azure OpenAI addresses models by deployment name
+const AZURE_CHAT_PATH: &str = "/openai/deployments/{model}/chat/completions";
+const AZURE_API_VERSION: &str = "2024-10-21";
+
+/// Headers in which adapters send the API key
+const KEY_HEADERS: [&str; 4] = ["authorization", "x-api-key", "api-key", "x-goog-api-key"];
+
+/// Append query parameters to a path that may already have some
+fn with_query(path: &str, query: &[(String, String)]) -> String {
+    let mut path = path.to_string();
+    for (key, value) in query {
+        path.push(if path.contains('?') { '&' } else { '?' });
+        path.push_str(&urlencoding::encode(key));
+        path.push('=');
+        path.push_str(&urlencoding::encode(value));
+    }
+    path
+}
+
+/// An adapter whose endpoint path, query parameters and API key placement are
+/// overridden by the provider's `path`, `query` and `auth` settings, for
+/// gateways and corporate proxies in front of a known API. Everything else is
+/// left to the wrapped adapter.
+pub struct ConfiguredAdapter {
+    name: String,
+    inner: Box<dyn ProviderAdapter>,
+    path: Option<String>,
+    query: Vec<(String, String)>,
+    auth: Option<AuthStyle>,
+    models_path: String,
+}
+
+impl ConfiguredAdapter {
+    pub fn new(inner: Box<dyn ProviderAdapter>, provider: &ProviderConfig) -> Self {
+        let mut query: Vec<(String, String)> = provider
+            .query
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        query.sort();
+        Self {
+            name: inner.name().to_string(),
+            models_path: with_query(inner.models_path(), &query),
+            path: provider.path.clone(),
+            auth: provider.auth,
+            query,
+            inner,
+        }
+    }
+
+    /// Azure OpenAI: Chat Completions under a deployment path, with an
+    /// `api-version` query parameter and the key in an `api-key` header.
+    /// The model is the deployment name. Provider settings take precedence,
+    /// so `auth: bearer` works with Entra ID tokens and `path` can point at
+    /// the `/openai/v1` API.
+    pub fn azure(provider: &ProviderConfig) -> Self {
+        let mut provider = provider.clone();
+        provider
+            .path
+            .get_or_insert_with(|| AZURE_CHAT_PATH.to_string());
+        provider
+            .query
+            .entry("api-version".to_string())
+            .or_insert_with(|| AZURE_API_VERSION.to_string());
+        provider.auth.get_or_insert(AuthStyle::ApiKey);
+
+        let mut adapter = Self::new(Box::new(OpenAIAdapter::new()), &provider);
+        adapter.name = "azure".to_string();
+        adapter.models_path = with_query("/openai/models", &adapter.query);
+        adapter
+    }
+}
+
+#[async_trait]
+impl ProviderAdapter for ConfiguredAdapter {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn transform_request(&self, request: &ChatCompletionRequest) -> Result<Value, ProviderError> {
+        self.inner.transform_request(request)
+    }
+
+    fn transform_request_with_thinking(
+        &self,
+        request: &ChatCompletionRequest,
+        thinking: &ThinkingConfig,
+    ) -> Result<Value, ProviderError> {
+        self.inner
+            .transform_request_with_thinking(request, thinking)
+    }
+
+    fn transform_response(&self, response: Value, stream: bool) -> Result<Value, ProviderError> {
+        self.inner.transform_response(response, stream)
+    }
+
+    fn chat_endpoint(&self) -> &str {
+        self.path
+            .as_deref()
+            .unwrap_or_else(|| self.inner.chat_endpoint())
+    }
+
+    fn get_headers(&self, api_key: &str) -> Vec<(String, String)> {
+        let mut headers = self.inner.get_headers(api_key);
+        let Some(auth) = self.auth else {
+            return headers;
+        };
+        headers.retain(|(name, _)| !KEY_HEADERS.contains(&name.to_lowercase().as_str()));
+        let key_header = match auth {
+            AuthStyle::Bearer => Some(("Authorization", format!("Bearer {}", api_key))),
+            AuthStyle::ApiKey => Some(("api-key", api_key.to_string())),
+            AuthStyle::XApiKey => Some(("x-api-key", api_key.to_string())),
+            AuthStyle::None => None,
+        };
+        if let Some((name, value)) = key_header.filter(|_| !api_key.is_empty()) {
+            headers.push((name.to_string(), value));
+        }
+        headers
+    }
+
+    fn chat_path(&self, model: &str, stream: bool) -> String {
+        let path = match &self.path {
+            Some(template) => template.replace("{model}", model),
+            None => self.inner.chat_path(model, stream),
+        };
+        with_query(&path, &self.query)
+    }
+
+    fn openai_compatible(&self) -> bool {
+        self.inner.openai_compatible()
+    }
+
+    fn stream_decoder(&self) -> Box<dyn StreamDecoder> {
+        self.inner.stream_decoder()
+    }
+
+    fn response_usage(&self, response: &Value) -> Option<TokenUsage> {
+        self.inner.response_usage(response)
+    }
+
+    fn requires_api_key(&self) -> bool {
+        self.auth != Some(AuthStyle::None) && self.inner.requires_api_key()
+    }
+
+    async fn authorize(
+        &self,
+        client: &reqwest::Client,
+        request: reqwest::Request,
+    ) -> Result<reqwest::Request, ProviderError> {
+        self.inner.authorize(client, request).await
+    }
+
+    fn models_path(&self) -> &str {
+        &self.models_path
+    }
+
+    fn parse_models(&self, response: &Value) -> Vec<ListedModel> {
+        self.inner.parse_models(response)
+    }
+}
+
+/// Ask an OpenAI-compatible server to report token usage at the end of a stream.
+/// Without `stream_options.include_usage`, streamed responses carry no usage.
+fn request_stream_usage(body: &mut Value) {
+    if body["stream"] == json!(true) {
+        if !body["stream_options"].is_object() {
+            body["stream_options"] = json!({});
+        }
+        body["stream_options"]["include_usage"] = json!(true);
+    }
+}
+
+/// Chat Completions takes only text in `tool` messages, so images and files
+/// from tool results move to a user message after the results
+fn hoist_tool_media(body: &mut Value) {
+    let Some(messages) = body["messages"].as_array_mut() else {
+        return;
+    };
+    let mut result: Vec<Value> = Vec::new();
+    let mut media: Vec<Value> = Vec::new();
+    for mut message in messages.drain(..) {
+        let is_tool = message["role"] == "tool";
+        if !is_tool && !media.is_empty() {
+            result.push(tool_media_message(&mut media));
+        }
+        if let (true, Some(parts)) = (is_tool, message["content"].as_array()) {
+            let (text, other): (Vec<&Value>, Vec<&Value>) =
+                parts.iter().partition(|p| p["type"] == "text");
+            media.extend(other.into_iter().cloned());
+            let text: Vec<&str> = text.iter().filter_map(|p| p["text"].as_str()).collect();
+            message["content"] = json!(text.join("\n"));
+        }
+        result.push(message);
+    }
+    if !media.is_empty() {
+        result.push(tool_media_message(&mut media));
+    }
+    *messages = result;
+}
+
+fn tool_media_message(media: &mut Vec<Value>) -> Value {
+    let mut content =
+        vec![json!({"type": "text", "text": "Attachments from the tool results above:"})];
+    content.append(media);
+    json!({"role": "user", "content": content})
+}
+
+/// Get the appropriate adapter for a provider name
+pub fn get_adapter(provider: &str) -> Box<dyn ProviderAdapter> {
+    match provider.to_lowercase().as_str() {
+        "anthropic" => Box::new(AnthropicAdapter::new()),
+        "google" | "gemini" => Box::new(GoogleAdapter::new()),
+        "zai" | "glm" | "zhipu" => Box::new(ZaiAdapter::new()),
+        "deepseek" => Box::new(DeepSeekAdapter::new()),
+        "qwen" | "alibaba" => Box::new(QwenAdapter::new()),
+        "ollama" => Box::new(OllamaAdapter::new()),
+        "responses" | "openai-responses" => Box::new(ResponsesAdapter::new()),
+        "bedrock" => Box::new(BedrockAdapter::new(AwsConfig::default())),
+        "bedrock-invoke" => Box::new(BedrockInvokeAdapter::new(AwsConfig::default())),
+        "vertex" | "vertex-ai" => Box::new(VertexAdapter::new(VertexConfig::default())),
+        "azure" | "azure-openai" => Box::new(ConfiguredAdapter::azure(&ProviderConfig::default())),
+        // OpenAI-compatible providers (default)
+        // Includes: openai, local, lmstudio, localai, text-generation-webui, etc.
+        "openai" | "local" | "lmstudio" | "localai" => Box::new(OpenAIAdapter::new()),
+        // Default fallback for unknown providers (assume OpenAI-compatible)
+        _ => Box::new(OpenAIAdapter::new()),
+    }
+}
+
+/// The adapter for a configured provider: its `adapter` setting if present,
+/// otherwise the one named after the provider. Cloud adapters get the
+/// provider's `aws` or `vertex` settings, and `path`, `query` and `auth`
+/// override where and how any adapter sends its requests.
+pub fn adapter_for(name: &str, provider: &ProviderConfig) -> Box<dyn ProviderAdapter> {
+    let adapter = provider.adapter.as_deref().unwrap_or(name);
+    let aws = || provider.aws.clone().unwrap_or_default();
+    let adapter: Box<dyn ProviderAdapter> = match adapter.to_lowercase().as_str() {
+        "anthropic" => Box::new(AnthropicAdapter::with_cache(provider.cache.clone())),
+        "bedrock" => Box::new(BedrockAdapter::new(aws())),
+        "bedrock-invoke" => Box::new(BedrockInvokeAdapter::new(aws())),
+        "vertex" | "vertex-ai" => Box::new(VertexAdapter::new(
+            provider.vertex.clone().unwrap_or_default(),
+        )),
+        "azure" | "azure-openai" => return Box::new(ConfiguredAdapter::azure(provider)),
+        _ => get_adapter(adapter),
+    };
+    if provider.path.is_some() || !provider.query.is_empty() || provider.auth.is_some() {
+        Box::new(ConfiguredAdapter::new(adapter, provider))
+    } else {
+        adapter
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_test_request() -> ChatCompletionRequest {
+        ChatCompletionRequest {
             model: "gpt-4".to_string(),
             messages: vec![
                 ChatMessage {
@@ -1173,6 +2899,7 @@ mod tests {
             stream: None,
             tools: None,
             tool_choice: None,
+            response_format: None,
             extra: std::collections::HashMap::new(),
         }
     }
@@ -1427,6 +3154,275 @@ mod tests {
         assert_eq!(result["choices"][0]["finish_reason"], "stop");
     }
 
+    /// A tool-use round trip as OpenAI clients send it (note the null content)
+    fn create_tool_request() -> ChatCompletionRequest {
+        serde_json::from_value(json!({
+            "model": "test-model",
+            "messages": [
+                {"role": "system", "content": "You are helpful."},
+                {"role": "user", "content": "List files"},
+                {"role": "assistant", "content": null, "tool_calls": [
+                    {"id": "call_1", "type": "function",
+                     "function": {"name": "bash", "arguments": "{\"command\":\"ls\"}"}},
+                    {"id": "call_2", "type": "function",
+                     "function": {"name": "read_file", "arguments": "{\"path\":\"a\"}"}}
+                ]},
+                {"role": "tool", "tool_call_id": "call_1", "content": "a b"},
+                {"role": "tool", "tool_call_id": "call_2", "content": "hello"}
+            ]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_anthropic_tool_round_trip() {
+        let result = AnthropicAdapter::new()
+            .transform_request(&create_tool_request())
+            .unwrap();
+        let messages = result["messages"].as_array().unwrap();
+
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[1]["content"][0]["type"], "tool_use");
+        assert_eq!(messages[1]["content"][0]["input"]["command"], "ls");
+        // Parallel results share one user turn
+        assert_eq!(messages[2]["role"], "user");
+        assert_eq!(messages[2]["content"][1]["tool_use_id"], "call_2");
+    }
+
+    #[test]
+    fn test_anthropic_rolling_cache_breakpoints() {
+        let marked = |body: &Value| -> Vec<(usize, usize)> {
+            let messages = body["messages"].as_array().unwrap();
+            messages
+                .iter()
+                .enumerate()
+                .flat_map(|(i, m)| {
+                    let blocks = m["content"].as_array().unwrap();
+                    blocks
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, b)| b.get("cache_control").is_some())
+                        .map(move |(j, _)| (i, j))
+                })
+                .collect()
+        };
+        let mut request = create_tool_request();
+        request.tools = Some(vec![json!({
+            "type": "function",
+            "function": {"name": "bash", "description": "Run", "parameters": {}}
+        })]);
+
+        // System and tools use two breakpoints; the two latest user turns
+        // (here the first prompt and the merged tool results) get the rest
+        let body = AnthropicAdapter::new().transform_request(&request).unwrap();
+        assert_eq!(marked(&body), vec![(0, 0), (2, 1)]);
+        assert_eq!(
+            body["messages"][2]["content"][1]["cache_control"]["type"],
+            "ephemeral"
+        );
+        assert!(body["messages"][2]["content"][1]["cache_control"]
+            .get("ttl")
+            .is_none());
+
+        // More configured breakpoints than the API limit are capped at four
+        let cache = PromptCacheConfig {
+            breakpoints: 8,
+            ttl: CacheTtl::OneHour,
+            ..Default::default()
+        };
+        request
+            .messages
+            .push(serde_json::from_value(json!({"role": "user", "content": "Again"})).unwrap());
+        let adapter = AnthropicAdapter::with_cache(cache);
+        let body = adapter.transform_request(&request).unwrap();
+        assert_eq!(marked(&body), vec![(2, 1), (3, 0)]);
+        assert_eq!(body["system"][0]["cache_control"]["ttl"], "1h");
+        assert_eq!(body["tools"][0]["cache_control"]["ttl"], "1h");
+        assert!(adapter.get_headers("key").contains(&(
+            "anthropic-beta".to_string(),
+            "extended-cache-ttl-2025-04-11".to_string()
+        )));
+
+        let cache = PromptCacheConfig {
+            enabled: false,
+            ..Default::default()
+        };
+        let adapter = AnthropicAdapter::with_cache(cache);
+        let body = adapter.transform_request(&request).unwrap();
+        assert!(marked(&body).is_empty());
+        assert!(body["system"][0].get("cache_control").is_none());
+        assert!(body["tools"][0].get("cache_control").is_none());
+        assert_eq!(adapter.get_headers("key").len(), 3);
+    }
+
+    #[test]
+    fn test_response_format_mapping() {
+        let mut request = create_test_request();
+        request.response_format = Some(
+            serde_json::from_value(json!({
+                "type": "json_schema",
+                "json_schema": {
+                    "name": "answer",
+                    "strict": true,
+                    "schema": {
+                        "type": "object",
+                        "properties": {"value": {"type": "integer"}},
+                        "required": ["value"],
+                        "additionalProperties": false
+                    }
+                }
+            }))
+            .unwrap(),
+        );
+
+        let body = OpenAIAdapter::new().transform_request(&request).unwrap();
+        assert_eq!(body["response_format"]["json_schema"]["name"], "answer");
+
+        let body = ResponsesAdapter::new().transform_request(&request).unwrap();
+        assert_eq!(body["text"]["format"]["type"], "json_schema");
+        assert_eq!(body["text"]["format"]["name"], "answer");
+        assert_eq!(body["text"]["format"]["strict"], true);
+
+        let body = GoogleAdapter::new().transform_request(&request).unwrap();
+        let config = &body["generationConfig"];
+        assert_eq!(config["responseMimeType"], "application/json");
+        assert_eq!(config["responseSchema"]["required"][0], "value");
+        assert!(config["responseSchema"]
+            .get("additionalProperties")
+            .is_none());
+
+        let body = OllamaAdapter::new().transform_request(&request).unwrap();
+        assert_eq!(body["format"]["properties"]["value"]["type"], "integer");
+
+        // Anthropic forces a call of the response tool, without thinking
+        let adapter = AnthropicAdapter::new();
+        let thinking = ThinkingConfig {
+            enabled: true,
+            ..Default::default()
+        };
+        let body = adapter
+            .transform_request_with_thinking(&request, &thinking)
+            .unwrap();
+        assert_eq!(body["tools"][0]["name"], structured_output::RESPONSE_TOOL);
+        assert_eq!(body["tools"][0]["input_schema"]["required"][0], "value");
+        assert_eq!(
+            body["tool_choice"],
+            json!({"type": "tool", "name": structured_output::RESPONSE_TOOL})
+        );
+        assert!(body.get("thinking").is_none());
+
+        let response = adapter
+            .transform_response(
+                json!({
+                    "id": "msg_1",
+                    "content": [{
+                        "type": "tool_use",
+                        "id": "toolu_1",
+                        "name": structured_output::RESPONSE_TOOL,
+                        "input": {"value": 42}
+                    }],
+                    "stop_reason": "tool_use",
+                    "usage": {"input_tokens": 10, "output_tokens": 5}
+                }),
+                false,
+            )
+            .unwrap();
+        assert_eq!(
+            response["choices"][0]["message"]["content"],
+            "{\"value\":42}"
+        );
+        assert_eq!(response["choices"][0]["finish_reason"], "stop");
+
+        request.response_format = Some(ResponseFormat::JsonObject);
+        let body = OllamaAdapter::new().transform_request(&request).unwrap();
+        assert_eq!(body["format"], "json");
+        let body = BedrockAdapter::new(AwsConfig::default())
+            .transform_request(&request)
+            .unwrap();
+        assert_eq!(
+            body["toolConfig"]["toolChoice"]["tool"]["name"],
+            structured_output::RESPONSE_TOOL
+        );
+    }
+
+    #[test]
+    fn test_anthropic_thinking_params() {
+        let adapter = AnthropicAdapter::new();
+        let thinking = ThinkingConfig {
+            enabled: true,
+            budget_tokens: Some(8000),
+            ..Default::default()
+        };
+
+        let result = adapter
+            .transform_request_with_thinking(&create_test_request(), &thinking)
+            .unwrap();
+        assert_eq!(result["thinking"]["budget_tokens"], 8000);
+        assert_eq!(result["max_tokens"], 9000);
+
+        // Tool result continuations cannot carry the signed thinking block
+        let result = adapter
+            .transform_request_with_thinking(&create_tool_request(), &thinking)
+            .unwrap();
+        assert!(result.get("thinking").is_none());
+    }
+
+    #[test]
+    fn test_google_tool_round_trip() {
+        let adapter = GoogleAdapter::new();
+        let mut request = create_tool_request();
+        request.tools = Some(vec![json!({
+            "type": "function",
+            "function": {
+                "name": "bash",
+                "parameters": {
+                    "type": "object",
+                    "additionalProperties": false,
+                    "properties": {"command": {"type": "string", "default": "ls"}}
+                }
+            }
+        })]);
+        let result = adapter.transform_request(&request).unwrap();
+        let contents = result["contents"].as_array().unwrap();
+
+        assert_eq!(contents[1]["role"], "model");
+        assert_eq!(contents[1]["parts"][0]["functionCall"]["name"], "bash");
+        assert_eq!(
+            contents[2]["parts"][1]["functionResponse"]["name"],
+            "read_file"
+        );
+        assert_eq!(
+            contents[2]["parts"][1]["functionResponse"]["response"]["content"],
+            "hello"
+        );
+
+        let params = &result["tools"][0]["functionDeclarations"][0]["parameters"];
+        assert!(params.get("additionalProperties").is_none());
+        assert!(params["properties"]["command"].get("default").is_none());
+        assert_eq!(params["properties"]["command"]["type"], "string");
+
+        assert_eq!(
+            adapter.chat_path("gemini-2.5-flash", true),
+            "/v1beta/models/gemini-2.5-flash:streamGenerateContent?alt=sse"
+        );
+    }
+
+    #[test]
+    fn test_ollama_tool_round_trip() {
+        let result = OllamaAdapter::new()
+            .transform_request(&create_tool_request())
+            .unwrap();
+        let messages = result["messages"].as_array().unwrap();
+
+        // Ollama wants arguments as an object, not a JSON string
+        assert_eq!(
+            messages[2]["tool_calls"][0]["function"]["arguments"]["command"],
+            "ls"
+        );
+        assert_eq!(messages[3]["role"], "tool");
+        assert_eq!(messages[3]["content"], "a b");
+    }
+
     #[test]
     fn test_google_transform_response_no_candidates() {
         let adapter = GoogleAdapter::new();
@@ -1435,4 +3431,503 @@ mod tests {
         let result = adapter.transform_response(response, false);
         assert!(matches!(result, Err(ProviderError::InvalidResponse(_))));
     }
+
+    #[test]
+    fn test_response_usage() {
+        let anthropic = AnthropicAdapter::new().response_usage(&json!({
+            "usage": {"input_tokens": 10, "output_tokens": 5, "cache_creation_input_tokens": 300}
+        }));
+        assert_eq!(anthropic.unwrap().cache_write_tokens, 300);
+
+        let google = GoogleAdapter::new().response_usage(&json!({
+            "usageMetadata": {"promptTokenCount": 40, "candidatesTokenCount": 8, "thoughtsTokenCount": 2}
+        }));
+        assert_eq!(google.unwrap().output_tokens, 10);
+
+        let ollama = OllamaAdapter::new()
+            .response_usage(&json!({"done": true, "prompt_eval_count": 12, "eval_count": 3}));
+        assert_eq!(ollama.unwrap().input_tokens, 12);
+
+        let openai = OpenAIAdapter::new();
+        assert!(openai.response_usage(&json!({"choices": []})).is_none());
+
+        let mut request = create_test_request();
+        request.stream = Some(true);
+        let body = openai
+            .transform_request_with_thinking(&request, &ThinkingConfig::default())
+            .unwrap();
+        assert_eq!(body["stream_options"]["include_usage"], true);
+    }
+
+    #[test]
+    fn test_parse_models() {
+        let openai = OpenAIAdapter::new()
+            .parse_models(&json!({"object": "list", "data": [{"id": "gpt-4o"}, {"id": "o3"}]}));
+        assert_eq!(
+            openai,
+            vec![ListedModel::new("gpt-4o"), ListedModel::new("o3")]
+        );
+
+        let google = GoogleAdapter::new().parse_models(&json!({"models": [
+            {
+                "name": "models/gemini-2.5-pro",
+                "inputTokenLimit": 1048576,
+                "outputTokenLimit": 65536,
+                "supportedGenerationMethods": ["generateContent", "countTokens"]
+            },
+            {"name": "models/text-embedding-004", "supportedGenerationMethods": ["embedContent"]}
+        ]}));
+        assert_eq!(google.len(), 1);
+        assert_eq!(google[0].id, "gemini-2.5-pro");
+        assert_eq!(google[0].context_window, Some(1_048_576));
+
+        let ollama = OllamaAdapter::new()
+            .parse_models(&json!({"models": [{"name": "qwen3:8b", "size": 5200000000u64}]}));
+        assert_eq!(ollama, vec![ListedModel::new("qwen3:8b")]);
+        assert_eq!(OllamaAdapter::new().models_path(), "/api/tags");
+    }
+
+    #[test]
+    fn test_responses_tool_round_trip() {
+        let adapter = ResponsesAdapter::new();
+        // The turn that produced the calls, as the API returned it
+        remember_reasoning(&[
+            json!({"type": "reasoning", "id": "rs_1", "summary": [], "encrypted_content": "enc-1"}),
+            json!({"type": "function_call", "call_id": "call_1", "name": "bash", "arguments": "{}"}),
+            json!({"type": "function_call", "call_id": "call_2", "name": "read_file", "arguments": "{}"}),
+        ]);
+
+        let mut request = create_tool_request();
+        request.tools = Some(vec![
+            json!({"type": "function", "function": {"name": "bash", "parameters": {"type": "object"}}}),
+            json!({"type": "web_search"}),
+        ]);
+        let thinking = ThinkingConfig {
+            enabled: true,
+            reasoning_effort: Some("high".to_string()),
+            ..Default::default()
+        };
+        let body = adapter
+            .transform_request_with_thinking(&request, &thinking)
+            .unwrap();
+
+        assert_eq!(body["instructions"], "You are helpful.");
+        assert_eq!(body["store"], false);
+        assert_eq!(body["reasoning"]["effort"], "high");
+        assert_eq!(body["include"][0], "reasoning.encrypted_content");
+        assert_eq!(body["tools"][0]["name"], "bash");
+        assert_eq!(body["tools"][1], json!({"type": "web_search"}));
+
+        let input = body["input"].as_array().unwrap();
+        let kinds: Vec<&str> = input
+            .iter()
+            .map(|item| item["type"].as_str().unwrap_or("message"))
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                "message",
+                "reasoning",
+                "function_call",
+                "function_call",
+                "function_call_output",
+                "function_call_output"
+            ]
+        );
+        assert_eq!(input[0]["content"][0]["type"], "input_text");
+        // Replayed without the item id, since nothing is stored server-side
+        assert_eq!(input[1]["encrypted_content"], "enc-1");
+        assert!(input[1].get("id").is_none());
+        assert_eq!(input[2]["call_id"], "call_1");
+        assert_eq!(input[5]["output"], "hello");
+    }
+
+    #[test]
+    fn test_responses_transform_response() {
+        let response = json!({
+            "id": "resp_1",
+            "created_at": 1700000000,
+            "model": "o4-mini",
+            "output": [
+                {"type": "reasoning", "id": "rs_2", "encrypted_content": "enc-2",
+                 "summary": [{"type": "summary_text", "text": "Check the file."}]},
+                {"type": "message", "role": "assistant",
+                 "content": [{"type": "output_text", "text": "Reading it."}]},
+                {"type": "function_call", "call_id": "call_rsp2", "name": "read_file",
+                 "arguments": "{\"path\":\"a\"}"}
+            ],
+            "usage": {"input_tokens": 20, "output_tokens": 10,
+                      "input_tokens_details": {"cached_tokens": 5}}
+        });
+
+        let result = ResponsesAdapter::new()
+            .transform_response(response, false)
+            .unwrap();
+        let message = &result["choices"][0]["message"];
+        assert_eq!(message["content"], "Reading it.");
+        assert_eq!(message["reasoning_content"], "Check the file.");
+        assert_eq!(message["tool_calls"][0]["id"], "call_rsp2");
+        assert_eq!(result["choices"][0]["finish_reason"], "tool_calls");
+        assert_eq!(result["usage"]["prompt_tokens"], 20);
+        assert_eq!(
+            recall_reasoning("call_rsp2")[0]["encrypted_content"],
+            "enc-2"
+        );
+    }
+
+    #[test]
+    fn test_adapter_for() {
+        let mut provider: ProviderConfig =
+            serde_json::from_value(json!({"base_url": "https://api.openai.com"})).unwrap();
+        assert_eq!(adapter_for("openai", &provider).name(), "openai");
+
+        provider.adapter = Some("responses".to_string());
+        let adapter = adapter_for("openai", &provider);
+        assert_eq!(adapter.name(), "openai-responses");
+        assert_eq!(adapter.chat_endpoint(), "/v1/responses");
+
+        provider.adapter = None;
+        provider.vertex = Some(VertexConfig {
+            project_id: Some("my-project".to_string()),
+            region: Some("europe-west1".to_string()),
+            ..Default::default()
+        });
+        let adapter = adapter_for("vertex", &provider);
+        assert!(!adapter.requires_api_key());
+        assert_eq!(
+            adapter.chat_path("gemini-2.5-pro", false),
+            "/v1/projects/my-project/locations/europe-west1/publishers/google/models/gemini-2.5-pro:generateContent"
+        );
+        assert_eq!(
+            adapter.chat_path("claude-sonnet-4-5@20250929", true),
+            "/v1/projects/my-project/locations/europe-west1/publishers/anthropic/models/claude-sonnet-4-5@20250929:streamRawPredict"
+        );
+    }
+
+    #[test]
+    fn test_bedrock_tool_round_trip() {
+        let mut request = create_tool_request();
+        request.tools = Some(vec![json!({
+            "type": "function",
+            "function": {"name": "bash", "parameters": {"type": "object"}}
+        })]);
+        request.tool_choice = Some(json!("required"));
+        let body = BedrockAdapter::new(AwsConfig::default())
+            .transform_request(&request)
+            .unwrap();
+
+        assert_eq!(body["system"][0]["text"], "You are helpful.");
+        assert!(body.get("model").is_none());
+        assert_eq!(body["toolConfig"]["tools"][0]["toolSpec"]["name"], "bash");
+        assert_eq!(body["toolConfig"]["toolChoice"], json!({"any": {}}));
+
+        let messages = body["messages"].as_array().unwrap();
+        assert_eq!(messages.len(), 3);
+        // The null content of the assistant turn leaves only the tool uses
+        assert_eq!(messages[1]["content"].as_array().unwrap().len(), 2);
+        assert_eq!(
+            messages[1]["content"][0]["toolUse"]["input"]["command"],
+            "ls"
+        );
+        // Parallel results share one user turn
+        assert_eq!(messages[2]["role"], "user");
+        assert_eq!(
+            messages[2]["content"][1]["toolResult"]["toolUseId"],
+            "call_2"
+        );
+    }
+
+    #[test]
+    fn test_bedrock_transform_response() {
+        let response = json!({
+            "output": {"message": {"role": "assistant", "content": [
+                {"reasoningContent": {"reasoningText": {"text": "Look it up."}}},
+                {"text": "Checking."},
+                {"toolUse": {"toolUseId": "tooluse_1", "name": "get_weather",
+                             "input": {"city": "Paris"}}}
+            ]}},
+            "stopReason": "tool_use",
+            "usage": {"inputTokens": 30, "outputTokens": 12, "totalTokens": 42}
+        });
+
+        let result = BedrockAdapter::new(AwsConfig::default())
+            .transform_response(response, false)
+            .unwrap();
+        let message = &result["choices"][0]["message"];
+        assert_eq!(message["content"], "Checking.");
+        assert_eq!(message["reasoning_content"], "Look it up.");
+        assert_eq!(message["tool_calls"][0]["id"], "tooluse_1");
+        assert_eq!(
+            message["tool_calls"][0]["function"]["arguments"],
+            "{\"city\":\"Paris\"}"
+        );
+        assert_eq!(result["choices"][0]["finish_reason"], "tool_calls");
+        assert_eq!(result["usage"]["prompt_tokens"], 30);
+    }
+
+    #[test]
+    fn test_bedrock_invoke_request() {
+        let adapter = BedrockInvokeAdapter::new(AwsConfig::default());
+        let mut request = create_test_request();
+        request.stream = Some(true);
+        let body = adapter.transform_request(&request).unwrap();
+
+        assert_eq!(body["anthropic_version"], "bedrock-2023-05-31");
+        assert!(body.get("model").is_none());
+        assert!(body.get("stream").is_none());
+        assert_eq!(body["system"][0]["text"], "You are helpful.");
+        // Model ids contain a colon, which must be escaped in the path
+        assert_eq!(
+            adapter.chat_path("anthropic.claude-sonnet-4-5-20250929-v1:0", true),
+            "/model/anthropic.claude-sonnet-4-5-20250929-v1%3A0/invoke-with-response-stream"
+        );
+    }
+
+    #[test]
+    fn test_azure_adapter() {
+        let provider: ProviderConfig = serde_json::from_value(json!({
+            "base_url": "https://my-resource.openai.azure.com"
+        }))
+        .unwrap();
+        let adapter = adapter_for("azure", &provider);
+
+        assert_eq!(adapter.name(), "azure");
+        assert_eq!(
+            adapter.chat_path("gpt-4o-prod", true),
+            "/openai/deployments/gpt-4o-prod/chat/completions?api-version=2024-10-21"
+        );
+        assert_eq!(
+            adapter.models_path(),
+            "/openai/models?api-version=2024-10-21"
+        );
+        let headers = adapter.get_headers("azure-key");
+        assert!(headers.contains(&("api-key".to_string(), "azure-key".to_string())));
+        assert!(!headers.iter().any(|(name, _)| name == "Authorization"));
+
+        // Provider settings win over the Azure defaults
+        let provider: ProviderConfig = serde_json::from_value(json!({
+            "base_url": "https://my-resource.openai.azure.com",
+            "adapter": "azure",
+            "query": {"api-version": "2025-04-01-preview"},
+            "auth": "bearer"
+        }))
+        .unwrap();
+        let adapter = adapter_for("work", &provider);
+        assert!(adapter
+            .chat_path("gpt-4o", false)
+            .ends_with("?api-version=2025-04-01-preview"));
+        assert!(adapter.get_headers("entra-token").contains(&(
+            "Authorization".to_string(),
+            "Bearer entra-token".to_string()
+        )));
+    }
+
+    #[test]
+    fn test_configured_adapter_overrides() {
+        let provider: ProviderConfig = serde_json::from_value(json!({
+            "base_url": "https://gateway.example.com",
+            "adapter": "anthropic",
+            "path": "/llm/{model}/messages",
+            "query": {"team": "platform", "tier": "a b"},
+            "auth": "bearer"
+        }))
+        .unwrap();
+        let adapter = adapter_for("gateway", &provider);
+
+        assert_eq!(adapter.name(), "anthropic");
+        assert!(!adapter.openai_compatible());
+        assert_eq!(
+            adapter.chat_path("claude-sonnet-4-5", true),
+            "/llm/claude-sonnet-4-5/messages?team=platform&tier=a%20b"
+        );
+        let headers = adapter.get_headers("k");
+        assert!(headers.contains(&("Authorization".to_string(), "Bearer k".to_string())));
+        assert!(!headers.iter().any(|(name, _)| name == "x-api-key"));
+        // The Anthropic version header is untouched
+        assert!(headers.iter().any(|(name, _)| name == "anthropic-version"));
+
+        // Query parameters join ones already in the adapter's path
+        let provider: ProviderConfig = serde_json::from_value(json!({
+            "base_url": "https://gateway.example.com",
+            "adapter": "google",
+            "query": {"key": "g"},
+            "auth": "none"
+        }))
+        .unwrap();
+        let adapter = adapter_for("gemini-gateway", &provider);
+        assert!(adapter
+            .chat_path("gemini-2.5-pro", true)
+            .ends_with(":streamGenerateContent?alt=sse&key=g"));
+        assert!(!adapter.requires_api_key());
+        assert!(!adapter
+            .get_headers("")
+            .iter()
+            .any(|(name, _)| name.eq_ignore_ascii_case("x-goog-api-key")
+                || name.eq_ignore_ascii_case("authorization")));
+    }
+
+    #[tokio::test]
+    async fn test_bedrock_authorize_signs_request() {
+        let adapter = BedrockAdapter::new(AwsConfig {
+            region: Some("eu-west-1".to_string()),
+            access_key_id: Some("AKIDEXAMPLE".to_string()),
+            secret_access_key: Some("secret".to_string()),
+            session_token: Some("session".to_string()),
+            ..Default::default()
+        });
+        let client = reqwest::Client::new();
+        let request = client
+            .post("http://127.0.0.1:9/model/m/converse")
+            .header("content-type", "application/json")
+            .body("{}")
+            .build()
+            .unwrap();
+
+        let signed = adapter.authorize(&client, request).await.unwrap();
+        let authorization = signed.headers()["authorization"].to_str().unwrap();
+        assert!(authorization.starts_with("AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/"));
+        assert!(authorization.contains("/eu-west-1/bedrock/aws4_request"));
+        assert!(authorization
+            .contains("SignedHeaders=content-type;host;x-amz-date;x-amz-security-token"));
+        assert_eq!(signed.headers()["x-amz-security-token"], "session");
+
+        // A Bedrock API key is sent as-is
+        let request = client
+            .post("http://127.0.0.1:9/model/m/converse")
+            .header("authorization", "Bearer bedrock-key")
+            .build()
+            .unwrap();
+        let passed = adapter.authorize(&client, request).await.unwrap();
+        assert_eq!(passed.headers()["authorization"], "Bearer bedrock-key");
+        assert!(passed.headers().get("x-amz-date").is_none());
+    }
+
+    /// A prompt with an image and a PDF, and a tool result carrying an image
+    fn create_attachment_request() -> ChatCompletionRequest {
+        serde_json::from_value(json!({
+            "model": "test-model",
+            "messages": [
+                {"role": "user", "content": [
+                    {"type": "text", "text": "What do these show?"},
+                    {"type": "image_url", "image_url": {"url": "data:image/png;base64,iVBOR"}},
+                    {"type": "file", "file": {"filename": "spec v2.pdf", "file_data": "data:application/pdf;base64,JVBER"}}
+                ]},
+                {"role": "assistant", "content": null, "tool_calls": [
+                    {"id": "call_1", "type": "function",
+                     "function": {"name": "read_file", "arguments": "{\"path\":\"a.jpg\"}"}}
+                ]},
+                {"role": "tool", "tool_call_id": "call_1", "content": [
+                    {"type": "text", "text": "Image file 'a.jpg'"},
+                    {"type": "image_url", "image_url": {"url": "data:image/jpeg;base64,/9j/4"}}
+                ]},
+                {"role": "user", "content": "Compare them"}
+            ]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_attachment_conversions() {
+        let request = create_attachment_request();
+
+        let body = AnthropicAdapter::new().transform_request(&request).unwrap();
+        let content = &body["messages"][0]["content"];
+        assert_eq!(
+            content[1],
+            json!({"type": "image", "source": {"type": "base64", "media_type": "image/png", "data": "iVBOR"}})
+        );
+        assert_eq!(content[2]["type"], "document");
+        assert_eq!(content[2]["source"]["media_type"], "application/pdf");
+        assert_eq!(content[2]["title"], "spec v2.pdf");
+        let result = &body["messages"][2]["content"][0];
+        assert_eq!(result["type"], "tool_result");
+        assert_eq!(result["content"][0]["text"], "Image file 'a.jpg'");
+        assert_eq!(result["content"][1]["source"]["media_type"], "image/jpeg");
+
+        let body = GoogleAdapter::new().transform_request(&request).unwrap();
+        let parts = &body["contents"][0]["parts"];
+        assert_eq!(
+            parts[1],
+            json!({"inlineData": {"mimeType": "image/png", "data": "iVBOR"}})
+        );
+        assert_eq!(parts[2]["inlineData"]["mimeType"], "application/pdf");
+        let parts = &body["contents"][2]["parts"];
+        assert_eq!(
+            parts[0]["functionResponse"]["response"]["content"],
+            "Image file 'a.jpg'"
+        );
+        assert_eq!(parts[1]["inlineData"]["data"], "/9j/4");
+
+        let body = ResponsesAdapter::new().transform_request(&request).unwrap();
+        let content = &body["input"][0]["content"];
+        assert_eq!(
+            content[1],
+            json!({"type": "input_image", "image_url": "data:image/png;base64,iVBOR"})
+        );
+        assert_eq!(content[2]["type"], "input_file");
+        assert_eq!(content[2]["filename"], "spec v2.pdf");
+        let output = &body["input"][2]["output"];
+        assert_eq!(output[1]["type"], "input_image");
+
+        let body = BedrockAdapter::new(AwsConfig::default())
+            .transform_request(&request)
+            .unwrap();
+        let content = &body["messages"][0]["content"];
+        assert_eq!(
+            content[1],
+            json!({"image": {"format": "png", "source": {"bytes": "iVBOR"}}})
+        );
+        assert_eq!(content[2]["document"]["format"], "pdf");
+        assert_eq!(content[2]["document"]["name"], "spec v2");
+        let result = &body["messages"][2]["content"][0]["toolResult"];
+        assert_eq!(result["content"][1]["image"]["format"], "jpeg");
+
+        let body = OllamaAdapter::new().transform_request(&request).unwrap();
+        assert_eq!(body["messages"][0]["images"], json!(["iVBOR"]));
+        assert!(body["messages"][0]["content"]
+            .as_str()
+            .unwrap()
+            .contains("spec v2.pdf was not attached"));
+        assert_eq!(body["messages"][2]["images"], json!(["/9j/4"]));
+    }
+
+    #[test]
+    fn test_openai_moves_tool_images_to_user_turn() {
+        let body = OpenAIAdapter::new()
+            .transform_request(&create_attachment_request())
+            .unwrap();
+        let messages = body["messages"].as_array().unwrap();
+        assert_eq!(messages.len(), 5);
+        // User content parts pass through as-is
+        assert_eq!(messages[0]["content"][2]["type"], "file");
+        assert_eq!(messages[2]["content"], "Image file 'a.jpg'");
+        assert_eq!(messages[3]["role"], "user");
+        assert_eq!(messages[3]["content"][1]["type"], "image_url");
+        assert_eq!(messages[4]["content"], "Compare them");
+    }
+
+    #[test]
+    fn test_convert_messages_to_anthropic_attachments() {
+        let messages = convert_messages_to_anthropic(&[
+            json!({"role": "user", "content": [
+                {"type": "text", "text": "Look"},
+                {"type": "image_url", "image_url": {"url": "https://example.com/a.png"}},
+                {"type": "image", "source": {"type": "base64", "media_type": "image/gif", "data": "R0lG"}}
+            ]}),
+            json!({"role": "tool", "tool_call_id": "call_1", "content": [
+                {"type": "text", "text": "Image file 'b.png'"},
+                {"type": "image_url", "image_url": {"url": "data:image/png;base64,iVBOR"}}
+            ]}),
+        ]);
+        let content = &messages[0]["content"];
+        assert_eq!(content[0], json!({"type": "text", "text": "Look"}));
+        assert_eq!(
+            content[1],
+            json!({"type": "image", "source": {"type": "url", "url": "https://example.com/a.png"}})
+        );
+        // Blocks already in Anthropic format are kept
+        assert_eq!(content[2]["source"]["data"], "R0lG");
+        let result = &messages[1]["content"][0]["content"];
+        assert_eq!(result[1]["source"]["data"], "iVBOR");
+    }
 }
@@ -4,50 +4,70 @@
 //! after running hooks and injecting context.
 
 use axum::{
-    body::Body,
-    extract::{Request, State},
+    body::{Body, Bytes},
+    extract::{ConnectInfo, Request, State},
     http::{header, HeaderMap, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
     routing::{any, get},
-    Json, Router,
+    Extension, Json, Router,
 };
+use futures::StreamExt;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::net::SocketAddr;
 use std::sync::Arc;
 use thiserror::Error;
 use tokio::sync::RwLock;
 use tracing::{debug, info, warn};
 
-use crate::compaction::{CompactionConfig, ContextCompactor};
-use crate::config::{AppConfig, ProviderConfig};
+use crate::budget::{self, BudgetScope, BudgetStatus, Spend};
+use crate::compaction::{count_request_tokens, CompactionConfig, ContextCompactor};
+use crate::config::{AppConfig, Budget, ProviderConfig};
 use crate::context::ContextInjector;
-use crate::hooks::{
-    load_claude_code_hooks, merge_hooks_config, HookEngine, HookError, HookEvent, HookInput,
-    HookResult,
-};
+use crate::hooks::{HookEngine, HookError, HookEvent, HookInput, HookResult};
 use crate::mcp::McpManager;
-use crate::oauth::OAuthStore;
-use crate::plugins::PluginManager;
-use crate::providers::get_adapter;
-use crate::rules::{extract_extensions_from_tool_input, RulesEngine};
+use crate::memory::MemoryDb;
+use crate::models;
+use crate::oauth::{OAuthClient, OAuthSession, OAuthStore};
+use crate::providers::ProviderAdapter;
+use crate::reload::{self, Live};
+use crate::routing::{self, RequestFacts, Routed};
+use crate::rules::extract_extensions_from_tool_input;
 use crate::session::{get_session_context, SessionManager};
+use crate::stream::{StreamEvent, TokenUsage};
+use crate::structured_output::{self, ResponseFormat};
+use crate::tokenizer;
+use crate::upstream::{fallback_chain, send_with_fallback, Route, UpstreamError};
+use crate::usage;
 
 /// Shared state for the proxy
 #[derive(Clone)]
 pub struct ProxyState {
-    pub config: Arc<AppConfig>,
+    /// Config, hooks, rules, plugins and MCP servers, swapped on reload
+    pub live: Live,
     pub client: Client,
-    pub hook_engine: HookEngine,
-    pub rules_engine: RulesEngine,
     pub compactor: ContextCompactor,
     pub session_manager: Arc<RwLock<SessionManager>>,
-    pub plugin_manager: Arc<PluginManager>,
-    pub mcp_manager: Arc<RwLock<McpManager>>,
     /// OAuth session store for Claude Max authentication
     pub oauth_store: Arc<OAuthStore>,
+    /// Client used to refresh OAuth tokens
+    pub oauth_client: OAuthClient,
+    /// Loop-mode run whose spend `budgets.loop_run` caps
+    pub loop_run: Option<LoopRun>,
 }
 
+/// A `openclaudia loop` run, spanning every iteration's proxy server
+#[derive(Clone)]
+pub struct LoopRun {
+    pub started: chrono::DateTime<chrono::Utc>,
+    /// Stops the run once its budget is spent
+    pub stop: tokio::sync::watch::Sender<bool>,
+}
+
+/// Header naming the OAuth account a request should use
+pub const ACCOUNT_HEADER: &str = "x-openclaudia-account";
+
 /// Errors that can occur in the proxy
 #[derive(Error, Debug)]
 pub enum ProxyError {
@@ -68,6 +88,33 @@ pub enum ProxyError {
 
     #[error("Hook blocked request: {0}")]
     HookBlocked(String),
+
+    #[error("Invalid provider response: {0}")]
+    InvalidResponse(String),
+
+    #[error("Budget exhausted: {0}")]
+    BudgetExceeded(String),
+
+    #[error("Provider authorization failed: {0}")]
+    AuthFailed(String),
+
+    #[error("Reload failed: {0}")]
+    ReloadFailed(String),
+
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+}
+
+impl From<UpstreamError> for ProxyError {
+    fn from(error: UpstreamError) -> Self {
+        match error {
+            UpstreamError::Build(_, message) => ProxyError::InvalidBody(message),
+            UpstreamError::Transport(e) => ProxyError::RequestError(e),
+            UpstreamError::Auth(provider, message) => {
+                ProxyError::AuthFailed(format!("{}: {}", provider, message))
+            }
+        }
+    }
 }
 
 impl IntoResponse for ProxyError {
@@ -79,6 +126,11 @@ impl IntoResponse for ProxyError {
             ProxyError::InvalidBody(_) => (StatusCode::BAD_REQUEST, self.to_string()),
             ProxyError::JsonError(_) => (StatusCode::BAD_REQUEST, self.to_string()),
             ProxyError::HookBlocked(_) => (StatusCode::FORBIDDEN, self.to_string()),
+            ProxyError::InvalidResponse(_) => (StatusCode::BAD_GATEWAY, self.to_string()),
+            ProxyError::BudgetExceeded(_) => (StatusCode::PAYMENT_REQUIRED, self.to_string()),
+            ProxyError::AuthFailed(_) => (StatusCode::UNAUTHORIZED, self.to_string()),
+            ProxyError::ReloadFailed(_) => (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()),
+            ProxyError::Forbidden(_) => (StatusCode::FORBIDDEN, self.to_string()),
         };
 
         let body = serde_json::json!({
@@ -96,6 +148,7 @@ impl IntoResponse for ProxyError {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatMessage {
     pub role: String,
+    #[serde(default, deserialize_with = "deserialize_content")]
     pub content: MessageContent,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
@@ -113,6 +166,34 @@ pub enum MessageContent {
     Parts(Vec<ContentPart>),
 }
 
+impl Default for MessageContent {
+    fn default() -> Self {
+        MessageContent::Text(String::new())
+    }
+}
+
+impl MessageContent {
+    /// Plain text of the message, joining text parts with newlines
+    pub fn as_text(&self) -> String {
+        match self {
+            MessageContent::Text(t) => t.clone(),
+            MessageContent::Parts(parts) => parts
+                .iter()
+                .filter_map(|p| p.text.clone())
+                .collect::<Vec<_>>()
+                .join("\n"),
+        }
+    }
+}
+
+/// Accept `"content": null`, which OpenAI clients send on assistant tool-call turns
+fn deserialize_content<'de, D>(deserializer: D) -> Result<MessageContent, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Ok(Option::<MessageContent>::deserialize(deserializer)?.unwrap_or_default())
+}
+
 /// Content part for multimodal messages
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ContentPart {
@@ -122,6 +203,31 @@ pub struct ContentPart {
     pub text: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub image_url: Option<Value>,
+    /// `{filename, file_data}` of a `file` part; `file_data` is a data URL
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<Value>,
+}
+
+impl ContentPart {
+    /// Media type and base64 data of an inline image or file
+    pub fn inline_data(&self) -> Option<(&str, &str)> {
+        let url = match (&self.image_url, &self.file) {
+            (Some(image), _) => image.get("url").unwrap_or(image),
+            (None, Some(file)) => &file["file_data"],
+            (None, None) => return None,
+        };
+        parse_data_url(url.as_str()?)
+    }
+
+    /// Name of an attached file, for providers that label documents
+    pub fn filename(&self) -> Option<&str> {
+        self.file.as_ref()?.get("filename")?.as_str()
+    }
+}
+
+/// Split a base64 `data:` URL into its media type and data
+pub fn parse_data_url(url: &str) -> Option<(&str, &str)> {
+    url.strip_prefix("data:")?.split_once(";base64,")
 }
 
 /// OpenAI-compatible chat completion request
@@ -139,6 +245,9 @@ pub struct ChatCompletionRequest {
     pub tools: Option<Vec<Value>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tool_choice: Option<Value>,
+    /// JSON or JSON-schema reply (see [`crate::structured_output`])
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_format: Option<ResponseFormat>,
     #[serde(flatten)]
     pub extra: std::collections::HashMap<String, Value>,
 }
@@ -156,6 +265,8 @@ pub fn create_router(state: ProxyState) -> Router {
             axum::routing::post(auth_device_submit),
         )
         .route("/auth/status", get(auth_status))
+        // Re-read config, rules, hooks and plugins
+        .route("/admin/reload", axum::routing::post(admin_reload))
         // OpenAI-compatible endpoints
         .route("/v1/chat/completions", any(proxy_chat_completions))
         .route("/v1/completions", any(proxy_completions))
@@ -216,7 +327,7 @@ async fn auth_device_submit(
     State(state): State<ProxyState>,
     Json(payload): Json<serde_json::Value>,
 ) -> Result<impl IntoResponse, ProxyError> {
-    use crate::oauth::{parse_auth_code, OAuthClient, OAuthSession};
+    use crate::oauth::parse_auth_code;
 
     let mut code = payload["code"].as_str().unwrap_or("").to_string();
     let mut oauth_state = payload["state"].as_str().unwrap_or("").to_string();
@@ -245,6 +356,9 @@ async fn auth_device_submit(
 
     // Create session
     let mut session = OAuthSession::from_token_response(token_response);
+    if let Some(account) = payload["account"].as_str().filter(|a| !a.is_empty()) {
+        session.account = account.to_string();
+    }
 
     // Try to create API key if we have the scope
     if session.can_create_api_key() {
@@ -257,17 +371,19 @@ async fn auth_device_submit(
     }
 
     let session_id = session.id.clone();
+    let account = session.account.clone();
     state.oauth_store.store_session(session);
 
     info!(
-        "Device flow authentication successful, session: {}",
-        session_id
+        "Device flow authentication successful, session: {} (account {})",
+        session_id, account
     );
 
     Ok(Json(serde_json::json!({
         "success": true,
         "message": "Authentication successful",
-        "session_id": session_id
+        "session_id": session_id,
+        "account": account
     })))
 }
 
@@ -293,7 +409,8 @@ async fn auth_status(State(state): State<ProxyState>, headers: HeaderMap) -> imp
     match session {
         Some(s) => Json(serde_json::json!({
             "authenticated": true,
-            "session_id": s.id
+            "session_id": s.id,
+            "account": s.account
         })),
         None => Json(serde_json::json!({
             "authenticated": false,
@@ -302,17 +419,71 @@ async fn auth_status(State(state): State<ProxyState>, headers: HeaderMap) -> imp
     }
 }
 
-/// List available models (returns configured provider's models)
-async fn list_models(State(_state): State<ProxyState>) -> impl IntoResponse {
+/// Reload config, rules, hooks and plugins. On failure the previous
+/// configuration stays active. Only clients on the loopback interface may
+/// reload; requests without a known peer address are refused.
+async fn admin_reload(
+    State(state): State<ProxyState>,
+    peer: Option<Extension<ConnectInfo<SocketAddr>>>,
+) -> Result<Response, ProxyError> {
+    if !peer.is_some_and(|Extension(ConnectInfo(addr))| addr.ip().to_canonical().is_loopback()) {
+        return Err(ProxyError::Forbidden(
+            "/admin/reload only accepts loopback clients".to_string(),
+        ));
+    }
+    let report = state
+        .live
+        .reload()
+        .await
+        .map_err(ProxyError::ReloadFailed)?;
+    info!("Reloaded configuration: {}", report);
+    Ok(Json(serde_json::json!({
+        "status": "reloaded",
+        "hooks": report.hooks,
+        "rules": report.rules,
+        "plugins": report.plugins,
+        "mcp_servers": report.mcp_servers,
+        "warnings": report.warnings,
+    }))
+    .into_response())
+}
+
+/// List available models from the model registry, limited to configured
+/// providers, followed by the configured aliases
+async fn list_models(State(state): State<ProxyState>) -> impl IntoResponse {
+    let live = state.live.current();
+    let mut data: Vec<Value> = models::list(None)
+        .into_iter()
+        .filter(|m| live.config.providers.contains_key(&m.provider))
+        .map(|m| {
+            serde_json::json!({
+                "id": m.id,
+                "object": "model",
+                "owned_by": m.provider,
+                "context_window": m.context_window,
+                "max_output_tokens": m.max_output,
+            })
+        })
+        .collect();
+    data.extend(
+        routing::aliases(&live.config)
+            .into_iter()
+            .filter(|(_, routed)| live.config.providers.contains_key(&routed.provider))
+            .map(|(alias, routed)| {
+                let info = models::lookup(&routed.model);
+                serde_json::json!({
+                    "id": alias,
+                    "object": "model",
+                    "owned_by": routed.provider,
+                    "alias_for": routed.model,
+                    "context_window": info.as_ref().map(|m| m.context_window),
+                    "max_output_tokens": info.as_ref().map(|m| m.max_output),
+                })
+            }),
+    );
     Json(serde_json::json!({
         "object": "list",
-        "data": [
-            {"id": "claude-3-5-sonnet-20241022", "object": "model", "owned_by": "anthropic"},
-            {"id": "claude-3-5-haiku-20241022", "object": "model", "owned_by": "anthropic"},
-            {"id": "claude-3-opus-20240229", "object": "model", "owned_by": "anthropic"},
-            {"id": "gpt-4o", "object": "model", "owned_by": "openai"},
-            {"id": "gpt-4o-mini", "object": "model", "owned_by": "openai"},
-        ]
+        "data": data
     }))
 }
 
@@ -401,6 +572,10 @@ async fn proxy_chat_completions(
 ) -> Result<Response, ProxyError> {
     let request: ChatCompletionRequest =
         serde_json::from_str(&body).map_err(|e| ProxyError::InvalidBody(e.to_string()))?;
+    if let Some(format) = &request.response_format {
+        structured_output::check(format).map_err(ProxyError::InvalidBody)?;
+    }
+    let live = state.live.current();
 
     info!(
         model = %request.model,
@@ -408,25 +583,32 @@ async fn proxy_chat_completions(
         "Proxying chat completion request"
     );
 
-    // Determine target provider from model or config
-    let provider_name = determine_provider(&request.model, &state.config);
-    let provider = state
+    // Pick provider and model from routing rules, aliases or the model name
+    let routed = routing::route(&live.config, &RequestFacts::chat(&request, &headers));
+    log_route(&request.model, &routed);
+    let provider_name = routed.provider.clone();
+    let provider = live
         .config
         .get_provider(&provider_name)
         .ok_or_else(|| ProxyError::ProviderNotConfigured(provider_name.clone()))?;
 
-    // Get API key from header or config
-    let api_key = extract_api_key(&headers)
-        .or_else(|| provider.api_key.clone())
-        .ok_or_else(|| ProxyError::NoApiKey(provider_name.clone()))?;
+    // Get API key from header or config. Providers that sign their own
+    // requests never receive the client's key.
+    let api_key = if crate::providers::adapter_for(&provider_name, provider).requires_api_key() {
+        request_api_key(&headers, &routed, provider)?
+    } else {
+        provider.api_key.clone().unwrap_or_default()
+    };
 
     // Track request in session
-    {
+    let usage_session = {
         let mut sm = state.session_manager.write().await;
-        if let Some(session) = sm.get_session_mut() {
+        sm.get_session_mut().map(|session| {
             session.increment_requests();
-        }
-    }
+            session.id.clone()
+        })
+    };
+    enforce_budgets(&state, usage_session.as_deref().unwrap_or("proxy")).await?;
 
     // Run UserPromptSubmit hooks
     let last_user_message = request
@@ -446,7 +628,7 @@ async fn proxy_chat_completions(
     let hook_input = HookInput::new(HookEvent::UserPromptSubmit)
         .with_prompt(last_user_message.unwrap_or_default());
 
-    let hook_result = state
+    let hook_result = live
         .hook_engine
         .run(HookEvent::UserPromptSubmit, &hook_input)
         .await;
@@ -462,13 +644,14 @@ async fn proxy_chat_completions(
 
     // Inject context from hook results
     let mut request = request;
+    request.model = routed.model;
     ContextInjector::apply_prompt_modification(&mut request, &hook_result);
     ContextInjector::inject(&mut request, &hook_result);
 
     // Inject rules based on file extensions mentioned in messages
     let extensions = extract_extensions_from_messages(&request.messages);
     if !extensions.is_empty() {
-        let rules_content = state
+        let rules_content = live
             .rules_engine
             .get_combined_rules(&extensions.iter().map(|s| s.as_str()).collect::<Vec<_>>());
         if !rules_content.is_empty() {
@@ -478,7 +661,7 @@ async fn proxy_chat_completions(
 
     // Add MCP tools to request if available
     {
-        let mcp = state.mcp_manager.read().await;
+        let mcp = live.mcp_manager.read().await;
         let mcp_tools = mcp.tools_as_openai_functions();
         if !mcp_tools.is_empty() {
             let mut tools = request.tools.unwrap_or_default();
@@ -488,7 +671,7 @@ async fn proxy_chat_completions(
     }
 
     // Add plugin commands as available context
-    let plugin_commands: Vec<String> = state
+    let plugin_commands: Vec<String> = live
         .plugin_manager
         .all_commands()
         .iter()
@@ -526,7 +709,7 @@ async fn proxy_chat_completions(
                         sm.get_session().map(|s| s.id.clone())
                     };
                     let hook_result = run_pre_tool_use_hooks(
-                        &state.hook_engine,
+                        &live.hook_engine,
                         session_id.as_deref(),
                         name,
                         args,
@@ -569,7 +752,7 @@ async fn proxy_chat_completions(
 
     // Compact context if needed (for long conversations)
     let compaction_result = compactor
-        .compact(&mut request, Some(&state.hook_engine), None)
+        .compact(&mut request, Some(&live.hook_engine), None)
         .await;
 
     match compaction_result {
@@ -596,30 +779,213 @@ async fn proxy_chat_completions(
         }
     }
 
-    // Get the provider adapter for request transformation
-    let adapter = get_adapter(&provider_name);
-    debug!(provider = adapter.name(), "Using provider adapter");
+    // The caller's key belongs to the provider it asked for; fallbacks use their own
+    let mut routes = fallback_chain(&live.config, &provider_name, provider, &request.model);
+    routes[0].provider.api_key = Some(api_key);
+
+    let response = send_chat(&state, &routes, &request, usage_session.clone()).await?;
 
+    // Streamed replies have reached the client before they could be checked
+    if request.stream.unwrap_or(false) {
+        return Ok(response);
+    }
+    enforce_response_format(&state, &routes, request, usage_session, response).await
+}
+
+/// Send a chat request down the fallback chain and translate the reply to
+/// OpenAI format
+async fn send_chat(
+    state: &ProxyState,
+    routes: &[Route],
+    request: &ChatCompletionRequest,
+    usage_session: Option<String>,
+) -> Result<Response, ProxyError> {
     let is_stream = request.stream.unwrap_or(false);
+    let live = state.live.current();
+
+    // Transform request to each provider's format with its thinking config
+    let served = send_with_fallback(routes, &live.config.proxy.retry, |route| {
+        let adapter = route.adapter();
+        debug!(provider = adapter.name(), model = %route.model, "Using provider adapter");
+        let mut routed = request.clone();
+        routed.model = route.model.clone();
+        let body = adapter
+            .transform_request_with_thinking(&routed, &route.provider.thinking)
+            .map_err(|e| e.to_string())?;
+        let api_key = route.provider.api_key.clone().unwrap_or_default();
+        Ok(provider_request(
+            &state.client,
+            &route.provider,
+            &adapter.chat_path(&route.model, is_stream),
+            &body,
+            is_stream,
+            adapter.get_headers(&api_key),
+        ))
+    })
+    .await?;
 
-    // Transform request to provider format with thinking config
-    let transformed_request = adapter
-        .transform_request_with_thinking(&request, &provider.thinking)
-        .map_err(|e| ProxyError::InvalidBody(e.to_string()))?;
+    if served.response.status().is_success() {
+        let mut sm = state.session_manager.write().await;
+        if let Some(session) = sm.get_session_mut() {
+            session.record_served(served.route.served());
+        }
+    }
 
-    // Forward to provider with transformed request
-    let response = forward_to_provider_raw(
-        &state.client,
-        provider,
-        &api_key,
-        adapter.chat_endpoint(),
-        &transformed_request,
+    // Clients of this endpoint expect OpenAI format back
+    let on_usage = usage_sink(state, served.route, request, usage_session);
+    adapt_response(
+        served.route.adapter(),
+        served.response,
+        request.model.clone(),
         is_stream,
-        adapter.get_headers(&api_key),
+        on_usage,
     )
-    .await?;
+    .await
+}
 
-    Ok(response)
+/// Check a reply against the request's `response_format`. A reply that does
+/// not conform is sent back once with the validation error, and the second
+/// reply is returned as it is.
+async fn enforce_response_format(
+    state: &ProxyState,
+    routes: &[Route],
+    mut request: ChatCompletionRequest,
+    usage_session: Option<String>,
+    response: Response,
+) -> Result<Response, ProxyError> {
+    let Some(format) = request.response_format.clone() else {
+        return Ok(response);
+    };
+    if !response.status().is_success() {
+        return Ok(response);
+    }
+
+    let (parts, body) = response.into_parts();
+    let body = axum::body::to_bytes(body, usize::MAX)
+        .await
+        .map_err(|e| ProxyError::InvalidResponse(e.to_string()))?;
+    let reply = serde_json::from_slice::<Value>(&body)
+        .ok()
+        .and_then(|body| {
+            let message = &body["choices"][0]["message"];
+            // Tool calls come before the final reply and are not checked
+            let calls_tools = message["tool_calls"]
+                .as_array()
+                .is_some_and(|c| !c.is_empty());
+            (!calls_tools)
+                .then(|| message["content"].as_str().map(str::to_string))
+                .flatten()
+        });
+    let error = reply
+        .as_deref()
+        .and_then(|reply| structured_output::validate(&format, reply).err());
+    let (Some(reply), Some(error)) = (reply, error) else {
+        return Ok(Response::from_parts(parts, Body::from(body)));
+    };
+
+    warn!(error = %error, "Reply does not match response_format, retrying once");
+    request
+        .messages
+        .extend(structured_output::retry_messages(&reply, &error));
+    send_chat(state, routes, &request, usage_session).await
+}
+
+/// Refuse requests once the session or daily budget is spent
+async fn enforce_budgets(state: &ProxyState, session_id: &str) -> Result<(), ProxyError> {
+    let live = state.live.current();
+    let budgets = &live.config.budgets;
+    let cwd = std::env::current_dir().unwrap_or_else(|_| ".".into());
+    let session_spend = if budgets.session == Budget::default() {
+        Spend::default()
+    } else {
+        MemoryDb::open_for_project(&cwd)
+            .and_then(|db| db.usage_for_session(session_id))
+            .map(|summary| Spend::from(&summary))
+            .unwrap_or_default()
+    };
+    let loop_status = match &state.loop_run {
+        Some(run) if budgets.loop_run != Budget::default() => {
+            let spend = MemoryDb::open_for_project(&cwd)
+                .and_then(|db| db.usage_since(run.started))
+                .map(|summary| Spend::from(&summary))
+                .unwrap_or_default();
+            budget::check(
+                BudgetScope::Loop,
+                &run.started.to_rfc3339(),
+                budgets,
+                &spend,
+            )
+        }
+        _ => BudgetStatus::Ok,
+    };
+    for status in [
+        budget::check(BudgetScope::Session, session_id, budgets, &session_spend),
+        loop_status,
+        budget::check_daily(budgets, &cwd),
+    ] {
+        match status {
+            BudgetStatus::Ok => {}
+            BudgetStatus::Warning(message) => warn!(%message, "Budget warning"),
+            BudgetStatus::Exceeded { scope, message } => {
+                budget::run_stop_hook(&live.hook_engine, session_id, scope, &message).await;
+                if let (BudgetScope::Loop, Some(run)) = (scope, &state.loop_run) {
+                    warn!(%message, "Stopping loop");
+                    let _ = run.stop.send(true);
+                }
+                return Err(ProxyError::BudgetExceeded(message));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// ID budgets are tracked under for requests that do not count as new
+/// session requests
+async fn current_session_id(state: &ProxyState) -> String {
+    let sm = state.session_manager.read().await;
+    sm.get_session()
+        .map(|session| session.id.clone())
+        .unwrap_or_else(|| "proxy".to_string())
+}
+
+/// Called once with the token usage reported by a successful response
+type UsageSink = Box<dyn FnOnce(TokenUsage) + Send>;
+
+/// Feed reported usage into the proxy session and the project usage log, and
+/// calibrate the model's tokenizer against the reported prompt size
+fn usage_sink(
+    state: &ProxyState,
+    route: &Route,
+    request: &ChatCompletionRequest,
+    session_id: Option<String>,
+) -> UsageSink {
+    let session_manager = state.session_manager.clone();
+    let live = state.live.current();
+    let pricing = live.config.pricing.clone();
+    let provider = route.provider_name.clone();
+    let model = route.model.clone();
+    let cache_ttl = route.provider.cache.ttl;
+    let request = request.clone();
+    Box::new(move |turn_usage| {
+        tokio::spawn(async move {
+            let estimated = count_request_tokens(tokenizer::base_for_model(&model), &request);
+            tokenizer::calibrate(&model, estimated, turn_usage.prompt_tokens());
+            if let Some(session) = session_manager.write().await.get_session_mut() {
+                session.add_tokens(turn_usage.total_tokens());
+            }
+            if let Ok(cwd) = std::env::current_dir() {
+                usage::record(
+                    &cwd,
+                    session_id.as_deref().unwrap_or("proxy"),
+                    &provider,
+                    &model,
+                    &turn_usage,
+                    &pricing,
+                    cache_ttl,
+                );
+            }
+        });
+    })
 }
 
 /// Handle MCP tool calls from the model response
@@ -669,18 +1035,22 @@ async fn proxy_completions(
     let request: Value =
         serde_json::from_str(&body).map_err(|e| ProxyError::InvalidBody(e.to_string()))?;
 
+    let live = state.live.current();
     let model = request["model"]
         .as_str()
         .unwrap_or("gpt-3.5-turbo-instruct");
-    let provider_name = determine_provider(model, &state.config);
-    let provider = state
+    let routed = routing::route(&live.config, &RequestFacts::json(model, &request, &headers));
+    log_route(model, &routed);
+    let provider_name = routed.provider.clone();
+    let provider = live
         .config
         .get_provider(&provider_name)
         .ok_or_else(|| ProxyError::ProviderNotConfigured(provider_name.clone()))?;
 
-    let api_key = extract_api_key(&headers)
-        .or_else(|| provider.api_key.clone())
-        .ok_or_else(|| ProxyError::NoApiKey(provider_name.clone()))?;
+    let api_key = request_api_key(&headers, &routed, provider)?;
+    let mut request = request;
+    request["model"] = Value::String(routed.model);
+    enforce_budgets(&state, &current_session_id(&state).await).await?;
 
     let is_stream = request["stream"].as_bool().unwrap_or(false);
     let response = forward_to_provider(
@@ -706,40 +1076,53 @@ async fn proxy_anthropic_messages(
     let mut request: Value =
         serde_json::from_str(&body).map_err(|e| ProxyError::InvalidBody(e.to_string()))?;
 
-    let provider = state
+    let live = state.live.current();
+    let provider = live
         .config
         .get_provider("anthropic")
         .ok_or_else(|| ProxyError::ProviderNotConfigured("anthropic".to_string()))?;
 
-    // Check for OAuth session from cookie first
-    let session = headers
-        .get(header::COOKIE)
-        .and_then(|v| v.to_str().ok())
-        .and_then(|cookies| {
-            cookies.split(';').find_map(|cookie| {
-                let cookie = cookie.trim();
-                cookie
-                    .strip_prefix("anthropic_session=")
-                    .map(|s| s.to_string())
-            })
-        })
-        .and_then(|session_id| {
-            debug!(
-                "[/v1/messages] Looking up session from cookie: {}",
-                session_id
-            );
-            state.oauth_store.get_session(&session_id)
-        });
-
-    // Fallback: check for ANY valid session if no cookie provided
-    let session = session.or_else(|| {
-        debug!("[/v1/messages] No cookie session, checking for any valid session...");
-        state.oauth_store.get_any_valid_session()
-    });
+    // This endpoint always talks to Anthropic, so only routes that stay
+    // there can change the model
+    let requested = request["model"].as_str().unwrap_or_default().to_string();
+    let routed = routing::route(
+        &live.config,
+        &RequestFacts::json(&requested, &request, &headers),
+    );
+    let routed_account = if routed.provider == "anthropic" {
+        log_route(&requested, &routed);
+        request["model"] = Value::String(routed.model);
+        routed.account
+    } else {
+        debug!(
+            provider = %routed.provider,
+            "[/v1/messages] Ignoring route to another provider"
+        );
+        None
+    };
+    let session = select_oauth_session(&state, &live.config, &headers, routed_account)?;
+    enforce_budgets(&state, &current_session_id(&state).await).await?;
 
     // If we have an OAuth session, use Bearer token auth with Claude Code prompt injection
     if let Some(session) = session {
-        info!("[/v1/messages] Using OAuth session: {}", session.id);
+        info!(
+            "[/v1/messages] Using OAuth session: {} (account {})",
+            session.id, session.account
+        );
+
+        // Refresh tokens that are about to expire before sending
+        let session = match state
+            .oauth_store
+            .refresh_session(&state.oauth_client, &session.id, None)
+            .await
+        {
+            Ok(refreshed) => refreshed,
+            Err(e) if !session.credentials.is_expired() => {
+                warn!("{:#}; using the current token", e);
+                session
+            }
+            Err(e) => return Err(ProxyError::AuthFailed(format!("{:#}", e))),
+        };
 
         // CRITICAL: Inject Claude Code system prompt (this is what makes OAuth work!)
         // The API validates that requests contain this identifier
@@ -765,22 +1148,41 @@ async fn proxy_anthropic_messages(
         }
 
         let url = format!("{}/v1/messages", provider.base_url);
-        let response = state
-            .client
-            .post(&url)
-            .header(
-                "Authorization",
-                format!("Bearer {}", session.credentials.access_token),
-            )
-            .header(
-                "anthropic-beta",
-                "oauth-2025-04-20,computer-use-2025-01-24,fine-grained-tool-streaming-2025-05-14",
-            )
-            .header("anthropic-version", "2023-06-01")
-            .header("content-type", "application/json")
-            .json(&request)
-            .send()
-            .await?;
+        let send = |access_token: &str| {
+            state
+                .client
+                .post(&url)
+                .header("Authorization", format!("Bearer {}", access_token))
+                .header(
+                    "anthropic-beta",
+                    "oauth-2025-04-20,computer-use-2025-01-24,fine-grained-tool-streaming-2025-05-14",
+                )
+                .header("anthropic-version", "2023-06-01")
+                .header("content-type", "application/json")
+                .json(&request)
+                .send()
+        };
+        let mut response = send(&session.credentials.access_token).await?;
+
+        // A revoked or early-expired token gets one refresh and retry
+        if response.status() == StatusCode::UNAUTHORIZED
+            && session.credentials.refresh_token.is_some()
+        {
+            warn!(
+                "[/v1/messages] OAuth token for account {} rejected, refreshing",
+                session.account
+            );
+            let refreshed = state
+                .oauth_store
+                .refresh_session(
+                    &state.oauth_client,
+                    &session.id,
+                    Some(&session.credentials.access_token),
+                )
+                .await
+                .map_err(|e| ProxyError::AuthFailed(format!("{:#}", e)))?;
+            response = send(&refreshed.credentials.access_token).await?;
+        }
 
         return convert_response(response).await;
     }
@@ -804,6 +1206,63 @@ async fn proxy_anthropic_messages(
     Ok(response)
 }
 
+/// OAuth session for a Claude Max request: the `anthropic_session` cookie,
+/// else the account named by [`ACCOUNT_HEADER`], else the one picked by a
+/// routing rule, else `oauth.default_account`, else the default session.
+/// Naming an account that is not signed in is an error rather than a silent
+/// switch to another account.
+fn select_oauth_session(
+    state: &ProxyState,
+    config: &AppConfig,
+    headers: &HeaderMap,
+    routed_account: Option<String>,
+) -> Result<Option<OAuthSession>, ProxyError> {
+    let cookie_session = headers
+        .get(header::COOKIE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|cookies| {
+            cookies.split(';').find_map(|cookie| {
+                let cookie = cookie.trim();
+                cookie
+                    .strip_prefix("anthropic_session=")
+                    .map(|s| s.to_string())
+            })
+        })
+        .and_then(|session_id| {
+            debug!(
+                "[/v1/messages] Looking up session from cookie: {}",
+                session_id
+            );
+            state.oauth_store.get_session(&session_id)
+        });
+    if cookie_session.is_some() {
+        return Ok(cookie_session);
+    }
+
+    let account = headers
+        .get(ACCOUNT_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string)
+        .or(routed_account)
+        .or_else(|| config.oauth.default_account.clone());
+    match account {
+        Some(account) => state
+            .oauth_store
+            .account_session(&account)
+            .map(Some)
+            .ok_or_else(|| {
+                ProxyError::AuthFailed(format!(
+                    "no OAuth session for account '{}', run 'openclaudia auth --account {}'",
+                    account, account
+                ))
+            }),
+        None => {
+            debug!("[/v1/messages] No account requested, using the default session");
+            Ok(state.oauth_store.get_any_valid_session())
+        }
+    }
+}
+
 /// Passthrough for unhandled routes
 async fn proxy_passthrough(
     State(state): State<ProxyState>,
@@ -811,14 +1270,15 @@ async fn proxy_passthrough(
     request: Request,
 ) -> Result<Response, ProxyError> {
     let path = request.uri().path();
-    let provider = state
+    let live = state.live.current();
+    let provider = live
         .config
         .active_provider()
-        .ok_or_else(|| ProxyError::ProviderNotConfigured(state.config.proxy.target.clone()))?;
+        .ok_or_else(|| ProxyError::ProviderNotConfigured(live.config.proxy.target.clone()))?;
 
     let api_key = extract_api_key(&headers)
         .or_else(|| provider.api_key.clone())
-        .ok_or_else(|| ProxyError::NoApiKey(state.config.proxy.target.clone()))?;
+        .ok_or_else(|| ProxyError::NoApiKey(live.config.proxy.target.clone()))?;
 
     let url = format!("{}{}", provider.base_url, path);
     debug!(url = %url, "Passthrough request");
@@ -835,39 +1295,41 @@ async fn proxy_passthrough(
     }
 
     // Set auth header based on provider
-    req_builder = set_auth_header(req_builder, &state.config.proxy.target, &api_key);
+    req_builder = set_auth_header(req_builder, &live.config.proxy.target, &api_key);
 
     let response = req_builder.send().await?;
     convert_response(response).await
 }
 
-/// Determine which provider to use based on model name
-fn determine_provider(model: &str, config: &AppConfig) -> String {
-    let model_lower = model.to_lowercase();
-    if model_lower.starts_with("claude") || model_lower.starts_with("anthropic") {
-        "anthropic".to_string()
-    } else if model_lower.starts_with("gpt")
-        || model_lower.starts_with("o1")
-        || model_lower.starts_with("o3")
-    {
-        "openai".to_string()
-    } else if model_lower.starts_with("gemini") {
-        "google".to_string()
-    } else if model_lower.starts_with("glm") {
-        // Z.AI/GLM models (OpenAI-compatible)
-        "zai".to_string()
-    } else if model_lower.starts_with("deepseek") {
-        // DeepSeek models (OpenAI-compatible)
-        "deepseek".to_string()
-    } else if model_lower.starts_with("qwen") {
-        // Alibaba Qwen models (OpenAI-compatible)
-        "qwen".to_string()
-    } else {
-        // Fall back to configured target
-        config.proxy.target.clone()
+/// Log where routing sent a request when it changed anything
+fn log_route(requested: &str, routed: &Routed) {
+    if routed.model != requested || routed.rule.is_some() {
+        info!(
+            requested = %requested,
+            provider = %routed.provider,
+            model = %routed.model,
+            rule = routed.rule.as_deref().unwrap_or("alias"),
+            "Routed request"
+        );
     }
 }
 
+/// API key for a routed request. The client's key is meant for the provider
+/// its requested model implies, so a request routed to any other provider
+/// uses that provider's own key.
+fn request_api_key(
+    headers: &HeaderMap,
+    routed: &Routed,
+    provider: &ProviderConfig,
+) -> Result<String, ProxyError> {
+    let client_key = (!routed.switched_provider)
+        .then(|| extract_api_key(headers))
+        .flatten();
+    client_key
+        .or_else(|| provider.api_key.clone())
+        .ok_or_else(|| ProxyError::NoApiKey(routed.provider.clone()))
+}
+
 /// Extract API key from Authorization header
 fn extract_api_key(headers: &HeaderMap) -> Option<String> {
     headers
@@ -876,11 +1338,13 @@ fn extract_api_key(headers: &HeaderMap) -> Option<String> {
         .and_then(|v| v.strip_prefix("Bearer "))
         .map(|s| s.to_string())
         .or_else(|| {
-            // Also check x-api-key header (Anthropic style)
-            headers
-                .get("x-api-key")
-                .and_then(|v| v.to_str().ok())
-                .map(|s| s.to_string())
+            // Also check x-api-key (Anthropic style) and api-key (Azure style)
+            ["x-api-key", "api-key"].iter().find_map(|name| {
+                headers
+                    .get(*name)
+                    .and_then(|v| v.to_str().ok())
+                    .map(|s| s.to_string())
+            })
         })
 }
 
@@ -932,16 +1396,15 @@ fn set_auth_header(
     req
 }
 
-/// Forward request to upstream provider with raw Value body and custom headers
-async fn forward_to_provider_raw(
+/// Build a request to an upstream provider with raw Value body and custom headers
+fn provider_request(
     client: &Client,
     provider: &ProviderConfig,
-    _api_key: &str,
     path: &str,
     body: &Value,
     is_stream: bool,
     custom_headers: Vec<(String, String)>,
-) -> Result<Response, ProxyError> {
+) -> reqwest::RequestBuilder {
     let url = format!("{}{}", provider.base_url, path);
     debug!(url = %url, stream = is_stream, "Forwarding to provider (raw)");
 
@@ -957,12 +1420,115 @@ async fn forward_to_provider_raw(
         req = req.header(key.as_str(), value.as_str());
     }
 
-    let response = req.send().await?;
-    convert_response(response).await
+    req
+}
+
+/// Translate a successful provider response to OpenAI chat completions format.
+/// Streams are re-encoded event by event through the adapter's stream decoder.
+async fn adapt_response(
+    adapter: Box<dyn ProviderAdapter>,
+    response: reqwest::Response,
+    model: String,
+    is_stream: bool,
+    on_usage: UsageSink,
+) -> Result<Response, ProxyError> {
+    if !response.status().is_success() {
+        return convert_response(response).await;
+    }
+
+    if adapter.openai_compatible() {
+        let (builder, body) = response_parts(response).await?;
+        if let Some(usage) = body_usage(adapter.as_ref(), &body, is_stream) {
+            on_usage(usage);
+        }
+        return Ok(builder.body(Body::from(body)).unwrap());
+    }
+
+    if !is_stream {
+        let body: Value = response.json().await?;
+        if let Some(usage) = adapter.response_usage(&body) {
+            on_usage(usage);
+        }
+        let converted = adapter
+            .transform_response(body, false)
+            .map_err(|e| ProxyError::InvalidResponse(e.to_string()))?;
+        return Ok(Json(converted).into_response());
+    }
+
+    let id = format!("chatcmpl-{}", uuid::Uuid::new_v4());
+    let created = chrono::Utc::now().timestamp();
+    // Shared so the trailing chunk can flush the decoder once the body ends
+    let decoder = Arc::new(std::sync::Mutex::new(adapter.stream_decoder()));
+    let usage = Arc::new(std::sync::Mutex::new(None));
+    let encode = move |events: Vec<StreamEvent>| {
+        let mut out = String::new();
+        for chunk in events
+            .iter()
+            .filter_map(|e| e.to_openai_chunk(&id, &model, created))
+        {
+            out.push_str(&format!("data: {}\n\n", chunk));
+        }
+        out
+    };
+    let finish_encode = encode.clone();
+    let finish_decoder = decoder.clone();
+    let finish_usage = usage.clone();
+    let events = response
+        .bytes_stream()
+        .map(move |chunk| {
+            let events = decoder.lock().unwrap().decode(&chunk?);
+            if let Some(latest) = last_usage(&events) {
+                *usage.lock().unwrap() = Some(latest);
+            }
+            Ok::<_, reqwest::Error>(Bytes::from(encode(events)))
+        })
+        .chain(futures::stream::once(async move {
+            let events = finish_decoder.lock().unwrap().finish();
+            let latest = last_usage(&events).or(finish_usage.lock().unwrap().take());
+            if let Some(latest) = latest {
+                on_usage(latest);
+            }
+            Ok(Bytes::from(finish_encode(events) + "data: [DONE]\n\n"))
+        }));
+
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "text/event-stream")
+        .header(header::CACHE_CONTROL, "no-cache")
+        .body(Body::from_stream(events))
+        .unwrap())
+}
+
+/// Latest usage among `events`; providers may repeat cumulative totals
+fn last_usage(events: &[StreamEvent]) -> Option<TokenUsage> {
+    events.iter().rev().find_map(|event| match event {
+        StreamEvent::Usage(usage) => Some(*usage),
+        _ => None,
+    })
+}
+
+/// Usage reported in a buffered response body in the provider's own format
+fn body_usage(adapter: &dyn ProviderAdapter, body: &[u8], is_stream: bool) -> Option<TokenUsage> {
+    if is_stream {
+        let mut decoder = adapter.stream_decoder();
+        let mut events = decoder.decode(body);
+        events.extend(decoder.finish());
+        last_usage(&events)
+    } else {
+        adapter.response_usage(&serde_json::from_slice(body).ok()?)
+    }
 }
 
 /// Convert reqwest response to axum response
 async fn convert_response(response: reqwest::Response) -> Result<Response, ProxyError> {
+    let (builder, body) = response_parts(response).await?;
+    Ok(builder.body(Body::from(body)).unwrap())
+}
+
+/// Status, headers and buffered body of an upstream response
+async fn response_parts(
+    response: reqwest::Response,
+) -> Result<(axum::http::response::Builder, Bytes), ProxyError> {
     let status = StatusCode::from_u16(response.status().as_u16())
         .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
 
@@ -978,24 +1544,21 @@ async fn convert_response(response: reqwest::Response) -> Result<Response, Proxy
     }
 
     let body = response.bytes().await?;
-    Ok(builder.body(Body::from(body)).unwrap())
+    Ok((builder, body))
 }
 
-/// Start the proxy server
-pub async fn start_server(config: AppConfig) -> anyhow::Result<()> {
-    let addr = format!("{}:{}", config.proxy.host, config.proxy.port);
-
+/// Build the shared proxy state. `mcp` connects the MCP servers of enabled
+/// plugins.
+async fn build_state(
+    config: AppConfig,
+    overrides: &[(&str, String)],
+    mcp: bool,
+    loop_run: Option<LoopRun>,
+) -> anyhow::Result<ProxyState> {
     let client = Client::builder()
         .timeout(std::time::Duration::from_secs(300))
         .build()?;
 
-    // Load hooks from both OpenClaudia config and Claude Code settings.json
-    let claude_hooks = load_claude_code_hooks();
-    let merged_hooks = merge_hooks_config(config.hooks.clone(), claude_hooks);
-    let hook_engine = HookEngine::new(merged_hooks);
-
-    let rules_engine = RulesEngine::new(".openclaudia/rules");
-
     // Initialize compactor with default model context
     let compactor = ContextCompactor::new(CompactionConfig::default());
 
@@ -1004,69 +1567,42 @@ pub async fn start_server(config: AppConfig) -> anyhow::Result<()> {
         &config.session.persist_path,
     )));
 
-    // Initialize plugin manager and discover plugins
-    let mut plugin_manager = PluginManager::new();
-    let plugin_errors = plugin_manager.discover();
-    for err in plugin_errors {
-        warn!(error = %err, "Plugin discovery error");
-    }
-    let plugin_manager = Arc::new(plugin_manager);
+    // Hooks from both OpenClaudia config and Claude Code settings.json, rules,
+    // plugins and their MCP servers
+    let live = Live::start(config, overrides, mcp).await;
 
-    // Initialize MCP manager and connect to configured servers
-    let mcp_manager = Arc::new(RwLock::new(McpManager::new()));
-    {
-        let mut mcp = mcp_manager.write().await;
-        for (plugin, server) in plugin_manager.all_mcp_servers() {
-            match server.transport.as_str() {
-                "stdio" => {
-                    if let Some(command) = &server.command {
-                        let args: Vec<&str> = server.args.iter().map(|s| s.as_str()).collect();
-                        match mcp.connect_stdio(&server.name, command, &args).await {
-                            Ok(()) => {
-                                info!(server = %server.name, plugin = %plugin.name(), "Connected MCP (stdio)")
-                            }
-                            Err(e) => {
-                                warn!(server = %server.name, error = %e, "MCP connect failed")
-                            }
-                        }
-                    }
-                }
-                "http" => {
-                    if let Some(url) = &server.url {
-                        match mcp.connect_http(&server.name, url).await {
-                            Ok(()) => {
-                                info!(server = %server.name, plugin = %plugin.name(), "Connected MCP (http)")
-                            }
-                            Err(e) => {
-                                warn!(server = %server.name, error = %e, "MCP connect failed")
-                            }
-                        }
-                    }
-                }
-                _ => {
-                    warn!(server = %server.name, transport = %server.transport, "Unknown MCP transport")
-                }
-            }
-        }
-        if mcp.server_count() > 0 {
-            info!(connected = mcp.server_count(), "MCP servers initialized");
-        }
-    }
-
-    // Initialize OAuth store for Claude Max authentication
+    // Initialize OAuth store for Claude Max authentication, refreshing
+    // tokens in the background before they expire
     let oauth_store = Arc::new(OAuthStore::new());
+    let oauth_client = OAuthClient::new();
+    oauth_store.spawn_refresher(oauth_client.clone());
 
-    let state = ProxyState {
-        config: Arc::new(config),
+    Ok(ProxyState {
+        live,
         client,
-        hook_engine,
-        rules_engine,
         compactor,
         session_manager,
-        plugin_manager,
-        mcp_manager,
         oauth_store,
+        oauth_client,
+        loop_run,
+    })
+}
+
+/// Start the proxy server. `overrides` are command-line config overrides,
+/// re-applied whenever the config is reloaded.
+pub async fn start_server(config: AppConfig, overrides: &[(&str, String)]) -> anyhow::Result<()> {
+    let addr = format!("{}:{}", config.proxy.host, config.proxy.port);
+    let state = build_state(config, overrides, true, None).await?;
+
+    // Reload when config, rules or plugins change on disk
+    let _watcher = match reload::watch(state.live.clone()) {
+        Ok(watcher) => Some(watcher),
+        Err(e) => {
+            warn!(error = %e, "Could not watch config files, use POST /admin/reload instead");
+            None
+        }
     };
+    let live = state.live.current();
 
     // Fire SessionStart hook and inject session context
     let (session_id, session_context) = {
@@ -1077,7 +1613,7 @@ pub async fn start_server(config: AppConfig) -> anyhow::Result<()> {
     };
 
     let start_input = HookInput::new(HookEvent::SessionStart).with_session_id(&session_id);
-    let start_result = state
+    let start_result = live
         .hook_engine
         .run(HookEvent::SessionStart, &start_input)
         .await;
@@ -1090,7 +1626,7 @@ pub async fn start_server(config: AppConfig) -> anyhow::Result<()> {
         "Session started"
     );
 
-    let app = create_router(state);
+    let app = create_router(state).into_make_service_with_connect_info::<SocketAddr>();
 
     info!(address = %addr, "Starting OpenClaudia proxy server");
 
@@ -1100,55 +1636,19 @@ pub async fn start_server(config: AppConfig) -> anyhow::Result<()> {
     Ok(())
 }
 
-/// Start the proxy server with graceful shutdown support
+/// Start the proxy server with graceful shutdown support. Plugin MCP servers
+/// are not connected and config files are not watched. `loop_run` is the
+/// loop-mode run whose budget every request is checked against.
 pub async fn start_server_with_shutdown(
     config: AppConfig,
+    overrides: &[(&str, String)],
     mut shutdown_rx: tokio::sync::watch::Receiver<bool>,
+    loop_run: Option<LoopRun>,
 ) -> anyhow::Result<()> {
     let addr = format!("{}:{}", config.proxy.host, config.proxy.port);
+    let state = build_state(config, overrides, false, loop_run).await?;
 
-    let client = Client::builder()
-        .timeout(std::time::Duration::from_secs(300))
-        .build()?;
-
-    let hook_engine = HookEngine::new(config.hooks.clone());
-    let rules_engine = RulesEngine::new(".openclaudia/rules");
-
-    // Initialize compactor with default model context
-    let compactor = ContextCompactor::new(CompactionConfig::default());
-
-    // Initialize session manager
-    let session_manager = Arc::new(RwLock::new(SessionManager::new(
-        &config.session.persist_path,
-    )));
-
-    // Initialize plugin manager and discover plugins
-    let mut plugin_manager = PluginManager::new();
-    let plugin_errors = plugin_manager.discover();
-    for err in plugin_errors {
-        warn!(error = %err, "Plugin discovery error");
-    }
-    let plugin_manager = Arc::new(plugin_manager);
-
-    // Initialize MCP manager
-    let mcp_manager = Arc::new(RwLock::new(McpManager::new()));
-
-    // Initialize OAuth store for Claude Max authentication
-    let oauth_store = Arc::new(OAuthStore::new());
-
-    let state = ProxyState {
-        config: Arc::new(config),
-        client,
-        hook_engine,
-        rules_engine,
-        compactor,
-        session_manager,
-        plugin_manager,
-        mcp_manager,
-        oauth_store,
-    };
-
-    let app = create_router(state);
+    let app = create_router(state).into_make_service_with_connect_info::<SocketAddr>();
 
     info!(address = %addr, "Starting OpenClaudia proxy server (with shutdown support)");
 
@@ -0,0 +1,486 @@
+//! Hot Reload - Swap config, hooks, rules, plugins and MCP servers at runtime.
+//!
+//! Everything built from config files lives in one [`Reloadable`] snapshot.
+//! Readers take the current snapshot with [`Live::current`] and keep using it
+//! for the rest of a request, so a reload never mixes old and new state.
+//! [`Live::reload`] builds the next snapshot and swaps it in; if the config no
+//! longer loads, the previous snapshot stays in place. The `models:`
+//! overrides are re-applied to the model registry with every config loaded.
+//!
+//! MCP connections are shared between snapshots and reconciled in place:
+//! servers whose definition is unchanged stay connected, new and changed ones
+//! are connected before the swap and removed ones are closed after it.
+//!
+//! [`watch`] reloads when `.openclaudia/`, `~/.openclaudia/` or a Claude Code
+//! `settings.json` changes. The `/reload` chat command and `POST /admin/reload`
+//! on the proxy reload on demand.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use serde::Serialize;
+use tokio::sync::{mpsc, Mutex, RwLock};
+use tracing::{info, warn};
+
+use crate::config::{self, AppConfig};
+use crate::hooks::{load_claude_code_hooks, merge_hooks_config, HookEngine};
+use crate::mcp::McpManager;
+use crate::models;
+use crate::plugins::{PluginManager, PluginMcpServer};
+use crate::rules::RulesEngine;
+
+/// Quiet period after a file change before reloading, so an editor's
+/// save (often several writes or a rename) triggers one reload
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Config file names that trigger a reload wherever they are watched
+const CONFIG_FILES: &[&str] = &["config.yaml", "config.local.yaml", "settings.json"];
+
+/// State built from config files, replaced as a whole on reload
+pub struct Reloadable {
+    pub config: Arc<AppConfig>,
+    /// Hooks from config.yaml merged with Claude Code settings
+    pub hook_engine: HookEngine,
+    pub rules_engine: RulesEngine,
+    pub plugin_manager: Arc<PluginManager>,
+    /// Connections to the MCP servers of enabled plugins, shared by snapshots
+    pub mcp_manager: Arc<RwLock<McpManager>>,
+    /// Hook entries across all events
+    hook_count: usize,
+}
+
+impl Reloadable {
+    fn report(&self, mcp_servers: usize, warnings: Vec<String>) -> ReloadReport {
+        ReloadReport {
+            hooks: self.hook_count,
+            rules: self.rules_engine.all_rules().len(),
+            plugins: self.plugin_manager.count(),
+            mcp_servers,
+            warnings,
+        }
+    }
+}
+
+/// What a reload loaded
+#[derive(Debug, Clone, Serialize)]
+pub struct ReloadReport {
+    pub hooks: usize,
+    pub rules: usize,
+    pub plugins: usize,
+    pub mcp_servers: usize,
+    /// Plugins that failed to load and MCP servers that failed to connect
+    pub warnings: Vec<String>,
+}
+
+impl fmt::Display for ReloadReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} hooks, {} rules, {} plugins, {} MCP servers",
+            self.hooks, self.rules, self.plugins, self.mcp_servers
+        )
+    }
+}
+
+/// Reads the config a reload swaps in
+type Loader = dyn Fn() -> Result<AppConfig, String> + Send + Sync;
+
+/// Handle to the current [`Reloadable`] snapshot; clones share it
+#[derive(Clone)]
+pub struct Live {
+    current: Arc<std::sync::RwLock<Arc<Reloadable>>>,
+    /// Loads the config files with the command-line overrides re-applied
+    load: Arc<Loader>,
+    /// Whether plugin MCP servers are connected (the proxy does, chat does not)
+    mcp: bool,
+    /// Serializes reloads
+    reloading: Arc<Mutex<()>>,
+}
+
+impl Live {
+    /// Build the first snapshot from an already loaded config
+    pub async fn start(config: AppConfig, overrides: &[(&str, String)], mcp: bool) -> Self {
+        let rules_engine = RulesEngine::new(".openclaudia/rules");
+
+        let mut plugin_manager = PluginManager::new();
+        for err in plugin_manager.discover() {
+            warn!(error = %err, "Plugin discovery error");
+        }
+
+        let mut mcp_manager = McpManager::new();
+        if mcp {
+            for error in connect_servers(&mut mcp_manager, &mcp_servers(&plugin_manager)).await {
+                warn!("{}", error);
+            }
+            if mcp_manager.server_count() > 0 {
+                info!(
+                    connected = mcp_manager.server_count(),
+                    "MCP servers initialized"
+                );
+            }
+        }
+
+        models::apply_config(&config);
+        let (hook_engine, hook_count) = build_hooks(&config);
+        let snapshot = Reloadable {
+            config: Arc::new(config),
+            hook_engine,
+            hook_count,
+            rules_engine,
+            plugin_manager: Arc::new(plugin_manager),
+            mcp_manager: Arc::new(RwLock::new(mcp_manager)),
+        };
+        let overrides: Vec<(String, String)> = overrides
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.clone()))
+            .collect();
+        Self {
+            current: Arc::new(std::sync::RwLock::new(Arc::new(snapshot))),
+            load: Arc::new(move || {
+                let overrides: Vec<(&str, String)> = overrides
+                    .iter()
+                    .map(|(key, value)| (key.as_str(), value.clone()))
+                    .collect();
+                config::load_config_with(&overrides).map_err(|e| e.to_string())
+            }),
+            mcp,
+            reloading: Arc::new(Mutex::new(())),
+        }
+    }
+
+    /// Reload from `load` instead of the standard config files
+    pub fn with_loader(
+        mut self,
+        load: impl Fn() -> Result<AppConfig, String> + Send + Sync + 'static,
+    ) -> Self {
+        self.load = Arc::new(load);
+        self
+    }
+
+    /// The current snapshot
+    pub fn current(&self) -> Arc<Reloadable> {
+        match self.current.read() {
+            Ok(current) => current.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+
+    /// Re-read every config file and swap in the result. On error the
+    /// previous snapshot stays active.
+    pub async fn reload(&self) -> Result<ReloadReport, String> {
+        let _reloading = self.reloading.lock().await;
+
+        let config = (self.load)()?;
+        models::apply_config(&config);
+        let previous = self.current();
+
+        let mut rules_engine = previous.rules_engine.clone();
+        rules_engine.reload();
+
+        let mut plugin_manager = (*previous.plugin_manager).clone();
+        let mut warnings: Vec<String> = plugin_manager
+            .reload()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+
+        let mut closing = Vec::new();
+        if self.mcp {
+            let (errors, removed) = sync_servers(
+                &previous.mcp_manager,
+                &mcp_servers(&previous.plugin_manager),
+                &mcp_servers(&plugin_manager),
+            )
+            .await;
+            warnings.extend(errors);
+            closing = removed;
+        }
+        let connected = previous.mcp_manager.read().await.server_count();
+
+        let (hook_engine, hook_count) = build_hooks(&config);
+        let next = Reloadable {
+            config: Arc::new(config),
+            hook_engine,
+            hook_count,
+            rules_engine,
+            plugin_manager: Arc::new(plugin_manager),
+            mcp_manager: previous.mcp_manager.clone(),
+        };
+        let report = next.report(connected, warnings);
+        match self.current.write() {
+            Ok(mut current) => *current = Arc::new(next),
+            Err(poisoned) => *poisoned.into_inner() = Arc::new(next),
+        }
+
+        for server in closing {
+            let name = server.name().to_string();
+            if let Err(e) = server.close().await {
+                warn!(server = %name, error = %e, "Failed to close MCP server");
+            }
+        }
+        Ok(report)
+    }
+}
+
+/// Hook engine for a config, with Claude Code hooks merged in
+fn build_hooks(config: &AppConfig) -> (HookEngine, usize) {
+    let hooks = merge_hooks_config(config.hooks.clone(), load_claude_code_hooks());
+    let count = hooks.len();
+    (HookEngine::new(hooks), count)
+}
+
+/// MCP server definitions of enabled plugins, by server name
+fn mcp_servers(plugins: &PluginManager) -> HashMap<String, PluginMcpServer> {
+    plugins
+        .all_mcp_servers()
+        .into_iter()
+        .map(|(_, server)| (server.name.clone(), server.clone()))
+        .collect()
+}
+
+/// Connect servers, returning an error message for each that failed
+async fn connect_servers(
+    manager: &mut McpManager,
+    servers: &HashMap<String, PluginMcpServer>,
+) -> Vec<String> {
+    let mut errors = Vec::new();
+    for server in servers.values() {
+        let result = match (server.transport.as_str(), &server.command, &server.url) {
+            ("stdio", Some(command), _) => {
+                let args: Vec<&str> = server.args.iter().map(|s| s.as_str()).collect();
+                manager.connect_stdio(&server.name, command, &args).await
+            }
+            ("http", _, Some(url)) => manager.connect_http(&server.name, url).await,
+            ("stdio", None, _) | ("http", _, None) => continue,
+            (transport, _, _) => {
+                errors.push(format!(
+                    "MCP server {}: unknown transport '{}'",
+                    server.name, transport
+                ));
+                continue;
+            }
+        };
+        match result {
+            Ok(()) => info!(server = %server.name, transport = %server.transport, "Connected MCP"),
+            Err(e) => errors.push(format!("MCP server {}: {}", server.name, e)),
+        }
+    }
+    errors
+}
+
+/// Bring the shared connections in line with the new plugin set. New and
+/// changed servers connect without holding the lock; the swap itself is one
+/// write. Returns connection errors and the replaced connections to close.
+async fn sync_servers(
+    manager: &RwLock<McpManager>,
+    previous: &HashMap<String, PluginMcpServer>,
+    next: &HashMap<String, PluginMcpServer>,
+) -> (Vec<String>, Vec<crate::mcp::McpServer>) {
+    let connected = manager.read().await.server_names();
+    let changed: HashMap<String, PluginMcpServer> = next
+        .iter()
+        .filter(|(name, server)| !connected.contains(name) || previous.get(*name) != Some(server))
+        .map(|(name, server)| (name.clone(), server.clone()))
+        .collect();
+
+    let mut fresh = McpManager::new();
+    let errors = connect_servers(&mut fresh, &changed).await;
+
+    let mut manager = manager.write().await;
+    let mut removed: Vec<_> = connected
+        .iter()
+        .filter(|name| !next.contains_key(*name) || changed.contains_key(*name))
+        .filter_map(|name| manager.take(name))
+        .collect();
+    removed.extend(manager.merge(fresh));
+    (errors, removed)
+}
+
+/// Whether a changed path is one reloading depends on
+fn is_relevant(path: &Path) -> bool {
+    let in_dir = |dir: &str| path.components().any(|c| c.as_os_str() == dir);
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| CONFIG_FILES.contains(&name))
+        || in_dir("rules")
+        || in_dir("plugins")
+}
+
+/// Directories to watch: project and user `.openclaudia/` (recursively) and
+/// the `.claude/` directories holding Claude Code settings
+fn watched_dirs() -> Vec<(PathBuf, RecursiveMode)> {
+    let home = dirs::home_dir();
+    let mut dirs = vec![
+        (PathBuf::from(".openclaudia"), RecursiveMode::Recursive),
+        (PathBuf::from(".claude"), RecursiveMode::NonRecursive),
+    ];
+    if let Some(home) = home {
+        dirs.push((home.join(".openclaudia"), RecursiveMode::Recursive));
+        dirs.push((home.join(".claude"), RecursiveMode::NonRecursive));
+    }
+    dirs.into_iter().filter(|(dir, _)| dir.is_dir()).collect()
+}
+
+/// Reload whenever a watched config, rules or plugin file changes. Watching
+/// stops when the returned watcher is dropped.
+pub fn watch(live: Live) -> notify::Result<RecommendedWatcher> {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        if let Ok(event) = event {
+            if !event.kind.is_access() && event.paths.iter().any(|p| is_relevant(p)) {
+                let _ = tx.send(());
+            }
+        }
+    })?;
+    for (dir, mode) in watched_dirs() {
+        watcher.watch(&dir, mode)?;
+    }
+
+    tokio::spawn(async move {
+        while rx.recv().await.is_some() {
+            tokio::time::sleep(DEBOUNCE).await;
+            while rx.try_recv().is_ok() {}
+            match live.reload().await {
+                Ok(report) => {
+                    info!("Reloaded configuration: {}", report);
+                    for warning in &report.warnings {
+                        warn!("{}", warning);
+                    }
+                }
+                Err(e) => warn!("Reload failed, keeping the previous configuration: {}", e),
+            }
+        }
+    });
+    Ok(watcher)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_relevant() {
+        assert!(is_relevant(Path::new(".openclaudia/config.yaml")));
+        assert!(is_relevant(Path::new(".openclaudia/config.local.yaml")));
+        assert!(is_relevant(Path::new("/home/me/.claude/settings.json")));
+        assert!(is_relevant(Path::new(".openclaudia/rules/rust.md")));
+        assert!(is_relevant(Path::new(
+            "/home/me/.openclaudia/plugins/git/plugin.json"
+        )));
+        assert!(!is_relevant(Path::new(".openclaudia/memory.db-journal")));
+        assert!(!is_relevant(Path::new(".openclaudia/session/abc.json")));
+        assert!(!is_relevant(Path::new(".openclaudia/config.schema.json")));
+    }
+
+    /// A live handle reloading from `path` as the project config
+    async fn live_from(path: &Path) -> Live {
+        let path = path.to_path_buf();
+        let load = move || {
+            config::load_files(&[(config::ConfigScope::Project, path.clone())], &[])
+                .map(|layered| layered.config)
+                .map_err(|e| e.to_string())
+        };
+        let config = load().unwrap();
+        Live::start(config, &[], false).await.with_loader(load)
+    }
+
+    #[tokio::test]
+    async fn test_reload_swaps_snapshot() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.yaml");
+        std::fs::write(&path, "proxy:\n  port: 1000\n").unwrap();
+        let live = live_from(&path).await;
+        let before = live.current();
+        assert_eq!(before.config.proxy.port, 1000);
+
+        std::fs::write(&path, "proxy:\n  port: 2000\n").unwrap();
+        live.reload().await.unwrap();
+        assert_eq!(live.current().config.proxy.port, 2000);
+        // Readers holding the old snapshot keep seeing it
+        assert_eq!(before.config.proxy.port, 1000);
+        // Clones share the swapped snapshot
+        assert_eq!(live.clone().current().config.proxy.port, 2000);
+    }
+
+    #[tokio::test]
+    async fn test_reload_keeps_snapshot_on_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.yaml");
+        std::fs::write(&path, "proxy:\n  port: 1000\n").unwrap();
+        let live = live_from(&path).await;
+
+        for broken in [
+            "proxy:\n  port: [1000\n",
+            "proxy:\n  target: ${OC_RELOAD_TEST_TARGET\n",
+        ] {
+            std::fs::write(&path, broken).unwrap();
+            assert!(live.reload().await.is_err(), "{}", broken);
+            assert_eq!(live.current().config.proxy.port, 1000);
+        }
+
+        std::fs::write(&path, "proxy:\n  port: 3000\n").unwrap();
+        live.reload().await.unwrap();
+        assert_eq!(live.current().config.proxy.port, 3000);
+    }
+
+    #[tokio::test]
+    async fn test_admin_reload_route() {
+        use axum::body::Body;
+        use axum::extract::ConnectInfo;
+        use axum::http::{Request, StatusCode};
+        use std::net::SocketAddr;
+        use tower::ServiceExt;
+
+        use crate::compaction::{CompactionConfig, ContextCompactor};
+        use crate::oauth::{OAuthClient, OAuthStore};
+        use crate::proxy::{create_router, ProxyState};
+        use crate::session::SessionManager;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.yaml");
+        std::fs::write(&path, "proxy:\n  port: 1000\n").unwrap();
+        let live = live_from(&path).await;
+        let router = create_router(ProxyState {
+            live: live.clone(),
+            client: reqwest::Client::new(),
+            compactor: ContextCompactor::new(CompactionConfig::default()),
+            session_manager: Arc::new(RwLock::new(SessionManager::new(dir.path()))),
+            oauth_store: Arc::new(OAuthStore::in_memory()),
+            oauth_client: OAuthClient::new(),
+            loop_run: None,
+        });
+        let reload = |peer: Option<&str>| {
+            let mut request = Request::post("/admin/reload").body(Body::empty()).unwrap();
+            if let Some(peer) = peer {
+                let addr: SocketAddr = peer.parse().unwrap();
+                request.extensions_mut().insert(ConnectInfo(addr));
+            }
+            router.clone().oneshot(request)
+        };
+        std::fs::write(&path, "proxy:\n  port: 2000\n").unwrap();
+
+        for peer in [None, Some("192.168.1.20:5000")] {
+            let response = reload(peer).await.unwrap();
+            assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        }
+        assert_eq!(live.current().config.proxy.port, 1000);
+
+        let response = reload(Some("127.0.0.1:5000")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["status"], "reloaded");
+        assert_eq!(live.current().config.proxy.port, 2000);
+
+        std::fs::write(&path, "proxy: [\n").unwrap();
+        let response = reload(Some("[::1]:5000")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(live.current().config.proxy.port, 2000);
+    }
+}
@@ -0,0 +1,350 @@
+//! Model Routing - Picks the provider and model for each proxied request.
+//!
+//! `routing.rules` match on the requested model, tools, images, the estimated
+//! prompt size, header values and the calling client; the first match sets
+//! the provider, model and/or Claude Max account. `routing.aliases` map names such as `fast` or
+//! `smart` to concrete models. Whatever is left open follows from the model
+//! registry, falling back to the configured target.
+
+use std::cell::OnceCell;
+
+use axum::http::{header, HeaderMap};
+use serde_json::Value;
+
+use crate::compaction::count_request_tokens;
+use crate::config::{AppConfig, ModelAlias, RouteMatch};
+use crate::models;
+use crate::proxy::{ChatCompletionRequest, MessageContent};
+use crate::tokenizer::{self, Tokenizer};
+
+/// Request properties routing rules match on
+pub struct RequestFacts<'a> {
+    pub model: &'a str,
+    pub headers: &'a HeaderMap,
+    pub has_tools: bool,
+    pub has_images: bool,
+    /// Estimates the prompt size; only called once a rule asks for it
+    count_tokens: Box<dyn Fn() -> usize + 'a>,
+    tokens: OnceCell<usize>,
+}
+
+impl<'a> RequestFacts<'a> {
+    /// Facts of an OpenAI chat completion request
+    pub fn chat(request: &'a ChatCompletionRequest, headers: &'a HeaderMap) -> Self {
+        let has_images = request.messages.iter().any(|m| match &m.content {
+            MessageContent::Parts(parts) => parts
+                .iter()
+                .any(|p| p.image_url.is_some() || p.content_type == "image_url"),
+            MessageContent::Text(_) => false,
+        });
+        Self {
+            model: &request.model,
+            headers,
+            has_tools: request.tools.as_ref().is_some_and(|t| !t.is_empty()),
+            has_images,
+            count_tokens: Box::new(move || {
+                count_request_tokens(&tokenizer::for_model(&request.model), request)
+            }),
+            tokens: OnceCell::new(),
+        }
+    }
+
+    /// Facts of a raw JSON request (Anthropic messages, legacy completions)
+    pub fn json(model: &'a str, body: &'a Value, headers: &'a HeaderMap) -> Self {
+        let has_images = body["messages"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|m| m["content"].as_array())
+            .flatten()
+            .any(|block| matches!(block["type"].as_str(), Some("image" | "image_url")));
+        Self {
+            model,
+            headers,
+            has_tools: body["tools"].as_array().is_some_and(|t| !t.is_empty()),
+            has_images,
+            count_tokens: Box::new(move || tokenizer::for_model(model).count(&body.to_string())),
+            tokens: OnceCell::new(),
+        }
+    }
+
+    fn prompt_tokens(&self) -> usize {
+        *self.tokens.get_or_init(|| (self.count_tokens)())
+    }
+
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).and_then(|v| v.to_str().ok())
+    }
+}
+
+/// Where a request goes
+#[derive(Debug, Clone, PartialEq)]
+pub struct Routed {
+    pub provider: String,
+    pub model: String,
+    /// The provider differs from the one the requested model implies, so the
+    /// client's API key is not meant for it
+    pub switched_provider: bool,
+    /// Claude Max account named by the matching rule
+    pub account: Option<String>,
+    /// Name of the matching rule (`rules[i]` when unnamed)
+    pub rule: Option<String>,
+}
+
+/// Pick the provider and model for a request
+pub fn route(config: &AppConfig, facts: &RequestFacts) -> Routed {
+    let routing = &config.routing;
+    let matched = routing
+        .rules
+        .iter()
+        .enumerate()
+        .find(|(_, rule)| matches(&rule.when, facts));
+    let rule_model = matched.and_then(|(_, rule)| rule.model.as_deref());
+    let rule_provider = matched.and_then(|(_, rule)| rule.provider.as_deref());
+
+    // An alias's provider belongs to its model, so it only applies when the
+    // alias supplies the model
+    let alias = routing.aliases.get(facts.model);
+    let (model, alias_provider) = match (rule_model, alias) {
+        (Some(model), _) => (model, None),
+        (None, Some(alias)) => (alias.model(), alias.provider()),
+        (None, None) => (facts.model, None),
+    };
+    let provider = rule_provider
+        .or(alias_provider)
+        .map(str::to_string)
+        .unwrap_or_else(|| provider_for_model(model, config));
+
+    Routed {
+        switched_provider: provider != provider_for_model(facts.model, config),
+        provider,
+        model: model.to_string(),
+        account: matched.and_then(|(_, rule)| rule.account.clone()),
+        rule: matched.map(|(i, rule)| rule.name.clone().unwrap_or_else(|| format!("rules[{}]", i))),
+    }
+}
+
+/// Configured aliases with the provider and model each resolves to, by name
+pub fn aliases(config: &AppConfig) -> Vec<(&str, Routed)> {
+    let mut aliases: Vec<(&str, Routed)> = config
+        .routing
+        .aliases
+        .iter()
+        .map(|(name, alias)| (name.as_str(), alias_route(config, name, alias)))
+        .collect();
+    aliases.sort_by(|a, b| a.0.cmp(b.0));
+    aliases
+}
+
+fn alias_route(config: &AppConfig, name: &str, alias: &ModelAlias) -> Routed {
+    let provider = alias
+        .provider()
+        .map(str::to_string)
+        .unwrap_or_else(|| provider_for_model(alias.model(), config));
+    Routed {
+        switched_provider: provider != provider_for_model(name, config),
+        provider,
+        model: alias.model().to_string(),
+        account: None,
+        rule: None,
+    }
+}
+
+/// Provider for a model name. The model registry knows the provider; unknown
+/// models or unconfigured providers fall back to the configured target.
+pub fn provider_for_model(model: &str, config: &AppConfig) -> String {
+    models::provider_for(model)
+        .filter(|provider| config.providers.contains_key(provider))
+        .unwrap_or_else(|| config.proxy.target.clone())
+}
+
+/// Whether every condition of a rule holds. The prompt is only tokenized
+/// once the cheaper conditions have matched.
+fn matches(when: &RouteMatch, facts: &RequestFacts) -> bool {
+    let client = facts
+        .header(header::USER_AGENT.as_str())
+        .unwrap_or_default()
+        .to_lowercase();
+    when.model
+        .as_deref()
+        .is_none_or(|pattern| model_matches(pattern, facts.model))
+        && when.has_tools.is_none_or(|t| t == facts.has_tools)
+        && when.has_images.is_none_or(|i| i == facts.has_images)
+        && when
+            .client
+            .as_deref()
+            .is_none_or(|c| client.contains(&c.to_lowercase()))
+        && when
+            .headers
+            .iter()
+            .all(|(name, value)| facts.header(name) == Some(value.as_str()))
+        && when
+            .min_prompt_tokens
+            .is_none_or(|min| facts.prompt_tokens() >= min)
+        && when
+            .max_prompt_tokens
+            .is_none_or(|max| facts.prompt_tokens() <= max)
+}
+
+/// Exact model name, or a prefix when the pattern ends in `*`
+fn model_matches(pattern: &str, model: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => model.starts_with(prefix),
+        None => model == pattern,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn config() -> AppConfig {
+        serde_yaml::from_str(
+            r#"
+proxy:
+  target: anthropic
+providers:
+  anthropic: { base_url: "https://api.anthropic.com" }
+  google: { base_url: "https://generativelanguage.googleapis.com" }
+  ollama: { base_url: "http://localhost:11434" }
+routing:
+  aliases:
+    fast: claude-haiku-4-5
+    local: { provider: ollama, model: llama3.2 }
+    smart: gemini-2.5-pro
+  rules:
+    - name: large-context
+      when: { min_prompt_tokens: 2000 }
+      provider: google
+      model: gemini-2.5-pro
+    - when: { headers: { X-Task: summarize }, has_tools: false }
+      provider: ollama
+      model: llama3.2
+    - when: { model: "claude-opus*", client: cursor }
+      model: claude-sonnet-4-5
+    - when: { client: claude-cli }
+      account: work
+    - when: { model: claude-3-5-haiku }
+      model: gemini-2.5-flash
+    - when: { has_images: true, model: local }
+      provider: ollama
+      model: llava
+"#,
+        )
+        .unwrap()
+    }
+
+    fn request(model: &str, text: &str) -> Value {
+        json!({"model": model, "messages": [{"role": "user", "content": text}]})
+    }
+
+    fn route_json(config: &AppConfig, body: &Value, headers: &HeaderMap) -> Routed {
+        let model = body["model"].as_str().unwrap();
+        route(config, &RequestFacts::json(model, body, headers))
+    }
+
+    #[test]
+    fn test_aliases_and_default_provider() {
+        let config = config();
+        let headers = HeaderMap::new();
+
+        let routed = route_json(&config, &request("fast", "hi"), &headers);
+        assert_eq!(routed.provider, "anthropic");
+        assert_eq!(routed.model, "claude-haiku-4-5");
+        assert!(!routed.switched_provider);
+        assert_eq!(routed.rule, None);
+
+        let routed = route_json(&config, &request("local", "hi"), &headers);
+        assert_eq!(
+            (routed.provider.as_str(), routed.model.as_str()),
+            ("ollama", "llama3.2")
+        );
+        assert!(routed.switched_provider);
+
+        // An alias to another vendor's model switches provider without naming one
+        let routed = route_json(&config, &request("smart", "hi"), &headers);
+        assert_eq!(routed.provider, "google");
+        assert!(routed.switched_provider);
+
+        let routed = route_json(&config, &request("gemini-2.5-flash", "hi"), &headers);
+        assert_eq!(routed.provider, "google");
+        assert!(!routed.switched_provider);
+        let routed = route_json(&config, &request("unknown-model", "hi"), &headers);
+        assert_eq!(routed.provider, "anthropic");
+
+        let names: Vec<&str> = aliases(&config).iter().map(|(name, _)| *name).collect();
+        assert_eq!(names, ["fast", "local", "smart"]);
+    }
+
+    #[test]
+    fn test_rules() {
+        let config = config();
+        let mut headers = HeaderMap::new();
+
+        let large = request("fast", &"lorem ipsum dolor ".repeat(2000));
+        let routed = route_json(&config, &large, &headers);
+        assert_eq!(routed.provider, "google");
+        assert_eq!(routed.model, "gemini-2.5-pro");
+        assert_eq!(routed.rule.as_deref(), Some("large-context"));
+
+        headers.insert("x-task", "summarize".parse().unwrap());
+        let routed = route_json(&config, &request("smart", "hi"), &headers);
+        assert_eq!(
+            (routed.provider.as_str(), routed.model.as_str()),
+            ("ollama", "llama3.2")
+        );
+        assert_eq!(routed.rule.as_deref(), Some("rules[1]"));
+        assert!(routed.switched_provider);
+
+        // Tools rule out the summarization rule
+        let mut with_tools = request("claude-opus-4-5", "hi");
+        with_tools["tools"] = json!([{"name": "read"}]);
+        let routed = route_json(&config, &with_tools, &headers);
+        assert_eq!(routed.model, "claude-opus-4-5");
+        assert_eq!(routed.rule, None);
+
+        headers.insert(header::USER_AGENT, "Cursor/1.4".parse().unwrap());
+        let routed = route_json(&config, &with_tools, &headers);
+        assert_eq!(routed.model, "claude-sonnet-4-5");
+        assert_eq!(routed.provider, "anthropic");
+        assert_eq!(routed.account, None);
+
+        // Rules can pick just the account, keeping the requested model
+        let mut headers = HeaderMap::new();
+        headers.insert(header::USER_AGENT, "claude-cli/2.0".parse().unwrap());
+        let routed = route_json(&config, &with_tools, &headers);
+        assert_eq!(routed.model, "claude-opus-4-5");
+        assert_eq!(routed.account.as_deref(), Some("work"));
+
+        let image = json!({"model": "local", "messages": [{"role": "user", "content": [
+            {"type": "image", "source": {"type": "base64", "media_type": "image/png", "data": ""}}
+        ]}]});
+        let routed = route_json(&config, &image, &HeaderMap::new());
+        assert_eq!(
+            (routed.provider.as_str(), routed.model.as_str()),
+            ("ollama", "llava")
+        );
+        assert!(routed.switched_provider);
+
+        // A rule that only sets a model from another vendor still switches provider
+        let routed = route_json(
+            &config,
+            &request("claude-3-5-haiku", "hi"),
+            &HeaderMap::new(),
+        );
+        assert_eq!(
+            (routed.provider.as_str(), routed.model.as_str()),
+            ("google", "gemini-2.5-flash")
+        );
+        assert!(routed.switched_provider);
+    }
+
+    #[test]
+    fn test_model_patterns() {
+        assert!(model_matches("claude-opus*", "claude-opus-4-5"));
+        assert!(!model_matches("claude-opus", "claude-opus-4-5"));
+        assert!(model_matches("fast", "fast"));
+        assert!(model_matches("*", "anything"));
+    }
+}
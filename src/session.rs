@@ -6,6 +6,7 @@
 //!
 //! Treats agents like shift workers with documented handoffs.
 
+use crate::tools::TodoItem;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::fs;
@@ -38,6 +39,33 @@ pub struct SessionProgress {
     pub files_modified: Vec<String>,
     /// Notes for next session
     pub handoff_notes: String,
+    /// Todo list from the todo_write tool
+    #[serde(default)]
+    pub todos: Vec<TodoItem>,
+}
+
+/// Which provider and model answered a request, after retries and fallback
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServedTurn {
+    /// Provider name from the config
+    pub provider: String,
+    /// Model requested from that provider
+    pub model: String,
+    /// Whether this was a fallback rather than the configured target
+    pub fallback: bool,
+    /// When the response arrived
+    pub at: DateTime<Utc>,
+}
+
+impl ServedTurn {
+    pub fn new(provider: impl Into<String>, model: impl Into<String>, fallback: bool) -> Self {
+        Self {
+            provider: provider.into(),
+            model: model.into(),
+            fallback,
+            at: Utc::now(),
+        }
+    }
 }
 
 /// A single agent session
@@ -59,6 +87,9 @@ pub struct Session {
     pub request_count: u64,
     /// Total tokens used (approximate)
     pub total_tokens: u64,
+    /// Provider that served each request
+    #[serde(default)]
+    pub served_by: Vec<ServedTurn>,
 }
 
 impl Session {
@@ -74,6 +105,7 @@ impl Session {
             parent_session_id: None,
             request_count: 0,
             total_tokens: 0,
+            served_by: Vec::new(),
         }
     }
 
@@ -89,6 +121,7 @@ impl Session {
             parent_session_id: Some(parent_id.to_string()),
             request_count: 0,
             total_tokens: 0,
+            served_by: Vec::new(),
         }
     }
 
@@ -103,6 +136,12 @@ impl Session {
         self.touch();
     }
 
+    /// Record which provider served a request
+    pub fn record_served(&mut self, served: ServedTurn) {
+        self.served_by.push(served);
+        self.touch();
+    }
+
     /// Add tokens to the total
     pub fn add_tokens(&mut self, tokens: u64) {
         self.total_tokens += tokens;
@@ -130,6 +169,12 @@ impl Session {
         self.touch();
     }
 
+    /// Record the current todo list
+    pub fn set_todos(&mut self, todos: Vec<TodoItem>) {
+        self.progress.todos = todos;
+        self.touch();
+    }
+
     /// Generate a handoff summary for the next agent
     pub fn generate_handoff(&self) -> String {
         let mut handoff = String::new();
@@ -167,6 +212,25 @@ impl Session {
             handoff.push('\n');
         }
 
+        let unfinished: Vec<&TodoItem> = self
+            .progress
+            .todos
+            .iter()
+            .filter(|t| !t.is_completed())
+            .collect();
+        if !unfinished.is_empty() {
+            handoff.push_str("### Unfinished Todos\n");
+            for todo in unfinished {
+                let marker = if todo.status == "in_progress" {
+                    " (in progress)"
+                } else {
+                    ""
+                };
+                handoff.push_str(&format!("- [ ] {}{}\n", todo.content, marker));
+            }
+            handoff.push('\n');
+        }
+
         if !self.progress.decisions.is_empty() {
             handoff.push_str("### Key Decisions\n");
             for decision in &self.progress.decisions {
@@ -375,7 +439,7 @@ impl SessionManager {
         }
 
         // Sort by created_at descending
-        sessions.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+        sessions.sort_by_key(|s| std::cmp::Reverse(s.created_at));
         sessions
     }
 
@@ -480,6 +544,28 @@ mod tests {
         assert!(handoff.contains("Implemented feature X"));
         assert!(handoff.contains("Test feature X"));
         assert!(handoff.contains("needs tests"));
+        assert!(!handoff.contains("Unfinished Todos"));
+    }
+
+    #[test]
+    fn test_generate_handoff_with_unfinished_todos() {
+        let todo = |content: &str, status: &str| TodoItem {
+            content: content.to_string(),
+            status: status.to_string(),
+            active_form: content.to_string(),
+        };
+        let mut session = Session::new_initializer();
+        session.set_todos(vec![
+            todo("Write parser", "completed"),
+            todo("Add tests", "in_progress"),
+            todo("Update docs", "pending"),
+        ]);
+
+        let handoff = session.generate_handoff();
+        assert!(handoff.contains("### Unfinished Todos"));
+        assert!(handoff.contains("- [ ] Add tests (in progress)"));
+        assert!(handoff.contains("- [ ] Update docs"));
+        assert!(!handoff.contains("Write parser"));
     }
 
     #[test]
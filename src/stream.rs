@@ -0,0 +1,1583 @@
+//! Streaming Events - Provider-agnostic model of a streamed model response.
+//!
+//! Every provider streams differently: Anthropic sends typed SSE events,
+//! OpenAI-compatible servers send `chat.completion.chunk` SSE, the OpenAI
+//! Responses API sends typed `response.*` SSE events, Gemini sends
+//! whole `GenerateContentResponse` objects over SSE, Bedrock sends binary AWS
+//! event stream frames, and Ollama sends newline-delimited JSON. Each has a
+//! [`StreamDecoder`] here that turns raw body bytes into the same
+//! [`StreamEvent`]s, so the chat loop, subagents and the proxy consume one
+//! event model.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::tools::ToolCallAccumulator;
+
+/// Token counts reported by the provider.
+///
+/// `input_tokens` excludes cached input, which is billed differently and
+/// counted on its own. `reasoning_tokens` is the part of `output_tokens`
+/// spent thinking.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenUsage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    /// Input read from the provider's prompt cache
+    #[serde(default)]
+    pub cache_read_tokens: u64,
+    /// Input written to the provider's prompt cache
+    #[serde(default)]
+    pub cache_write_tokens: u64,
+    #[serde(default)]
+    pub reasoning_tokens: u64,
+}
+
+impl TokenUsage {
+    /// Parse an OpenAI `usage` object, where cached tokens are part of `prompt_tokens`
+    pub fn from_openai(usage: &Value) -> Self {
+        let prompt = usage["prompt_tokens"].as_u64().unwrap_or(0);
+        let cached = usage["prompt_tokens_details"]["cached_tokens"]
+            .as_u64()
+            .unwrap_or(0);
+        Self {
+            input_tokens: prompt.saturating_sub(cached),
+            output_tokens: usage["completion_tokens"].as_u64().unwrap_or(0),
+            cache_read_tokens: cached,
+            cache_write_tokens: 0,
+            reasoning_tokens: usage["completion_tokens_details"]["reasoning_tokens"]
+                .as_u64()
+                .unwrap_or(0),
+        }
+    }
+
+    /// Parse an OpenAI Responses API `usage` object, where cached tokens are
+    /// part of `input_tokens`
+    pub fn from_responses(usage: &Value) -> Self {
+        let input = usage["input_tokens"].as_u64().unwrap_or(0);
+        let cached = usage["input_tokens_details"]["cached_tokens"]
+            .as_u64()
+            .unwrap_or(0);
+        Self {
+            input_tokens: input.saturating_sub(cached),
+            output_tokens: usage["output_tokens"].as_u64().unwrap_or(0),
+            cache_read_tokens: cached,
+            cache_write_tokens: 0,
+            reasoning_tokens: usage["output_tokens_details"]["reasoning_tokens"]
+                .as_u64()
+                .unwrap_or(0),
+        }
+    }
+
+    /// Parse an Anthropic `usage` object
+    pub fn from_anthropic(usage: &Value) -> Self {
+        let count = |key: &str| usage[key].as_u64().unwrap_or(0);
+        Self {
+            input_tokens: count("input_tokens"),
+            output_tokens: count("output_tokens"),
+            cache_read_tokens: count("cache_read_input_tokens"),
+            cache_write_tokens: count("cache_creation_input_tokens"),
+            reasoning_tokens: 0,
+        }
+    }
+
+    /// Parse Gemini `usageMetadata`, where thoughts are counted apart from the
+    /// candidates and cached content is part of the prompt
+    pub fn from_gemini(usage: &Value) -> Self {
+        let count = |key: &str| usage[key].as_u64().unwrap_or(0);
+        let thoughts = count("thoughtsTokenCount");
+        Self {
+            input_tokens: count("promptTokenCount")
+                .saturating_sub(count("cachedContentTokenCount")),
+            output_tokens: count("candidatesTokenCount") + thoughts,
+            cache_read_tokens: count("cachedContentTokenCount"),
+            cache_write_tokens: 0,
+            reasoning_tokens: thoughts,
+        }
+    }
+
+    /// Parse a Bedrock Converse `usage` object
+    pub fn from_bedrock(usage: &Value) -> Self {
+        let count = |key: &str| usage[key].as_u64().unwrap_or(0);
+        Self {
+            input_tokens: count("inputTokens"),
+            output_tokens: count("outputTokens"),
+            cache_read_tokens: count("cacheReadInputTokens"),
+            cache_write_tokens: count("cacheWriteInputTokens"),
+            reasoning_tokens: 0,
+        }
+    }
+
+    /// Parse the counters of a final Ollama `/api/chat` message
+    pub fn from_ollama(response: &Value) -> Self {
+        Self {
+            input_tokens: response["prompt_eval_count"].as_u64().unwrap_or(0),
+            output_tokens: response["eval_count"].as_u64().unwrap_or(0),
+            ..Default::default()
+        }
+    }
+
+    /// All input tokens, cached or not
+    pub fn prompt_tokens(&self) -> u64 {
+        self.input_tokens + self.cache_read_tokens + self.cache_write_tokens
+    }
+
+    pub fn total_tokens(&self) -> u64 {
+        self.prompt_tokens() + self.output_tokens
+    }
+
+    /// Render as an OpenAI `usage` object
+    pub fn to_openai(&self) -> Value {
+        json!({
+            "prompt_tokens": self.prompt_tokens(),
+            "completion_tokens": self.output_tokens,
+            "total_tokens": self.total_tokens(),
+            "prompt_tokens_details": {"cached_tokens": self.cache_read_tokens},
+            "completion_tokens_details": {"reasoning_tokens": self.reasoning_tokens}
+        })
+    }
+}
+
+impl std::ops::AddAssign for TokenUsage {
+    fn add_assign(&mut self, other: Self) {
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+        self.cache_read_tokens += other.cache_read_tokens;
+        self.cache_write_tokens += other.cache_write_tokens;
+        self.reasoning_tokens += other.reasoning_tokens;
+    }
+}
+
+/// Why the model stopped generating
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StopReason {
+    EndTurn,
+    ToolUse,
+    MaxTokens,
+    ContentFilter,
+    Other(String),
+}
+
+impl StopReason {
+    pub fn from_openai(reason: &str) -> Self {
+        match reason {
+            "stop" => StopReason::EndTurn,
+            "tool_calls" | "function_call" => StopReason::ToolUse,
+            "length" => StopReason::MaxTokens,
+            "content_filter" => StopReason::ContentFilter,
+            other => StopReason::Other(other.to_string()),
+        }
+    }
+
+    pub fn from_anthropic(reason: &str) -> Self {
+        match reason {
+            "end_turn" | "stop_sequence" => StopReason::EndTurn,
+            "tool_use" => StopReason::ToolUse,
+            "max_tokens" => StopReason::MaxTokens,
+            "refusal" => StopReason::ContentFilter,
+            other => StopReason::Other(other.to_string()),
+        }
+    }
+
+    pub fn from_gemini(reason: &str) -> Self {
+        match reason {
+            "STOP" => StopReason::EndTurn,
+            "MAX_TOKENS" => StopReason::MaxTokens,
+            "SAFETY" | "RECITATION" | "BLOCKLIST" | "PROHIBITED_CONTENT" | "SPII" => {
+                StopReason::ContentFilter
+            }
+            other => StopReason::Other(other.to_string()),
+        }
+    }
+
+    pub fn from_bedrock(reason: &str) -> Self {
+        match reason {
+            "end_turn" | "stop_sequence" => StopReason::EndTurn,
+            "tool_use" => StopReason::ToolUse,
+            "max_tokens" => StopReason::MaxTokens,
+            "guardrail_intervened" | "content_filtered" => StopReason::ContentFilter,
+            other => StopReason::Other(other.to_string()),
+        }
+    }
+
+    /// Reason for a Responses API turn ending `incomplete`
+    pub fn from_responses_incomplete(reason: &str) -> Self {
+        match reason {
+            "max_output_tokens" => StopReason::MaxTokens,
+            "content_filter" => StopReason::ContentFilter,
+            other => StopReason::Other(other.to_string()),
+        }
+    }
+
+    /// The equivalent OpenAI `finish_reason`
+    pub fn as_openai(&self) -> &str {
+        match self {
+            StopReason::EndTurn => "stop",
+            StopReason::ToolUse => "tool_calls",
+            StopReason::MaxTokens => "length",
+            StopReason::ContentFilter => "content_filter",
+            StopReason::Other(reason) => reason,
+        }
+    }
+}
+
+/// One normalized event from a streamed response
+#[derive(Debug, Clone, PartialEq)]
+pub enum StreamEvent {
+    /// Assistant text
+    TextDelta(String),
+    /// Reasoning/thinking text
+    ThinkingDelta(String),
+    /// A tool call begins; its arguments follow as deltas
+    ToolCallStart {
+        index: usize,
+        id: String,
+        name: String,
+    },
+    /// A fragment of a tool call's JSON arguments
+    ToolCallDelta { index: usize, arguments: String },
+    /// A tool call's arguments are complete
+    ToolCallEnd { index: usize },
+    /// Token usage so far; later values replace earlier ones
+    Usage(TokenUsage),
+    /// Generation finished
+    Stop(StopReason),
+    /// The provider reported an error mid-stream
+    Error(String),
+}
+
+impl StreamEvent {
+    /// Render as an OpenAI `chat.completion.chunk` for clients of the proxy.
+    /// Tool call ends have no OpenAI equivalent and render as `None`.
+    pub fn to_openai_chunk(&self, id: &str, model: &str, created: i64) -> Option<Value> {
+        let choice = |delta: Value, finish_reason: Value| {
+            json!({
+                "id": id,
+                "object": "chat.completion.chunk",
+                "created": created,
+                "model": model,
+                "choices": [{
+                    "index": 0,
+                    "delta": delta,
+                    "finish_reason": finish_reason
+                }]
+            })
+        };
+
+        match self {
+            StreamEvent::TextDelta(text) => Some(choice(json!({"content": text}), Value::Null)),
+            StreamEvent::ThinkingDelta(text) => {
+                Some(choice(json!({"reasoning_content": text}), Value::Null))
+            }
+            StreamEvent::ToolCallStart { index, id, name } => Some(choice(
+                json!({"tool_calls": [{
+                    "index": index,
+                    "id": id,
+                    "type": "function",
+                    "function": {"name": name, "arguments": ""}
+                }]}),
+                Value::Null,
+            )),
+            StreamEvent::ToolCallDelta { index, arguments } => Some(choice(
+                json!({"tool_calls": [{"index": index, "function": {"arguments": arguments}}]}),
+                Value::Null,
+            )),
+            StreamEvent::ToolCallEnd { .. } => None,
+            StreamEvent::Usage(usage) => Some(json!({
+                "id": id,
+                "object": "chat.completion.chunk",
+                "created": created,
+                "model": model,
+                "choices": [],
+                "usage": usage.to_openai()
+            })),
+            StreamEvent::Stop(reason) => Some(choice(json!({}), json!(reason.as_openai()))),
+            StreamEvent::Error(message) => Some(json!({
+                "error": {"message": message, "type": "provider_error"}
+            })),
+        }
+    }
+}
+
+/// Incremental decoder for a provider's streaming response body
+pub trait StreamDecoder: Send {
+    /// Decode the next chunk of the body, returning the events it completes
+    fn decode(&mut self, chunk: &[u8]) -> Vec<StreamEvent>;
+
+    /// Flush whatever is left once the body ends, closing open tool calls
+    fn finish(&mut self) -> Vec<StreamEvent>;
+}
+
+/// Splits a byte stream into complete lines, keeping partial lines (and split
+/// UTF-8 sequences) buffered until the rest arrives
+#[derive(Default)]
+struct LineBuffer {
+    buf: Vec<u8>,
+}
+
+impl LineBuffer {
+    fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        self.buf.extend_from_slice(chunk);
+        let mut lines = Vec::new();
+        while let Some(pos) = self.buf.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.buf.drain(..=pos).collect();
+            if let Some(line) = Self::clean(&line) {
+                lines.push(line);
+            }
+        }
+        lines
+    }
+
+    /// Take a final line that was not newline-terminated
+    fn flush(&mut self) -> Option<String> {
+        let rest = std::mem::take(&mut self.buf);
+        Self::clean(&rest)
+    }
+
+    fn clean(line: &[u8]) -> Option<String> {
+        let line = String::from_utf8_lossy(line).trim().to_string();
+        (!line.is_empty()).then_some(line)
+    }
+}
+
+/// Parse the JSON payload of an SSE `data:` line, skipping comments, event
+/// names and the `[DONE]` sentinel
+fn sse_data(line: &str) -> Option<Value> {
+    let data = line.strip_prefix("data:")?.trim();
+    if data == "[DONE]" {
+        return None;
+    }
+    serde_json::from_str(data).ok()
+}
+
+/// Message of an `{"error": ...}` payload, which may be an object or a string
+fn error_message(json: &Value) -> Option<String> {
+    let error = json.get("error").filter(|e| !e.is_null())?;
+    Some(
+        error
+            .get("message")
+            .and_then(|m| m.as_str())
+            .or_else(|| error.as_str())
+            .map(String::from)
+            .unwrap_or_else(|| error.to_string()),
+    )
+}
+
+/// Tool calls that have started but not yet ended
+#[derive(Default)]
+struct OpenToolCalls {
+    open: Vec<usize>,
+}
+
+impl OpenToolCalls {
+    fn start(&mut self, index: usize, id: &str, name: &str, out: &mut Vec<StreamEvent>) {
+        self.open.push(index);
+        out.push(StreamEvent::ToolCallStart {
+            index,
+            id: id.to_string(),
+            name: name.to_string(),
+        });
+    }
+
+    fn end(&mut self, index: usize, out: &mut Vec<StreamEvent>) {
+        if let Some(pos) = self.open.iter().position(|i| *i == index) {
+            self.open.remove(pos);
+            out.push(StreamEvent::ToolCallEnd { index });
+        }
+    }
+
+    fn end_all(&mut self, out: &mut Vec<StreamEvent>) {
+        for index in std::mem::take(&mut self.open) {
+            out.push(StreamEvent::ToolCallEnd { index });
+        }
+    }
+}
+
+/// Decoder for OpenAI-compatible `chat.completion.chunk` SSE streams
+#[derive(Default)]
+pub struct OpenAIDecoder {
+    lines: LineBuffer,
+    tools: OpenToolCalls,
+    /// Indexes that have started at some point, open or not
+    seen: Vec<usize>,
+}
+
+impl OpenAIDecoder {
+    fn decode_line(&mut self, line: &str, out: &mut Vec<StreamEvent>) {
+        let Some(json) = sse_data(line) else {
+            return;
+        };
+        if let Some(message) = error_message(&json) {
+            out.push(StreamEvent::Error(message));
+            return;
+        }
+
+        if let Some(choice) = json.get("choices").and_then(|c| c.get(0)) {
+            let delta = &choice["delta"];
+            // DeepSeek, Qwen and GLM use reasoning_content; some servers use reasoning
+            let thinking = delta
+                .get("reasoning_content")
+                .or_else(|| delta.get("reasoning"))
+                .and_then(|t| t.as_str());
+            if let Some(text) = thinking.filter(|t| !t.is_empty()) {
+                out.push(StreamEvent::ThinkingDelta(text.to_string()));
+            }
+            if let Some(text) = delta["content"].as_str().filter(|t| !t.is_empty()) {
+                out.push(StreamEvent::TextDelta(text.to_string()));
+            }
+            for call in delta["tool_calls"].as_array().into_iter().flatten() {
+                let index = call["index"].as_u64().unwrap_or(0) as usize;
+                if !self.seen.contains(&index) {
+                    self.seen.push(index);
+                    self.tools.start(
+                        index,
+                        call["id"].as_str().unwrap_or_default(),
+                        call["function"]["name"].as_str().unwrap_or_default(),
+                        out,
+                    );
+                }
+                let arguments = call["function"]["arguments"].as_str().unwrap_or_default();
+                if !arguments.is_empty() {
+                    out.push(StreamEvent::ToolCallDelta {
+                        index,
+                        arguments: arguments.to_string(),
+                    });
+                }
+            }
+            if let Some(reason) = choice["finish_reason"].as_str() {
+                self.tools.end_all(out);
+                out.push(StreamEvent::Stop(StopReason::from_openai(reason)));
+            }
+        }
+
+        if let Some(usage) = json.get("usage").filter(|u| u.is_object()) {
+            out.push(StreamEvent::Usage(TokenUsage::from_openai(usage)));
+        }
+    }
+}
+
+impl StreamDecoder for OpenAIDecoder {
+    fn decode(&mut self, chunk: &[u8]) -> Vec<StreamEvent> {
+        let mut out = Vec::new();
+        for line in self.lines.push(chunk) {
+            self.decode_line(&line, &mut out);
+        }
+        out
+    }
+
+    fn finish(&mut self) -> Vec<StreamEvent> {
+        let mut out = Vec::new();
+        if let Some(line) = self.lines.flush() {
+            self.decode_line(&line, &mut out);
+        }
+        self.tools.end_all(&mut out);
+        out
+    }
+}
+
+/// Decoder for Anthropic Messages API SSE streams
+#[derive(Default)]
+pub struct AnthropicDecoder {
+    lines: LineBuffer,
+    tools: OpenToolCalls,
+    /// Content block index -> tool call index
+    tool_blocks: std::collections::HashMap<u64, usize>,
+    usage: TokenUsage,
+}
+
+impl AnthropicDecoder {
+    fn decode_line(&mut self, line: &str, out: &mut Vec<StreamEvent>) {
+        let Some(json) = sse_data(line) else {
+            return;
+        };
+        let block_index = json["index"].as_u64().unwrap_or(0);
+
+        match json["type"].as_str().unwrap_or_default() {
+            "message_start" => {
+                self.usage = TokenUsage::from_anthropic(&json["message"]["usage"]);
+            }
+            "content_block_start" if json["content_block"]["type"] == "tool_use" => {
+                let index = self.tool_blocks.len();
+                self.tool_blocks.insert(block_index, index);
+                self.tools.start(
+                    index,
+                    json["content_block"]["id"].as_str().unwrap_or_default(),
+                    json["content_block"]["name"].as_str().unwrap_or_default(),
+                    out,
+                );
+            }
+            "content_block_delta" => {
+                let delta = &json["delta"];
+                match delta["type"].as_str().unwrap_or_default() {
+                    "text_delta" => {
+                        if let Some(text) = delta["text"].as_str() {
+                            out.push(StreamEvent::TextDelta(text.to_string()));
+                        }
+                    }
+                    "thinking_delta" => {
+                        if let Some(text) = delta["thinking"].as_str() {
+                            out.push(StreamEvent::ThinkingDelta(text.to_string()));
+                        }
+                    }
+                    "input_json_delta" => {
+                        let arguments = delta["partial_json"].as_str().unwrap_or_default();
+                        if let Some(&index) = self.tool_blocks.get(&block_index) {
+                            if !arguments.is_empty() {
+                                out.push(StreamEvent::ToolCallDelta {
+                                    index,
+                                    arguments: arguments.to_string(),
+                                });
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            "content_block_stop" => {
+                if let Some(&index) = self.tool_blocks.get(&block_index) {
+                    self.tools.end(index, out);
+                }
+            }
+            "message_delta" => {
+                // Counts here are cumulative and replace those from message_start
+                if let Some(delta) = json["usage"].as_object() {
+                    let count = |key: &str| delta.get(key).and_then(|v| v.as_u64());
+                    let usage = &mut self.usage;
+                    usage.output_tokens = count("output_tokens").unwrap_or(usage.output_tokens);
+                    usage.input_tokens = count("input_tokens").unwrap_or(usage.input_tokens);
+                    usage.cache_read_tokens =
+                        count("cache_read_input_tokens").unwrap_or(usage.cache_read_tokens);
+                    usage.cache_write_tokens =
+                        count("cache_creation_input_tokens").unwrap_or(usage.cache_write_tokens);
+                    out.push(StreamEvent::Usage(self.usage));
+                }
+                if let Some(reason) = json["delta"]["stop_reason"].as_str() {
+                    out.push(StreamEvent::Stop(StopReason::from_anthropic(reason)));
+                }
+            }
+            "error" => {
+                out.push(StreamEvent::Error(
+                    error_message(&json).unwrap_or_else(|| "Unknown stream error".to_string()),
+                ));
+            }
+            _ => {}
+        }
+    }
+}
+
+impl StreamDecoder for AnthropicDecoder {
+    fn decode(&mut self, chunk: &[u8]) -> Vec<StreamEvent> {
+        let mut out = Vec::new();
+        for line in self.lines.push(chunk) {
+            self.decode_line(&line, &mut out);
+        }
+        out
+    }
+
+    fn finish(&mut self) -> Vec<StreamEvent> {
+        let mut out = Vec::new();
+        if let Some(line) = self.lines.flush() {
+            self.decode_line(&line, &mut out);
+        }
+        self.tools.end_all(&mut out);
+        out
+    }
+}
+
+/// Decoder for OpenAI Responses API (`/v1/responses`) SSE streams.
+/// Reasoning items of a finished turn are handed to
+/// [`remember_reasoning`](crate::providers::remember_reasoning) so the next
+/// tool-loop request can send them back.
+#[derive(Default)]
+pub struct ResponsesDecoder {
+    lines: LineBuffer,
+    tools: OpenToolCalls,
+    /// Output item index -> tool call index
+    tool_items: std::collections::HashMap<u64, usize>,
+}
+
+impl ResponsesDecoder {
+    fn decode_line(&mut self, line: &str, out: &mut Vec<StreamEvent>) {
+        let Some(json) = sse_data(line) else {
+            return;
+        };
+        let item_index = json["output_index"].as_u64().unwrap_or(0);
+
+        match json["type"].as_str().unwrap_or_default() {
+            "response.output_item.added" if json["item"]["type"] == "function_call" => {
+                let index = self.tool_items.len();
+                self.tool_items.insert(item_index, index);
+                self.tools.start(
+                    index,
+                    json["item"]["call_id"].as_str().unwrap_or_default(),
+                    json["item"]["name"].as_str().unwrap_or_default(),
+                    out,
+                );
+            }
+            "response.output_text.delta" | "response.refusal.delta" => {
+                if let Some(text) = json["delta"].as_str() {
+                    out.push(StreamEvent::TextDelta(text.to_string()));
+                }
+            }
+            "response.reasoning_summary_text.delta" | "response.reasoning_text.delta" => {
+                if let Some(text) = json["delta"].as_str() {
+                    out.push(StreamEvent::ThinkingDelta(text.to_string()));
+                }
+            }
+            "response.function_call_arguments.delta" => {
+                let arguments = json["delta"].as_str().unwrap_or_default();
+                if let Some(&index) = self.tool_items.get(&item_index) {
+                    if !arguments.is_empty() {
+                        out.push(StreamEvent::ToolCallDelta {
+                            index,
+                            arguments: arguments.to_string(),
+                        });
+                    }
+                }
+            }
+            "response.output_item.done" => {
+                if let Some(&index) = self.tool_items.get(&item_index) {
+                    self.tools.end(index, out);
+                }
+            }
+            "response.completed" | "response.incomplete" => {
+                let response = &json["response"];
+                crate::providers::remember_reasoning(
+                    response["output"].as_array().map_or(&[], Vec::as_slice),
+                );
+                self.tools.end_all(out);
+                if let Some(usage) = response.get("usage").filter(|u| u.is_object()) {
+                    out.push(StreamEvent::Usage(TokenUsage::from_responses(usage)));
+                }
+                let reason = if let Some(reason) = response["incomplete_details"]["reason"].as_str()
+                {
+                    StopReason::from_responses_incomplete(reason)
+                } else if self.tool_items.is_empty() {
+                    StopReason::EndTurn
+                } else {
+                    StopReason::ToolUse
+                };
+                out.push(StreamEvent::Stop(reason));
+            }
+            "response.failed" => {
+                out.push(StreamEvent::Error(
+                    error_message(&json["response"])
+                        .unwrap_or_else(|| "Response failed".to_string()),
+                ));
+            }
+            "error" => {
+                out.push(StreamEvent::Error(
+                    json["message"]
+                        .as_str()
+                        .map(String::from)
+                        .or_else(|| error_message(&json))
+                        .unwrap_or_else(|| "Unknown stream error".to_string()),
+                ));
+            }
+            _ => {}
+        }
+    }
+}
+
+impl StreamDecoder for ResponsesDecoder {
+    fn decode(&mut self, chunk: &[u8]) -> Vec<StreamEvent> {
+        let mut out = Vec::new();
+        for line in self.lines.push(chunk) {
+            self.decode_line(&line, &mut out);
+        }
+        out
+    }
+
+    fn finish(&mut self) -> Vec<StreamEvent> {
+        let mut out = Vec::new();
+        if let Some(line) = self.lines.flush() {
+            self.decode_line(&line, &mut out);
+        }
+        self.tools.end_all(&mut out);
+        out
+    }
+}
+
+/// Decoder for Gemini `streamGenerateContent?alt=sse` streams
+#[derive(Default)]
+pub struct GeminiDecoder {
+    lines: LineBuffer,
+    tools: OpenToolCalls,
+    tool_calls: usize,
+}
+
+impl GeminiDecoder {
+    fn decode_line(&mut self, line: &str, out: &mut Vec<StreamEvent>) {
+        let Some(json) = sse_data(line) else {
+            return;
+        };
+        if let Some(message) = error_message(&json) {
+            out.push(StreamEvent::Error(message));
+            return;
+        }
+
+        let candidate = &json["candidates"][0];
+        for part in candidate["content"]["parts"]
+            .as_array()
+            .into_iter()
+            .flatten()
+        {
+            if let Some(call) = part.get("functionCall") {
+                // Gemini sends each call whole and without an id, so mint one
+                // for the tool result to refer to
+                let index = self.tool_calls;
+                self.tool_calls += 1;
+                let id = format!("call_{}", uuid::Uuid::new_v4());
+                self.tools
+                    .start(index, &id, call["name"].as_str().unwrap_or_default(), out);
+                out.push(StreamEvent::ToolCallDelta {
+                    index,
+                    arguments: serde_json::to_string(&call["args"])
+                        .unwrap_or_else(|_| "{}".to_string()),
+                });
+                self.tools.end(index, out);
+            } else if let Some(text) = part["text"].as_str() {
+                if part["thought"].as_bool().unwrap_or(false) {
+                    out.push(StreamEvent::ThinkingDelta(text.to_string()));
+                } else {
+                    out.push(StreamEvent::TextDelta(text.to_string()));
+                }
+            }
+        }
+
+        if let Some(usage) = json.get("usageMetadata") {
+            out.push(StreamEvent::Usage(TokenUsage::from_gemini(usage)));
+        }
+        if let Some(reason) = candidate["finishReason"].as_str() {
+            // Gemini reports STOP even when the turn ends in function calls
+            let reason = if self.tool_calls > 0 {
+                StopReason::ToolUse
+            } else {
+                StopReason::from_gemini(reason)
+            };
+            out.push(StreamEvent::Stop(reason));
+        }
+    }
+}
+
+impl StreamDecoder for GeminiDecoder {
+    fn decode(&mut self, chunk: &[u8]) -> Vec<StreamEvent> {
+        let mut out = Vec::new();
+        for line in self.lines.push(chunk) {
+            self.decode_line(&line, &mut out);
+        }
+        out
+    }
+
+    fn finish(&mut self) -> Vec<StreamEvent> {
+        let mut out = Vec::new();
+        if let Some(line) = self.lines.flush() {
+            self.decode_line(&line, &mut out);
+        }
+        self.tools.end_all(&mut out);
+        out
+    }
+}
+
+/// Decoder for Ollama's newline-delimited JSON `/api/chat` stream
+#[derive(Default)]
+pub struct OllamaDecoder {
+    lines: LineBuffer,
+    tools: OpenToolCalls,
+    tool_calls: usize,
+}
+
+impl OllamaDecoder {
+    fn decode_line(&mut self, line: &str, out: &mut Vec<StreamEvent>) {
+        let Ok(json) = serde_json::from_str::<Value>(line) else {
+            return;
+        };
+        if let Some(message) = error_message(&json) {
+            out.push(StreamEvent::Error(message));
+            return;
+        }
+
+        let message = &json["message"];
+        if let Some(text) = message["thinking"].as_str().filter(|t| !t.is_empty()) {
+            out.push(StreamEvent::ThinkingDelta(text.to_string()));
+        }
+        if let Some(text) = message["content"].as_str().filter(|t| !t.is_empty()) {
+            out.push(StreamEvent::TextDelta(text.to_string()));
+        }
+        for call in message["tool_calls"].as_array().into_iter().flatten() {
+            // Ollama sends each call whole, with arguments as an object
+            let index = self.tool_calls;
+            self.tool_calls += 1;
+            self.tools.start(
+                index,
+                &format!("call_{}", index),
+                call["function"]["name"].as_str().unwrap_or_default(),
+                out,
+            );
+            let arguments = &call["function"]["arguments"];
+            out.push(StreamEvent::ToolCallDelta {
+                index,
+                arguments: arguments
+                    .as_str()
+                    .map(String::from)
+                    .unwrap_or_else(|| arguments.to_string()),
+            });
+            self.tools.end(index, out);
+        }
+
+        if json["done"].as_bool().unwrap_or(false) {
+            out.push(StreamEvent::Usage(TokenUsage::from_ollama(&json)));
+            let reason = if self.tool_calls > 0 {
+                StopReason::ToolUse
+            } else if json["done_reason"] == "length" {
+                StopReason::MaxTokens
+            } else {
+                StopReason::EndTurn
+            };
+            out.push(StreamEvent::Stop(reason));
+        }
+    }
+}
+
+impl StreamDecoder for OllamaDecoder {
+    fn decode(&mut self, chunk: &[u8]) -> Vec<StreamEvent> {
+        let mut out = Vec::new();
+        for line in self.lines.push(chunk) {
+            self.decode_line(&line, &mut out);
+        }
+        out
+    }
+
+    fn finish(&mut self) -> Vec<StreamEvent> {
+        let mut out = Vec::new();
+        if let Some(line) = self.lines.flush() {
+            self.decode_line(&line, &mut out);
+        }
+        self.tools.end_all(&mut out);
+        out
+    }
+}
+
+/// One message of an AWS event stream: its string headers and raw payload
+struct EventStreamMessage {
+    headers: std::collections::HashMap<String, String>,
+    payload: Vec<u8>,
+}
+
+impl EventStreamMessage {
+    fn header(&self, name: &str) -> &str {
+        self.headers.get(name).map_or("", String::as_str)
+    }
+}
+
+/// Splits an AWS event stream (`application/vnd.amazon.eventstream`) into
+/// messages, keeping partial frames buffered until the rest arrives.
+///
+/// Each frame is a 12-byte prelude (total length, headers length, prelude
+/// CRC32), typed headers, the payload and a CRC32 of the whole message.
+#[derive(Default)]
+struct EventStreamBuffer {
+    buf: Vec<u8>,
+}
+
+impl EventStreamBuffer {
+    fn push(&mut self, chunk: &[u8]) -> Vec<Result<EventStreamMessage, String>> {
+        self.buf.extend_from_slice(chunk);
+        let mut messages = Vec::new();
+        while self.buf.len() >= 12 {
+            let total = u32_at(&self.buf, 0) as usize;
+            if total < 16 {
+                self.buf.clear();
+                messages.push(Err("Malformed event stream frame".to_string()));
+                break;
+            }
+            if self.buf.len() < total {
+                break;
+            }
+            let frame: Vec<u8> = self.buf.drain(..total).collect();
+            messages.push(Self::parse(&frame));
+        }
+        messages
+    }
+
+    fn parse(frame: &[u8]) -> Result<EventStreamMessage, String> {
+        let total = frame.len();
+        let headers_len = u32_at(frame, 4) as usize;
+        if crc32fast::hash(&frame[..8]) != u32_at(frame, 8)
+            || crc32fast::hash(&frame[..total - 4]) != u32_at(frame, total - 4)
+            || 12 + headers_len > total - 4
+        {
+            return Err("Corrupt event stream frame (checksum mismatch)".to_string());
+        }
+
+        let mut headers = std::collections::HashMap::new();
+        let raw = &frame[12..12 + headers_len];
+        let mut pos = 0;
+        while pos < raw.len() {
+            let name_len = raw[pos] as usize;
+            let name =
+                String::from_utf8_lossy(raw.get(pos + 1..pos + 1 + name_len).unwrap_or_default());
+            pos += 1 + name_len;
+            let Some(&kind) = raw.get(pos) else { break };
+            pos += 1;
+            // Value sizes by type: bool true/false, byte, short, int, long,
+            // byte array, string, timestamp, uuid
+            let size = match kind {
+                0 | 1 => 0,
+                2 => 1,
+                3 => 2,
+                4 => 4,
+                5 | 8 => 8,
+                6 | 7 => {
+                    let len = raw
+                        .get(pos..pos + 2)
+                        .map_or(0, |b| u16::from_be_bytes([b[0], b[1]]));
+                    pos += 2;
+                    len as usize
+                }
+                9 => 16,
+                _ => return Err(format!("Unknown event stream header type {}", kind)),
+            };
+            if kind == 7 {
+                let value = raw.get(pos..pos + size).unwrap_or_default();
+                headers.insert(
+                    name.into_owned(),
+                    String::from_utf8_lossy(value).into_owned(),
+                );
+            }
+            pos += size;
+        }
+
+        Ok(EventStreamMessage {
+            headers,
+            payload: frame[12 + headers_len..total - 4].to_vec(),
+        })
+    }
+}
+
+fn u32_at(buf: &[u8], pos: usize) -> u32 {
+    u32::from_be_bytes([buf[pos], buf[pos + 1], buf[pos + 2], buf[pos + 3]])
+}
+
+/// Error carried by an event stream message, if it is an exception or error
+fn event_stream_error(message: &EventStreamMessage) -> Option<String> {
+    match message.header(":message-type") {
+        "exception" => {
+            let payload: Value = serde_json::from_slice(&message.payload).unwrap_or_default();
+            let detail = payload["message"]
+                .as_str()
+                .or_else(|| payload["Message"].as_str())
+                .unwrap_or("no details");
+            Some(format!("{}: {}", message.header(":exception-type"), detail))
+        }
+        "error" => Some(format!(
+            "{}: {}",
+            message.header(":error-code"),
+            message.header(":error-message")
+        )),
+        _ => None,
+    }
+}
+
+/// Decoder for Bedrock `converse-stream` event streams
+#[derive(Default)]
+pub struct BedrockDecoder {
+    frames: EventStreamBuffer,
+    tools: OpenToolCalls,
+    /// Content block index -> tool call index
+    tool_blocks: std::collections::HashMap<u64, usize>,
+}
+
+impl BedrockDecoder {
+    fn decode_message(&mut self, message: &EventStreamMessage, out: &mut Vec<StreamEvent>) {
+        if let Some(error) = event_stream_error(message) {
+            out.push(StreamEvent::Error(error));
+            return;
+        }
+        let Ok(json) = serde_json::from_slice::<Value>(&message.payload) else {
+            return;
+        };
+        let block_index = json["contentBlockIndex"].as_u64().unwrap_or(0);
+
+        match message.header(":event-type") {
+            "contentBlockStart" => {
+                if let Some(tool) = json["start"].get("toolUse") {
+                    let index = self.tool_blocks.len();
+                    self.tool_blocks.insert(block_index, index);
+                    self.tools.start(
+                        index,
+                        tool["toolUseId"].as_str().unwrap_or_default(),
+                        tool["name"].as_str().unwrap_or_default(),
+                        out,
+                    );
+                }
+            }
+            "contentBlockDelta" => {
+                let delta = &json["delta"];
+                if let Some(text) = delta["text"].as_str() {
+                    out.push(StreamEvent::TextDelta(text.to_string()));
+                } else if let Some(text) = delta["reasoningContent"]["text"].as_str() {
+                    out.push(StreamEvent::ThinkingDelta(text.to_string()));
+                } else if let Some(arguments) = delta["toolUse"]["input"].as_str() {
+                    if let Some(&index) = self.tool_blocks.get(&block_index) {
+                        if !arguments.is_empty() {
+                            out.push(StreamEvent::ToolCallDelta {
+                                index,
+                                arguments: arguments.to_string(),
+                            });
+                        }
+                    }
+                }
+            }
+            "contentBlockStop" => {
+                if let Some(&index) = self.tool_blocks.get(&block_index) {
+                    self.tools.end(index, out);
+                }
+            }
+            "messageStop" => {
+                self.tools.end_all(out);
+                let reason = json["stopReason"].as_str().unwrap_or("end_turn");
+                out.push(StreamEvent::Stop(StopReason::from_bedrock(reason)));
+            }
+            "metadata" => {
+                if let Some(usage) = json.get("usage") {
+                    out.push(StreamEvent::Usage(TokenUsage::from_bedrock(usage)));
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl StreamDecoder for BedrockDecoder {
+    fn decode(&mut self, chunk: &[u8]) -> Vec<StreamEvent> {
+        let mut out = Vec::new();
+        for message in self.frames.push(chunk) {
+            match message {
+                Ok(message) => self.decode_message(&message, &mut out),
+                Err(error) => out.push(StreamEvent::Error(error)),
+            }
+        }
+        out
+    }
+
+    fn finish(&mut self) -> Vec<StreamEvent> {
+        let mut out = Vec::new();
+        self.tools.end_all(&mut out);
+        out
+    }
+}
+
+/// Decoder for Bedrock `invoke-with-response-stream` event streams of Claude
+/// models. Each `chunk` event carries one base64-encoded Anthropic Messages
+/// stream event, which is handed to an [`AnthropicDecoder`].
+#[derive(Default)]
+pub struct BedrockInvokeDecoder {
+    frames: EventStreamBuffer,
+    inner: AnthropicDecoder,
+}
+
+impl StreamDecoder for BedrockInvokeDecoder {
+    fn decode(&mut self, chunk: &[u8]) -> Vec<StreamEvent> {
+        use base64::Engine;
+
+        let mut out = Vec::new();
+        for message in self.frames.push(chunk) {
+            let message = match message {
+                Ok(message) => message,
+                Err(error) => {
+                    out.push(StreamEvent::Error(error));
+                    continue;
+                }
+            };
+            if let Some(error) = event_stream_error(&message) {
+                out.push(StreamEvent::Error(error));
+                continue;
+            }
+            let Ok(json) = serde_json::from_slice::<Value>(&message.payload) else {
+                continue;
+            };
+            let event = json["bytes"]
+                .as_str()
+                .and_then(|b| base64::engine::general_purpose::STANDARD.decode(b).ok())
+                .and_then(|bytes| serde_json::from_slice::<Value>(&bytes).ok());
+            if let Some(event) = event {
+                out.extend(self.inner.decode(format!("data: {}\n", event).as_bytes()));
+            }
+        }
+        out
+    }
+
+    fn finish(&mut self) -> Vec<StreamEvent> {
+        self.inner.finish()
+    }
+}
+
+/// Decoder for Vertex AI streams, which are Gemini SSE for Google models and
+/// Anthropic SSE for Claude models. The first line tells them apart.
+#[derive(Default)]
+pub struct VertexDecoder {
+    pending: Vec<u8>,
+    inner: Option<Box<dyn StreamDecoder>>,
+}
+
+impl VertexDecoder {
+    /// Pick the decoder once a complete first line is buffered
+    fn choose(&mut self, at_end: bool) {
+        let first_line = self
+            .pending
+            .split(|b| *b == b'\n')
+            .map(|line| String::from_utf8_lossy(line).trim().to_string())
+            .find(|line| !line.is_empty());
+        let complete = at_end || self.pending.contains(&b'\n');
+        let Some(line) = first_line.filter(|_| complete) else {
+            return;
+        };
+        let anthropic = line.starts_with("event:")
+            || sse_data(&line).is_some_and(|json| json.get("type").is_some());
+        self.inner = Some(if anthropic {
+            Box::new(AnthropicDecoder::default())
+        } else {
+            Box::new(GeminiDecoder::default())
+        });
+    }
+}
+
+impl StreamDecoder for VertexDecoder {
+    fn decode(&mut self, chunk: &[u8]) -> Vec<StreamEvent> {
+        if let Some(inner) = &mut self.inner {
+            return inner.decode(chunk);
+        }
+        self.pending.extend_from_slice(chunk);
+        self.choose(false);
+        match &mut self.inner {
+            Some(inner) => inner.decode(&std::mem::take(&mut self.pending)),
+            None => Vec::new(),
+        }
+    }
+
+    fn finish(&mut self) -> Vec<StreamEvent> {
+        if self.inner.is_none() {
+            self.choose(true);
+        }
+        let pending = std::mem::take(&mut self.pending);
+        match &mut self.inner {
+            Some(inner) => {
+                let mut out = inner.decode(&pending);
+                out.extend(inner.finish());
+                out
+            }
+            None => Vec::new(),
+        }
+    }
+}
+
+/// Collects stream events into a complete assistant turn
+#[derive(Debug, Default)]
+pub struct StreamAccumulator {
+    pub text: String,
+    pub thinking: String,
+    pub tool_calls: ToolCallAccumulator,
+    pub usage: Option<TokenUsage>,
+    pub stop_reason: Option<StopReason>,
+    pub error: Option<String>,
+}
+
+impl StreamAccumulator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, event: &StreamEvent) {
+        match event {
+            StreamEvent::TextDelta(text) => self.text.push_str(text),
+            StreamEvent::ThinkingDelta(text) => self.thinking.push_str(text),
+            StreamEvent::ToolCallStart { index, id, name } => {
+                self.tool_calls
+                    .process_fragment(*index, Some(id), Some(name), "")
+            }
+            StreamEvent::ToolCallDelta { index, arguments } => self
+                .tool_calls
+                .process_fragment(*index, None, None, arguments),
+            StreamEvent::ToolCallEnd { .. } => {}
+            StreamEvent::Usage(usage) => self.usage = Some(*usage),
+            StreamEvent::Stop(reason) => self.stop_reason = Some(reason.clone()),
+            StreamEvent::Error(message) => self.error = Some(message.clone()),
+        }
+    }
+
+    /// The turn as an OpenAI-format assistant message
+    pub fn to_message(&self) -> Value {
+        let mut message = json!({
+            "role": "assistant",
+            "content": self.text
+        });
+        let calls: Vec<Value> = self
+            .tool_calls
+            .finalize()
+            .iter()
+            .map(|tc| {
+                json!({
+                    "id": tc.id,
+                    "type": tc.call_type,
+                    "function": {
+                        "name": tc.function.name,
+                        "arguments": tc.function.arguments
+                    }
+                })
+            })
+            .collect();
+        if !calls.is_empty() {
+            message["tool_calls"] = json!(calls);
+        }
+        message
+    }
+}
+
+/// Read a streaming response to the end, passing each event to `on_event`
+pub async fn read_stream(
+    response: reqwest::Response,
+    decoder: &mut dyn StreamDecoder,
+    mut on_event: impl FnMut(StreamEvent),
+) -> Result<(), reqwest::Error> {
+    use futures::StreamExt;
+
+    let mut stream = response.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        for event in decoder.decode(&chunk?) {
+            on_event(event);
+        }
+    }
+    for event in decoder.finish() {
+        on_event(event);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Decode a recorded body in small pieces so events straddle chunk boundaries
+    fn decode_fixture(mut decoder: impl StreamDecoder, body: impl AsRef<[u8]>) -> Vec<StreamEvent> {
+        let mut events: Vec<StreamEvent> = body
+            .as_ref()
+            .chunks(7)
+            .flat_map(|chunk| decoder.decode(chunk))
+            .collect();
+        events.extend(decoder.finish());
+        events
+    }
+
+    fn accumulate(events: &[StreamEvent]) -> StreamAccumulator {
+        let mut acc = StreamAccumulator::new();
+        for event in events {
+            acc.push(event);
+        }
+        acc
+    }
+
+    #[test]
+    fn test_anthropic_tool_use_fixture() {
+        let events = decode_fixture(
+            AnthropicDecoder::default(),
+            include_str!("../tests/fixtures/streams/anthropic_tool_use.sse"),
+        );
+
+        assert_eq!(
+            events[0],
+            StreamEvent::ThinkingDelta("The user wants the file list.".to_string())
+        );
+        assert!(events.contains(&StreamEvent::ToolCallStart {
+            index: 0,
+            id: "toolu_01T1x1fJ34qAmk2tNTrN7Up6".to_string(),
+            name: "bash".to_string(),
+        }));
+        assert!(events.contains(&StreamEvent::ToolCallEnd { index: 0 }));
+        assert_eq!(events.last(), Some(&StreamEvent::Stop(StopReason::ToolUse)));
+
+        let acc = accumulate(&events);
+        assert_eq!(acc.text, "Let me check the directory — ok.");
+        assert_eq!(
+            acc.usage,
+            Some(TokenUsage {
+                input_tokens: 472,
+                output_tokens: 89,
+                cache_read_tokens: 2048,
+                ..Default::default()
+            })
+        );
+        let calls = acc.tool_calls.finalize();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].function.arguments, "{\"command\": \"ls -la\"}");
+    }
+
+    #[test]
+    fn test_anthropic_error_fixture() {
+        let events = decode_fixture(
+            AnthropicDecoder::default(),
+            include_str!("../tests/fixtures/streams/anthropic_overloaded.sse"),
+        );
+
+        assert_eq!(events[0], StreamEvent::TextDelta("Partial".to_string()));
+        assert_eq!(events[1], StreamEvent::Error("Overloaded".to_string()));
+    }
+
+    #[test]
+    fn test_openai_tool_calls_fixture() {
+        let events = decode_fixture(
+            OpenAIDecoder::default(),
+            include_str!("../tests/fixtures/streams/openai_tool_calls.sse"),
+        );
+
+        assert_eq!(
+            events[0],
+            StreamEvent::ToolCallStart {
+                index: 0,
+                id: "call_abc".to_string(),
+                name: "read_file".to_string(),
+            }
+        );
+        // Both calls close before the stop event
+        let stop = events
+            .iter()
+            .position(|e| *e == StreamEvent::Stop(StopReason::ToolUse))
+            .unwrap();
+        assert_eq!(events[stop - 2], StreamEvent::ToolCallEnd { index: 0 });
+        assert_eq!(events[stop - 1], StreamEvent::ToolCallEnd { index: 1 });
+
+        let acc = accumulate(&events);
+        let calls = acc.tool_calls.finalize();
+        assert_eq!(calls[0].function.arguments, "{\"path\": \"Cargo.toml\"}");
+        assert_eq!(calls[1].function.name, "list_files");
+        let usage = acc.usage.unwrap();
+        assert_eq!(usage.output_tokens, 34);
+        // Cached tokens are split out of prompt_tokens
+        assert_eq!((usage.input_tokens, usage.cache_read_tokens), (56, 64));
+        assert_eq!(acc.to_message()["tool_calls"][1]["id"], "call_def");
+    }
+
+    #[test]
+    fn test_openai_reasoning_fixture() {
+        let acc = accumulate(&decode_fixture(
+            OpenAIDecoder::default(),
+            include_str!("../tests/fixtures/streams/deepseek_reasoning.sse"),
+        ));
+
+        assert_eq!(acc.thinking, "Two plus two is four.");
+        assert_eq!(acc.text, "4");
+        assert_eq!(acc.stop_reason, Some(StopReason::EndTurn));
+        assert_eq!(acc.usage.unwrap().input_tokens, 9);
+        assert_eq!(acc.usage.unwrap().reasoning_tokens, 8);
+    }
+
+    #[test]
+    fn test_responses_fixture() {
+        let events = decode_fixture(
+            ResponsesDecoder::default(),
+            include_str!("../tests/fixtures/streams/openai_responses.sse"),
+        );
+
+        assert_eq!(
+            events[0],
+            StreamEvent::ThinkingDelta("Need the manifest first.".to_string())
+        );
+        assert!(events.contains(&StreamEvent::ToolCallStart {
+            index: 0,
+            id: "call_rsp1".to_string(),
+            name: "read_file".to_string(),
+        }));
+        assert!(events.contains(&StreamEvent::ToolCallEnd { index: 0 }));
+        assert_eq!(events.last(), Some(&StreamEvent::Stop(StopReason::ToolUse)));
+
+        let acc = accumulate(&events);
+        assert_eq!(acc.text, "Reading Cargo.toml.");
+        assert_eq!(
+            acc.usage,
+            Some(TokenUsage {
+                input_tokens: 54,
+                output_tokens: 48,
+                cache_read_tokens: 256,
+                reasoning_tokens: 24,
+                ..Default::default()
+            })
+        );
+        let calls = acc.tool_calls.finalize();
+        assert_eq!(calls[0].function.arguments, "{\"path\": \"Cargo.toml\"}");
+
+        // The encrypted reasoning is kept for the tool result follow-up
+        let reasoning = crate::providers::recall_reasoning("call_rsp1");
+        assert_eq!(reasoning[0]["encrypted_content"], "gAAAAABo-enc");
+    }
+
+    #[test]
+    fn test_gemini_function_call_fixture() {
+        let events = decode_fixture(
+            GeminiDecoder::default(),
+            include_str!("../tests/fixtures/streams/gemini_function_call.sse"),
+        );
+        let acc = accumulate(&events);
+
+        assert!(acc.thinking.starts_with("**Checking files**\nI should"));
+        assert_eq!(acc.text, "I will list the files.");
+        let calls = acc.tool_calls.finalize();
+        assert_eq!(calls.len(), 1);
+        assert!(calls[0].id.starts_with("call_"));
+        assert_eq!(calls[0].function.name, "list_files");
+        assert_eq!(calls[0].function.arguments, "{\"path\":\".\"}");
+        assert!(events.contains(&StreamEvent::ToolCallEnd { index: 0 }));
+        // Gemini says STOP, but the turn ended in a function call
+        assert_eq!(acc.stop_reason, Some(StopReason::ToolUse));
+        assert_eq!(
+            acc.usage,
+            Some(TokenUsage {
+                input_tokens: 58,
+                output_tokens: 40,
+                reasoning_tokens: 21,
+                ..Default::default()
+            })
+        );
+    }
+
+    #[test]
+    fn test_ollama_fixtures() {
+        let acc = accumulate(&decode_fixture(
+            OllamaDecoder::default(),
+            include_str!("../tests/fixtures/streams/ollama_chat.ndjson"),
+        ));
+        assert_eq!(acc.thinking, "User wants a greeting.");
+        assert_eq!(acc.text, "Hello there!");
+        assert_eq!(acc.stop_reason, Some(StopReason::EndTurn));
+        assert_eq!(
+            acc.usage,
+            Some(TokenUsage {
+                input_tokens: 26,
+                output_tokens: 11,
+                ..Default::default()
+            })
+        );
+
+        let acc = accumulate(&decode_fixture(
+            OllamaDecoder::default(),
+            include_str!("../tests/fixtures/streams/ollama_tool_call.ndjson"),
+        ));
+        let calls = acc.tool_calls.finalize();
+        assert_eq!(calls[0].id, "call_0");
+        assert_eq!(calls[0].function.arguments, "{\"command\":\"pwd\"}");
+        assert_eq!(acc.stop_reason, Some(StopReason::ToolUse));
+
+        // The error body has no trailing newline and is only seen on finish
+        let events = decode_fixture(
+            OllamaDecoder::default(),
+            include_str!("../tests/fixtures/streams/ollama_error.ndjson"),
+        );
+        assert_eq!(
+            events,
+            vec![StreamEvent::Error(
+                "model \"nope\" not found, try pulling it first".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_bedrock_converse_fixture() {
+        let events = decode_fixture(
+            BedrockDecoder::default(),
+            include_bytes!("../tests/fixtures/streams/bedrock_converse.bin"),
+        );
+
+        assert_eq!(
+            events[0],
+            StreamEvent::ThinkingDelta("Need the weather.".to_string())
+        );
+        assert!(events.contains(&StreamEvent::ToolCallStart {
+            index: 0,
+            id: "tooluse_kZJMlvQmRJ6eAyJE5GIl7Q".to_string(),
+            name: "get_weather".to_string(),
+        }));
+        assert!(events.contains(&StreamEvent::ToolCallEnd { index: 0 }));
+
+        let acc = accumulate(&events);
+        assert_eq!(acc.text, "Checking now.");
+        assert_eq!(acc.stop_reason, Some(StopReason::ToolUse));
+        assert_eq!(
+            acc.usage,
+            Some(TokenUsage {
+                input_tokens: 412,
+                output_tokens: 57,
+                cache_read_tokens: 128,
+                ..Default::default()
+            })
+        );
+        let calls = acc.tool_calls.finalize();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].function.arguments, "{\"city\": \"Paris\"}");
+    }
+
+    #[test]
+    fn test_bedrock_invoke_fixture() {
+        let events = decode_fixture(
+            BedrockInvokeDecoder::default(),
+            include_bytes!("../tests/fixtures/streams/bedrock_invoke.bin"),
+        );
+        let acc = accumulate(&events);
+
+        assert_eq!(acc.text, "Hello from Bedrock.");
+        assert_eq!(acc.stop_reason, Some(StopReason::EndTurn));
+        assert_eq!(acc.usage.unwrap().output_tokens, 9);
+        assert_eq!(
+            events.last(),
+            Some(&StreamEvent::Error(
+                "throttlingException: Too many requests, please wait before trying again."
+                    .to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_event_stream_checksum() {
+        let mut frame = include_bytes!("../tests/fixtures/streams/bedrock_converse.bin").to_vec();
+        let total = u32_at(&frame, 0) as usize;
+        frame[total - 5] ^= 0xff;
+
+        let events = BedrockDecoder::default().decode(&frame[..total]);
+        assert_eq!(
+            events,
+            vec![StreamEvent::Error(
+                "Corrupt event stream frame (checksum mismatch)".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_vertex_decoder_sniffs_format() {
+        let anthropic = include_str!("../tests/fixtures/streams/anthropic_tool_use.sse");
+        assert_eq!(
+            decode_fixture(VertexDecoder::default(), anthropic),
+            decode_fixture(AnthropicDecoder::default(), anthropic)
+        );
+
+        let gemini = include_str!("../tests/fixtures/streams/gemini_function_call.sse");
+        let acc = accumulate(&decode_fixture(VertexDecoder::default(), gemini));
+        assert_eq!(acc.text, "I will list the files.");
+        assert_eq!(acc.tool_calls.finalize()[0].function.name, "list_files");
+    }
+
+    #[test]
+    fn test_to_openai_chunk() {
+        let chunk = StreamEvent::TextDelta("Hi".to_string())
+            .to_openai_chunk("id1", "m", 0)
+            .unwrap();
+        assert_eq!(chunk["object"], "chat.completion.chunk");
+        assert_eq!(chunk["choices"][0]["delta"]["content"], "Hi");
+
+        let chunk = StreamEvent::Stop(StopReason::MaxTokens)
+            .to_openai_chunk("id1", "m", 0)
+            .unwrap();
+        assert_eq!(chunk["choices"][0]["finish_reason"], "length");
+
+        assert!(StreamEvent::ToolCallEnd { index: 0 }
+            .to_openai_chunk("id1", "m", 0)
+            .is_none());
+    }
+}
@@ -0,0 +1,372 @@
+//! Structured Output - JSON and JSON-schema responses.
+//!
+//! A request's `response_format` uses the OpenAI shape (`{"type":
+//! "json_schema", "json_schema": {"name", "schema", "strict"}}` or `{"type":
+//! "json_object"}`) and each adapter maps it to its provider's mechanism:
+//!
+//! - OpenAI-compatible APIs take it as-is; the Responses API as `text.format`
+//! - Gemini gets `responseMimeType` and `responseSchema`
+//! - Ollama gets `format`
+//! - Anthropic and Bedrock have no such option, so the schema becomes the
+//!   input of a single tool the model is forced to call, and
+//!   [`unwrap_response_tool`] and [`ResponseToolDecoder`] turn that call back
+//!   into JSON reply text
+//!
+//! The proxy checks non-streamed replies with [`validate`] and asks once more,
+//! with the validation error, when a reply does not conform.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::proxy::{ChatCompletionRequest, ChatMessage, MessageContent};
+use crate::stream::{StopReason, StreamDecoder, StreamEvent};
+
+/// Name of the tool that carries the reply for providers without a native
+/// response format
+pub const RESPONSE_TOOL: &str = "json_response";
+
+/// Most validation errors reported back to the model on a retry
+const MAX_REPORTED_ERRORS: usize = 5;
+
+/// Requested format of the reply text
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ResponseFormat {
+    Text,
+    /// Any JSON object
+    JsonObject,
+    /// JSON matching a schema
+    JsonSchema {
+        json_schema: JsonSchema,
+    },
+}
+
+/// A named JSON schema for the reply
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JsonSchema {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strict: Option<bool>,
+}
+
+impl ResponseFormat {
+    /// Schema the reply must match; `json_object` means any object
+    pub fn schema(&self) -> Option<Value> {
+        match self {
+            ResponseFormat::Text => None,
+            ResponseFormat::JsonObject => Some(json!({"type": "object"})),
+            ResponseFormat::JsonSchema { json_schema } => Some(
+                json_schema
+                    .schema
+                    .clone()
+                    .unwrap_or_else(|| json!({"type": "object"})),
+            ),
+        }
+    }
+}
+
+/// Reject a `response_format` whose schema is not valid JSON Schema
+pub fn check(format: &ResponseFormat) -> Result<(), String> {
+    match format.schema() {
+        Some(schema) => jsonschema::validator_for(&schema)
+            .map(|_| ())
+            .map_err(|e| format!("Invalid response_format schema: {}", e)),
+        None => Ok(()),
+    }
+}
+
+/// Check reply text against the requested format
+pub fn validate(format: &ResponseFormat, text: &str) -> Result<Value, String> {
+    let Some(schema) = format.schema() else {
+        return Ok(json!(text));
+    };
+    let value: Value = serde_json::from_str(text.trim())
+        .map_err(|e| format!("The reply is not valid JSON: {}", e))?;
+    let validator = jsonschema::validator_for(&schema)
+        .map_err(|e| format!("Invalid response_format schema: {}", e))?;
+    let errors: Vec<String> = validator
+        .iter_errors(&value)
+        .take(MAX_REPORTED_ERRORS)
+        .map(|e| match e.instance_path().as_str() {
+            "" => e.to_string(),
+            path => format!("{} (at {})", e, path),
+        })
+        .collect();
+    if errors.is_empty() {
+        Ok(value)
+    } else {
+        Err(errors.join("; "))
+    }
+}
+
+/// Messages that return a non-conforming reply to the model with the error
+pub fn retry_messages(reply: &str, error: &str) -> [ChatMessage; 2] {
+    let message = |role: &str, text: String| ChatMessage {
+        role: role.to_string(),
+        content: MessageContent::Text(text),
+        name: None,
+        tool_calls: None,
+        tool_call_id: None,
+    };
+    [
+        message("assistant", reply.to_string()),
+        message(
+            "user",
+            format!(
+                "Your reply does not match the required JSON schema: {}\n\n\
+                 Reply again with only the corrected JSON.",
+                error
+            ),
+        ),
+    ]
+}
+
+/// The request with its response format turned into a forced call of
+/// [`RESPONSE_TOOL`], or None when it asks for no JSON
+pub fn with_response_tool(request: &ChatCompletionRequest) -> Option<ChatCompletionRequest> {
+    let format = request.response_format.as_ref()?;
+    let schema = format.schema()?;
+    let description = match format {
+        ResponseFormat::JsonSchema { json_schema } => format!(
+            "Give your final answer as the input of this tool, as `{}`{}",
+            json_schema.name,
+            json_schema
+                .description
+                .as_ref()
+                .map(|d| format!(": {}", d))
+                .unwrap_or_default()
+        ),
+        _ => "Give your final answer as the input of this tool".to_string(),
+    };
+
+    let mut forced = request.clone();
+    forced.response_format = None;
+    forced.tools.get_or_insert_with(Vec::new).push(json!({
+        "type": "function",
+        "function": {
+            "name": RESPONSE_TOOL,
+            "description": description,
+            "parameters": schema
+        }
+    }));
+    forced.tool_choice = Some(json!({"type": "function", "function": {"name": RESPONSE_TOOL}}));
+    Some(forced)
+}
+
+/// Whether the request forces a tool call for its response format; providers
+/// refuse extended thinking together with a forced tool
+pub fn forces_tool(request: &ChatCompletionRequest) -> bool {
+    request
+        .response_format
+        .as_ref()
+        .is_some_and(|f| f.schema().is_some())
+}
+
+/// Turn a [`RESPONSE_TOOL`] call in an OpenAI-format response into reply text
+pub fn unwrap_response_tool(response: &mut Value) {
+    let choice = &mut response["choices"][0];
+    let Some(calls) = choice["message"]["tool_calls"].as_array_mut() else {
+        return;
+    };
+    let Some(position) = calls
+        .iter()
+        .position(|c| c["function"]["name"] == RESPONSE_TOOL)
+    else {
+        return;
+    };
+    let call = calls.remove(position);
+    let empty = calls.is_empty();
+    let message = &mut choice["message"];
+    message["content"] = call["function"]["arguments"].clone();
+    if empty {
+        if let Some(message) = message.as_object_mut() {
+            message.remove("tool_calls");
+        }
+        choice["finish_reason"] = json!("stop");
+    }
+}
+
+/// Wraps a provider's stream decoder so a [`RESPONSE_TOOL`] call streams as
+/// reply text
+pub struct ResponseToolDecoder {
+    inner: Box<dyn StreamDecoder>,
+    /// Stream index of the response tool call, once started
+    index: Option<usize>,
+}
+
+impl ResponseToolDecoder {
+    pub fn new(inner: Box<dyn StreamDecoder>) -> Self {
+        Self { inner, index: None }
+    }
+
+    fn translate(&mut self, events: Vec<StreamEvent>) -> Vec<StreamEvent> {
+        events
+            .into_iter()
+            .filter_map(|event| match event {
+                StreamEvent::ToolCallStart { index, name, .. } if name == RESPONSE_TOOL => {
+                    self.index = Some(index);
+                    None
+                }
+                StreamEvent::ToolCallDelta { index, arguments } if self.index == Some(index) => {
+                    Some(StreamEvent::TextDelta(arguments))
+                }
+                StreamEvent::ToolCallEnd { index } if self.index == Some(index) => None,
+                StreamEvent::Stop(StopReason::ToolUse) if self.index.is_some() => {
+                    Some(StreamEvent::Stop(StopReason::EndTurn))
+                }
+                other => Some(other),
+            })
+            .collect()
+    }
+}
+
+impl StreamDecoder for ResponseToolDecoder {
+    fn decode(&mut self, chunk: &[u8]) -> Vec<StreamEvent> {
+        let events = self.inner.decode(chunk);
+        self.translate(events)
+    }
+
+    fn finish(&mut self) -> Vec<StreamEvent> {
+        let events = self.inner.finish();
+        self.translate(events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn person_format() -> ResponseFormat {
+        serde_json::from_value(json!({
+            "type": "json_schema",
+            "json_schema": {
+                "name": "person",
+                "strict": true,
+                "schema": {
+                    "type": "object",
+                    "properties": {
+                        "name": {"type": "string"},
+                        "age": {"type": "integer", "minimum": 0}
+                    },
+                    "required": ["name", "age"],
+                    "additionalProperties": false
+                }
+            }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_validate() {
+        let format = person_format();
+        assert_eq!(
+            validate(&format, " {\"name\": \"Ada\", \"age\": 36}\n").unwrap()["age"],
+            36
+        );
+
+        let error = validate(&format, r#"{"name": "Ada", "age": -1}"#).unwrap_err();
+        assert!(error.contains("/age"), "{}", error);
+        assert!(validate(&format, r#"{"name": "Ada"}"#).is_err());
+        assert!(validate(&format, "Sure! Here is the JSON").is_err());
+
+        assert!(validate(&ResponseFormat::JsonObject, "[1, 2]").is_err());
+        assert!(validate(&ResponseFormat::JsonObject, "{}").is_ok());
+        assert!(validate(&ResponseFormat::Text, "plain words").is_ok());
+        assert!(check(&format).is_ok());
+
+        let broken: ResponseFormat = serde_json::from_value(json!({
+            "type": "json_schema",
+            "json_schema": {"name": "x", "schema": {"type": "no-such-type"}}
+        }))
+        .unwrap();
+        assert!(check(&broken).is_err());
+    }
+
+    #[test]
+    fn test_response_tool_round_trip() {
+        let mut request: ChatCompletionRequest = serde_json::from_value(json!({
+            "model": "claude-sonnet-4-5",
+            "messages": [{"role": "user", "content": "Who wrote the first program?"}]
+        }))
+        .unwrap();
+        assert!(with_response_tool(&request).is_none());
+
+        request.response_format = Some(person_format());
+        assert!(forces_tool(&request));
+        let forced = with_response_tool(&request).unwrap();
+        assert!(forced.response_format.is_none());
+        let tools = forced.tools.unwrap();
+        assert_eq!(tools[0]["function"]["name"], RESPONSE_TOOL);
+        assert_eq!(tools[0]["function"]["parameters"]["required"][1], "age");
+        assert_eq!(
+            forced.tool_choice.unwrap()["function"]["name"],
+            RESPONSE_TOOL
+        );
+
+        let mut response = json!({
+            "choices": [{
+                "message": {
+                    "role": "assistant",
+                    "content": "",
+                    "tool_calls": [{
+                        "id": "toolu_1",
+                        "type": "function",
+                        "function": {"name": RESPONSE_TOOL, "arguments": "{\"name\":\"Ada\",\"age\":36}"}
+                    }]
+                },
+                "finish_reason": "tool_calls"
+            }]
+        });
+        unwrap_response_tool(&mut response);
+        let choice = &response["choices"][0];
+        assert_eq!(
+            choice["message"]["content"],
+            "{\"name\":\"Ada\",\"age\":36}"
+        );
+        assert!(choice["message"].get("tool_calls").is_none());
+        assert_eq!(choice["finish_reason"], "stop");
+    }
+
+    #[test]
+    fn test_response_tool_decoder() {
+        struct Replay(Vec<StreamEvent>);
+        impl StreamDecoder for Replay {
+            fn decode(&mut self, _chunk: &[u8]) -> Vec<StreamEvent> {
+                std::mem::take(&mut self.0)
+            }
+            fn finish(&mut self) -> Vec<StreamEvent> {
+                Vec::new()
+            }
+        }
+
+        let mut decoder = ResponseToolDecoder::new(Box::new(Replay(vec![
+            StreamEvent::ToolCallStart {
+                index: 0,
+                id: "toolu_1".to_string(),
+                name: RESPONSE_TOOL.to_string(),
+            },
+            StreamEvent::ToolCallDelta {
+                index: 0,
+                arguments: "{\"name\":".to_string(),
+            },
+            StreamEvent::ToolCallDelta {
+                index: 0,
+                arguments: "\"Ada\"}".to_string(),
+            },
+            StreamEvent::ToolCallEnd { index: 0 },
+            StreamEvent::Stop(StopReason::ToolUse),
+        ])));
+        assert_eq!(
+            decoder.decode(b""),
+            vec![
+                StreamEvent::TextDelta("{\"name\":".to_string()),
+                StreamEvent::TextDelta("\"Ada\"}".to_string()),
+                StreamEvent::Stop(StopReason::EndTurn),
+            ]
+        );
+    }
+}
@@ -7,8 +7,17 @@
 //! - Isolated conversation contexts per subagent
 //! - Background execution with async tracking
 
-use crate::config::AppConfig;
+use crate::budget::{self, BudgetScope, BudgetStatus, Spend};
+use crate::config::{AppConfig, IssueBackend, ProviderConfig};
+use crate::hooks::HookEngine;
+use crate::issues;
+use crate::memory::Issue;
+use crate::providers::ProviderAdapter;
+use crate::proxy::ChatCompletionRequest;
+use crate::stream::{read_stream, StreamAccumulator};
 use crate::tools::{execute_tool, ToolCall};
+use crate::upstream::{fallback_chain, send_with_fallback, Route};
+use crate::usage;
 use regex::Regex;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
@@ -25,9 +34,9 @@ const MAX_SUBAGENT_TURNS: usize = 50;
 /// Maximum tokens for subagent responses
 const SUBAGENT_MAX_TOKENS: u32 = 8192;
 
-/// Detect chainlink issue references in text (e.g., #123, issue 123, issue #123)
+/// Detect issue references in text (e.g., #123, issue 123, issue #123)
 /// Returns the first issue ID found, if any
-fn detect_chainlink_issue(text: &str) -> Option<u32> {
+fn detect_issue_reference(text: &str) -> Option<u32> {
     // Match patterns like #123, issue 123, issue #123, chainlink #123
     let re = Regex::new(r"(?i)(?:#(\d+)|issue\s*#?(\d+)|chainlink\s*#?(\d+))").ok()?;
 
@@ -47,6 +56,7 @@ fn detect_chainlink_issue(text: &str) -> Option<u32> {
 /// Spawn a TestBuilder agent to work alongside a coding agent
 fn spawn_companion_test_builder(
     issue_id: u32,
+    issue: Option<&Issue>,
     original_task: &str,
     app_config: &AppConfig,
 ) -> Option<String> {
@@ -56,8 +66,19 @@ fn spawn_companion_test_builder(
         .unwrap_or_else(|_| ".".to_string());
 
     let test_builder_task = format!("Adversarial testing for issue #{}", issue_id);
+    // Embed the issue when the native store has it, so the agent starts with the intent
+    let issue_context = match issue {
+        Some(issue) if !issue.description.is_empty() => {
+            format!(
+                "\n\n## Issue #{}: {}\n{}",
+                issue.id, issue.title, issue.description
+            )
+        }
+        Some(issue) => format!("\n\n## Issue #{}: {}", issue.id, issue.title),
+        None => String::new(),
+    };
     let test_builder_prompt = format!(
-        r#"A coding agent is working on chainlink issue #{}. YOUR JOB IS TO BREAK THEIR CODE.
+        r#"A coding agent is working on issue #{}. YOUR JOB IS TO BREAK THEIR CODE.{}
 
 ## Working Directory
 You are working in: {}
@@ -69,15 +90,15 @@ Original task they're working on: {}
 Don't trust them. They probably made mistakes. Find those mistakes.
 
 ## Instructions
-1. Check the issue: `chainlink show {}`
+1. Check the issue: `issue_show` with id {}
 2. Watch for their changes: `git diff` and `git diff --cached`
 3. Read their code critically - what did they miss? What edge cases did they ignore?
 4. Write ADVERSARIAL tests designed to break their implementation
 5. Run the tests. Hunt for failures.
 
 ## When You Find Bugs (and you will)
-- Reopen the issue: `chainlink reopen {}`
-- Add a detailed comment explaining exactly how their code fails
+- Reopen the issue: `issue_reopen` with id {}
+- Add a detailed comment with `issue_comment` explaining exactly how their code fails
 - Include "⚠️ ALERT: Test failure detected - coding agent's work has bugs"
 
 ## Categories to Attack
@@ -88,7 +109,7 @@ Don't trust them. They probably made mistakes. Find those mistakes.
 - Concurrency (if applicable)
 
 Start by checking what they've changed. Then break it."#,
-        issue_id, working_dir, original_task, issue_id, issue_id
+        issue_id, issue_context, working_dir, original_task, issue_id, issue_id
     );
 
     let config = SubagentConfig {
@@ -208,21 +229,43 @@ impl AgentType {
                 "list_files",
                 "web_fetch",
                 "web_search",
+                "browser",
+                "todo_write",
+                "todo_read",
+                "issue_create",
+                "issue_list",
+                "issue_show",
+                "issue_comment",
+                "issue_close",
+                "issue_reopen",
+                "issue_label",
+                "issue_depend",
             ],
             AgentType::Explore => {
                 vec!["bash", "read_file", "list_files", "web_fetch", "web_search"]
             }
-            AgentType::Plan => vec!["bash", "read_file", "list_files", "web_fetch", "web_search"],
+            AgentType::Plan => vec![
+                "bash",
+                "read_file",
+                "list_files",
+                "web_fetch",
+                "web_search",
+                "todo_write",
+                "todo_read",
+            ],
             AgentType::Guide => vec!["read_file", "list_files", "web_fetch", "web_search"],
             AgentType::TestBuilder => vec![
-                "bash",        // For running tests (cargo test, pytest)
-                "bash_output", // For monitoring long-running tests
-                "kill_shell",  // For stopping hung tests
-                "read_file",   // For reading source code to understand what to test
-                "write_file",  // For creating new test files
-                "edit_file",   // For adding tests to existing test files
-                "list_files",  // For finding test files and source files
-                "chainlink",   // For reading issues, reopening on failure, adding comments
+                "bash",          // For running tests (cargo test, pytest)
+                "bash_output",   // For monitoring long-running tests
+                "kill_shell",    // For stopping hung tests
+                "read_file",     // For reading source code to understand what to test
+                "write_file",    // For creating new test files
+                "edit_file",     // For adding tests to existing test files
+                "list_files",    // For finding test files and source files
+                "issue_show",    // For reading the issue being worked
+                "issue_comment", // For reporting failures on the issue
+                "issue_reopen",  // For reopening the issue when tests fail
+                "browser",       // For end-to-end testing of web apps
             ],
         }
     }
@@ -331,7 +374,7 @@ You work in parallel with coding agents, but you are NOT their friend. You are t
 ## Workflow
 
 ### 1. Understand What You're Attacking
-- Use `chainlink show <id>` to read the issue - understand the INTENT
+- Use `issue_show` to read the issue - understand the INTENT
 - Run `git diff` to see what changed - this is your attack surface
 - Read the modified code - look for assumptions, shortcuts, missing checks
 - Think: "If I were trying to break this, what would I try?"
@@ -381,7 +424,7 @@ You work in parallel with coding agents, but you are NOT their friend. You are t
 
 **Their code is wrong (THE GOAL):**
 - DO NOT fix it. That's their job, and you'd probably mess it up anyway.
-- Reopen the issue: `chainlink reopen <id>`
+- Reopen the issue: `issue_reopen` with the issue id
 - Add DETAILED comment:
   - Exact test that failed
   - Expected vs actual behavior
@@ -429,6 +472,8 @@ pub struct BackgroundAgent {
     pub error: Mutex<Option<String>>,
     /// Number of turns executed
     pub turns: AtomicU64,
+    /// Tokens and cost reported for the agent's requests
+    pub spend: Mutex<Spend>,
 }
 
 /// Manager for background agents
@@ -454,6 +499,7 @@ impl BackgroundAgentManager {
             result: Mutex::new(None),
             error: Mutex::new(None),
             turns: AtomicU64::new(0),
+            spend: Mutex::new(Spend::default()),
         });
 
         if let Ok(mut agents) = self.agents.lock() {
@@ -488,6 +534,15 @@ impl BackgroundAgentManager {
         }
     }
 
+    /// Add one request's spend to an agent's total
+    pub fn add_spend(&self, id: &str, spend: Spend) {
+        if let Some(agent) = self.get(id) {
+            if let Ok(mut total) = agent.spend.lock() {
+                *total += spend;
+            }
+        }
+    }
+
     /// Increment turn counter for an agent
     pub fn increment_turns(&self, id: &str) -> u64 {
         if let Some(agent) = self.get(id) {
@@ -678,16 +733,26 @@ pub async fn run_subagent(
                 .unwrap_or_else(|| "claude-sonnet-4-20250514".to_string())
         });
 
-    // Get provider config
-    let (base_url, api_key) =
-        if let Some(provider_config) = app_config.providers.get(&app_config.proxy.target) {
-            (
-                provider_config.base_url.clone(),
-                provider_config.api_key.clone().unwrap_or_default(),
-            )
-        } else {
-            ("https://api.anthropic.com/v1".to_string(), String::new())
-        };
+    // Get provider config; requests go through the same adapter as the chat loop
+    let provider = app_config
+        .providers
+        .get(&app_config.proxy.target)
+        .cloned()
+        .unwrap_or_else(|| ProviderConfig {
+            api_key: None,
+            base_url: "https://api.anthropic.com".to_string(),
+            model: None,
+            headers: HashMap::new(),
+            thinking: Default::default(),
+            adapter: None,
+            aws: None,
+            vertex: None,
+            path: None,
+            query: HashMap::new(),
+            auth: None,
+            cache: Default::default(),
+        });
+    let routes = fallback_chain(app_config, &app_config.proxy.target, &provider, &model);
 
     // Run the agent loop
     let mut final_output = String::new();
@@ -710,6 +775,22 @@ pub async fn run_subagent(
             };
         }
 
+        if let Some(message) = check_subagent_budget(&agent_id, app_config).await {
+            let output = if final_output.is_empty() {
+                format!("Stopped: {}", message)
+            } else {
+                format!("Stopped: {}\n\nLast output:\n{}", message, final_output)
+            };
+            BACKGROUND_AGENTS.fail(&agent_id, output.clone());
+            return SubagentResult {
+                agent_id,
+                success: false,
+                output,
+                turns_used: turns,
+                is_background: config.run_in_background,
+            };
+        }
+
         // Build the request
         let request_body = json!({
             "model": model,
@@ -719,23 +800,19 @@ pub async fn run_subagent(
         });
 
         // Make the API call
-        let response = match make_api_call(client, &base_url, &api_key, &request_body).await {
-            Ok(r) => r,
-            Err(e) => {
-                BACKGROUND_AGENTS.fail(&agent_id, e.clone());
-                return SubagentResult {
-                    agent_id,
-                    success: false,
-                    output: e,
-                    turns_used: turns,
-                    is_background: config.run_in_background,
-                };
+        let response = make_api_call(
+            client,
+            &routes,
+            app_config,
+            &format!("subagent:{}", agent_id),
+            &request_body,
+        )
+        .await;
+        let assistant_message = match response {
+            Ok((msg, spend)) => {
+                BACKGROUND_AGENTS.add_spend(&agent_id, spend);
+                msg
             }
-        };
-
-        // Parse the response
-        let assistant_message = match parse_response(&response) {
-            Ok(msg) => msg,
             Err(e) => {
                 BACKGROUND_AGENTS.fail(&agent_id, e.clone());
                 return SubagentResult {
@@ -820,13 +897,11 @@ pub async fn run_subagent(
                 },
             };
 
-            let result = execute_tool(&tc);
+            // Each subagent keeps its own todo list, separate from the parent session
+            let result =
+                crate::tools::with_todo_scope(&todo_scope(&agent_id), || execute_tool(&tc));
 
-            messages.push(json!({
-                "role": "tool",
-                "tool_call_id": tool_id,
-                "content": result.content
-            }));
+            messages.push(result.to_message(crate::models::supports_vision(&model)));
         }
     }
 
@@ -842,52 +917,70 @@ pub async fn run_subagent(
     }
 }
 
-/// Make an API call to the LLM provider
+/// Check the subagent and daily budgets before another turn, printing
+/// first-time warnings. Returns the message of a limit that has been reached,
+/// after firing the `Stop` hook for it.
+async fn check_subagent_budget(agent_id: &str, app_config: &AppConfig) -> Option<String> {
+    let spend = BACKGROUND_AGENTS
+        .get(agent_id)
+        .and_then(|agent| agent.spend.lock().ok().map(|spend| *spend))
+        .unwrap_or_default();
+    let cwd = std::env::current_dir().unwrap_or_else(|_| ".".into());
+    for status in [
+        budget::check(BudgetScope::Subagent, agent_id, &app_config.budgets, &spend),
+        budget::check_daily(&app_config.budgets, &cwd),
+    ] {
+        match status {
+            BudgetStatus::Ok => {}
+            BudgetStatus::Warning(message) => {
+                eprintln!("\x1b[33mAgent {}: {}\x1b[0m", agent_id, message)
+            }
+            BudgetStatus::Exceeded { scope, message } => {
+                let hooks = HookEngine::new(app_config.hooks.clone());
+                budget::run_stop_hook(&hooks, &format!("subagent:{}", agent_id), scope, &message)
+                    .await;
+                return Some(message);
+            }
+        }
+    }
+    None
+}
+
+/// Make a streaming API call to the LLM provider, retrying and falling back
+/// along `routes`, and collect the assistant message in OpenAI format.
+/// Reported token usage is recorded in the project usage log under
+/// `usage_session` and returned with the message.
 async fn make_api_call(
     client: &Client,
-    base_url: &str,
-    api_key: &str,
+    routes: &[Route],
+    app_config: &AppConfig,
+    usage_session: &str,
     request_body: &Value,
-) -> Result<Value, String> {
-    // Determine if this is Anthropic or OpenAI format
-    let is_anthropic = base_url.contains("anthropic.com");
-
-    let (endpoint, headers) = if is_anthropic {
-        (
-            format!("{}/messages", base_url.trim_end_matches('/')),
-            vec![
-                ("x-api-key".to_string(), api_key.to_string()),
-                ("anthropic-version".to_string(), "2023-06-01".to_string()),
-                ("content-type".to_string(), "application/json".to_string()),
-            ],
-        )
-    } else {
-        (
-            format!("{}/chat/completions", base_url.trim_end_matches('/')),
-            vec![
-                ("Authorization".to_string(), format!("Bearer {}", api_key)),
-                ("Content-type".to_string(), "application/json".to_string()),
-            ],
-        )
-    };
-
-    // Transform request for Anthropic if needed
-    let body = if is_anthropic {
-        transform_to_anthropic(request_body)
-    } else {
-        request_body.clone()
-    };
-
-    let mut req = client.post(&endpoint);
-    for (key, value) in headers {
-        req = req.header(&key, &value);
-    }
-    req = req.json(&body);
+) -> Result<(Value, Spend), String> {
+    let served = send_with_fallback(routes, &app_config.proxy.retry, |route| {
+        let adapter = route.adapter();
+        let mut request = request_body.clone();
+        request["model"] = json!(route.model);
+        let body = build_request_body(adapter.as_ref(), &route.provider, &request)?;
+        let endpoint = format!(
+            "{}{}",
+            route.provider.base_url,
+            adapter.chat_path(&route.model, true)
+        );
 
-    let response = req
-        .send()
-        .await
-        .map_err(|e| format!("Request failed: {}", e))?;
+        let mut req = client.post(&endpoint);
+        let api_key = route.provider.api_key.clone().unwrap_or_default();
+        for (key, value) in adapter.get_headers(&api_key) {
+            req = req.header(&key, &value);
+        }
+        for (key, value) in &route.provider.headers {
+            req = req.header(key, value);
+        }
+        Ok(req.json(&body))
+    })
+    .await
+    .map_err(|e| e.to_string())?;
+    let response = served.response;
 
     if !response.status().is_success() {
         let status = response.status();
@@ -898,214 +991,45 @@ async fn make_api_call(
         return Err(format!("API error ({}): {}", status, text));
     }
 
-    let json: Value = response
-        .json()
+    let mut turn = StreamAccumulator::new();
+    let mut decoder = served.route.adapter().stream_decoder();
+    read_stream(response, decoder.as_mut(), |event| turn.push(&event))
         .await
-        .map_err(|e| format!("Failed to parse response: {}", e))?;
-
-    // Transform Anthropic response to OpenAI format if needed
-    if is_anthropic {
-        Ok(transform_from_anthropic(&json))
-    } else {
-        Ok(json)
-    }
-}
-
-/// Transform OpenAI-format request to Anthropic format
-fn transform_to_anthropic(request: &Value) -> Value {
-    let messages = request.get("messages").and_then(|m| m.as_array());
-    let tools = request.get("tools").and_then(|t| t.as_array());
-
-    // Extract system message
-    let system: Option<String> = messages.and_then(|msgs| {
-        msgs.iter()
-            .find(|m| m.get("role").and_then(|r| r.as_str()) == Some("system"))
-            .and_then(|m| m.get("content"))
-            .and_then(|c| c.as_str())
-            .map(String::from)
-    });
-
-    // Convert messages (excluding system)
-    let converted_messages: Vec<Value> = messages
-        .map(|msgs| {
-            msgs.iter()
-                .filter(|m| m.get("role").and_then(|r| r.as_str()) != Some("system"))
-                .map(|m| {
-                    let role = m.get("role").and_then(|r| r.as_str()).unwrap_or("user");
-                    let content = m.get("content").cloned().unwrap_or(json!(""));
-
-                    // Handle tool role -> user with tool_result
-                    if role == "tool" {
-                        let tool_call_id = m
-                            .get("tool_call_id")
-                            .and_then(|id| id.as_str())
-                            .unwrap_or("");
-                        return json!({
-                            "role": "user",
-                            "content": [{
-                                "type": "tool_result",
-                                "tool_use_id": tool_call_id,
-                                "content": content
-                            }]
-                        });
-                    }
-
-                    // Handle assistant with tool_calls
-                    if role == "assistant" {
-                        if let Some(tool_calls) = m.get("tool_calls").and_then(|tc| tc.as_array()) {
-                            let mut content_parts: Vec<Value> = Vec::new();
-
-                            // Add text content if present
-                            if let Some(text) = m.get("content").and_then(|c| c.as_str()) {
-                                if !text.is_empty() {
-                                    content_parts.push(json!({
-                                        "type": "text",
-                                        "text": text
-                                    }));
-                                }
-                            }
-
-                            // Convert tool calls to tool_use
-                            let empty_func = json!({});
-                            for tc in tool_calls {
-                                let id = tc.get("id").and_then(|i| i.as_str()).unwrap_or("");
-                                let func = tc.get("function").unwrap_or(&empty_func);
-                                let name = func.get("name").and_then(|n| n.as_str()).unwrap_or("");
-                                let args_str = func
-                                    .get("arguments")
-                                    .and_then(|a| a.as_str())
-                                    .unwrap_or("{}");
-                                let input: Value =
-                                    serde_json::from_str(args_str).unwrap_or_else(|_| json!({}));
-
-                                content_parts.push(json!({
-                                    "type": "tool_use",
-                                    "id": id,
-                                    "name": name,
-                                    "input": input
-                                }));
-                            }
-
-                            return json!({
-                                "role": "assistant",
-                                "content": content_parts
-                            });
-                        }
-                    }
-
-                    // Standard message
-                    let content_array = if let Some(text) = content.as_str() {
-                        json!([{"type": "text", "text": text}])
-                    } else {
-                        content
-                    };
-
-                    json!({
-                        "role": if role == "assistant" { "assistant" } else { "user" },
-                        "content": content_array
-                    })
-                })
-                .collect()
-        })
-        .unwrap_or_default();
-
-    // Convert tools
-    let converted_tools: Vec<Value> = tools
-        .map(|ts| {
-            ts.iter()
-                .filter_map(|t| {
-                    let func = t.get("function")?;
-                    Some(json!({
-                        "name": func.get("name")?,
-                        "description": func.get("description").unwrap_or(&json!("")),
-                        "input_schema": func.get("parameters").unwrap_or(&json!({}))
-                    }))
-                })
-                .collect()
-        })
-        .unwrap_or_default();
-
-    let mut body = json!({
-        "model": request.get("model").and_then(|m| m.as_str()).unwrap_or("claude-sonnet-4-20250514"),
-        "messages": converted_messages,
-        "max_tokens": request.get("max_tokens").and_then(|m| m.as_u64()).unwrap_or(SUBAGENT_MAX_TOKENS as u64)
-    });
-
-    if let Some(sys) = system {
-        body["system"] = json!(sys);
-    }
-
-    if !converted_tools.is_empty() {
-        body["tools"] = json!(converted_tools);
-    }
-
-    body
-}
-
-/// Transform Anthropic response to OpenAI format
-fn transform_from_anthropic(response: &Value) -> Value {
-    let content = response.get("content").and_then(|c| c.as_array());
-
-    let mut text_content = String::new();
-    let mut tool_calls: Vec<Value> = Vec::new();
-
-    if let Some(parts) = content {
-        for part in parts {
-            match part.get("type").and_then(|t| t.as_str()) {
-                Some("text") => {
-                    if let Some(text) = part.get("text").and_then(|t| t.as_str()) {
-                        text_content.push_str(text);
-                    }
-                }
-                Some("tool_use") => {
-                    let id = part.get("id").and_then(|i| i.as_str()).unwrap_or("");
-                    let name = part.get("name").and_then(|n| n.as_str()).unwrap_or("");
-                    let empty_input = json!({});
-                    let input = part.get("input").unwrap_or(&empty_input);
-
-                    tool_calls.push(json!({
-                        "id": id,
-                        "type": "function",
-                        "function": {
-                            "name": name,
-                            "arguments": serde_json::to_string(input).unwrap_or_else(|_| "{}".to_string())
-                        }
-                    }));
-                }
-                _ => {}
-            }
-        }
+        .map_err(|e| format!("Failed to read response: {}", e))?;
+
+    let mut spend = Spend::default();
+    if let Some(turn_usage) = &turn.usage {
+        let cwd = std::env::current_dir().unwrap_or_else(|_| ".".into());
+        let cost = usage::record(
+            &cwd,
+            usage_session,
+            &served.route.provider_name,
+            &served.route.model,
+            turn_usage,
+            &app_config.pricing,
+            served.route.provider.cache.ttl,
+        );
+        spend = Spend::new(turn_usage, cost.unwrap_or(0.0));
     }
 
-    let mut message = json!({
-        "role": "assistant",
-        "content": text_content
-    });
-
-    if !tool_calls.is_empty() {
-        message["tool_calls"] = json!(tool_calls);
+    if let Some(error) = &turn.error {
+        return Err(format!("Stream error: {}", error));
     }
-
-    message
+    Ok((turn.to_message(), spend))
 }
 
-/// Parse the response to extract the assistant message
-fn parse_response(response: &Value) -> Result<Value, String> {
-    // OpenAI format
-    if let Some(choices) = response.get("choices").and_then(|c| c.as_array()) {
-        if let Some(first) = choices.first() {
-            if let Some(message) = first.get("message") {
-                return Ok(message.clone());
-            }
-        }
-    }
-
-    // Direct message (already transformed)
-    if response.get("role").is_some() {
-        return Ok(response.clone());
-    }
-
-    Err("Could not parse response".to_string())
+/// Transform an OpenAI-format request into the provider's streaming request body
+fn build_request_body(
+    adapter: &dyn ProviderAdapter,
+    provider: &ProviderConfig,
+    request: &Value,
+) -> Result<Value, String> {
+    let mut request: ChatCompletionRequest = serde_json::from_value(request.clone())
+        .map_err(|e| format!("Invalid subagent request: {}", e))?;
+    request.stream = Some(true);
+    adapter
+        .transform_request_with_thinking(&request, &provider.thinking)
+        .map_err(|e| e.to_string())
 }
 
 // === Tool Execution ===
@@ -1156,13 +1080,18 @@ pub fn execute_task_tool(args: &HashMap<String, Value>, app_config: &AppConfig)
     // Create HTTP client
     let client = Client::new();
 
-    // Auto-spawn TestBuilder for GeneralPurpose agents working on chainlink issues
+    // Auto-spawn TestBuilder for GeneralPurpose agents working on tracked issues
     let mut companion_agent_id: Option<String> = None;
     if agent_type == AgentType::GeneralPurpose {
-        // Check both description and prompt for chainlink issue references
+        // Check both description and prompt for issue references
         let combined_text = format!("{} {}", description, prompt);
-        if let Some(issue_id) = detect_chainlink_issue(&combined_text) {
-            companion_agent_id = spawn_companion_test_builder(issue_id, description, app_config);
+        if let Some(issue_id) = detect_issue_reference(&combined_text) {
+            // The native store can confirm the issue exists; chainlink can't be queried cheaply
+            let issue = issues::lookup_issue(issue_id as i64);
+            if issue.is_some() || issues::configured_backend() == IssueBackend::Chainlink {
+                companion_agent_id =
+                    spawn_companion_test_builder(issue_id, issue.as_ref(), description, app_config);
+            }
         }
     }
 
@@ -1268,6 +1197,38 @@ pub fn execute_task_tool(args: &HashMap<String, Value>, app_config: &AppConfig)
     }
 }
 
+/// Todo scope used for a subagent's tool calls
+pub fn todo_scope(agent_id: &str) -> String {
+    format!("agent:{}", agent_id)
+}
+
+/// Format a subagent's todo list for agent_output, if it has one
+fn agent_todos_section(agent_id: &str) -> String {
+    let todos = crate::tools::get_todo_list_for(&todo_scope(agent_id));
+    if todos.is_empty() {
+        String::new()
+    } else {
+        format!("\n\nTodos:\n{}", crate::tools::format_todo_list(&todos))
+    }
+}
+
+/// Format a subagent's reported usage and remaining budget for agent_output
+fn agent_usage_section(agent: &BackgroundAgent) -> String {
+    let spend = agent.spend.lock().map(|spend| *spend).unwrap_or_default();
+    let mut section = format!(
+        "\nUsage: {} tokens, {}",
+        usage::format_tokens(spend.tokens),
+        usage::format_cost(spend.cost_usd)
+    );
+    let limit = crate::config::load_config()
+        .map(|c| c.budgets.subagent)
+        .unwrap_or_default();
+    if let Some(left) = budget::remaining(&limit, &spend) {
+        section.push_str(&format!(" (budget: {})", left));
+    }
+    section
+}
+
 /// Execute the AgentOutput tool
 pub fn execute_agent_output_tool(args: &HashMap<String, Value>) -> (String, bool) {
     let agent_id = match args.get("agent_id").and_then(|v| v.as_str()) {
@@ -1323,16 +1284,23 @@ pub fn execute_agent_output_tool(args: &HashMap<String, Value>) -> (String, bool
         if let Some(err) = error {
             (
                 format!(
-                    "Agent '{}' failed after {} turns:\n{}",
-                    agent_id, turns, err
+                    "Agent '{}' failed after {} turns:\n{}{}",
+                    agent_id,
+                    turns,
+                    err,
+                    agent_usage_section(&agent)
                 ),
                 true,
             )
         } else if let Some(output) = result {
             (
                 format!(
-                    "Agent '{}' completed in {} turns:\n\n{}",
-                    agent_id, turns, output
+                    "Agent '{}' completed in {} turns:{}\n\n{}{}",
+                    agent_id,
+                    turns,
+                    agent_usage_section(&agent),
+                    output,
+                    agent_todos_section(agent_id)
                 ),
                 false,
             )
@@ -1345,8 +1313,12 @@ pub fn execute_agent_output_tool(args: &HashMap<String, Value>) -> (String, bool
     } else {
         (
             format!(
-                "Agent '{}' is still running ({} turns so far)\nTask: {}",
-                agent_id, turns, agent.task
+                "Agent '{}' is still running ({} turns so far)\nTask: {}{}{}",
+                agent_id,
+                turns,
+                agent.task,
+                agent_usage_section(&agent),
+                agent_todos_section(agent_id)
             ),
             false,
         )
@@ -1378,27 +1350,27 @@ mod tests {
     }
 
     #[test]
-    fn test_detect_chainlink_issue() {
+    fn test_detect_issue_reference() {
         // Basic #N pattern
-        assert_eq!(detect_chainlink_issue("#123"), Some(123));
-        assert_eq!(detect_chainlink_issue("Working on #42"), Some(42));
+        assert_eq!(detect_issue_reference("#123"), Some(123));
+        assert_eq!(detect_issue_reference("Working on #42"), Some(42));
 
         // issue N pattern
-        assert_eq!(detect_chainlink_issue("issue 99"), Some(99));
-        assert_eq!(detect_chainlink_issue("Issue 456"), Some(456));
-        assert_eq!(detect_chainlink_issue("issue #789"), Some(789));
+        assert_eq!(detect_issue_reference("issue 99"), Some(99));
+        assert_eq!(detect_issue_reference("Issue 456"), Some(456));
+        assert_eq!(detect_issue_reference("issue #789"), Some(789));
 
         // chainlink N pattern
-        assert_eq!(detect_chainlink_issue("chainlink 101"), Some(101));
-        assert_eq!(detect_chainlink_issue("Chainlink #202"), Some(202));
+        assert_eq!(detect_issue_reference("chainlink 101"), Some(101));
+        assert_eq!(detect_issue_reference("Chainlink #202"), Some(202));
 
         // No match
-        assert_eq!(detect_chainlink_issue("no issue here"), None);
-        assert_eq!(detect_chainlink_issue("# not a number"), None);
+        assert_eq!(detect_issue_reference("no issue here"), None);
+        assert_eq!(detect_issue_reference("# not a number"), None);
 
         // Embedded in longer text
         assert_eq!(
-            detect_chainlink_issue("Implement feature for issue #168 please"),
+            detect_issue_reference("Implement feature for issue #168 please"),
             Some(168)
         );
     }
@@ -1449,6 +1421,16 @@ mod tests {
         let turns = manager.increment_turns(&id);
         assert_eq!(turns, 1);
 
+        // Accumulate spend across requests
+        let spend = Spend {
+            tokens: 1_500,
+            cost_usd: 0.01,
+        };
+        manager.add_spend(&id, spend);
+        manager.add_spend(&id, spend);
+        assert_eq!(agent.spend.lock().unwrap().tokens, 3_000);
+        assert!(agent_usage_section(&agent).starts_with("\nUsage: 3.0k tokens, $0.02"));
+
         // Finish the agent
         manager.finish(&id, "Test result".to_string());
         assert!(agent.finished.load(Ordering::SeqCst));
@@ -1459,7 +1441,7 @@ mod tests {
     }
 
     #[test]
-    fn test_transform_to_anthropic() {
+    fn test_build_request_body_anthropic() {
         let request = json!({
             "model": "test-model",
             "messages": [
@@ -1468,13 +1450,26 @@ mod tests {
             ],
             "max_tokens": 1000
         });
+        let provider = ProviderConfig {
+            api_key: None,
+            base_url: "https://api.anthropic.com".to_string(),
+            model: None,
+            headers: HashMap::new(),
+            thinking: Default::default(),
+            adapter: None,
+            aws: None,
+            vertex: None,
+            path: None,
+            query: HashMap::new(),
+            auth: None,
+            cache: Default::default(),
+        };
 
-        let anthropic = transform_to_anthropic(&request);
+        let adapter = crate::providers::get_adapter("anthropic");
+        let anthropic = build_request_body(adapter.as_ref(), &provider, &request).unwrap();
         assert_eq!(anthropic.get("model").unwrap().as_str(), Some("test-model"));
-        assert_eq!(
-            anthropic.get("system").unwrap().as_str(),
-            Some("System prompt")
-        );
+        assert_eq!(anthropic["system"][0]["text"], "System prompt");
         assert!(anthropic.get("messages").unwrap().as_array().unwrap().len() == 1);
+        assert_eq!(anthropic["stream"], true);
     }
 }
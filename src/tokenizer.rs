@@ -0,0 +1,273 @@
+//! Token counting for context management.
+//!
+//! OpenAI models are counted exactly with the bundled cl100k/o200k BPE ranks.
+//! Claude and Gemini tokenizers are not public, so their counts are a BPE
+//! count scaled by a measured ratio. On top of that, every model keeps a
+//! calibration factor learned from the `usage` numbers providers report, so
+//! estimates converge on what the provider actually bills.
+
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+use tiktoken_rs::CoreBPE;
+
+/// Weight of the newest observation in the calibration average
+const CALIBRATION_ALPHA: f64 = 0.3;
+
+/// Calibration factors are clamped to this range to ignore outliers
+const CALIBRATION_RANGE: (f64, f64) = (0.5, 2.0);
+
+/// Longest slice handed to the BPE at once. Merging is quadratic in the
+/// length of a single pre-token, so long unbroken runs (minified code,
+/// base64) are split; cuts land before whitespace to keep words whole.
+const MAX_CHUNK_BYTES: usize = 1024;
+
+/// Learned `reported / estimated` ratio per model
+static CALIBRATION: LazyLock<Mutex<HashMap<String, f64>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Counts the tokens a model sees for a piece of text
+pub trait Tokenizer: Send + Sync {
+    /// Name shown in status output (e.g. `o200k_base`)
+    fn name(&self) -> &str;
+
+    /// Number of tokens in `text`
+    fn count(&self, text: &str) -> usize;
+}
+
+/// Exact count with one of the bundled OpenAI encodings
+pub struct BpeTokenizer {
+    name: &'static str,
+    bpe: fn() -> &'static CoreBPE,
+}
+
+impl Tokenizer for BpeTokenizer {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn count(&self, text: &str) -> usize {
+        let bpe = (self.bpe)();
+        chunks(text)
+            .map(|chunk| bpe.encode_ordinary(chunk).len())
+            .sum()
+    }
+}
+
+/// Split text into pieces of at most [`MAX_CHUNK_BYTES`]
+fn chunks(text: &str) -> impl Iterator<Item = &str> {
+    let mut rest = text;
+    std::iter::from_fn(move || {
+        if rest.is_empty() {
+            return None;
+        }
+        let mut end = rest.len().min(MAX_CHUNK_BYTES);
+        if end < rest.len() {
+            while !rest.is_char_boundary(end) {
+                end -= 1;
+            }
+            if let Some(space) = rest[..end].rfind(char::is_whitespace) {
+                if space > 0 {
+                    end = space;
+                }
+            }
+        }
+        let (chunk, tail) = rest.split_at(end);
+        rest = tail;
+        Some(chunk)
+    })
+}
+
+/// A BPE count scaled to a provider whose tokenizer is not published
+pub struct ScaledTokenizer {
+    name: &'static str,
+    base: &'static BpeTokenizer,
+    ratio: f64,
+}
+
+impl Tokenizer for ScaledTokenizer {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn count(&self, text: &str) -> usize {
+        (self.base.count(text) as f64 * self.ratio).round() as usize
+    }
+}
+
+pub static CL100K: BpeTokenizer = BpeTokenizer {
+    name: "cl100k_base",
+    bpe: tiktoken_rs::cl100k_base_singleton,
+};
+
+pub static O200K: BpeTokenizer = BpeTokenizer {
+    name: "o200k_base",
+    bpe: tiktoken_rs::o200k_base_singleton,
+};
+
+/// Claude's tokenizer splits English and code into roughly 15% more tokens than cl100k
+pub static CLAUDE: ScaledTokenizer = ScaledTokenizer {
+    name: "claude (estimated)",
+    base: &CL100K,
+    ratio: 1.15,
+};
+
+/// Gemini's SentencePiece vocabulary lands close to o200k, slightly above it
+pub static GEMINI: ScaledTokenizer = ScaledTokenizer {
+    name: "gemini (estimated)",
+    base: &O200K,
+    ratio: 1.05,
+};
+
+/// The uncalibrated tokenizer for a model. Open-weight models (Llama, Qwen,
+/// DeepSeek, GLM) use 100k-150k vocabularies, which o200k approximates best.
+pub fn base_for_model(model: &str) -> &'static dyn Tokenizer {
+    let model = model.to_lowercase();
+    let bare = model.rsplit('/').next().unwrap_or(&model);
+    if bare.contains("claude") {
+        &CLAUDE
+    } else if bare.contains("gemini") || bare.contains("gemma") {
+        &GEMINI
+    } else if ["gpt-4", "gpt-3.5", "text-embedding"]
+        .iter()
+        .any(|p| bare.starts_with(p))
+        && !["gpt-4o", "gpt-4.1", "gpt-4.5"]
+            .iter()
+            .any(|p| bare.starts_with(p))
+    {
+        &CL100K
+    } else {
+        &O200K
+    }
+}
+
+/// A model's tokenizer with its learned calibration applied
+pub struct ModelTokenizer {
+    model: String,
+    base: &'static dyn Tokenizer,
+}
+
+impl Tokenizer for ModelTokenizer {
+    fn name(&self) -> &str {
+        self.base.name()
+    }
+
+    fn count(&self, text: &str) -> usize {
+        (self.base.count(text) as f64 * calibration(&self.model)).round() as usize
+    }
+}
+
+/// The calibrated tokenizer to use for `model`
+pub fn for_model(model: &str) -> ModelTokenizer {
+    ModelTokenizer {
+        model: model.to_string(),
+        base: base_for_model(model),
+    }
+}
+
+/// Current calibration factor for a model (1.0 until usage has been seen)
+pub fn calibration(model: &str) -> f64 {
+    CALIBRATION
+        .lock()
+        .ok()
+        .and_then(|factors| factors.get(model).copied())
+        .unwrap_or(1.0)
+}
+
+/// Learn from a provider-reported prompt size. `estimated` must come from
+/// [`base_for_model`], i.e. without calibration applied.
+pub fn calibrate(model: &str, estimated: usize, reported: u64) {
+    if estimated == 0 || reported == 0 {
+        return;
+    }
+    let observed =
+        (reported as f64 / estimated as f64).clamp(CALIBRATION_RANGE.0, CALIBRATION_RANGE.1);
+    if let Ok(mut factors) = CALIBRATION.lock() {
+        factors
+            .entry(model.to_string())
+            .and_modify(|factor| {
+                *factor = *factor * (1.0 - CALIBRATION_ALPHA) + observed * CALIBRATION_ALPHA
+            })
+            .or_insert(observed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bpe_counts() {
+        assert_eq!(CL100K.count(""), 0);
+        assert_eq!(CL100K.count("hello world"), 2);
+        assert_eq!(O200K.count("hello world"), 2);
+
+        // CJK text is far denser than chars/4 suggests
+        let cjk = "你好世界，这是一个测试";
+        assert!(O200K.count(cjk) > cjk.chars().count() / 4);
+    }
+
+    #[test]
+    fn test_chunks() {
+        let long = "x".repeat(5000);
+        assert!(chunks(&long).all(|c| c.len() <= MAX_CHUNK_BYTES));
+        assert_eq!(chunks(&long).collect::<String>(), long);
+
+        // Cuts keep multi-byte characters and words intact
+        let mixed = "世界 word ".repeat(300);
+        let pieces: Vec<&str> = chunks(&mixed).collect();
+        assert!(pieces.len() > 1);
+        assert!(pieces[1..].iter().all(|c| c.starts_with(' ')));
+        assert_eq!(pieces.concat(), mixed);
+        assert_eq!(chunks("").count(), 0);
+    }
+
+    #[test]
+    fn test_base_for_model() {
+        assert_eq!(base_for_model("gpt-4o-mini").name(), "o200k_base");
+        assert_eq!(base_for_model("gpt-4-turbo").name(), "cl100k_base");
+        assert_eq!(base_for_model("gpt-5").name(), "o200k_base");
+        assert_eq!(
+            base_for_model("anthropic/claude-sonnet-4").name(),
+            "claude (estimated)"
+        );
+        assert_eq!(
+            base_for_model("gemini-2.5-pro").name(),
+            "gemini (estimated)"
+        );
+        assert_eq!(base_for_model("qwen3:8b").name(), "o200k_base");
+    }
+
+    #[test]
+    fn test_scaled_tokenizer() {
+        let text = "fn main() { println!(\"hello\"); }";
+        let expected = (CL100K.count(text) as f64 * 1.15).round() as usize;
+        assert_eq!(CLAUDE.count(text), expected);
+    }
+
+    #[test]
+    fn test_calibration() {
+        let model = "calibration-test-model";
+        assert_eq!(calibration(model), 1.0);
+
+        calibrate(model, 100, 120);
+        assert!((calibration(model) - 1.2).abs() < 1e-9);
+
+        // Moving average toward the new observation
+        calibrate(model, 100, 100);
+        assert!((calibration(model) - 1.14).abs() < 1e-9);
+
+        // Outliers are clamped, empty observations ignored
+        calibrate(model, 10, 1_000);
+        assert!(calibration(model) <= 2.0);
+        let before = calibration(model);
+        calibrate(model, 0, 50);
+        assert_eq!(calibration(model), before);
+
+        let text = "hello world";
+        let tokenizer = for_model(model);
+        assert_eq!(
+            tokenizer.count(text),
+            (O200K.count(text) as f64 * before).round() as usize
+        );
+    }
+}
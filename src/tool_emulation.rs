@@ -0,0 +1,803 @@
+//! Emulated Tool Calling - Tools for models without native function calling.
+//!
+//! Many local models (Ollama, llama.cpp) reject or ignore `tools`. For those,
+//! tool schemas are rendered into the system prompt, calls are parsed out of
+//! the reply text and results go back as user-turn text:
+//!
+//! - Calls are `<invoke>` blocks, the format [`ToolInterceptor`] parses for
+//!   the Claude Code proxy, or JSON in a ```` ```json ```` fence or a
+//!   `<tool_call>` tag, which many open models are trained to emit
+//! - Earlier calls and results in the transcript are rendered as the same text
+//!
+//! [`EmulatedToolsAdapter`] wraps a provider adapter so everything else sees
+//! ordinary tool calls, and routes use it for models the registry marks as
+//! lacking tool support (see [`crate::models::emulates_tools`]).
+
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+use uuid::Uuid;
+
+use crate::config::ThinkingConfig;
+use crate::models::ListedModel;
+use crate::providers::{ProviderAdapter, ProviderError};
+use crate::proxy::{ChatCompletionRequest, ChatMessage, MessageContent};
+use crate::stream::{StopReason, StreamDecoder, StreamEvent, TokenUsage};
+use crate::tool_intercept::{format_tool_results_xml, ToolInterceptor};
+
+/// Parameter schemas of the tools last offered to an emulating model, by
+/// tool name. Parsed parameter values are typed with them, since `<invoke>`
+/// parameters are plain text.
+static SCHEMAS: LazyLock<Mutex<HashMap<String, Value>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Openings of a tool call block in reply text, with their closing tags
+const BLOCKS: [(&str, &str); 5] = [
+    ("<function_calls>", "</function_calls>"),
+    ("<invoke name=\"", "</invoke>"),
+    ("<tool_call>", "</tool_call>"),
+    ("```json", "```"),
+    ("```tool_call", "```"),
+];
+
+/// Instructions placed before the tool list in the system prompt
+const PROTOCOL: &str = "# Tools
+
+You can call the tools listed below. To call tools, reply with a block in exactly \
+this format, then stop and wait; the results arrive in the next user message:
+
+<function_calls>
+<invoke name=\"TOOL_NAME\">
+<parameter name=\"PARAMETER_NAME\">VALUE</parameter>
+</invoke>
+</function_calls>
+
+Put one <invoke> per call inside the block. Write string values as-is and all \
+other values (numbers, booleans, arrays, objects) as JSON. Never describe a call \
+instead of making it, and never write the results yourself.
+
+## Available tools
+";
+
+/// Render tool definitions (OpenAI format) as system prompt text
+pub fn render_tools_prompt(tools: &[Value]) -> String {
+    let mut prompt = PROTOCOL.to_string();
+    for tool in tools {
+        let func = tool.get("function").unwrap_or(tool);
+        let Some(name) = func["name"].as_str() else {
+            continue;
+        };
+        prompt.push_str(&format!("\n### {}\n", name));
+        if let Some(description) = func["description"].as_str() {
+            prompt.push_str(description.trim());
+            prompt.push('\n');
+        }
+        if let Some(parameters) = func.get("parameters") {
+            prompt.push_str(&format!("Parameters (JSON Schema): {}\n", parameters));
+        }
+    }
+    prompt
+}
+
+/// Render tool calls (OpenAI format) as the `<invoke>` text a model would write
+fn render_calls(calls: &[Value]) -> String {
+    let mut text = String::from("<function_calls>\n");
+    for call in calls {
+        let func = &call["function"];
+        text.push_str(&format!(
+            "<invoke name=\"{}\">\n",
+            func["name"].as_str().unwrap_or_default()
+        ));
+        let arguments: Value = func["arguments"]
+            .as_str()
+            .and_then(|a| serde_json::from_str(a).ok())
+            .unwrap_or_else(|| json!({}));
+        for (key, value) in arguments.as_object().into_iter().flatten() {
+            let value = match value {
+                Value::String(s) => s.clone(),
+                other => other.to_string(),
+            };
+            text.push_str(&format!(
+                "<parameter name=\"{}\">{}</parameter>\n",
+                key, value
+            ));
+        }
+        text.push_str("</invoke>\n");
+    }
+    text.push_str("</function_calls>");
+    text
+}
+
+/// Rewrite a request for a model without native tools: the tool list moves
+/// into the system prompt, and tool calls and results in the history become
+/// assistant and user text. Requests without tools are returned unchanged.
+pub fn emulate_request(request: &ChatCompletionRequest) -> ChatCompletionRequest {
+    let tools = request.tools.clone().unwrap_or_default();
+    let has_history = request
+        .messages
+        .iter()
+        .any(|m| m.role == "tool" || m.tool_calls.is_some());
+    if tools.is_empty() && !has_history {
+        return request.clone();
+    }
+
+    if !tools.is_empty() {
+        let mut schemas = SCHEMAS.lock().unwrap_or_else(|e| e.into_inner());
+        for tool in &tools {
+            let func = tool.get("function").unwrap_or(tool);
+            if let Some(name) = func["name"].as_str() {
+                schemas.insert(name.to_string(), func["parameters"].clone());
+            }
+        }
+    }
+
+    let text_message = |role: &str, content: String| ChatMessage {
+        role: role.to_string(),
+        content: MessageContent::Text(content),
+        name: None,
+        tool_calls: None,
+        tool_call_id: None,
+    };
+    let mut messages: Vec<ChatMessage> = Vec::new();
+    let mut results: Vec<(String, String, bool)> = Vec::new();
+    for m in &request.messages {
+        if m.role == "tool" {
+            let id = m.tool_call_id.clone().unwrap_or_default();
+            results.push((id, m.content.as_text(), false));
+            continue;
+        }
+        if !results.is_empty() {
+            let xml = format_tool_results_xml(&std::mem::take(&mut results));
+            messages.push(text_message("user", xml));
+        }
+        match m.tool_calls.as_deref() {
+            Some(calls) if !calls.is_empty() => {
+                let text = m.content.as_text();
+                let calls = render_calls(calls);
+                let content = if text.trim().is_empty() {
+                    calls
+                } else {
+                    format!("{}\n\n{}", text.trim_end(), calls)
+                };
+                messages.push(text_message("assistant", content));
+            }
+            _ => messages.push(m.clone()),
+        }
+    }
+    if !results.is_empty() {
+        messages.push(text_message("user", format_tool_results_xml(&results)));
+    }
+
+    if !tools.is_empty() {
+        let prompt = render_tools_prompt(&tools);
+        match messages.iter_mut().find(|m| m.role == "system") {
+            Some(system) => {
+                let text = system.content.as_text();
+                system.content = MessageContent::Text(format!("{}\n\n{}", text, prompt));
+            }
+            None => messages.insert(0, text_message("system", prompt)),
+        }
+    }
+
+    ChatCompletionRequest {
+        messages,
+        tools: None,
+        tool_choice: None,
+        ..request.clone()
+    }
+}
+
+/// A parsed tool call
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextToolCall {
+    pub name: String,
+    pub arguments: Value,
+}
+
+/// A piece of reply text: plain text or a tool call found in it
+#[derive(Debug, Clone, PartialEq)]
+enum Piece {
+    Text(String),
+    Call(TextToolCall),
+}
+
+/// Type a parameter value by the tool's schema. Strings, and parameters the
+/// schema does not describe, stay text; anything else is read as JSON.
+fn typed_value(tool: &str, parameter: &str, value: String) -> Value {
+    let schemas = SCHEMAS.lock().unwrap_or_else(|e| e.into_inner());
+    let kind = schemas
+        .get(tool)
+        .and_then(|schema| schema["properties"][parameter]["type"].as_str())
+        .unwrap_or("string");
+    if kind == "string" {
+        return Value::String(value);
+    }
+    serde_json::from_str(value.trim()).unwrap_or(Value::String(value))
+}
+
+/// Calls in `<invoke>` blocks, parsed by the proxy's interceptor
+fn parse_invoke_block(block: &str) -> Vec<TextToolCall> {
+    let mut interceptor = ToolInterceptor::new();
+    interceptor.push(block);
+    let mut calls = Vec::new();
+    loop {
+        let (intercepted, _, _) = interceptor.extract_tool_calls();
+        if intercepted.is_empty() {
+            break;
+        }
+        for call in intercepted {
+            let mut parameters: Vec<_> = call.parameters.into_iter().collect();
+            parameters.sort();
+            let arguments: serde_json::Map<String, Value> = parameters
+                .into_iter()
+                .map(|(key, value)| {
+                    let value = typed_value(&call.name, &key, value.trim().to_string());
+                    (key, value)
+                })
+                .collect();
+            calls.push(TextToolCall {
+                name: call.name,
+                arguments: Value::Object(arguments),
+            });
+        }
+    }
+    calls
+}
+
+/// Calls written as JSON: `{"name": ..., "arguments": {...}}` or an array of
+/// them. In code fences, which may hold ordinary JSON, only offered tools count.
+fn parse_json_block(body: &str, fenced: bool) -> Vec<TextToolCall> {
+    let Ok(value) = serde_json::from_str::<Value>(body.trim()) else {
+        return Vec::new();
+    };
+    let items = match value {
+        Value::Array(items) => items,
+        other => vec![other],
+    };
+    let schemas = SCHEMAS.lock().unwrap_or_else(|e| e.into_inner());
+    let mut calls = Vec::new();
+    for item in items {
+        let Some(name) = item["name"].as_str().or_else(|| item["tool"].as_str()) else {
+            return Vec::new();
+        };
+        let arguments = ["arguments", "parameters", "input"]
+            .iter()
+            .find_map(|key| item.get(*key))
+            .cloned();
+        // Some models send arguments as a JSON string, like the OpenAI API
+        let arguments = match arguments {
+            Some(Value::String(s)) => serde_json::from_str(&s).ok(),
+            other => other,
+        };
+        match arguments {
+            Some(arguments @ Value::Object(_)) if !fenced || schemas.contains_key(name) => {
+                calls.push(TextToolCall {
+                    name: name.to_string(),
+                    arguments,
+                });
+            }
+            _ => return Vec::new(),
+        }
+    }
+    calls
+}
+
+fn parse_block(open: &str, close: &str, block: &str) -> Vec<TextToolCall> {
+    if open.starts_with("```") || open == "<tool_call>" {
+        let body = block[open.len()..]
+            .strip_suffix(close)
+            .unwrap_or(&block[open.len()..]);
+        parse_json_block(body, open.starts_with("```"))
+    } else {
+        parse_invoke_block(block)
+    }
+}
+
+/// Splits streamed reply text into plain text and tool calls, holding back
+/// text that may be the start of a call block
+#[derive(Debug, Default)]
+struct TextToolParser {
+    pending: String,
+}
+
+impl TextToolParser {
+    fn push(&mut self, text: &str) -> Vec<Piece> {
+        self.pending.push_str(text);
+        self.drain(false)
+    }
+
+    fn finish(&mut self) -> Vec<Piece> {
+        self.drain(true)
+    }
+
+    /// Length of a suffix of the pending text that could begin a block
+    fn partial_opening(&self) -> usize {
+        BLOCKS
+            .iter()
+            .flat_map(|(open, _)| (1..open.len()).map(|n| &open[..n]))
+            .filter(|prefix| self.pending.ends_with(prefix))
+            .map(str::len)
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn drain(&mut self, at_end: bool) -> Vec<Piece> {
+        let mut out = Vec::new();
+        loop {
+            let found = BLOCKS
+                .iter()
+                .filter_map(|(open, close)| Some((self.pending.find(open)?, *open, *close)))
+                .min_by_key(|(start, _, _)| *start);
+            let Some((start, open, close)) = found else {
+                let keep = if at_end { 0 } else { self.partial_opening() };
+                let text: String = self.pending.drain(..self.pending.len() - keep).collect();
+                if !text.is_empty() {
+                    out.push(Piece::Text(text));
+                }
+                break;
+            };
+            if start > 0 {
+                out.push(Piece::Text(self.pending.drain(..start).collect()));
+            }
+
+            let end = self.pending[open.len()..]
+                .find(close)
+                .map(|i| open.len() + i + close.len());
+            let block: String = match end {
+                Some(end) => self.pending.drain(..end).collect(),
+                // A reply cut off inside a block (at a stop sequence or the
+                // token limit) keeps the calls that are complete
+                None if at_end => std::mem::take(&mut self.pending),
+                None => break,
+            };
+            let calls = parse_block(open, close, &block);
+            if calls.is_empty() {
+                out.push(Piece::Text(block));
+            } else {
+                out.extend(calls.into_iter().map(Piece::Call));
+            }
+        }
+        out
+    }
+}
+
+/// Split a complete reply into its text before any tool call and the calls
+pub fn parse_tool_calls(text: &str) -> (String, Vec<TextToolCall>) {
+    let mut parser = TextToolParser::default();
+    let mut pieces = parser.push(text);
+    pieces.extend(parser.finish());
+
+    let mut content = String::new();
+    let mut calls = Vec::new();
+    for piece in pieces {
+        match piece {
+            Piece::Text(text) if calls.is_empty() => content.push_str(&text),
+            Piece::Text(_) => {}
+            Piece::Call(call) => calls.push(call),
+        }
+    }
+    (content.trim_end().to_string(), calls)
+}
+
+fn call_id() -> String {
+    format!("call_{}", &Uuid::new_v4().simple().to_string()[..24])
+}
+
+/// Stream decoder turning tool calls in a model's text into tool call events.
+/// Text after the first call is dropped: the model should have stopped there,
+/// and whatever follows is usually an imagined result.
+pub struct EmulatedToolsDecoder {
+    inner: Box<dyn StreamDecoder>,
+    parser: TextToolParser,
+    calls: usize,
+}
+
+impl EmulatedToolsDecoder {
+    pub fn new(inner: Box<dyn StreamDecoder>) -> Self {
+        Self {
+            inner,
+            parser: TextToolParser::default(),
+            calls: 0,
+        }
+    }
+
+    fn emit(&mut self, pieces: Vec<Piece>, out: &mut Vec<StreamEvent>) {
+        for piece in pieces {
+            match piece {
+                Piece::Text(text) if self.calls == 0 => out.push(StreamEvent::TextDelta(text)),
+                Piece::Text(_) => {}
+                Piece::Call(call) => {
+                    let index = self.calls;
+                    self.calls += 1;
+                    out.push(StreamEvent::ToolCallStart {
+                        index,
+                        id: call_id(),
+                        name: call.name,
+                    });
+                    out.push(StreamEvent::ToolCallDelta {
+                        index,
+                        arguments: call.arguments.to_string(),
+                    });
+                    out.push(StreamEvent::ToolCallEnd { index });
+                }
+            }
+        }
+    }
+
+    fn translate(&mut self, events: Vec<StreamEvent>) -> Vec<StreamEvent> {
+        let mut out = Vec::new();
+        for event in events {
+            match event {
+                StreamEvent::TextDelta(text) => {
+                    let pieces = self.parser.push(&text);
+                    self.emit(pieces, &mut out);
+                }
+                StreamEvent::Stop(reason) => {
+                    let pieces = self.parser.finish();
+                    self.emit(pieces, &mut out);
+                    let reason = match reason {
+                        StopReason::EndTurn if self.calls > 0 => StopReason::ToolUse,
+                        other => other,
+                    };
+                    out.push(StreamEvent::Stop(reason));
+                }
+                other => out.push(other),
+            }
+        }
+        out
+    }
+}
+
+impl StreamDecoder for EmulatedToolsDecoder {
+    fn decode(&mut self, chunk: &[u8]) -> Vec<StreamEvent> {
+        let events = self.inner.decode(chunk);
+        self.translate(events)
+    }
+
+    fn finish(&mut self) -> Vec<StreamEvent> {
+        let events = self.inner.finish();
+        let mut out = self.translate(events);
+        let pieces = self.parser.finish();
+        self.emit(pieces, &mut out);
+        out
+    }
+}
+
+/// An adapter that gives a model without native function calling emulated
+/// tools. Requests are rewritten by [`emulate_request`] before the wrapped
+/// adapter sees them, and replies are parsed for calls after it.
+pub struct EmulatedToolsAdapter {
+    inner: Box<dyn ProviderAdapter>,
+}
+
+impl EmulatedToolsAdapter {
+    pub fn new(inner: Box<dyn ProviderAdapter>) -> Self {
+        Self { inner }
+    }
+}
+
+#[async_trait]
+impl ProviderAdapter for EmulatedToolsAdapter {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn transform_request(&self, request: &ChatCompletionRequest) -> Result<Value, ProviderError> {
+        self.inner.transform_request(&emulate_request(request))
+    }
+
+    fn transform_request_with_thinking(
+        &self,
+        request: &ChatCompletionRequest,
+        thinking: &ThinkingConfig,
+    ) -> Result<Value, ProviderError> {
+        self.inner
+            .transform_request_with_thinking(&emulate_request(request), thinking)
+    }
+
+    fn transform_response(&self, response: Value, stream: bool) -> Result<Value, ProviderError> {
+        let mut response = self.inner.transform_response(response, stream)?;
+        let message = &mut response["choices"][0]["message"];
+        let Some(text) = message["content"].as_str() else {
+            return Ok(response);
+        };
+        let (content, calls) = parse_tool_calls(text);
+        if calls.is_empty() {
+            return Ok(response);
+        }
+
+        message["content"] = if content.is_empty() {
+            Value::Null
+        } else {
+            json!(content)
+        };
+        message["tool_calls"] = calls
+            .into_iter()
+            .map(|call| {
+                json!({
+                    "id": call_id(),
+                    "type": "function",
+                    "function": {"name": call.name, "arguments": call.arguments.to_string()}
+                })
+            })
+            .collect();
+        response["choices"][0]["finish_reason"] = json!("tool_calls");
+        Ok(response)
+    }
+
+    fn chat_endpoint(&self) -> &str {
+        self.inner.chat_endpoint()
+    }
+
+    fn get_headers(&self, api_key: &str) -> Vec<(String, String)> {
+        self.inner.get_headers(api_key)
+    }
+
+    fn chat_path(&self, model: &str, stream: bool) -> String {
+        self.inner.chat_path(model, stream)
+    }
+
+    /// Replies always need parsing, even from OpenAI-compatible servers
+    fn openai_compatible(&self) -> bool {
+        false
+    }
+
+    fn stream_decoder(&self) -> Box<dyn StreamDecoder> {
+        Box::new(EmulatedToolsDecoder::new(self.inner.stream_decoder()))
+    }
+
+    fn response_usage(&self, response: &Value) -> Option<TokenUsage> {
+        self.inner.response_usage(response)
+    }
+
+    fn requires_api_key(&self) -> bool {
+        self.inner.requires_api_key()
+    }
+
+    async fn authorize(
+        &self,
+        client: &reqwest::Client,
+        request: reqwest::Request,
+    ) -> Result<reqwest::Request, ProviderError> {
+        self.inner.authorize(client, request).await
+    }
+
+    fn models_path(&self) -> &str {
+        self.inner.models_path()
+    }
+
+    fn parse_models(&self, response: &Value) -> Vec<ListedModel> {
+        self.inner.parse_models(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream::{OllamaDecoder, StreamAccumulator};
+
+    fn message(role: &str, content: &str) -> ChatMessage {
+        ChatMessage {
+            role: role.to_string(),
+            content: MessageContent::Text(content.to_string()),
+            name: None,
+            tool_calls: None,
+            tool_call_id: None,
+        }
+    }
+
+    fn request(messages: Vec<ChatMessage>, tools: Option<Vec<Value>>) -> ChatCompletionRequest {
+        ChatCompletionRequest {
+            model: "llama3.2".to_string(),
+            messages,
+            temperature: None,
+            max_tokens: None,
+            stream: None,
+            tools,
+            tool_choice: None,
+            response_format: None,
+            extra: HashMap::new(),
+        }
+    }
+
+    /// Tool names are unique to each test: offered schemas are process-wide
+    fn tool(name: &str) -> Value {
+        json!({
+            "type": "function",
+            "function": {
+                "name": name,
+                "description": "Run a shell command",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "command": {"type": "string"},
+                        "timeout": {"type": "number"}
+                    },
+                    "required": ["command"]
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn test_emulate_request() {
+        let mut assistant = message("assistant", "");
+        assistant.tool_calls = Some(vec![json!({
+            "id": "call_1",
+            "type": "function",
+            "function": {"name": "EmuRead", "arguments": "{\"path\":\"a.rs\",\"limit\":10}"}
+        })]);
+        let mut result = message("tool", "fn main() {}");
+        result.tool_call_id = Some("call_1".to_string());
+        let original = request(
+            vec![
+                message("system", "You are helpful."),
+                message("user", "Show a.rs"),
+                assistant,
+                result,
+            ],
+            Some(vec![tool("EmuRead")]),
+        );
+
+        let emulated = emulate_request(&original);
+        assert!(emulated.tools.is_none());
+        assert_eq!(emulated.messages.len(), 4);
+
+        let system = emulated.messages[0].content.as_text();
+        assert!(system.starts_with("You are helpful.\n\n# Tools"));
+        assert!(system.contains("### EmuRead\nRun a shell command\n"));
+        assert!(system.contains("\"required\":[\"command\"]"));
+
+        let call = &emulated.messages[2];
+        assert!(call.tool_calls.is_none());
+        assert_eq!(
+            call.content.as_text(),
+            "<function_calls>\n<invoke name=\"EmuRead\">\n\
+             <parameter name=\"limit\">10</parameter>\n\
+             <parameter name=\"path\">a.rs</parameter>\n\
+             </invoke>\n</function_calls>"
+        );
+
+        let results = &emulated.messages[3];
+        assert_eq!(results.role, "user");
+        assert!(results.tool_call_id.is_none());
+        assert!(results.content.as_text().contains("fn main() {}"));
+
+        // Nothing to emulate
+        let plain = request(vec![message("user", "Hi")], None);
+        assert_eq!(emulate_request(&plain).messages.len(), 1);
+    }
+
+    #[test]
+    fn test_emulate_request_without_system_prompt() {
+        let emulated = emulate_request(&request(
+            vec![message("user", "Hi")],
+            Some(vec![tool("EmuNoSystem")]),
+        ));
+        assert_eq!(emulated.messages.len(), 2);
+        assert_eq!(emulated.messages[0].role, "system");
+        assert!(emulated.messages[0]
+            .content
+            .as_text()
+            .contains("### EmuNoSystem"));
+    }
+
+    #[test]
+    fn test_parse_tool_calls() {
+        emulate_request(&request(vec![], Some(vec![tool("EmuParse")])));
+
+        let (text, calls) = parse_tool_calls(
+            "Sure.\n<function_calls>\n<invoke name=\"EmuParse\">\n\
+             <parameter name=\"command\">echo 5</parameter>\n\
+             <parameter name=\"timeout\">5</parameter>\n</invoke>\n</function_calls>",
+        );
+        assert_eq!(text, "Sure.");
+        assert_eq!(calls.len(), 1);
+        // Typed by the schema: the string stays a string, the number is parsed
+        assert_eq!(
+            calls[0].arguments,
+            json!({"command": "echo 5", "timeout": 5})
+        );
+
+        let (text, calls) = parse_tool_calls(
+            "```json\n{\"name\": \"EmuParse\", \"arguments\": {\"command\": \"ls\"}}\n```",
+        );
+        assert_eq!(text, "");
+        assert_eq!(calls[0].name, "EmuParse");
+        assert_eq!(calls[0].arguments, json!({"command": "ls"}));
+
+        let (_, calls) = parse_tool_calls(
+            "<tool_call>\n{\"name\": \"EmuParse\", \"arguments\": \"{\\\"command\\\": \\\"pwd\\\"}\"}\n</tool_call>",
+        );
+        assert_eq!(calls[0].arguments, json!({"command": "pwd"}));
+
+        // Ordinary JSON in a fence is text, not a call
+        let reply = "Example:\n```json\n{\"name\": \"package\", \"arguments\": {}}\n```";
+        let (text, calls) = parse_tool_calls(reply);
+        assert!(calls.is_empty());
+        assert_eq!(text, reply);
+    }
+
+    #[test]
+    fn test_parser_holds_back_partial_markers() {
+        let mut parser = TextToolParser::default();
+        assert_eq!(
+            parser.push("Done <"),
+            vec![Piece::Text("Done ".to_string())]
+        );
+        assert_eq!(parser.push("b>"), vec![Piece::Text("<b>".to_string())]);
+        assert_eq!(parser.push("```"), vec![]);
+        assert_eq!(parser.finish(), vec![Piece::Text("```".to_string())]);
+    }
+
+    #[test]
+    fn test_emulated_tools_stream() {
+        emulate_request(&request(vec![], Some(vec![tool("EmuBash")])));
+
+        let mut decoder = EmulatedToolsDecoder::new(Box::new(OllamaDecoder::default()));
+        let body = include_str!("../tests/fixtures/streams/ollama_emulated_tools.ndjson");
+        let mut acc = StreamAccumulator::new();
+        for chunk in body.as_bytes().chunks(7) {
+            decoder.decode(chunk).iter().for_each(|e| acc.push(e));
+        }
+        decoder.finish().iter().for_each(|e| acc.push(e));
+
+        // The imagined result after the call is dropped
+        assert_eq!(acc.text, "Let me look at the files.\n");
+        let calls = acc.tool_calls.finalize();
+        assert_eq!(calls.len(), 1);
+        assert!(calls[0].id.starts_with("call_"));
+        assert_eq!(calls[0].function.name, "EmuBash");
+        assert_eq!(
+            calls[0].function.arguments,
+            "{\"command\":\"ls -la\",\"timeout\":5000}"
+        );
+        assert_eq!(acc.stop_reason, Some(StopReason::ToolUse));
+        assert_eq!(acc.usage.map(|u| u.input_tokens), Some(410));
+    }
+
+    #[test]
+    fn test_emulated_tools_adapter() {
+        let adapter = EmulatedToolsAdapter::new(crate::providers::get_adapter("ollama"));
+        assert!(!adapter.openai_compatible());
+
+        let body = adapter
+            .transform_request(&request(
+                vec![message("user", "List files")],
+                Some(vec![tool("EmuAdapter")]),
+            ))
+            .unwrap();
+        assert!(body.get("tools").is_none());
+        assert!(body["messages"][0]["content"]
+            .as_str()
+            .unwrap()
+            .contains("### EmuAdapter"));
+
+        let response = adapter
+            .transform_response(
+                json!({
+                    "model": "llama3.2",
+                    "message": {
+                        "role": "assistant",
+                        "content": "<invoke name=\"EmuAdapter\">\n<parameter name=\"command\">ls</parameter>\n</invoke>"
+                    },
+                    "done_reason": "stop",
+                    "done": true
+                }),
+                false,
+            )
+            .unwrap();
+        let choice = &response["choices"][0];
+        assert_eq!(choice["finish_reason"], "tool_calls");
+        assert!(choice["message"]["content"].is_null());
+        assert_eq!(
+            choice["message"]["tool_calls"][0]["function"]["name"],
+            "EmuAdapter"
+        );
+        assert_eq!(
+            choice["message"]["tool_calls"][0]["function"]["arguments"],
+            "{\"command\":\"ls\"}"
+        );
+    }
+}
@@ -35,6 +35,8 @@ impl InterceptedToolCall {
             "grep" => "grep",
             "webfetch" | "web_fetch" => "web_fetch",
             "websearch" | "web_search" => "web_search",
+            "todowrite" | "todo_write" => "todo_write",
+            "todoread" | "todo_read" => "todo_read",
             _ => &name_lower,
         };
 
@@ -310,29 +312,20 @@ impl ToolInterceptor {
             "bash" => {
                 parameters.insert("command".to_string(), content);
             }
-            "read" | "read_file" => {
-                if !parameters.contains_key("path") && !parameters.contains_key("file_path") {
-                    parameters.insert("path".to_string(), content);
-                }
+            "read" | "read_file"
+                if !parameters.contains_key("path") && !parameters.contains_key("file_path") =>
+            {
+                parameters.insert("path".to_string(), content);
             }
-            "write" | "write_file" => {
-                // Content is the file content, path should be in attributes
-                if !content.is_empty() {
-                    parameters.insert("content".to_string(), content);
-                }
+            // Content is the file content, path should be in attributes
+            "write" | "write_file" if !content.is_empty() => {
+                parameters.insert("content".to_string(), content);
             }
             "edit" | "edit_file" => {
                 // Content might be used for something, but usually params are in attributes
             }
-            "glob" => {
-                if !parameters.contains_key("pattern") {
-                    parameters.insert("pattern".to_string(), content);
-                }
-            }
-            "grep" => {
-                if !parameters.contains_key("pattern") {
-                    parameters.insert("pattern".to_string(), content);
-                }
+            "glob" | "grep" if !parameters.contains_key("pattern") => {
+                parameters.insert("pattern".to_string(), content);
             }
             _ => {}
         }
@@ -342,7 +335,7 @@ impl ToolInterceptor {
             parameters,
             id: format!(
                 "toolu_{}",
-                Uuid::new_v4().to_string().replace("-", "")[..24].to_string()
+                &Uuid::new_v4().to_string().replace("-", "")[..24]
             ),
         })
     }
@@ -415,7 +408,7 @@ impl ToolInterceptor {
             tools.push(InterceptedToolCall {
                 name: tool_name,
                 parameters,
-                id: format!("toolu_{}", Uuid::new_v4().to_string().replace("-", "")[..24].to_string()),
+                id: format!("toolu_{}", &Uuid::new_v4().to_string().replace("-", "")[..24]),
             });
 
             search_start = invoke_end + INVOKE_CLOSE.len();
@@ -12,8 +12,14 @@
 //! - memory_update: Update existing memory
 //! - core_memory_update: Update core memory sections
 //!
+//! Issue tracker tools (`issue_*`) are implemented in [`crate::issues`].
+//!
+use crate::attachments;
+use crate::browser;
 use crate::config::AppConfig;
+use crate::issues;
 use crate::memory::{MemoryDb, SECTION_PERSONA, SECTION_PROJECT_INFO, SECTION_USER_PREFS};
+use crate::network::NetworkPolicy;
 use crate::subagent;
 use crate::web::{self, WebConfig};
 use serde::{Deserialize, Serialize};
@@ -230,9 +236,54 @@ pub struct TodoItem {
     pub active_form: String,
 }
 
-/// Global todo list storage
-static TODO_LIST: std::sync::LazyLock<Mutex<Vec<TodoItem>>> =
-    std::sync::LazyLock::new(|| Mutex::new(Vec::new()));
+impl TodoItem {
+    pub fn is_completed(&self) -> bool {
+        self.status == "completed"
+    }
+}
+
+/// Scope used when no chat session or subagent has claimed the todo tools
+pub const DEFAULT_TODO_SCOPE: &str = "default";
+
+/// Todo lists keyed by scope (chat session ID or `agent:<id>` for subagents)
+static TODO_LISTS: std::sync::LazyLock<Mutex<HashMap<String, Vec<TodoItem>>>> =
+    std::sync::LazyLock::new(|| Mutex::new(HashMap::new()));
+
+thread_local! {
+    /// Todo scope for tool calls made on this thread
+    static TODO_SCOPE: std::cell::RefCell<String> =
+        std::cell::RefCell::new(DEFAULT_TODO_SCOPE.to_string());
+}
+
+fn current_todo_scope() -> String {
+    TODO_SCOPE.with(|scope| scope.borrow().clone())
+}
+
+/// Run `f` with todo tools reading and writing the list for `scope`
+///
+/// Tool execution is synchronous, so the scope holds for every tool call
+/// made inside `f` on this thread.
+pub fn with_todo_scope<R>(scope: &str, f: impl FnOnce() -> R) -> R {
+    let previous = TODO_SCOPE.with(|s| s.replace(scope.to_string()));
+    let result = f();
+    TODO_SCOPE.with(|s| *s.borrow_mut() = previous);
+    result
+}
+
+/// Run `f` against a todo list owned by the caller (e.g. a saved chat session)
+///
+/// The list is handed to the todo tools for the duration of `f` and any
+/// updates are written back to `todos` afterwards.
+pub fn with_owned_todos<R>(scope: &str, todos: &mut Vec<TodoItem>, f: impl FnOnce() -> R) -> R {
+    set_todo_list_for(scope, std::mem::take(todos));
+    let result = with_todo_scope(scope, f);
+    *todos = TODO_LISTS
+        .lock()
+        .ok()
+        .and_then(|mut lists| lists.remove(scope))
+        .unwrap_or_default();
+    result
+}
 
 /// Tool call from the model
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -256,6 +307,32 @@ pub struct ToolResult {
     pub tool_call_id: String,
     pub content: String,
     pub is_error: bool,
+    /// Image content part for vision-capable models (from `read_file`);
+    /// `content` describes it for the rest
+    pub image: Option<Value>,
+}
+
+impl ToolResult {
+    /// The `tool` message reporting this result. With `vision`, an image is
+    /// sent as a content part after the text.
+    pub fn to_message(&self, vision: bool) -> Value {
+        let content = match &self.image {
+            Some(image) if vision => json!([
+                {"type": "text", "text": self.content},
+                image
+            ]),
+            Some(_) => json!(format!(
+                "{}\n(The image itself is not shown: this model does not accept images.)",
+                self.content
+            )),
+            None => json!(self.content),
+        };
+        json!({
+            "role": "tool",
+            "tool_call_id": self.tool_call_id,
+            "content": content
+        })
+    }
 }
 
 /// Get all tool definitions for the API request (OpenAI function format)
@@ -320,7 +397,7 @@ pub fn get_tool_definitions() -> Value {
             "type": "function",
             "function": {
                 "name": "read_file",
-                "description": "Read the contents of a file. Returns the file content as text with line numbers.",
+                "description": "Read the contents of a file. Returns the file content as text with line numbers. Images (PNG, JPEG, GIF, WebP) are shown to you as images when the model supports them.",
                 "parameters": {
                     "type": "object",
                     "properties": {
@@ -408,7 +485,7 @@ pub fn get_tool_definitions() -> Value {
             "type": "function",
             "function": {
                 "name": "chainlink",
-                "description": "Task management tool for tracking issues and work. Commands: 'create \"title\" -p priority' (create issue), 'close ID' (close issue), 'comment ID \"text\"' (add comment), 'label ID label' (add label), 'list' (show open issues), 'show ID' (show issue details), 'subissue ID \"title\"' (create subissue), 'session start/end/work ID' (session management).",
+                "description": "Raw passthrough to the external chainlink CLI (only if installed; prefer the issue_* tools). Commands: 'create \"title\" -p priority' (create issue), 'close ID' (close issue), 'comment ID \"text\"' (add comment), 'label ID label' (add label), 'list' (show open issues), 'show ID' (show issue details), 'subissue ID \"title\"' (create subissue), 'session start/end/work ID' (session management).",
                 "parameters": {
                     "type": "object",
                     "properties": {
@@ -421,6 +498,193 @@ pub fn get_tool_definitions() -> Value {
                 }
             }
         },
+        {
+            "type": "function",
+            "function": {
+                "name": "issue_create",
+                "description": "Create an issue in the project's issue tracker. Issues persist across sessions. Create one before starting significant work.",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "title": {
+                            "type": "string",
+                            "description": "Short issue title"
+                        },
+                        "description": {
+                            "type": "string",
+                            "description": "Details, acceptance criteria, context"
+                        },
+                        "priority": {
+                            "type": "string",
+                            "enum": ["low", "medium", "high", "critical"],
+                            "description": "Priority (default: medium)"
+                        },
+                        "labels": {
+                            "type": "array",
+                            "items": { "type": "string" },
+                            "description": "Labels to attach (e.g., bug, feature)"
+                        },
+                        "parent": {
+                            "type": "integer",
+                            "description": "Parent issue ID to create this as a subissue"
+                        }
+                    },
+                    "required": ["title"]
+                }
+            }
+        },
+        {
+            "type": "function",
+            "function": {
+                "name": "issue_list",
+                "description": "List issues, highest priority first. Shows labels and open blockers.",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "status": {
+                            "type": "string",
+                            "enum": ["open", "closed", "all"],
+                            "description": "Which issues to list (default: open)"
+                        },
+                        "label": {
+                            "type": "string",
+                            "description": "Only list issues with this label"
+                        }
+                    },
+                    "required": []
+                }
+            }
+        },
+        {
+            "type": "function",
+            "function": {
+                "name": "issue_show",
+                "description": "Show an issue with its description, labels, blockers and comments.",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "id": {
+                            "type": "integer",
+                            "description": "Issue ID"
+                        }
+                    },
+                    "required": ["id"]
+                }
+            }
+        },
+        {
+            "type": "function",
+            "function": {
+                "name": "issue_comment",
+                "description": "Add a comment to an issue (progress notes, findings, test failures).",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "id": {
+                            "type": "integer",
+                            "description": "Issue ID"
+                        },
+                        "content": {
+                            "type": "string",
+                            "description": "Comment text"
+                        }
+                    },
+                    "required": ["id", "content"]
+                }
+            }
+        },
+        {
+            "type": "function",
+            "function": {
+                "name": "issue_close",
+                "description": "Close an issue when the work is complete.",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "id": {
+                            "type": "integer",
+                            "description": "Issue ID"
+                        },
+                        "comment": {
+                            "type": "string",
+                            "description": "Optional closing comment"
+                        }
+                    },
+                    "required": ["id"]
+                }
+            }
+        },
+        {
+            "type": "function",
+            "function": {
+                "name": "issue_reopen",
+                "description": "Reopen a closed issue (e.g., when a test shows the fix is incomplete).",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "id": {
+                            "type": "integer",
+                            "description": "Issue ID"
+                        },
+                        "comment": {
+                            "type": "string",
+                            "description": "Optional comment explaining why"
+                        }
+                    },
+                    "required": ["id"]
+                }
+            }
+        },
+        {
+            "type": "function",
+            "function": {
+                "name": "issue_label",
+                "description": "Add or remove a label on an issue.",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "id": {
+                            "type": "integer",
+                            "description": "Issue ID"
+                        },
+                        "label": {
+                            "type": "string",
+                            "description": "Label name"
+                        },
+                        "remove": {
+                            "type": "boolean",
+                            "description": "Remove the label instead of adding it (default: false)"
+                        }
+                    },
+                    "required": ["id", "label"]
+                }
+            }
+        },
+        {
+            "type": "function",
+            "function": {
+                "name": "issue_depend",
+                "description": "Mark an issue as blocked by another issue, or remove that dependency.",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "id": {
+                            "type": "integer",
+                            "description": "The blocked issue ID"
+                        },
+                        "blocked_by": {
+                            "type": "integer",
+                            "description": "The issue ID that must be finished first"
+                        },
+                        "remove": {
+                            "type": "boolean",
+                            "description": "Remove the dependency instead of adding it (default: false)"
+                        }
+                    },
+                    "required": ["id", "blocked_by"]
+                }
+            }
+        },
         {
             "type": "function",
             "function": {
@@ -442,7 +706,7 @@ pub fn get_tool_definitions() -> Value {
             "type": "function",
             "function": {
                 "name": "web_search",
-                "description": "Search the web and return relevant results. Uses DuckDuckGo by default (free, no API key). Falls back to Tavily or Brave API if configured. Returns titles, snippets, and URLs.",
+                "description": "Search the web and return relevant results. Uses the backend configured in config.yaml (SearXNG, DuckDuckGo, Tavily or Brave); DuckDuckGo is used by default. Returns titles, snippets, and URLs.",
                 "parameters": {
                     "type": "object",
                     "properties": {
@@ -453,6 +717,16 @@ pub fn get_tool_definitions() -> Value {
                         "limit": {
                             "type": "integer",
                             "description": "Maximum number of results to return (default: 5)"
+                        },
+                        "allowed_domains": {
+                            "type": "array",
+                            "items": {"type": "string"},
+                            "description": "Only include results from these domains (e.g. [\"docs.rs\"])"
+                        },
+                        "blocked_domains": {
+                            "type": "array",
+                            "items": {"type": "string"},
+                            "description": "Never include results from these domains"
                         }
                     },
                     "required": ["query"]
@@ -476,11 +750,61 @@ pub fn get_tool_definitions() -> Value {
                 }
             }
         },
+        {
+            "type": "function",
+            "function": {
+                "name": "browser",
+                "description": "Drive a persistent headless Chrome session to test web apps. The session survives across calls: navigate first, then click, type, wait for elements, evaluate JavaScript, read console logs, or inspect the accessibility tree. Requires the 'browser' feature to be enabled at build time.",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "action": {
+                            "type": "string",
+                            "enum": ["navigate", "click", "type", "wait_for", "evaluate", "content", "console", "accessibility_tree", "close"],
+                            "description": "What to do in the browser session"
+                        },
+                        "session": {
+                            "type": "string",
+                            "description": "Session name (default: 'default'). Use different names for independent browsers."
+                        },
+                        "url": {
+                            "type": "string",
+                            "description": "URL to load (navigate)"
+                        },
+                        "selector": {
+                            "type": "string",
+                            "description": "CSS selector of the target element (click, type, wait_for)"
+                        },
+                        "text": {
+                            "type": "string",
+                            "description": "Text to type into the element (type)"
+                        },
+                        "submit": {
+                            "type": "boolean",
+                            "description": "Press Enter after typing (type, default: false)"
+                        },
+                        "script": {
+                            "type": "string",
+                            "description": "JavaScript expression to evaluate; promises are awaited (evaluate)"
+                        },
+                        "timeout_ms": {
+                            "type": "integer",
+                            "description": "How long to wait for the selector in milliseconds (wait_for, default: 5000)"
+                        },
+                        "clear": {
+                            "type": "boolean",
+                            "description": "Clear captured console output after reading it (console, default: false)"
+                        }
+                    },
+                    "required": ["action"]
+                }
+            }
+        },
         {
             "type": "function",
             "function": {
                 "name": "todo_write",
-                "description": "Create and manage a structured task list. Use this as a fallback when chainlink is unavailable. Helps track progress and show the user what you're working on. Only one task should be 'in_progress' at a time.",
+                "description": "Create and manage a structured task list for the current session. Use issue tools for work that should persist across sessions. Helps track progress and show the user what you're working on. Only one task should be 'in_progress' at a time.",
                 "parameters": {
                     "type": "object",
                     "properties": {
@@ -719,6 +1043,23 @@ fn execute_kill_shell(args: &HashMap<String, Value>) -> (String, bool) {
     }
 }
 
+/// Read an image file for `read_file`: a description and the image part.
+/// None when the path is not an image.
+fn read_image_file(args: &HashMap<String, Value>) -> Option<Result<(String, Value), String>> {
+    let path = Path::new(args.get("path")?.as_str()?);
+    let media_type = attachments::media_type(path).filter(|m| m.starts_with("image/"))?;
+    Some(attachments::load(path).map(|part| {
+        let size = fs::metadata(path).map(|m| m.len()).unwrap_or_default();
+        let description = format!(
+            "Image file '{}' ({}, {} bytes)",
+            path.display(),
+            media_type,
+            size
+        );
+        (description, part)
+    }))
+}
+
 /// Read a file's contents
 fn execute_read_file(args: &HashMap<String, Value>) -> (String, bool) {
     let path = match args.get("path").and_then(|v| v.as_str()) {
@@ -904,11 +1245,14 @@ fn execute_list_files(args: &HashMap<String, Value>) -> (String, bool) {
 /// Execute chainlink command for task management
 /// Uses Git Bash on Windows (which has access to Windows PATH)
 fn execute_chainlink(args: &HashMap<String, Value>) -> (String, bool) {
-    let cmd_args = match args.get("args").and_then(|v| v.as_str()) {
-        Some(a) => a,
-        None => return ("Missing 'args' argument".to_string(), true),
-    };
+    match args.get("args").and_then(|v| v.as_str()) {
+        Some(cmd_args) => run_chainlink(cmd_args),
+        None => ("Missing 'args' argument".to_string(), true),
+    }
+}
 
+/// Run `chainlink <cmd_args>` through the shell. Arguments must already be quoted.
+pub(crate) fn run_chainlink(cmd_args: &str) -> (String, bool) {
     // Use Git Bash to run chainlink (same approach as execute_bash)
     #[cfg(windows)]
     let output = {
@@ -997,32 +1341,51 @@ impl ToolCallAccumulator {
             for tc in tool_calls {
                 let index = tc.get("index").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
 
-                // Ensure we have enough slots
-                while self.tool_calls.len() <= index {
-                    self.tool_calls.push(PartialToolCall::default());
-                }
-
-                let partial = &mut self.tool_calls[index];
-                partial.index = index;
-
-                if let Some(id) = tc.get("id").and_then(|v| v.as_str()) {
-                    partial.id = id.to_string();
-                }
                 if let Some(t) = tc.get("type").and_then(|v| v.as_str()) {
-                    partial.call_type = t.to_string();
-                }
-                if let Some(func) = tc.get("function") {
-                    if let Some(name) = func.get("name").and_then(|v| v.as_str()) {
-                        partial.function_name = name.to_string();
-                    }
-                    if let Some(args) = func.get("arguments").and_then(|v| v.as_str()) {
-                        partial.function_arguments.push_str(args);
-                    }
+                    self.slot(index).call_type = t.to_string();
                 }
+                let func = tc.get("function");
+                self.process_fragment(
+                    index,
+                    tc.get("id").and_then(|v| v.as_str()),
+                    func.and_then(|f| f.get("name")).and_then(|v| v.as_str()),
+                    func.and_then(|f| f.get("arguments"))
+                        .and_then(|v| v.as_str())
+                        .unwrap_or(""),
+                );
             }
         }
     }
 
+    /// Process a normalized tool call fragment from a provider stream parser
+    pub fn process_fragment(
+        &mut self,
+        index: usize,
+        id: Option<&str>,
+        name: Option<&str>,
+        arguments: &str,
+    ) {
+        let partial = self.slot(index);
+        if let Some(id) = id {
+            partial.id = id.to_string();
+        }
+        if let Some(name) = name {
+            partial.function_name = name.to_string();
+        }
+        partial.function_arguments.push_str(arguments);
+    }
+
+    fn slot(&mut self, index: usize) -> &mut PartialToolCall {
+        // Ensure we have enough slots
+        while self.tool_calls.len() <= index {
+            self.tool_calls.push(PartialToolCall::default());
+        }
+
+        let partial = &mut self.tool_calls[index];
+        partial.index = index;
+        partial
+    }
+
     /// Convert accumulated partials to complete tool calls
     pub fn finalize(&self) -> Vec<ToolCall> {
         self.tool_calls
@@ -1037,7 +1400,12 @@ impl ToolCallAccumulator {
                 },
                 function: FunctionCall {
                     name: tc.function_name.clone(),
-                    arguments: tc.function_arguments.clone(),
+                    // Providers stream no argument text at all for parameterless calls
+                    arguments: if tc.function_arguments.is_empty() {
+                        "{}".to_string()
+                    } else {
+                        tc.function_arguments.clone()
+                    },
                 },
             })
             .collect()
@@ -1180,17 +1548,28 @@ pub fn execute_tool_with_memory(tool_call: &ToolCall, memory_db: Option<&MemoryD
     let args: HashMap<String, Value> =
         serde_json::from_str(&tool_call.function.arguments).unwrap_or_default();
 
+    let mut image = None;
     let (content, is_error) = match tool_call.function.name.as_str() {
         // Standard tools
         "bash" => execute_bash(&args),
         "bash_output" => execute_bash_output(&args),
         "kill_shell" => execute_kill_shell(&args),
-        "read_file" => execute_read_file(&args),
+        "read_file" => match read_image_file(&args) {
+            Some(Ok((description, part))) => {
+                image = Some(part);
+                (description, false)
+            }
+            Some(Err(e)) => (e, true),
+            None => execute_read_file(&args),
+        },
         "write_file" => execute_write_file(&args),
         "edit_file" => execute_edit_file(&args),
         "list_files" => execute_list_files(&args),
         "chainlink" => execute_chainlink(&args),
 
+        // Issue tracker tools (native store works without stateful mode)
+        name if issues::is_issue_tool(name) => issues::execute_issue_tool(name, &args, memory_db),
+
         // Memory tools (require stateful mode)
         "memory_save" => {
             if let Some(db) = memory_db {
@@ -1237,8 +1616,9 @@ pub fn execute_tool_with_memory(tool_call: &ToolCall, memory_db: Option<&MemoryD
         "web_fetch" => execute_web_fetch(&args),
         "web_search" => execute_web_search(&args),
         "web_browser" => execute_web_browser(&args),
+        "browser" => browser::execute_browser_tool(&args, &NetworkPolicy::load()),
 
-        // Todo tools (fallback for chainlink)
+        // Todo tools (session-scoped task list)
         "todo_write" => execute_todo_write(&args),
         "todo_read" => execute_todo_read(),
 
@@ -1256,6 +1636,7 @@ pub fn execute_tool_with_memory(tool_call: &ToolCall, memory_db: Option<&MemoryD
         tool_call_id: tool_call.id.clone(),
         content,
         is_error,
+        image,
     }
 }
 
@@ -1292,6 +1673,7 @@ pub fn execute_tool_full(
         tool_call_id: tool_call.id.clone(),
         content,
         is_error,
+        image: None,
     }
 }
 
@@ -1414,16 +1796,19 @@ fn execute_web_fetch(args: &HashMap<String, Value>) -> (String, bool) {
         );
     }
 
+    // Private/loopback targets, domain lists and size limits come from config
+    let policy = NetworkPolicy::load();
+
     // Use tokio runtime to execute async function
     let result = match Handle::try_current() {
         Ok(handle) => {
             // We're in an async context, use block_in_place
-            tokio::task::block_in_place(|| handle.block_on(web::fetch_url(url)))
+            tokio::task::block_in_place(|| handle.block_on(web::fetch_url(url, &policy)))
         }
         Err(_) => {
             // Create a new runtime for sync context
             match tokio::runtime::Runtime::new() {
-                Ok(rt) => rt.block_on(web::fetch_url(url)),
+                Ok(rt) => rt.block_on(web::fetch_url(url, &policy)),
                 Err(e) => return (format!("Failed to create runtime: {}", e), true),
             }
         }
@@ -1453,7 +1838,7 @@ fn execute_web_fetch(args: &HashMap<String, Value>) -> (String, bool) {
     }
 }
 
-/// Search the web using Tavily or Brave API
+/// Search the web using the configured search backend
 fn execute_web_search(args: &HashMap<String, Value>) -> (String, bool) {
     let query = match args.get("query").and_then(|v| v.as_str()) {
         Some(q) => q,
@@ -1462,9 +1847,26 @@ fn execute_web_search(args: &HashMap<String, Value>) -> (String, bool) {
 
     let limit = args.get("limit").and_then(|v| v.as_u64()).unwrap_or(5) as usize;
 
-    // Load web config from environment
-    // Falls back to DuckDuckGo with headless browser if no API keys configured
-    let config = WebConfig::from_env();
+    // Load web config from config.yaml (env vars fill in missing API keys)
+    let mut config = WebConfig::load();
+
+    // Per-call domain filters add to the configured ones
+    let domain_list = |key: &str| -> Vec<String> {
+        args.get(key)
+            .and_then(|v| v.as_array())
+            .map(|arr| {
+                arr.iter()
+                    .filter_map(|d| d.as_str().map(String::from))
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+    config
+        .allowed_domains
+        .extend(domain_list("allowed_domains"));
+    config
+        .blocked_domains
+        .extend(domain_list("blocked_domains"));
 
     // Use tokio runtime to execute async function
     let result = match Handle::try_current() {
@@ -1499,7 +1901,7 @@ fn execute_web_browser(args: &HashMap<String, Value>) -> (String, bool) {
         );
     }
 
-    match web::fetch_with_browser(url) {
+    match web::fetch_with_browser(url, &NetworkPolicy::load()) {
         Ok(fetch_result) => {
             let mut output = String::new();
             if let Some(title) = fetch_result.title {
@@ -1587,10 +1989,10 @@ fn execute_todo_write(args: &HashMap<String, Value>) -> (String, bool) {
         String::new()
     };
 
-    // Update the global todo list
-    match TODO_LIST.lock() {
-        Ok(mut list) => {
-            *list = new_todos.clone();
+    // Update the todo list for the current scope
+    match TODO_LISTS.lock() {
+        Ok(mut lists) => {
+            lists.insert(current_todo_scope(), new_todos.clone());
         }
         Err(e) => return (format!("Failed to update todo list: {}", e), true),
     }
@@ -1622,8 +2024,11 @@ fn execute_todo_write(args: &HashMap<String, Value>) -> (String, bool) {
 
 /// Read the current todo list
 fn execute_todo_read() -> (String, bool) {
-    let todos = match TODO_LIST.lock() {
-        Ok(list) => list.clone(),
+    let todos = match TODO_LISTS.lock() {
+        Ok(lists) => lists
+            .get(&current_todo_scope())
+            .cloned()
+            .unwrap_or_default(),
         Err(e) => return (format!("Failed to read todo list: {}", e), true),
     };
 
@@ -1631,6 +2036,11 @@ fn execute_todo_read() -> (String, bool) {
         return ("No todos in list.".to_string(), false);
     }
 
+    (format_todo_list(&todos), false)
+}
+
+/// Format a todo list as a numbered checklist with a status summary
+pub fn format_todo_list(todos: &[TodoItem]) -> String {
     let mut output = String::new();
     for (i, todo) in todos.iter().enumerate() {
         let status_icon = match todo.status.as_str() {
@@ -1657,18 +2067,34 @@ fn execute_todo_read() -> (String, bool) {
         completed, in_progress, pending
     ));
 
-    (output, false)
+    output
 }
 
-/// Get the current todo list (for external use)
+/// Get the todo list for the current scope (for external use)
 pub fn get_todo_list() -> Vec<TodoItem> {
-    TODO_LIST.lock().map(|l| l.clone()).unwrap_or_default()
+    get_todo_list_for(&current_todo_scope())
 }
 
-/// Clear the todo list
+/// Clear the todo list for the current scope
 pub fn clear_todo_list() {
-    if let Ok(mut list) = TODO_LIST.lock() {
-        list.clear();
+    if let Ok(mut lists) = TODO_LISTS.lock() {
+        lists.remove(&current_todo_scope());
+    }
+}
+
+/// Get the todo list for a specific scope
+pub fn get_todo_list_for(scope: &str) -> Vec<TodoItem> {
+    TODO_LISTS
+        .lock()
+        .ok()
+        .and_then(|lists| lists.get(scope).cloned())
+        .unwrap_or_default()
+}
+
+/// Replace the todo list for a specific scope
+pub fn set_todo_list_for(scope: &str, todos: Vec<TodoItem>) {
+    if let Ok(mut lists) = TODO_LISTS.lock() {
+        lists.insert(scope.to_string(), todos);
     }
 }
 
@@ -1732,4 +2158,37 @@ mod tests {
         assert_eq!(calls[0].function.name, "bash");
         assert_eq!(calls[0].function.arguments, "{\"command\": \"ls\"}");
     }
+
+    #[test]
+    fn test_todo_scopes_are_isolated() {
+        let write = |content: &str| {
+            let mut args = HashMap::new();
+            args.insert(
+                "todos".to_string(),
+                json!([{"content": content, "status": "pending", "activeForm": content}]),
+            );
+            execute_todo_write(&args)
+        };
+
+        with_todo_scope("agent:test-a", || write("Task A"));
+        with_todo_scope("agent:test-b", || write("Task B"));
+
+        assert_eq!(get_todo_list_for("agent:test-a")[0].content, "Task A");
+        assert_eq!(get_todo_list_for("agent:test-b")[0].content, "Task B");
+        let read = with_todo_scope("agent:test-a", execute_todo_read).0;
+        assert!(read.contains("Task A") && !read.contains("Task B"));
+
+        // Owned lists are handed to the tools and written back
+        let mut session_todos = vec![TodoItem {
+            content: "Existing".to_string(),
+            status: "in_progress".to_string(),
+            active_form: "Doing existing".to_string(),
+        }];
+        let read = with_owned_todos("session-test", &mut session_todos, execute_todo_read).0;
+        assert!(read.contains("Existing"));
+        with_owned_todos("session-test", &mut session_todos, || write("Replaced"));
+        assert_eq!(session_todos.len(), 1);
+        assert_eq!(session_todos[0].content, "Replaced");
+        assert!(get_todo_list_for("session-test").is_empty());
+    }
 }
@@ -18,6 +18,8 @@ use ratatui::{
 };
 use std::io::{self, stdout, Write};
 
+use crate::tools::TodoItem;
+
 /// Purple color for branding (from logo)
 const PURPLE: Color = Color::Rgb(147, 112, 219);
 /// Gold color for accents (from logo)
@@ -186,6 +188,55 @@ pub fn render_input_prompt(mode: &str) -> io::Result<()> {
     Ok(())
 }
 
+/// One-line summary of the todo list, or None when nothing is left to do
+pub fn format_todo_status(todos: &[TodoItem]) -> Option<String> {
+    if todos.is_empty() || todos.iter().all(|t| t.is_completed()) {
+        return None;
+    }
+    let done = todos.iter().filter(|t| t.is_completed()).count();
+    let mut line = format!("Todos {}/{}", done, todos.len());
+    if let Some(active) = todos.iter().find(|t| t.status == "in_progress") {
+        line.push_str(&format!(" ▸ {}", active.active_form));
+    }
+    Some(line)
+}
+
+/// Render the todo status line above the input prompt
+pub fn render_todo_status(todos: &[TodoItem]) -> io::Result<()> {
+    let Some(line) = format_todo_status(todos) else {
+        return Ok(());
+    };
+    let mut stdout = io::stdout();
+
+    use crossterm::style::{ResetColor, SetForegroundColor};
+    stdout.execute(SetForegroundColor(crossterm::style::Color::Cyan))?;
+    writeln!(stdout, "  {}", line)?;
+    stdout.execute(ResetColor)?;
+    stdout.flush()
+}
+
+/// Render the full todo checklist after the list changes
+pub fn render_todo_panel(todos: &[TodoItem]) -> io::Result<()> {
+    if todos.is_empty() {
+        return Ok(());
+    }
+    let mut stdout = io::stdout();
+
+    use crossterm::style::{Color as TermColor, ResetColor, SetForegroundColor};
+    writeln!(stdout)?;
+    for todo in todos {
+        let (marker, color, text) = match todo.status.as_str() {
+            "completed" => ("✓", TermColor::DarkGrey, &todo.content),
+            "in_progress" => ("▸", TermColor::Yellow, &todo.active_form),
+            _ => ("○", TermColor::White, &todo.content),
+        };
+        stdout.execute(SetForegroundColor(color))?;
+        writeln!(stdout, "  {} {}", marker, text)?;
+    }
+    stdout.execute(ResetColor)?;
+    stdout.flush()
+}
+
 /// Get the current username from environment
 fn get_username() -> Option<String> {
     std::env::var("USER")
@@ -215,6 +266,27 @@ pub fn clear_screen() -> io::Result<()> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_format_todo_status() {
+        let todo = |content: &str, status: &str| TodoItem {
+            content: content.to_string(),
+            status: status.to_string(),
+            active_form: format!("{}ing", content),
+        };
+        assert_eq!(format_todo_status(&[]), None);
+        assert_eq!(format_todo_status(&[todo("Build", "completed")]), None);
+
+        let todos = vec![
+            todo("Build", "completed"),
+            todo("Test", "in_progress"),
+            todo("Ship", "pending"),
+        ];
+        assert_eq!(
+            format_todo_status(&todos).as_deref(),
+            Some("Todos 1/3 ▸ Testing")
+        );
+    }
+
     #[test]
     fn test_capitalize_first() {
         assert_eq!(capitalize_first("anthropic"), "Anthropic");
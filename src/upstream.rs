@@ -0,0 +1,511 @@
+//! Upstream Calls - Retries with backoff and provider fallback chains.
+//!
+//! Rate limits (429), overloads (529) and transient connection failures are
+//! retried with exponential backoff and jitter, honoring `retry-after`. When a
+//! provider stays unavailable, the next provider in `proxy.fallback` is tried
+//! with its mapped model. Used by the proxy, the chat loop and subagents.
+
+use std::time::Duration;
+
+use rand::Rng;
+use reqwest::header::HeaderMap;
+use reqwest::{RequestBuilder, Response, StatusCode};
+use thiserror::Error;
+use tracing::warn;
+
+use crate::config::{AppConfig, ProviderConfig, RetryConfig};
+use crate::models;
+use crate::providers::{adapter_for, ProviderAdapter};
+use crate::session::ServedTurn;
+use crate::tool_emulation::EmulatedToolsAdapter;
+
+/// Errors from an upstream call that produced no response at all
+#[derive(Error, Debug)]
+pub enum UpstreamError {
+    #[error("Failed to build request for {0}: {1}")]
+    Build(String, String),
+
+    #[error("Failed to authorize request for {0}: {1}")]
+    Auth(String, String),
+
+    #[error("Request failed: {0}")]
+    Transport(#[from] reqwest::Error),
+}
+
+/// One provider in a fallback chain and the model to request from it
+#[derive(Debug, Clone)]
+pub struct Route {
+    pub provider_name: String,
+    pub provider: ProviderConfig,
+    pub model: String,
+    /// False for the configured target, true for fallbacks
+    pub fallback: bool,
+}
+
+impl Route {
+    /// The provider's adapter, with emulated tools when the model has no
+    /// native function calling
+    pub fn adapter(&self) -> Box<dyn ProviderAdapter> {
+        let adapter = adapter_for(&self.provider_name, &self.provider);
+        if models::emulates_tools(&self.model) {
+            Box::new(EmulatedToolsAdapter::new(adapter))
+        } else {
+            adapter
+        }
+    }
+
+    pub fn served(&self) -> ServedTurn {
+        ServedTurn::new(&self.provider_name, &self.model, self.fallback)
+    }
+}
+
+/// A response and the route that produced it
+pub struct Served<'a> {
+    pub response: Response,
+    pub route: &'a Route,
+}
+
+/// The target provider followed by each configured, distinct fallback
+pub fn fallback_chain(
+    config: &AppConfig,
+    provider_name: &str,
+    provider: &ProviderConfig,
+    model: &str,
+) -> Vec<Route> {
+    let mut routes = vec![Route {
+        provider_name: provider_name.to_string(),
+        provider: provider.clone(),
+        model: model.to_string(),
+        fallback: false,
+    }];
+
+    for entry in &config.proxy.fallback {
+        if routes.iter().any(|r| r.provider_name == entry.provider) {
+            continue;
+        }
+        let Some(fallback) = config.get_provider(&entry.provider) else {
+            warn!(provider = %entry.provider, "Fallback provider is not configured, skipping");
+            continue;
+        };
+        let mapped = entry
+            .models
+            .get(model)
+            .or(entry.model.as_ref())
+            .or(fallback.model.as_ref())
+            .map(String::as_str)
+            .unwrap_or(model);
+        routes.push(Route {
+            provider_name: entry.provider.clone(),
+            provider: fallback.clone(),
+            model: mapped.to_string(),
+            fallback: true,
+        });
+    }
+    routes
+}
+
+/// Whether a status means "try again later" rather than "this request is wrong"
+pub fn is_retryable_status(status: StatusCode) -> bool {
+    matches!(status.as_u16(), 408 | 429 | 500 | 502 | 503 | 504 | 529)
+}
+
+fn is_retryable_error(error: &reqwest::Error) -> bool {
+    error.is_connect() || error.is_timeout()
+}
+
+/// Delay requested by the server via `retry-after-ms` or `retry-after`
+/// (seconds or an HTTP date)
+pub fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+
+    if let Some(ms) = header("retry-after-ms").and_then(parse_seconds) {
+        return Some(seconds(ms / 1000.0));
+    }
+    let value = header("retry-after")?.trim();
+    if let Some(secs) = parse_seconds(value) {
+        return Some(seconds(secs));
+    }
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    (date.with_timezone(&chrono::Utc) - chrono::Utc::now())
+        .to_std()
+        .ok()
+}
+
+/// A number of seconds, ignoring `NaN`
+fn parse_seconds(value: &str) -> Option<f64> {
+    value.trim().parse::<f64>().ok().filter(|v| !v.is_nan())
+}
+
+/// Seconds as a duration; values too large to represent (such as `inf`)
+/// saturate and are later capped at `max_backoff_ms`
+fn seconds(secs: f64) -> Duration {
+    Duration::try_from_secs_f64(secs.max(0.0)).unwrap_or(Duration::MAX)
+}
+
+/// Delay before retry number `attempt` (0-based). A server-provided delay wins;
+/// otherwise exponential backoff with jitter in the upper half of the window.
+pub fn backoff_delay(
+    retry: &RetryConfig,
+    attempt: u32,
+    server_delay: Option<Duration>,
+) -> Duration {
+    let max = Duration::from_millis(retry.max_backoff_ms);
+    if let Some(delay) = server_delay {
+        return delay.min(max);
+    }
+    let base = retry
+        .initial_backoff_ms
+        .saturating_mul(1u64 << attempt.min(20))
+        .min(retry.max_backoff_ms);
+    Duration::from_millis(rand::thread_rng().gen_range(base / 2..=base))
+}
+
+/// Send a request along the chain, retrying each route before moving to the next.
+///
+/// `build` creates a fresh request for a route on every attempt, which the
+/// route's adapter then authorizes (signing it, for Bedrock). The returned
+/// response may still be an error status: non-retryable failures (such as 400
+/// or 401) are returned as-is, as is the last failure once the chain runs out.
+pub async fn send_with_fallback<'a>(
+    routes: &'a [Route],
+    retry: &RetryConfig,
+    mut build: impl FnMut(&Route) -> Result<RequestBuilder, String>,
+) -> Result<Served<'a>, UpstreamError> {
+    for (i, route) in routes.iter().enumerate() {
+        let is_last = i + 1 == routes.len();
+        let mut attempt = 0;
+        loop {
+            let (client, request) = build(route)
+                .map_err(|e| UpstreamError::Build(route.provider_name.clone(), e))?
+                .build_split();
+            let request = route
+                .adapter()
+                .authorize(&client, request?)
+                .await
+                .map_err(|e| UpstreamError::Auth(route.provider_name.clone(), e.to_string()))?;
+            let can_retry = attempt < retry.max_retries;
+
+            let server_delay = match client.execute(request).await {
+                Ok(response) if !is_retryable_status(response.status()) => {
+                    return Ok(Served { response, route });
+                }
+                Ok(response) => {
+                    if !can_retry && is_last {
+                        return Ok(Served { response, route });
+                    }
+                    warn!(
+                        provider = %route.provider_name,
+                        status = %response.status(),
+                        attempt,
+                        "Upstream request failed"
+                    );
+                    retry_after(response.headers())
+                }
+                Err(e) if is_retryable_error(&e) => {
+                    if !can_retry && is_last {
+                        return Err(e.into());
+                    }
+                    warn!(
+                        provider = %route.provider_name,
+                        error = %e,
+                        attempt,
+                        "Upstream request failed"
+                    );
+                    None
+                }
+                Err(e) => return Err(e.into()),
+            };
+
+            if !can_retry {
+                warn!(
+                    from = %route.provider_name,
+                    to = %routes[i + 1].provider_name,
+                    "Provider unavailable, falling back"
+                );
+                break;
+            }
+            tokio::time::sleep(backoff_delay(retry, attempt, server_delay)).await;
+            attempt += 1;
+        }
+    }
+    unreachable!("send_with_fallback called with no routes")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{AwsConfig, FallbackProvider, VertexConfig};
+    use std::collections::HashMap;
+    use wiremock::matchers::{header, header_regex, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn provider(base_url: &str) -> ProviderConfig {
+        ProviderConfig {
+            api_key: None,
+            base_url: base_url.to_string(),
+            model: None,
+            headers: HashMap::new(),
+            thinking: Default::default(),
+            adapter: None,
+            aws: None,
+            vertex: None,
+            path: None,
+            query: HashMap::new(),
+            auth: None,
+            cache: Default::default(),
+        }
+    }
+
+    fn fast_retry(max_retries: u32) -> RetryConfig {
+        RetryConfig {
+            max_retries,
+            initial_backoff_ms: 1,
+            max_backoff_ms: 5,
+        }
+    }
+
+    fn route(name: &str, base_url: &str, fallback: bool) -> Route {
+        Route {
+            provider_name: name.to_string(),
+            provider: provider(base_url),
+            model: "m".to_string(),
+            fallback,
+        }
+    }
+
+    fn post(client: &reqwest::Client, route: &Route) -> Result<RequestBuilder, String> {
+        Ok(client.post(format!("{}/v1/chat", route.provider.base_url)))
+    }
+
+    #[test]
+    fn test_fallback_chain_model_mapping() {
+        let mut config: AppConfig = serde_json::from_value(serde_json::json!({
+            "proxy": {},
+            "providers": {
+                "anthropic": {"base_url": "https://api.anthropic.com"},
+                "openrouter": {"base_url": "https://openrouter.ai/api"},
+                "ollama": {"base_url": "http://localhost:11434", "model": "llama3"}
+            }
+        }))
+        .unwrap();
+        config.proxy.fallback = vec![
+            FallbackProvider {
+                provider: "openrouter".to_string(),
+                model: None,
+                models: HashMap::from([(
+                    "claude-sonnet-4".to_string(),
+                    "anthropic/claude-sonnet-4".to_string(),
+                )]),
+            },
+            FallbackProvider {
+                provider: "missing".to_string(),
+                model: None,
+                models: HashMap::new(),
+            },
+            FallbackProvider {
+                provider: "ollama".to_string(),
+                model: None,
+                models: HashMap::new(),
+            },
+        ];
+        let primary = config.get_provider("anthropic").unwrap().clone();
+
+        let routes = fallback_chain(&config, "anthropic", &primary, "claude-sonnet-4");
+        let names: Vec<_> = routes.iter().map(|r| r.provider_name.as_str()).collect();
+        assert_eq!(names, ["anthropic", "openrouter", "ollama"]);
+        assert!(!routes[0].fallback && routes[1].fallback);
+        assert_eq!(routes[1].model, "anthropic/claude-sonnet-4");
+        // No mapping: the provider's configured model
+        assert_eq!(routes[2].model, "llama3");
+    }
+
+    #[test]
+    fn test_retry_after_parsing() {
+        let mut headers = HeaderMap::new();
+        headers.insert("retry-after", "2".parse().unwrap());
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(2)));
+
+        headers.insert("retry-after-ms", "150".parse().unwrap());
+        assert_eq!(retry_after(&headers), Some(Duration::from_millis(150)));
+
+        let mut headers = HeaderMap::new();
+        let later = chrono::Utc::now() + chrono::Duration::seconds(30);
+        headers.insert("retry-after", later.to_rfc2822().parse().unwrap());
+        let delay = retry_after(&headers).unwrap();
+        assert!(delay > Duration::from_secs(25) && delay <= Duration::from_secs(30));
+
+        // Hostile values must not panic; huge ones are capped by the backoff limit
+        let retry = RetryConfig {
+            max_retries: 1,
+            initial_backoff_ms: 100,
+            max_backoff_ms: 1000,
+        };
+        for value in ["inf", "1e30", "-inf"] {
+            let mut headers = HeaderMap::new();
+            headers.insert("retry-after", value.parse().unwrap());
+            let delay = backoff_delay(&retry, 0, retry_after(&headers));
+            assert!(delay <= Duration::from_secs(1), "{}", value);
+        }
+        let mut headers = HeaderMap::new();
+        headers.insert("retry-after", "NaN".parse().unwrap());
+        assert_eq!(retry_after(&headers), None);
+        headers.insert("retry-after-ms", "1e300".parse().unwrap());
+        assert_eq!(retry_after(&headers), Some(Duration::MAX));
+    }
+
+    #[test]
+    fn test_backoff_delay_bounds() {
+        let retry = RetryConfig {
+            max_retries: 5,
+            initial_backoff_ms: 100,
+            max_backoff_ms: 1000,
+        };
+        for _ in 0..20 {
+            let first = backoff_delay(&retry, 0, None);
+            assert!(first >= Duration::from_millis(50) && first <= Duration::from_millis(100));
+            let capped = backoff_delay(&retry, 10, None);
+            assert!(capped >= Duration::from_millis(500) && capped <= Duration::from_millis(1000));
+        }
+        assert_eq!(
+            backoff_delay(&retry, 0, Some(Duration::from_secs(60))),
+            Duration::from_millis(1000)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_retries_then_succeeds() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/chat"))
+            .respond_with(ResponseTemplate::new(529))
+            .up_to_n_times(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/v1/chat"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+
+        let client = reqwest::Client::new();
+        let routes = [route("anthropic", &server.uri(), false)];
+        let served = send_with_fallback(&routes, &fast_retry(3), |r| post(&client, r))
+            .await
+            .unwrap();
+        assert_eq!(served.response.status(), 200);
+        assert_eq!(server.received_requests().await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_falls_back_after_retries() {
+        let primary = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(429).insert_header("retry-after", "0"))
+            .mount(&primary)
+            .await;
+        let secondary = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&secondary)
+            .await;
+
+        let client = reqwest::Client::new();
+        let routes = [
+            route("anthropic", &primary.uri(), false),
+            route("ollama", &secondary.uri(), true),
+        ];
+        let served = send_with_fallback(&routes, &fast_retry(1), |r| post(&client, r))
+            .await
+            .unwrap();
+        assert_eq!(served.route.provider_name, "ollama");
+        assert!(served.route.served().fallback);
+        assert_eq!(primary.received_requests().await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_client_errors_are_not_retried() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(400))
+            .mount(&server)
+            .await;
+
+        let client = reqwest::Client::new();
+        let routes = [
+            route("anthropic", &server.uri(), false),
+            route("ollama", &server.uri(), true),
+        ];
+        let served = send_with_fallback(&routes, &fast_retry(3), |r| post(&client, r))
+            .await
+            .unwrap();
+        assert_eq!(served.response.status(), 400);
+        assert_eq!(served.route.provider_name, "anthropic");
+        assert_eq!(server.received_requests().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_cloud_routes_are_authorized() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/model/m/converse"))
+            .and(header_regex(
+                "authorization",
+                "^AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/\\d{8}/us-west-2/bedrock/aws4_request, ",
+            ))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/oauth/token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "access_token": "ya29.user",
+                "expires_in": 3600
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/v1/chat"))
+            .and(header("authorization", "Bearer ya29.user"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+
+        let client = reqwest::Client::new();
+        let mut bedrock = route("bedrock", &server.uri(), false);
+        bedrock.provider.aws = Some(AwsConfig {
+            region: Some("us-west-2".to_string()),
+            access_key_id: Some("AKIDEXAMPLE".to_string()),
+            secret_access_key: Some("secret".to_string()),
+            ..Default::default()
+        });
+        let routes = [bedrock];
+        let served = send_with_fallback(&routes, &fast_retry(0), |r| {
+            Ok(client
+                .post(format!("{}/model/m/converse", r.provider.base_url))
+                .body("{}"))
+        })
+        .await
+        .unwrap();
+        assert_eq!(served.response.status(), 200);
+
+        let dir = tempfile::tempdir().unwrap();
+        let credentials = dir.path().join("adc.json");
+        std::fs::write(
+            &credentials,
+            r#"{"type": "authorized_user", "client_id": "c", "client_secret": "s", "refresh_token": "r"}"#,
+        )
+        .unwrap();
+        let mut vertex = route("vertex", &server.uri(), false);
+        vertex.provider.vertex = Some(VertexConfig {
+            project_id: Some("p".to_string()),
+            credentials_file: Some(credentials.display().to_string()),
+            token_url: Some(format!("{}/oauth/token", server.uri())),
+            ..Default::default()
+        });
+        let routes = [vertex];
+        let served = send_with_fallback(&routes, &fast_retry(0), |r| post(&client, r))
+            .await
+            .unwrap();
+        assert_eq!(served.response.status(), 200);
+    }
+}
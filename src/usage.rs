@@ -0,0 +1,250 @@
+//! Token usage pricing and accounting.
+//!
+//! Every model response reports a [`TokenUsage`]; this module prices it with
+//! the model registry (overridable via `pricing:` in config.yaml) and records
+//! it in the project database so `openclaudia usage` can report totals per
+//! model and per day.
+
+use crate::config::{CacheTtl, ModelPrice};
+use crate::memory::MemoryDb;
+use crate::models;
+use crate::stream::TokenUsage;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Longest key in `prices` that `model` starts with
+fn longest_prefix<'a, I>(model: &str, prices: I) -> Option<ModelPrice>
+where
+    I: IntoIterator<Item = (&'a str, &'a ModelPrice)>,
+{
+    prices
+        .into_iter()
+        .filter(|(prefix, _)| model.starts_with(prefix))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map(|(_, price)| *price)
+}
+
+/// Price for a model: config overrides first, then the model registry.
+/// Router-style names like `anthropic/claude-sonnet-4` also match on the
+/// part after the last `/`.
+pub fn price_for(model: &str, overrides: &HashMap<String, ModelPrice>) -> Option<ModelPrice> {
+    let bare = model.rsplit('/').next().unwrap_or(model);
+    [model, bare]
+        .into_iter()
+        .find_map(|name| longest_prefix(name, overrides.iter().map(|(k, v)| (k.as_str(), v))))
+        .or_else(|| models::lookup(model).and_then(|m| m.pricing))
+}
+
+/// Price per million cache-write tokens. The catalog's `cache_write` price is
+/// for 5-minute entries; 1-hour entries cost twice the input price.
+fn cache_write_price(price: &ModelPrice, ttl: CacheTtl) -> f64 {
+    match (ttl, price.cache_write) {
+        (_, None) => price.input,
+        (CacheTtl::FiveMinutes, Some(write)) => write,
+        (CacheTtl::OneHour, Some(_)) => 2.0 * price.input,
+    }
+}
+
+/// Cost in USD of one response, with cache writes priced for `ttl`.
+/// Reasoning tokens are billed as output and already included in
+/// `output_tokens`.
+pub fn cost_usd(usage: &TokenUsage, price: &ModelPrice, ttl: CacheTtl) -> f64 {
+    let per_token = |tokens: u64, per_million: f64| tokens as f64 * per_million / 1_000_000.0;
+    per_token(usage.input_tokens, price.input)
+        + per_token(usage.output_tokens, price.output)
+        + per_token(
+            usage.cache_read_tokens,
+            price.cache_read.unwrap_or(price.input),
+        )
+        + per_token(usage.cache_write_tokens, cache_write_price(price, ttl))
+}
+
+/// Share of prompt tokens read from the prompt cache, or None before any
+/// prompt tokens were sent
+pub fn cache_hit_rate(usage: &TokenUsage) -> Option<f64> {
+    let prompt = usage.input_tokens + usage.cache_read_tokens + usage.cache_write_tokens;
+    (prompt > 0).then(|| usage.cache_read_tokens as f64 / prompt as f64)
+}
+
+/// USD saved by prompt caching compared with sending every prompt token
+/// uncached: discounted reads minus the premium paid on cache writes.
+pub fn cache_savings_usd(usage: &TokenUsage, price: &ModelPrice, ttl: CacheTtl) -> f64 {
+    let read_discount = price.input - price.cache_read.unwrap_or(price.input);
+    let write_premium = cache_write_price(price, ttl) - price.input;
+    (usage.cache_read_tokens as f64 * read_discount
+        - usage.cache_write_tokens as f64 * write_premium)
+        / 1_000_000.0
+}
+
+/// Price a response and record it in the project database under `project_dir`.
+/// `ttl` is the provider's prompt cache lifetime, which sets the write price.
+/// Returns the cost, or None when the model has no known price.
+/// Recording failures are logged and never interrupt the caller.
+pub fn record(
+    project_dir: &Path,
+    session_id: &str,
+    provider: &str,
+    model: &str,
+    usage: &TokenUsage,
+    pricing: &HashMap<String, ModelPrice>,
+    ttl: CacheTtl,
+) -> Option<f64> {
+    let cost = price_for(model, pricing).map(|p| cost_usd(usage, &p, ttl));
+    match MemoryDb::open_for_project(project_dir) {
+        Ok(db) => {
+            if let Err(e) = db.usage_record(session_id, provider, model, usage, cost) {
+                tracing::warn!("Failed to record token usage: {}", e);
+            }
+        }
+        Err(e) => tracing::warn!("Failed to open usage store: {}", e),
+    }
+    cost
+}
+
+/// Compact token count (`950`, `12.3k`, `4.1M`)
+pub fn format_tokens(tokens: u64) -> String {
+    match tokens {
+        0..=999 => tokens.to_string(),
+        1_000..=999_999 => format!("{:.1}k", tokens as f64 / 1_000.0),
+        _ => format!("{:.1}M", tokens as f64 / 1_000_000.0),
+    }
+}
+
+/// Dollar amount with enough precision for small per-turn costs
+pub fn format_cost(cost: f64) -> String {
+    if cost > 0.0 && cost < 0.01 {
+        format!("${:.4}", cost)
+    } else {
+        format!("${:.2}", cost)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn price(input: f64, output: f64, cache_read: f64, cache_write: f64) -> ModelPrice {
+        ModelPrice {
+            input,
+            output,
+            cache_read: Some(cache_read),
+            cache_write: Some(cache_write),
+        }
+    }
+
+    #[test]
+    fn test_price_for_prefers_longest_prefix_and_config() {
+        let mut overrides = HashMap::new();
+        assert_eq!(
+            price_for("claude-opus-4-5-20251101", &overrides)
+                .unwrap()
+                .input,
+            5.0
+        );
+        assert_eq!(
+            price_for("claude-opus-4-20250514", &overrides)
+                .unwrap()
+                .input,
+            15.0
+        );
+        assert_eq!(price_for("gpt-4o-mini", &overrides).unwrap().input, 0.15);
+        assert_eq!(
+            price_for("anthropic/claude-sonnet-4", &overrides)
+                .unwrap()
+                .output,
+            15.0
+        );
+        assert!(price_for("qwen3:8b", &overrides).is_none());
+
+        overrides.insert(
+            "qwen3".to_string(),
+            ModelPrice {
+                input: 0.0,
+                output: 0.0,
+                cache_read: None,
+                cache_write: None,
+            },
+        );
+        overrides.insert("gpt-4o".to_string(), price(1.0, 2.0, 0.5, 1.0));
+        assert_eq!(price_for("qwen3:8b", &overrides).unwrap().output, 0.0);
+        assert_eq!(
+            price_for("gpt-4o-2024-08-06", &overrides).unwrap().input,
+            1.0
+        );
+    }
+
+    #[test]
+    fn test_cost_usd() {
+        let usage = TokenUsage {
+            input_tokens: 1_000_000,
+            output_tokens: 100_000,
+            cache_read_tokens: 2_000_000,
+            cache_write_tokens: 0,
+            reasoning_tokens: 50_000,
+        };
+        let sonnet = price(3.0, 15.0, 0.3, 3.75);
+        assert!((cost_usd(&usage, &sonnet, CacheTtl::FiveMinutes) - 5.1).abs() < 1e-9);
+
+        let uncached = ModelPrice {
+            input: 1.0,
+            output: 2.0,
+            cache_read: None,
+            cache_write: None,
+        };
+        assert!((cost_usd(&usage, &uncached, CacheTtl::FiveMinutes) - 3.2).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_cache_hit_rate_and_savings() {
+        assert_eq!(cache_hit_rate(&TokenUsage::default()), None);
+
+        let usage = TokenUsage {
+            input_tokens: 100_000,
+            output_tokens: 10_000,
+            cache_read_tokens: 800_000,
+            cache_write_tokens: 100_000,
+            reasoning_tokens: 0,
+        };
+        assert!((cache_hit_rate(&usage).unwrap() - 0.8).abs() < 1e-9);
+
+        // 0.8M reads save $2.70/M, 0.1M writes cost $0.75/M extra
+        let sonnet = price(3.0, 15.0, 0.3, 3.75);
+        assert!((cache_savings_usd(&usage, &sonnet, CacheTtl::FiveMinutes) - 2.085).abs() < 1e-9);
+        // 1h writes cost $6/M, $3/M above input
+        assert!((cache_savings_usd(&usage, &sonnet, CacheTtl::OneHour) - 1.86).abs() < 1e-9);
+
+        let uncached = ModelPrice {
+            input: 1.0,
+            output: 2.0,
+            cache_read: None,
+            cache_write: None,
+        };
+        assert_eq!(cache_savings_usd(&usage, &uncached, CacheTtl::OneHour), 0.0);
+    }
+
+    #[test]
+    fn test_cost_usd_cache_ttl() {
+        let usage = TokenUsage {
+            input_tokens: 100_000,
+            output_tokens: 10_000,
+            cache_read_tokens: 800_000,
+            cache_write_tokens: 100_000,
+            reasoning_tokens: 0,
+        };
+        // $0.30 input + $0.15 output + $0.24 reads, plus writes at $3.75/M
+        let sonnet = price(3.0, 15.0, 0.3, 3.75);
+        assert!((cost_usd(&usage, &sonnet, CacheTtl::FiveMinutes) - 1.065).abs() < 1e-9);
+        // 1h writes cost $6/M
+        assert!((cost_usd(&usage, &sonnet, CacheTtl::OneHour) - 1.29).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(format_tokens(950), "950");
+        assert_eq!(format_tokens(12_345), "12.3k");
+        assert_eq!(format_tokens(4_100_000), "4.1M");
+        assert_eq!(format_cost(0.0), "$0.00");
+        assert_eq!(format_cost(0.0042), "$0.0042");
+        assert_eq!(format_cost(1.5), "$1.50");
+    }
+}
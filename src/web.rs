@@ -2,12 +2,17 @@
 //!
 //! Provides web access capabilities for agents:
 //! - `web_fetch`: Fetch URL content via Jina Reader (free, handles JS/Cloudflare)
-//! - `web_search`: Search the web via Tavily, Brave API, or DuckDuckGo (headless browser)
+//! - `web_search`: Search the web via SearXNG, DuckDuckGo (headless browser), Tavily or Brave API
 //! - `web_browser`: Full browser automation via headless Chrome (optional feature)
 
+use crate::config::{SearchBackend, WebSearchConfig};
+use crate::network::{domain_matches, NetworkPolicy};
+use async_trait::async_trait;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use std::time::Duration;
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 /// Jina Reader base URL - converts any URL to clean markdown
 const JINA_READER_URL: &str = "https://r.jina.ai/";
@@ -22,19 +27,64 @@ const BRAVE_SEARCH_URL: &str = "https://api.search.brave.com/res/v1/web/search";
 #[cfg(feature = "browser")]
 const DUCKDUCKGO_HTML_URL: &str = "https://html.duckduckgo.com/html/";
 
-/// Web configuration for API keys
+/// Web configuration for search backends, domain filters and caching
 #[derive(Debug, Clone, Default)]
 pub struct WebConfig {
+    pub backend: SearchBackend,
+    pub searxng_url: Option<String>,
     pub tavily_api_key: Option<String>,
     pub brave_api_key: Option<String>,
+    pub allowed_domains: Vec<String>,
+    pub blocked_domains: Vec<String>,
+    /// Directory for cached search results (`None` disables caching)
+    pub cache_dir: Option<PathBuf>,
+    pub cache_ttl: Duration,
 }
 
 impl WebConfig {
     /// Load web config from environment variables
     pub fn from_env() -> Self {
         Self {
+            searxng_url: std::env::var("SEARXNG_URL").ok(),
             tavily_api_key: std::env::var("TAVILY_API_KEY").ok(),
             brave_api_key: std::env::var("BRAVE_API_KEY").ok(),
+            ..Default::default()
+        }
+    }
+
+    /// Build web config from the `web.search` section of config.yaml,
+    /// falling back to environment variables for unset keys
+    pub fn from_search_config(search: &WebSearchConfig) -> Self {
+        let env = Self::from_env();
+        let cache_dir = if search.cache {
+            search
+                .cache_dir
+                .clone()
+                .or_else(|| dirs::cache_dir().map(|d| d.join("openclaudia").join("search")))
+        } else {
+            None
+        };
+
+        Self {
+            backend: search.backend,
+            searxng_url: search.searxng_url.clone().or(env.searxng_url),
+            tavily_api_key: search.tavily_api_key.clone().or(env.tavily_api_key),
+            brave_api_key: search.brave_api_key.clone().or(env.brave_api_key),
+            allowed_domains: search.allowed_domains.clone(),
+            blocked_domains: search.blocked_domains.clone(),
+            cache_dir,
+            cache_ttl: Duration::from_secs(search.cache_ttl_minutes * 60),
+        }
+    }
+
+    /// Load web config from config.yaml, or from the environment if no config loads
+    pub fn load() -> Self {
+        match crate::config::load_config() {
+            Ok(config) => Self::from_search_config(&config.web.search),
+            Err(e) => {
+                tracing::debug!("Using environment web config: {}", e);
+                Self::from_search_config(&WebSearchConfig::default())
+            }
         }
     }
 }
@@ -61,30 +111,34 @@ pub struct SearchResult {
 /// - JavaScript rendering
 /// - Cloudflare bypass
 /// - Clean markdown output
-pub async fn fetch_url(url: &str) -> Result<FetchResult, String> {
-    let client = Client::builder()
-        .timeout(Duration::from_secs(30))
-        .build()
-        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+///
+/// The target URL must pass the network policy before anything is sent. The
+/// domain lists apply to the target, not to the reader service itself.
+pub async fn fetch_url(url: &str, policy: &NetworkPolicy) -> Result<FetchResult, String> {
+    policy.check_url_async(url).await?;
 
     // Use Jina Reader to fetch and convert to markdown
     let jina_url = format!("{}{}", JINA_READER_URL, url);
+    let reader_policy = NetworkPolicy {
+        allowed_domains: Vec::new(),
+        blocked_domains: Vec::new(),
+        ..policy.clone()
+    };
 
-    let response = client
-        .get(&jina_url)
-        .header("Accept", "text/markdown")
-        .send()
+    let response = reader_policy
+        .get(
+            &jina_url,
+            &[("Accept", "text/markdown")],
+            Duration::from_secs(30),
+        )
         .await
         .map_err(|e| format!("Failed to fetch URL: {}", e))?;
 
-    if !response.status().is_success() {
-        return Err(format!("HTTP error: {} - {}", response.status(), url));
+    if !(200..300).contains(&response.status) {
+        return Err(format!("HTTP error: {} - {}", response.status, url));
     }
 
-    let content = response
-        .text()
-        .await
-        .map_err(|e| format!("Failed to read response: {}", e))?;
+    let content = response.body;
 
     // Extract title from markdown if present (first # heading)
     let title = content
@@ -130,134 +184,406 @@ struct BraveResult {
     description: String,
 }
 
-/// Search the web using DuckDuckGo (default) or configured API provider
-pub async fn search_web(
-    query: &str,
-    config: &WebConfig,
-    limit: usize,
-) -> Result<Vec<SearchResult>, String> {
-    // Try DuckDuckGo first (free, no API key required)
-    // Fall back to paid APIs only if DDG fails or browser feature disabled
-    let ddg_error = match search_duckduckgo(query, limit) {
-        Ok(results) => return Ok(results),
-        Err(e) => {
-            tracing::warn!("DuckDuckGo search failed: {}", e);
-            e
+/// SearXNG JSON response structure
+#[derive(Debug, Deserialize)]
+struct SearxngResponse {
+    results: Vec<SearxngResult>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearxngResult {
+    title: String,
+    url: String,
+    #[serde(default)]
+    content: Option<String>,
+}
+
+/// A web search backend
+#[async_trait]
+pub trait SearchProvider: Send + Sync {
+    /// Backend name for logs and error messages
+    fn name(&self) -> &str;
+
+    /// Run a search and return up to `limit` results
+    async fn search(&self, query: &str, limit: usize) -> Result<Vec<SearchResult>, String>;
+}
+
+/// Self-hosted SearXNG instance (needs `search.formats: [json]` in its settings)
+pub struct SearxngProvider {
+    base_url: String,
+}
+
+impl SearxngProvider {
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
         }
-    };
+    }
+}
 
-    // Fall back to Tavily if configured
-    if let Some(api_key) = &config.tavily_api_key {
-        return search_tavily(query, api_key, limit).await;
+#[async_trait]
+impl SearchProvider for SearxngProvider {
+    fn name(&self) -> &str {
+        "SearXNG"
     }
 
-    // Fall back to Brave if configured
-    if let Some(api_key) = &config.brave_api_key {
-        return search_brave(query, api_key, limit).await;
+    async fn search(&self, query: &str, limit: usize) -> Result<Vec<SearchResult>, String> {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(15))
+            .build()
+            .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+
+        let response = client
+            .get(format!("{}/search", self.base_url))
+            .header("Accept", "application/json")
+            .query(&[("q", query), ("format", "json")])
+            .send()
+            .await
+            .map_err(|e| format!("SearXNG request failed: {}", e))?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(format!("SearXNG error {}: {}", status, body));
+        }
+
+        let searxng_response: SearxngResponse = response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse SearXNG response: {}", e))?;
+
+        Ok(searxng_response
+            .results
+            .into_iter()
+            .take(limit)
+            .map(|r| SearchResult {
+                title: r.title,
+                url: r.url,
+                snippet: r.content.unwrap_or_default(),
+            })
+            .collect())
     }
+}
+
+/// DuckDuckGo HTML results scraped with headless Chrome
+pub struct DuckDuckGoProvider;
 
-    Err(format!("Web search failed. DuckDuckGo error: {}. No fallback API keys configured (TAVILY_API_KEY or BRAVE_API_KEY).", ddg_error))
+#[async_trait]
+impl SearchProvider for DuckDuckGoProvider {
+    fn name(&self) -> &str {
+        "DuckDuckGo"
+    }
+
+    async fn search(&self, query: &str, limit: usize) -> Result<Vec<SearchResult>, String> {
+        search_duckduckgo(query, limit)
+    }
 }
 
-/// Search using Tavily API
-async fn search_tavily(
-    query: &str,
-    api_key: &str,
-    limit: usize,
-) -> Result<Vec<SearchResult>, String> {
-    let client = Client::builder()
-        .timeout(Duration::from_secs(15))
-        .build()
-        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
-
-    #[derive(Serialize)]
-    struct TavilyRequest<'a> {
-        api_key: &'a str,
-        query: &'a str,
-        max_results: usize,
-        include_answer: bool,
-    }
-
-    let request = TavilyRequest {
-        api_key,
-        query,
-        max_results: limit,
-        include_answer: false,
-    };
+/// Tavily search API
+pub struct TavilyProvider {
+    api_key: String,
+}
 
-    let response = client
-        .post(TAVILY_API_URL)
-        .json(&request)
-        .send()
-        .await
-        .map_err(|e| format!("Tavily API request failed: {}", e))?;
+impl TavilyProvider {
+    pub fn new(api_key: impl Into<String>) -> Self {
+        Self {
+            api_key: api_key.into(),
+        }
+    }
+}
 
-    if !response.status().is_success() {
-        let status = response.status();
-        let body = response.text().await.unwrap_or_default();
-        return Err(format!("Tavily API error {}: {}", status, body));
+#[async_trait]
+impl SearchProvider for TavilyProvider {
+    fn name(&self) -> &str {
+        "Tavily"
     }
 
-    let tavily_response: TavilyResponse = response
-        .json()
-        .await
-        .map_err(|e| format!("Failed to parse Tavily response: {}", e))?;
+    async fn search(&self, query: &str, limit: usize) -> Result<Vec<SearchResult>, String> {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(15))
+            .build()
+            .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+
+        #[derive(Serialize)]
+        struct TavilyRequest<'a> {
+            api_key: &'a str,
+            query: &'a str,
+            max_results: usize,
+            include_answer: bool,
+        }
+
+        let request = TavilyRequest {
+            api_key: &self.api_key,
+            query,
+            max_results: limit,
+            include_answer: false,
+        };
+
+        let response = client
+            .post(TAVILY_API_URL)
+            .json(&request)
+            .send()
+            .await
+            .map_err(|e| format!("Tavily API request failed: {}", e))?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(format!("Tavily API error {}: {}", status, body));
+        }
+
+        let tavily_response: TavilyResponse = response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse Tavily response: {}", e))?;
+
+        Ok(tavily_response
+            .results
+            .into_iter()
+            .map(|r| SearchResult {
+                title: r.title,
+                url: r.url,
+                snippet: r.content,
+            })
+            .collect())
+    }
+}
+
+/// Brave Search API
+pub struct BraveProvider {
+    api_key: String,
+}
 
-    Ok(tavily_response
-        .results
-        .into_iter()
-        .map(|r| SearchResult {
-            title: r.title,
-            url: r.url,
-            snippet: r.content,
-        })
-        .collect())
+impl BraveProvider {
+    pub fn new(api_key: impl Into<String>) -> Self {
+        Self {
+            api_key: api_key.into(),
+        }
+    }
 }
 
-/// Search using Brave Search API
-async fn search_brave(
+#[async_trait]
+impl SearchProvider for BraveProvider {
+    fn name(&self) -> &str {
+        "Brave"
+    }
+
+    async fn search(&self, query: &str, limit: usize) -> Result<Vec<SearchResult>, String> {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(15))
+            .build()
+            .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+
+        let response = client
+            .get(BRAVE_SEARCH_URL)
+            .header("X-Subscription-Token", &self.api_key)
+            .header("Accept", "application/json")
+            .query(&[("q", query), ("count", &limit.to_string())])
+            .send()
+            .await
+            .map_err(|e| format!("Brave Search API request failed: {}", e))?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(format!("Brave Search API error {}: {}", status, body));
+        }
+
+        let brave_response: BraveResponse = response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse Brave response: {}", e))?;
+
+        Ok(brave_response
+            .web
+            .map(|w| {
+                w.results
+                    .into_iter()
+                    .map(|r| SearchResult {
+                        title: r.title,
+                        url: r.url,
+                        snippet: r.description,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+}
+
+/// Build the list of search providers to try, in order
+///
+/// `Auto` tries SearXNG (if configured), then DuckDuckGo, then whichever
+/// paid APIs have keys. An explicit backend only uses that backend.
+pub fn build_search_providers(config: &WebConfig) -> Result<Vec<Box<dyn SearchProvider>>, String> {
+    let mut providers: Vec<Box<dyn SearchProvider>> = Vec::new();
+
+    match config.backend {
+        SearchBackend::Auto => {
+            if let Some(url) = &config.searxng_url {
+                providers.push(Box::new(SearxngProvider::new(url.as_str())));
+            }
+            providers.push(Box::new(DuckDuckGoProvider));
+            if let Some(key) = &config.tavily_api_key {
+                providers.push(Box::new(TavilyProvider::new(key.as_str())));
+            }
+            if let Some(key) = &config.brave_api_key {
+                providers.push(Box::new(BraveProvider::new(key.as_str())));
+            }
+        }
+        SearchBackend::Searxng => {
+            let url = config
+                .searxng_url
+                .as_ref()
+                .ok_or("SearXNG backend selected but web.search.searxng_url is not set")?;
+            providers.push(Box::new(SearxngProvider::new(url.as_str())));
+        }
+        SearchBackend::Duckduckgo => providers.push(Box::new(DuckDuckGoProvider)),
+        SearchBackend::Tavily => {
+            let key = config.tavily_api_key.as_ref().ok_or(
+                "Tavily backend selected but no API key configured (web.search.tavily_api_key or TAVILY_API_KEY)",
+            )?;
+            providers.push(Box::new(TavilyProvider::new(key.as_str())));
+        }
+        SearchBackend::Brave => {
+            let key = config.brave_api_key.as_ref().ok_or(
+                "Brave backend selected but no API key configured (web.search.brave_api_key or BRAVE_API_KEY)",
+            )?;
+            providers.push(Box::new(BraveProvider::new(key.as_str())));
+        }
+    }
+
+    Ok(providers)
+}
+
+/// Check a result URL against allowed/blocked domain lists
+///
+/// Blocked domains always win. An empty allow list allows everything.
+pub fn is_domain_allowed(url: &str, allowed: &[String], blocked: &[String]) -> bool {
+    let host = match reqwest::Url::parse(url)
+        .ok()
+        .and_then(|u| u.host_str().map(|h| h.to_lowercase()))
+    {
+        Some(h) => h,
+        None => return false,
+    };
+
+    if blocked.iter().any(|d| domain_matches(&host, d)) {
+        return false;
+    }
+
+    allowed.is_empty() || allowed.iter().any(|d| domain_matches(&host, d))
+}
+
+/// Cached search results stored on disk
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedSearch {
+    /// Unix timestamp (seconds) when the results were fetched
+    fetched_at: u64,
+    results: Vec<SearchResult>,
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Cache file path for a query under the given config
+fn search_cache_path(config: &WebConfig, query: &str, limit: usize) -> Option<PathBuf> {
+    let dir = config.cache_dir.as_ref()?;
+
+    let mut hasher = Sha256::new();
+    hasher.update(format!("{:?}", config.backend));
+    hasher.update([0]);
+    hasher.update(config.searxng_url.as_deref().unwrap_or_default());
+    hasher.update([0]);
+    hasher.update(query.trim().to_lowercase());
+    hasher.update([0]);
+    hasher.update(limit.to_string());
+    hasher.update([0]);
+    hasher.update(config.allowed_domains.join(","));
+    hasher.update([0]);
+    hasher.update(config.blocked_domains.join(","));
+
+    Some(dir.join(format!("{:x}.json", hasher.finalize())))
+}
+
+fn read_search_cache(path: &PathBuf, ttl: Duration) -> Option<Vec<SearchResult>> {
+    let content = std::fs::read_to_string(path).ok()?;
+    let cached: CachedSearch = serde_json::from_str(&content).ok()?;
+    if now_secs().saturating_sub(cached.fetched_at) > ttl.as_secs() {
+        return None;
+    }
+    Some(cached.results)
+}
+
+fn write_search_cache(path: &PathBuf, results: &[SearchResult]) {
+    let cached = CachedSearch {
+        fetched_at: now_secs(),
+        results: results.to_vec(),
+    };
+    let write = || -> std::io::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string(&cached)?)
+    };
+    if let Err(e) = write() {
+        tracing::warn!("Failed to write search cache {}: {}", path.display(), e);
+    }
+}
+
+/// Search the web using the configured backends
+///
+/// Results are filtered by the configured domain lists and cached on disk,
+/// so repeating a query within the cache TTL does not hit the network.
+pub async fn search_web(
     query: &str,
-    api_key: &str,
+    config: &WebConfig,
     limit: usize,
 ) -> Result<Vec<SearchResult>, String> {
-    let client = Client::builder()
-        .timeout(Duration::from_secs(15))
-        .build()
-        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
-
-    let response = client
-        .get(BRAVE_SEARCH_URL)
-        .header("X-Subscription-Token", api_key)
-        .header("Accept", "application/json")
-        .query(&[("q", query), ("count", &limit.to_string())])
-        .send()
-        .await
-        .map_err(|e| format!("Brave Search API request failed: {}", e))?;
+    let cache_path = search_cache_path(config, query, limit);
+    if let Some(path) = &cache_path {
+        if let Some(results) = read_search_cache(path, config.cache_ttl) {
+            tracing::debug!("Search cache hit for '{}'", query);
+            return Ok(results);
+        }
+    }
 
-    if !response.status().is_success() {
-        let status = response.status();
-        let body = response.text().await.unwrap_or_default();
-        return Err(format!("Brave Search API error {}: {}", status, body));
+    // Over-fetch when filtering so we still have enough results afterwards
+    let filtering = !config.allowed_domains.is_empty() || !config.blocked_domains.is_empty();
+    let fetch_limit = if filtering { limit * 3 } else { limit };
+
+    let mut errors = Vec::new();
+    for provider in build_search_providers(config)? {
+        match provider.search(query, fetch_limit).await {
+            Ok(results) => {
+                let results: Vec<SearchResult> = results
+                    .into_iter()
+                    .filter(|r| {
+                        is_domain_allowed(&r.url, &config.allowed_domains, &config.blocked_domains)
+                    })
+                    .take(limit)
+                    .collect();
+
+                // An empty page may be a transient backend problem, so it is not cached
+                if let Some(path) = cache_path.as_ref().filter(|_| !results.is_empty()) {
+                    write_search_cache(path, &results);
+                }
+                return Ok(results);
+            }
+            Err(e) => {
+                tracing::warn!("{} search failed: {}", provider.name(), e);
+                errors.push(format!("{}: {}", provider.name(), e));
+            }
+        }
     }
 
-    let brave_response: BraveResponse = response
-        .json()
-        .await
-        .map_err(|e| format!("Failed to parse Brave response: {}", e))?;
-
-    Ok(brave_response
-        .web
-        .map(|w| {
-            w.results
-                .into_iter()
-                .map(|r| SearchResult {
-                    title: r.title,
-                    url: r.url,
-                    snippet: r.description,
-                })
-                .collect()
-        })
-        .unwrap_or_default())
+    Err(format!(
+        "Web search failed. {}. Configure web.search in config.yaml (SearXNG, Tavily or Brave) or set TAVILY_API_KEY/BRAVE_API_KEY.",
+        errors.join("; ")
+    ))
 }
 
 /// Search DuckDuckGo using headless Chrome browser
@@ -376,16 +702,21 @@ pub fn search_duckduckgo(query: &str, limit: usize) -> Result<Vec<SearchResult>,
 
 #[cfg(not(feature = "browser"))]
 pub fn search_duckduckgo(_query: &str, _limit: usize) -> Result<Vec<SearchResult>, String> {
-    Err("DuckDuckGo search requires the browser feature. Rebuild with `cargo build --features browser` or set TAVILY_API_KEY/BRAVE_API_KEY.".to_string())
+    Err("DuckDuckGo search requires the browser feature. Rebuild with `cargo build --features browser` or configure another web.search backend.".to_string())
 }
 
 /// Fetch URL using headless Chrome browser
 ///
 /// Use this when Jina Reader fails (e.g., complex authentication, specific Cloudflare challenges)
+///
+/// Both the requested URL and the URL the browser ends up on must pass the
+/// network policy; content from a blocked final URL is never returned.
 #[cfg(feature = "browser")]
-pub fn fetch_with_browser(url: &str) -> Result<FetchResult, String> {
+pub fn fetch_with_browser(url: &str, policy: &NetworkPolicy) -> Result<FetchResult, String> {
     use headless_chrome::{Browser, LaunchOptions};
 
+    policy.check_url(url)?;
+
     let browser = Browser::new(
         LaunchOptions::default_builder()
             .headless(true)
@@ -404,6 +735,12 @@ pub fn fetch_with_browser(url: &str) -> Result<FetchResult, String> {
     tab.wait_until_navigated()
         .map_err(|e| format!("Navigation timeout: {}", e))?;
 
+    // Redirects inside the browser are not visible to us, so re-check where we landed
+    let final_url = tab.get_url();
+    if final_url != url {
+        policy.check_url(&final_url)?;
+    }
+
     // Wait a bit for JavaScript to render
     std::thread::sleep(Duration::from_secs(2));
 
@@ -423,7 +760,7 @@ pub fn fetch_with_browser(url: &str) -> Result<FetchResult, String> {
 }
 
 #[cfg(not(feature = "browser"))]
-pub fn fetch_with_browser(_url: &str) -> Result<FetchResult, String> {
+pub fn fetch_with_browser(_url: &str, _policy: &NetworkPolicy) -> Result<FetchResult, String> {
     Err("Browser feature not enabled. Rebuild with `cargo build --features browser`".to_string())
 }
 
@@ -477,4 +814,108 @@ mod tests {
         let formatted = format_search_results(&results);
         assert!(formatted.contains("No results found"));
     }
+
+    #[test]
+    fn test_domain_filter() {
+        let allowed = vec!["docs.rs".to_string()];
+        let blocked = vec!["pinterest.com".to_string()];
+
+        assert!(is_domain_allowed("https://docs.rs/serde", &allowed, &[]));
+        assert!(is_domain_allowed(
+            "https://www.docs.rs/serde",
+            &allowed,
+            &[]
+        ));
+        assert!(!is_domain_allowed(
+            "https://notdocs.rs/serde",
+            &allowed,
+            &[]
+        ));
+        assert!(!is_domain_allowed(
+            "https://www.pinterest.com/x",
+            &[],
+            &blocked
+        ));
+        assert!(is_domain_allowed("https://example.com", &[], &blocked));
+        assert!(!is_domain_allowed("not a url", &[], &[]));
+    }
+
+    #[test]
+    fn test_build_search_providers() {
+        let config = WebConfig {
+            searxng_url: Some("http://localhost:8888".to_string()),
+            tavily_api_key: Some("tvly-test".to_string()),
+            ..Default::default()
+        };
+        let names: Vec<String> = build_search_providers(&config)
+            .unwrap()
+            .iter()
+            .map(|p| p.name().to_string())
+            .collect();
+        assert_eq!(names, vec!["SearXNG", "DuckDuckGo", "Tavily"]);
+
+        let config = WebConfig {
+            backend: SearchBackend::Brave,
+            ..Default::default()
+        };
+        assert!(build_search_providers(&config).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_searxng_search_filtered_and_cached() {
+        use wiremock::matchers::{method, path, query_param};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/search"))
+            .and(query_param("format", "json"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "results": [
+                    {"title": "Rust", "url": "https://www.rust-lang.org/", "content": "A language"},
+                    {"title": "Spam", "url": "https://pinterest.com/rust", "content": "Pins"},
+                    {"title": "Docs", "url": "https://doc.rust-lang.org/book/"}
+                ]
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let cache_dir = tempfile::tempdir().unwrap();
+        let config = WebConfig {
+            backend: SearchBackend::Searxng,
+            searxng_url: Some(server.uri()),
+            blocked_domains: vec!["pinterest.com".to_string()],
+            cache_dir: Some(cache_dir.path().to_path_buf()),
+            cache_ttl: Duration::from_secs(60),
+            ..Default::default()
+        };
+
+        let results = search_web("rust", &config, 5).await.unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].title, "Rust");
+        assert_eq!(results[1].snippet, "");
+
+        // Second search is served from the on-disk cache (mock expects one call)
+        let cached = search_web("rust", &config, 5).await.unwrap();
+        assert_eq!(cached.len(), 2);
+        assert_eq!(cached[0].url, "https://www.rust-lang.org/");
+
+        // Another instance has its own cache entries, and empty pages are not cached
+        let empty = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/search"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(serde_json::json!({"results": []})),
+            )
+            .expect(2)
+            .mount(&empty)
+            .await;
+        let config = WebConfig {
+            searxng_url: Some(empty.uri()),
+            ..config
+        };
+        assert!(search_web("rust", &config, 5).await.unwrap().is_empty());
+        assert!(search_web("rust", &config, 5).await.unwrap().is_empty());
+    }
 }